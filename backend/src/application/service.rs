@@ -0,0 +1,58 @@
+mod activity_service;
+mod album_service;
+mod audio_query;
+mod audit_service;
+mod duplicate_service;
+mod fingerprint_service;
+mod genre_service;
+mod library_service;
+mod organizer_service;
+mod playlist_service;
+mod scrobble_service;
+mod search_service;
+mod smart_playlist_service;
+mod tag_edit_service;
+mod transcoding_service;
+mod user_service;
+
+pub use activity_service::{ActivityService, ActivityServiceError};
+pub use album_service::{AlbumService, AlbumServiceOptions};
+pub use audio_query::{
+    fold, AudioQuery, AudioQueryError, Comparison, Filter, FlagField, NumberField, Predicate,
+    QueryField, QuerySort, SqlQuery, SqlValue, TextField, TextOperator, FOLD_FUNCTION,
+};
+pub use audit_service::{
+    AuditIssue, AuditIssueKind, AuditReport, AuditService, AuditServiceOptions,
+};
+pub use duplicate_service::{
+    DuplicateGroup, DuplicateService, DuplicateServiceOptions, DuplicateStrategy,
+};
+pub use fingerprint_service::{
+    FingerprintService, FingerprintServiceError, FingerprintServiceOptions,
+};
+pub use genre_service::{GenreNode, GenreService};
+pub use library_service::{
+    AlbumFilter, AlbumSort, ArtistFilter, ArtistSort, ArtistSummary, GenreFilter, GenreSort,
+    GenreSummary, Library, LibraryService, LibraryServiceError, Page, Paginated, ScanStatus,
+    SortOrder, TrackFilter, TrackSort,
+};
+pub use organizer_service::{
+    FileOperation, FileOperationKind, FileOperationReport, OrganizeMode, OrganizerOptions,
+    OrganizerService, PathTemplate, PathTemplateError,
+};
+pub use playlist_service::{PlaylistService, PlaylistServiceError, ResolvedPlaylist};
+pub use scrobble_service::{
+    FlushReport, ScrobbleService, ScrobbleServiceError, ScrobbleServiceOptions,
+};
+pub use search_service::{
+    IndexUpdate, SearchField, SearchHit, SearchQuery, SearchService, SearchServiceError,
+};
+pub use smart_playlist_service::{SmartPlaylistService, SmartPlaylistServiceError};
+pub use tag_edit_service::{
+    BatchEditReport, FileEditReport, FileEditResult, TagEdit, TagEditService, TagEditServiceError,
+};
+pub use transcoding_service::{
+    Transcoded, TranscodedOutput, TranscodingService, TranscodingServiceError,
+    TranscodingServiceOptions,
+};
+pub use user_service::{UserService, UserServiceError, UserUpdate};
@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{Arc, RwLock},
+};
+
+use chrono::Utc;
+use thiserror::Error;
+
+use crate::domain::{
+    entity::{
+        activity::{Annotation, ItemKind, Play, Rating},
+        play_stats::PlayStats,
+    },
+    repository::ActivityRepository,
+};
+
+use super::{Library, Page, Paginated};
+
+#[derive(Error, Debug)]
+pub enum ActivityServiceError {
+    #[error("Unknown {0}: {1}")]
+    NotFound(ItemKind, String),
+    #[error("Failed to access activity: {0}")]
+    Repository(String),
+}
+
+/// Records plays and keeps the ratings and favorites of tracks, albums and artists, for
+/// each user apart.
+pub struct ActivityService<R> {
+    repository: R,
+    /// Statistics of tracks by identifier for each user, loaded when first needed and
+    /// dropped on changes.
+    stats: RwLock<HashMap<String, Arc<HashMap<String, PlayStats>>>>,
+}
+
+impl<R> ActivityService<R>
+where
+    R: ActivityRepository,
+    R::Error: Display,
+{
+    pub fn new(repository: R) -> Self {
+        Self {
+            repository,
+            stats: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Records a play of an audio of the library.
+    pub fn record_play(
+        &self,
+        user_id: &str,
+        library: &Library,
+        play: Play,
+    ) -> Result<Play, ActivityServiceError> {
+        check_exists(library, ItemKind::Track, &play.audio_id)?;
+        self.repository
+            .record_play(user_id, &play)
+            .map_err(|err| ActivityServiceError::Repository(err.to_string()))?;
+        self.invalidate(user_id);
+        Ok(play)
+    }
+
+    /// Plays from the most recent. Plays of audios no longer in the library are kept.
+    pub fn history(
+        &self,
+        user_id: &str,
+        page: Page,
+    ) -> Result<Paginated<Play>, ActivityServiceError> {
+        let total = self
+            .repository
+            .play_total(user_id)
+            .map_err(|err| ActivityServiceError::Repository(err.to_string()))?;
+        let items = self
+            .repository
+            .plays(user_id, page.offset, page.limit)
+            .map_err(|err| ActivityServiceError::Repository(err.to_string()))?;
+        Ok(Paginated {
+            total,
+            offset: page.offset,
+            items,
+        })
+    }
+
+    /// Statistics of every track that was played, rated or marked as a favorite.
+    pub fn stats(
+        &self,
+        user_id: &str,
+    ) -> Result<Arc<HashMap<String, PlayStats>>, ActivityServiceError> {
+        if let Some(stats) = self.stats.read().expect("stats lock poisoned").get(user_id) {
+            return Ok(Arc::clone(stats));
+        }
+        let mut cached = self.stats.write().expect("stats lock poisoned");
+        if let Some(stats) = cached.get(user_id) {
+            return Ok(Arc::clone(stats));
+        }
+        let stats = Arc::new(
+            self.repository
+                .track_stats(user_id)
+                .map_err(|err| ActivityServiceError::Repository(err.to_string()))?,
+        );
+        cached.insert(user_id.to_string(), Arc::clone(&stats));
+        Ok(stats)
+    }
+
+    /// The annotation of an item, empty when it was never rated nor marked as a favorite.
+    pub fn annotation(
+        &self,
+        user_id: &str,
+        kind: ItemKind,
+        id: &str,
+    ) -> Result<Annotation, ActivityServiceError> {
+        Ok(self
+            .repository
+            .annotation(user_id, kind, id)
+            .map_err(|err| ActivityServiceError::Repository(err.to_string()))?
+            .unwrap_or_else(|| Annotation::new(kind, id)))
+    }
+
+    pub fn annotations(
+        &self,
+        user_id: &str,
+        kind: ItemKind,
+    ) -> Result<Vec<Annotation>, ActivityServiceError> {
+        self.repository
+            .annotations(user_id, kind)
+            .map_err(|err| ActivityServiceError::Repository(err.to_string()))
+    }
+
+    /// Rates an item of the library, or removes its rating.
+    pub fn rate(
+        &self,
+        user_id: &str,
+        library: &Library,
+        kind: ItemKind,
+        id: &str,
+        rating: Option<Rating>,
+    ) -> Result<Annotation, ActivityServiceError> {
+        check_exists(library, kind, id)?;
+        let annotation = Annotation {
+            rating,
+            ..self.annotation(user_id, kind, id)?
+        };
+        self.save(user_id, &annotation)?;
+        Ok(annotation)
+    }
+
+    /// Marks an item of the library as a favorite or not. Marking a favorite again keeps
+    /// the date it was first marked.
+    pub fn favorite(
+        &self,
+        user_id: &str,
+        library: &Library,
+        kind: ItemKind,
+        id: &str,
+        favorite: bool,
+    ) -> Result<Annotation, ActivityServiceError> {
+        check_exists(library, kind, id)?;
+        let annotation = self.annotation(user_id, kind, id)?;
+        let favorited_at = favorite.then(|| annotation.favorited_at.unwrap_or_else(Utc::now));
+        let annotation = Annotation {
+            favorited_at,
+            ..annotation
+        };
+        self.save(user_id, &annotation)?;
+        Ok(annotation)
+    }
+
+    /// Rates the tracks that have a rating in their tags and none given by the user.
+    /// Returns the number of tracks rated.
+    pub fn import_tag_ratings(
+        &self,
+        user_id: &str,
+        library: &Library,
+    ) -> Result<usize, ActivityServiceError> {
+        let stats = self.stats(user_id)?;
+        let mut imported = 0;
+        for audio in library.audios() {
+            let id = audio.id();
+            let rated = stats.get(&id).is_some_and(|stats| stats.rating.is_some());
+            let Some(rating) = (*audio.rating()).filter(|_| !rated) else {
+                continue;
+            };
+            let annotation = Annotation {
+                rating: Some(rating),
+                ..self.annotation(user_id, ItemKind::Track, &id)?
+            };
+            self.repository
+                .save_annotation(user_id, &annotation)
+                .map_err(|err| ActivityServiceError::Repository(err.to_string()))?;
+            imported += 1;
+        }
+        self.invalidate(user_id);
+        Ok(imported)
+    }
+
+    /// Gives the activity recorded before there were users to the user, see
+    /// [`ActivityRepository::adopt_unowned`].
+    pub fn adopt_unowned(&self, user_id: &str) -> Result<usize, ActivityServiceError> {
+        let adopted = self
+            .repository
+            .adopt_unowned(user_id)
+            .map_err(|err| ActivityServiceError::Repository(err.to_string()))?;
+        self.invalidate(user_id);
+        Ok(adopted)
+    }
+
+    fn save(&self, user_id: &str, annotation: &Annotation) -> Result<(), ActivityServiceError> {
+        self.repository
+            .save_annotation(user_id, annotation)
+            .map_err(|err| ActivityServiceError::Repository(err.to_string()))?;
+        self.invalidate(user_id);
+        Ok(())
+    }
+
+    fn invalidate(&self, user_id: &str) {
+        self.stats
+            .write()
+            .expect("stats lock poisoned")
+            .remove(user_id);
+    }
+}
+
+fn check_exists(library: &Library, kind: ItemKind, id: &str) -> Result<(), ActivityServiceError> {
+    let exists = match kind {
+        ItemKind::Track => library.audio(id).is_some(),
+        ItemKind::Album => library.album(id).is_some(),
+        ItemKind::Artist => library.artist(id).is_some(),
+    };
+    if exists {
+        Ok(())
+    } else {
+        Err(ActivityServiceError::NotFound(kind, id.to_string()))
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::PathBuf,
+};
+
+use crate::domain::entity::{
+    album::Album,
+    audio::{artist::Artist, sort_key::SortKeyOptions, title::Title, Audio},
+};
+
+pub struct AlbumServiceOptions {
+    /// Minimum number of distinct track artists sharing an album title inside a single
+    /// folder for it to be considered a compilation when no track carries the flag.
+    pub min_distinct_artists: usize,
+    /// How album artists and titles are ordered in listings.
+    pub sort_key: SortKeyOptions,
+}
+
+impl Default for AlbumServiceOptions {
+    fn default() -> Self {
+        Self {
+            min_distinct_artists: 3,
+            sort_key: SortKeyOptions::default(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct AlbumService {
+    options: AlbumServiceOptions,
+}
+
+impl AlbumService {
+    pub fn new(options: AlbumServiceOptions) -> Self {
+        Self { options }
+    }
+
+    /// Groups audios into albums. Tracks are first grouped by folder and album title; each
+    /// group is then either kept together as a "Various Artists" compilation or split by
+    /// album artist. Tracks without an album artist join the album of the group when its
+    /// other tracks share a single one, and are split by track artist otherwise.
+    /// Albums are ordered by the sort keys of their artist and title.
+    pub fn group(&self, audios: impl IntoIterator<Item = Audio>) -> Vec<Album> {
+        let mut folders: BTreeMap<(PathBuf, String), Vec<Audio>> = BTreeMap::new();
+        for audio in audios {
+            let folder = audio
+                .path()
+                .parent()
+                .map(|parent| parent.to_path_buf())
+                .unwrap_or_default();
+            let key = (folder, audio.album_title().name().clone());
+            folders.entry(key).or_default().push(audio);
+        }
+
+        let mut albums = folders
+            .into_values()
+            .flat_map(|tracks| self.group_folder(tracks))
+            .collect::<Vec<_>>();
+        albums.sort_by_cached_key(|album| {
+            (
+                album.artist().sort_key(&self.options.sort_key),
+                album.title().sort_key(&self.options.sort_key),
+            )
+        });
+        albums
+    }
+
+    fn group_folder(&self, tracks: Vec<Audio>) -> Vec<Album> {
+        let album_title = tracks
+            .first()
+            .map(|track| track.album_title().clone())
+            .unwrap_or_default();
+
+        if self.is_compilation(&tracks) {
+            return vec![Album::new(album_title, Artist::various(), true, tracks)];
+        }
+
+        let shared_album_artist = Self::shared_album_artist(&tracks);
+        let mut by_artist: BTreeMap<String, (Artist, Vec<Audio>)> = BTreeMap::new();
+        for track in tracks {
+            let artist = match &shared_album_artist {
+                Some(album_artist) if track.album_artist().is_unknown() => album_artist,
+                _ => Self::effective_album_artist(&track),
+            };
+            by_artist
+                .entry(artist.name().clone())
+                .or_insert_with(|| (artist.clone(), Vec::new()))
+                .1
+                .push(track);
+        }
+
+        by_artist
+            .into_values()
+            .map(|(artist, tracks)| Album::new(album_title.clone(), artist, false, tracks))
+            .collect()
+    }
+
+    /// The album artist of the tracks of an album when they all credit the same one, or
+    /// none at all.
+    fn shared_album_artist(tracks: &[Audio]) -> Option<Artist> {
+        if *tracks[0].album_title() == Title::default() {
+            return None;
+        }
+        let mut album_artists = tracks
+            .iter()
+            .map(|track| track.album_artist())
+            .filter(|album_artist| !album_artist.is_unknown());
+        let first = album_artists.next()?;
+        album_artists
+            .all(|album_artist| album_artist == first)
+            .then(|| first.clone())
+    }
+
+    fn is_compilation(&self, tracks: &[Audio]) -> bool {
+        if tracks.iter().any(|track| *track.compilation()) {
+            return true;
+        }
+
+        if *tracks[0].album_title() == Title::default() {
+            return false;
+        }
+
+        let album_artists = tracks
+            .iter()
+            .map(|track| track.album_artist())
+            .filter(|album_artist| !album_artist.is_unknown())
+            .collect::<HashSet<_>>();
+        if album_artists.len() == 1 {
+            return false;
+        }
+
+        let artists = tracks
+            .iter()
+            .map(|track| track.artist())
+            .filter(|artist| !artist.is_unknown())
+            .collect::<HashSet<_>>();
+        artists.len() >= self.options.min_distinct_artists
+    }
+
+    fn effective_album_artist(track: &Audio) -> &Artist {
+        if track.album_artist().is_unknown() {
+            track.artist()
+        } else {
+            track.album_artist()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::entity::audio::{cover::Cover, genre::Genre, AudioBuilder};
+
+    use super::*;
+
+    /// Empty names stand for unknown ones.
+    fn audio(
+        path: &str,
+        album: &str,
+        artist: &str,
+        album_artist: &str,
+        compilation: bool,
+    ) -> Audio {
+        AudioBuilder::default()
+            .title(Title::default())
+            .artist(artist.parse::<Artist>().unwrap_or_default())
+            .year(None)
+            .album_title(album.parse::<Title>().unwrap_or_default())
+            .album_artist(album_artist.parse::<Artist>().unwrap_or_default())
+            .album_cover(Cover::default())
+            .genre(Genre::default())
+            .track_number(None)
+            .disc_number(None)
+            .compilation(compilation)
+            .path(PathBuf::from(path))
+            .build()
+            .unwrap()
+    }
+
+    fn summary(albums: &[Album]) -> Vec<(String, String, bool, usize)> {
+        albums
+            .iter()
+            .map(|album| {
+                (
+                    album.artist().name().clone(),
+                    album.title().name().clone(),
+                    *album.compilation(),
+                    album.tracks().len(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn groups_various_artists_compilations() {
+        let albums = AlbumService::default().group([
+            audio("/m/Hits/1.mp3", "Hits", "Abba", "", false),
+            audio("/m/Hits/2.mp3", "Hits", "Blur", "", false),
+            audio("/m/Hits/3.mp3", "Hits", "Cream", "", false),
+            // Two artists are not enough without the flag
+            audio("/m/Duets/1.mp3", "Duets", "Abba", "", false),
+            audio("/m/Duets/2.mp3", "Duets", "Blur", "", false),
+            // The flag of a single track makes a compilation
+            audio("/m/Live/1.mp3", "Live", "Abba", "Abba", true),
+            audio("/m/Live/2.mp3", "Live", "Blur", "Abba", false),
+        ]);
+        assert_eq!(
+            summary(&albums),
+            [
+                ("Abba".to_string(), "Duets".to_string(), false, 1),
+                ("Blur".to_string(), "Duets".to_string(), false, 1),
+                ("Various Artists".to_string(), "Hits".to_string(), true, 3),
+                ("Various Artists".to_string(), "Live".to_string(), true, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn shared_album_artist_keeps_album_together() {
+        let albums = AlbumService::default().group([
+            audio("/m/Tribute/1.mp3", "Tribute", "Abba", "Band", false),
+            audio("/m/Tribute/2.mp3", "Tribute", "Blur", "Band", false),
+            audio("/m/Tribute/3.mp3", "Tribute", "Cream", "", false),
+            // Same title in another folder is another album
+            audio("/m/Other/1.mp3", "Tribute", "Abba", "Band", false),
+            // Without a single album artist, the track artist is used
+            audio("/m/Split/1.mp3", "Split", "Abba", "Abba", false),
+            audio("/m/Split/2.mp3", "Split", "Blur", "Blur", false),
+            audio("/m/Split/3.mp3", "Split", "Abba", "", false),
+            // Loose tracks without an album are never a compilation
+            audio("/m/1.mp3", "", "Abba", "", false),
+            audio("/m/2.mp3", "", "Blur", "", false),
+            audio("/m/3.mp3", "", "Cream", "", false),
+        ]);
+        let unknown = Title::default().name().clone();
+        assert_eq!(
+            summary(&albums),
+            [
+                ("Abba".to_string(), "Split".to_string(), false, 2),
+                ("Abba".to_string(), unknown.clone(), false, 1),
+                ("Band".to_string(), "Tribute".to_string(), false, 1),
+                ("Band".to_string(), "Tribute".to_string(), false, 3),
+                ("Blur".to_string(), "Split".to_string(), false, 1),
+                ("Blur".to_string(), unknown.clone(), false, 1),
+                ("Cream".to_string(), unknown, false, 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::cmp::Ordering;
+
+use thiserror::Error;
+
+use crate::domain::entity::audio::{sort_key::SortKeyOptions, Audio};
+
+use super::SortOrder;
+
+mod filter;
+mod parser;
+mod sql;
+
+pub use filter::{
+    Comparison, Filter, FlagField, NumberField, Predicate, QueryField, TextField, TextOperator,
+};
+pub use sql::{SqlQuery, SqlValue, FOLD_FUNCTION};
+
+/// Errors of the query syntax. Positions count the characters before the term at fault.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum AudioQueryError {
+    #[error("Unknown field {name} at position {position}")]
+    UnknownField { name: String, position: usize },
+    #[error("Invalid value for {field} at position {position}: {value}")]
+    InvalidValue {
+        field: String,
+        value: String,
+        position: usize,
+    },
+    #[error("Unexpected {token} at position {position}")]
+    Unexpected { token: String, position: usize },
+    #[error("Unexpected end of query at position {0}")]
+    UnexpectedEnd(usize),
+    #[error("Unterminated quote at position {0}")]
+    UnterminatedQuote(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuerySort {
+    pub field: QueryField,
+    pub order: SortOrder,
+}
+
+/// Selects, orders and limits audios. Queries run the same against audios in memory and,
+/// translated with [`Self::to_sql`], against the stored library.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AudioQuery {
+    pub filter: Filter,
+    /// Audios equal on every key are ordered by path.
+    pub sort: Vec<QuerySort>,
+    pub limit: Option<usize>,
+}
+
+impl AudioQuery {
+    pub fn new(filter: Filter) -> Self {
+        Self {
+            filter,
+            ..Self::default()
+        }
+    }
+
+    pub fn sort_by(mut self, field: QueryField, order: SortOrder) -> Self {
+        self.sort.push(QuerySort { field, order });
+        self
+    }
+
+    pub fn limit(self, limit: usize) -> Self {
+        Self {
+            limit: Some(limit),
+            ..self
+        }
+    }
+
+    /// Parses the query syntax, for example
+    /// `format:flac year:1990..1999 missing:cover sort:-year limit:20`:
+    ///
+    /// - `field:value` matches text fields containing the value, numbers equal to it, within
+    ///   a range `min..max` or compared with `>`, `>=`, `<` or `<=`, as in `year:>2000` or
+    ///   `year>2000`, and flags `true` or `false`
+    /// - `field=value` matches text fields equal to the value
+    /// - `has:field` and `missing:field` match fields that are set or not
+    /// - conditions are combined with `and`, which may be left out, `or`, `not` or a leading
+    ///   `-`, and grouped with parentheses
+    /// - `sort:field`, or `sort:-field` for descending order, and `limit:n` apply to the
+    ///   whole query
+    ///
+    /// Text is compared case and diacritics insensitively and values with spaces are quoted.
+    pub fn parse(query: &str) -> Result<Self, AudioQueryError> {
+        parser::parse(query)
+    }
+
+    /// Returns the matching audios, ordered and limited.
+    pub fn execute<'a>(&self, audios: impl IntoIterator<Item = &'a Audio>) -> Vec<&'a Audio> {
+        let mut audios = audios
+            .into_iter()
+            .filter(|audio| self.filter.matches(audio))
+            .collect::<Vec<_>>();
+        audios.sort_by(|a, b| self.compare(a, b));
+        if let Some(limit) = self.limit {
+            audios.truncate(limit);
+        }
+        audios
+    }
+
+    pub fn to_sql(&self) -> SqlQuery {
+        sql::translate(self)
+    }
+
+    fn compare(&self, a: &Audio, b: &Audio) -> Ordering {
+        self.sort
+            .iter()
+            .map(|sort| {
+                let (a, b) = (sort.field.sort_value(a), sort.field.sort_value(b));
+                match (a, b) {
+                    // Missing values come last in both orders
+                    (None, None) => Ordering::Equal,
+                    (None, Some(_)) => Ordering::Greater,
+                    (Some(_), None) => Ordering::Less,
+                    (Some(a), Some(b)) => match sort.order {
+                        SortOrder::Ascending => a.cmp(&b),
+                        SortOrder::Descending => b.cmp(&a),
+                    },
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.path().to_string_lossy().cmp(&b.path().to_string_lossy()))
+    }
+}
+
+/// Lower cases and removes diacritics, so that text compares case and diacritics
+/// insensitively.
+pub fn fold(text: &str) -> String {
+    let options = SortKeyOptions {
+        articles: Vec::new(),
+        fold_diacritics: true,
+    };
+    options.normalize(text)
+}
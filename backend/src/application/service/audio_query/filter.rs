@@ -0,0 +1,286 @@
+use std::{cmp::Ordering, ops::Not};
+
+use crate::domain::entity::audio::{artist::Artist, genre::Genre, title::Title, Audio};
+
+use super::fold;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextField {
+    Title,
+    Artist,
+    AlbumTitle,
+    AlbumArtist,
+    Genre,
+    /// Lowercase file extension.
+    Format,
+    Path,
+    Lyrics,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NumberField {
+    Year,
+    TrackNumber,
+    DiscNumber,
+    /// In seconds.
+    Duration,
+    /// In kbit/s.
+    Bitrate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FlagField {
+    Compilation,
+    /// Whether the audio has a cover.
+    Cover,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QueryField {
+    Text(TextField),
+    Number(NumberField),
+    Flag(FlagField),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextOperator {
+    Contains,
+    Equals,
+    StartsWith,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+/// A condition on a single field. Conditions on a missing number never match.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    /// Compares case and diacritics insensitively.
+    Text {
+        field: TextField,
+        operator: TextOperator,
+        value: String,
+    },
+    Number {
+        field: NumberField,
+        comparison: Comparison,
+        value: f64,
+    },
+    Flag {
+        field: FlagField,
+        value: bool,
+    },
+    /// Numbers that are not set, text fields with their default value and audios that are
+    /// not compilations or have no cover.
+    Missing(QueryField),
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Filter {
+    /// Matches every audio.
+    #[default]
+    All,
+    Predicate(Predicate),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    pub fn text(field: TextField, operator: TextOperator, value: &str) -> Self {
+        Self::Predicate(Predicate::Text {
+            field,
+            operator,
+            value: value.to_owned(),
+        })
+    }
+
+    pub fn number(field: NumberField, comparison: Comparison, value: f64) -> Self {
+        Self::Predicate(Predicate::Number {
+            field,
+            comparison,
+            value,
+        })
+    }
+
+    pub fn flag(field: FlagField, value: bool) -> Self {
+        Self::Predicate(Predicate::Flag { field, value })
+    }
+
+    pub fn missing(field: QueryField) -> Self {
+        Self::Predicate(Predicate::Missing(field))
+    }
+
+    pub fn and(self, other: Filter) -> Self {
+        match self {
+            Self::All => other,
+            Self::And(mut filters) => {
+                filters.push(other);
+                Self::And(filters)
+            }
+            filter => Self::And(vec![filter, other]),
+        }
+    }
+
+    pub fn or(self, other: Filter) -> Self {
+        match self {
+            Self::Or(mut filters) => {
+                filters.push(other);
+                Self::Or(filters)
+            }
+            filter => Self::Or(vec![filter, other]),
+        }
+    }
+
+    pub fn matches(&self, audio: &Audio) -> bool {
+        match self {
+            Self::All => true,
+            Self::Predicate(predicate) => predicate.matches(audio),
+            Self::And(filters) => filters.iter().all(|filter| filter.matches(audio)),
+            Self::Or(filters) => filters.iter().any(|filter| filter.matches(audio)),
+            Self::Not(filter) => !filter.matches(audio),
+        }
+    }
+}
+
+impl Not for Filter {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        match self {
+            Self::Not(filter) => *filter,
+            filter => Self::Not(Box::new(filter)),
+        }
+    }
+}
+
+impl Predicate {
+    pub fn matches(&self, audio: &Audio) -> bool {
+        match self {
+            Self::Text {
+                field,
+                operator,
+                value,
+            } => {
+                let text = fold(&field.value(audio));
+                let value = fold(value);
+                match operator {
+                    TextOperator::Contains => text.contains(&value),
+                    TextOperator::Equals => text == value,
+                    TextOperator::StartsWith => text.starts_with(&value),
+                }
+            }
+            Self::Number {
+                field,
+                comparison,
+                value,
+            } => field.value(audio).is_some_and(|number| match comparison {
+                Comparison::Equal => number == *value,
+                Comparison::Less => number < *value,
+                Comparison::LessOrEqual => number <= *value,
+                Comparison::Greater => number > *value,
+                Comparison::GreaterOrEqual => number >= *value,
+            }),
+            Self::Flag { field, value } => field.value(audio) == *value,
+            Self::Missing(QueryField::Text(field)) => field.is_missing(audio),
+            Self::Missing(QueryField::Number(field)) => field.value(audio).is_none(),
+            Self::Missing(QueryField::Flag(field)) => !field.value(audio),
+        }
+    }
+}
+
+impl TextField {
+    pub(super) fn value(&self, audio: &Audio) -> String {
+        match self {
+            Self::Title => audio.title().name().clone(),
+            Self::Artist => audio.artist().name().clone(),
+            Self::AlbumTitle => audio.album_title().name().clone(),
+            Self::AlbumArtist => audio.album_artist().name().clone(),
+            Self::Genre => audio.genre().name().clone(),
+            Self::Format => audio
+                .path()
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+            Self::Path => audio.path().to_string_lossy().into_owned(),
+            Self::Lyrics => audio.lyrics().clone().unwrap_or_default(),
+        }
+    }
+
+    /// The value text fields have when they are not tagged.
+    pub(super) fn default_value(&self) -> String {
+        match self {
+            Self::Title | Self::AlbumTitle => Title::default().name().clone(),
+            Self::Artist | Self::AlbumArtist => Artist::default().name().clone(),
+            Self::Genre => Genre::default().name().clone(),
+            Self::Format | Self::Path | Self::Lyrics => String::new(),
+        }
+    }
+
+    fn is_missing(&self, audio: &Audio) -> bool {
+        self.value(audio) == self.default_value()
+    }
+}
+
+impl NumberField {
+    pub(super) fn value(&self, audio: &Audio) -> Option<f64> {
+        match self {
+            Self::Year => audio.year().map(|year| year.0 as f64),
+            Self::TrackNumber => audio.track_number().map(f64::from),
+            Self::DiscNumber => audio.disc_number().map(f64::from),
+            Self::Duration => audio.duration().map(|duration| duration.as_secs_f64()),
+            Self::Bitrate => audio.bitrate().map(f64::from),
+        }
+    }
+}
+
+impl FlagField {
+    pub(super) fn value(&self, audio: &Audio) -> bool {
+        match self {
+            Self::Compilation => *audio.compilation(),
+            Self::Cover => !audio.album_cover().is_default(),
+        }
+    }
+}
+
+/// A value audios are ordered by, `None` when missing.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum SortValue {
+    Text(String),
+    Number(f64),
+}
+
+impl Eq for SortValue {}
+
+impl PartialOrd for SortValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SortValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Text(a), Self::Text(b)) => a.cmp(b),
+            (Self::Number(a), Self::Number(b)) => a.total_cmp(b),
+            (Self::Text(_), Self::Number(_)) => Ordering::Greater,
+            (Self::Number(_), Self::Text(_)) => Ordering::Less,
+        }
+    }
+}
+
+impl QueryField {
+    pub(super) fn sort_value(&self, audio: &Audio) -> Option<SortValue> {
+        match self {
+            Self::Text(field) => Some(SortValue::Text(fold(&field.value(audio)))),
+            Self::Number(field) => field.value(audio).map(SortValue::Number),
+            Self::Flag(field) => Some(SortValue::Number(f64::from(u8::from(field.value(audio))))),
+        }
+    }
+}
@@ -0,0 +1,496 @@
+use std::{iter::Peekable, vec::IntoIter};
+
+use super::{
+    AudioQuery, AudioQueryError, Comparison, Filter, FlagField, NumberField, QueryField, QuerySort,
+    SortOrder, TextField, TextOperator,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    /// A term with its quotes removed, along with where the first quoted part starts.
+    /// Quoted terms are never keywords.
+    Term {
+        text: String,
+        quote: Option<usize>,
+    },
+}
+
+impl Token {
+    fn keyword(&self) -> Option<String> {
+        match self {
+            Self::Term { text, quote: None } => Some(text.to_lowercase()),
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::Open => "(".to_owned(),
+            Self::Close => ")".to_owned(),
+            Self::Term { text, .. } => format!("\"{text}\""),
+        }
+    }
+}
+
+pub(super) fn parse(query: &str) -> Result<AudioQuery, AudioQueryError> {
+    let mut sort = Vec::new();
+    let mut limit = None;
+    let mut tokens = Vec::new();
+    for (position, token) in tokenize(query)? {
+        if let Token::Term { text, quote: None } = &token {
+            if let Some(name) = text.strip_prefix("sort:") {
+                let (name, order) = match name.strip_prefix('-') {
+                    Some(name) => (name, SortOrder::Descending),
+                    None => (name, SortOrder::Ascending),
+                };
+                sort.push(QuerySort {
+                    field: field(name, position)?,
+                    order,
+                });
+                continue;
+            }
+            if let Some(value) = text.strip_prefix("limit:") {
+                limit = Some(
+                    value
+                        .parse()
+                        .map_err(|_| invalid("limit", value, position))?,
+                );
+                continue;
+            }
+        }
+        tokens.push((position, token));
+    }
+
+    let mut parser = Parser {
+        tokens: tokens.into_iter().peekable(),
+        end: query.chars().count(),
+    };
+    let filter = if parser.tokens.peek().is_none() {
+        Filter::All
+    } else {
+        parser.or()?
+    };
+    if let Some((position, token)) = parser.tokens.next() {
+        return Err(unexpected(&token, position));
+    }
+    Ok(AudioQuery {
+        filter,
+        sort,
+        limit,
+    })
+}
+
+/// Splits the query into tokens, along with the position of their first character.
+fn tokenize(query: &str) -> Result<Vec<(usize, Token)>, AudioQueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().enumerate().peekable();
+    while let Some((position, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push((position, Token::Open)),
+            ')' => tokens.push((position, Token::Close)),
+            c => {
+                let mut text = String::new();
+                let mut quote = None;
+                let mut next = Some((position, c));
+                while let Some((start, c)) = next {
+                    if c == '"' {
+                        quote = quote.or(Some(text.len()));
+                        loop {
+                            match chars.next() {
+                                Some((_, '"')) => break,
+                                Some((_, c)) => text.push(c),
+                                None => return Err(AudioQueryError::UnterminatedQuote(start)),
+                            }
+                        }
+                    } else {
+                        text.push(c);
+                    }
+                    next = chars.next_if(|(_, c)| !c.is_whitespace() && *c != '(' && *c != ')');
+                }
+                tokens.push((position, Token::Term { text, quote }));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Peekable<IntoIter<(usize, Token)>>,
+    /// Position of the end of the query.
+    end: usize,
+}
+
+impl Parser {
+    fn or(&mut self) -> Result<Filter, AudioQueryError> {
+        let mut filter = self.and()?;
+        while self.next_keyword_is("or") {
+            self.tokens.next();
+            filter = filter.or(self.and()?);
+        }
+        Ok(filter)
+    }
+
+    /// Conditions next to each other are combined with `and`.
+    fn and(&mut self) -> Result<Filter, AudioQueryError> {
+        let mut filters = vec![self.unary()?];
+        while !matches!(self.tokens.peek(), None | Some((_, Token::Close)))
+            && !self.next_keyword_is("or")
+        {
+            if self.next_keyword_is("and") {
+                self.tokens.next();
+            }
+            filters.push(self.unary()?);
+        }
+        Ok(if filters.len() == 1 {
+            filters.remove(0)
+        } else {
+            Filter::And(filters)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Filter, AudioQueryError> {
+        let (position, token) = self
+            .tokens
+            .next()
+            .ok_or(AudioQueryError::UnexpectedEnd(self.end))?;
+        match token {
+            Token::Open => {
+                let filter = self.or()?;
+                match self.tokens.next() {
+                    Some((_, Token::Close)) => Ok(filter),
+                    Some((position, token)) => Err(unexpected(&token, position)),
+                    None => Err(AudioQueryError::UnexpectedEnd(self.end)),
+                }
+            }
+            Token::Close => Err(unexpected(&token, position)),
+            _ if matches!(token.keyword().as_deref(), Some("not" | "-")) => Ok(!self.unary()?),
+            _ if matches!(token.keyword().as_deref(), Some("and" | "or")) => {
+                Err(unexpected(&token, position))
+            }
+            Token::Term { text, quote }
+                if text.len() > 1 && text.starts_with('-') && quote != Some(0) =>
+            {
+                Ok(!predicate(
+                    &text[1..],
+                    quote.map(|quote| quote - 1),
+                    position + 1,
+                )?)
+            }
+            Token::Term { text, quote } => predicate(&text, quote, position),
+        }
+    }
+
+    fn next_keyword_is(&mut self, keyword: &str) -> bool {
+        self.tokens
+            .peek()
+            .and_then(|(_, token)| token.keyword())
+            .is_some_and(|next| next == keyword)
+    }
+}
+
+/// Parses `field:value`, `field=value`, `field>value` and the like, `has:field` and
+/// `missing:field`, found at `position` of the query. Other terms match the title, artist or
+/// album title. Separators within quotes are part of the value.
+fn predicate(term: &str, quote: Option<usize>, position: usize) -> Result<Filter, AudioQueryError> {
+    let unquoted = &term[..quote.unwrap_or(term.len())];
+    let Some(separator) = unquoted.find([':', '=', '<', '>']) else {
+        return Ok(anywhere(term));
+    };
+    let name = &term[..separator];
+    let equals = term[separator..].starts_with('=');
+    // Comparisons may follow the field name directly, as in `year>2000`
+    let value = match &term[separator..] {
+        compared if compared.starts_with(['<', '>']) => compared,
+        separated => &separated[1..],
+    };
+    match name.to_lowercase().as_str() {
+        "has" => return Ok(!Filter::missing(field(value, position)?)),
+        "missing" => return Ok(Filter::missing(field(value, position)?)),
+        _ => {}
+    }
+    match field(name, position)? {
+        QueryField::Text(_) if value.starts_with(['<', '>']) => Err(invalid(name, value, position)),
+        QueryField::Text(field) => {
+            let (operator, value) = if equals {
+                (TextOperator::Equals, value)
+            } else if let Some(prefix) = value.strip_suffix('*') {
+                (TextOperator::StartsWith, prefix)
+            } else {
+                (TextOperator::Contains, value)
+            };
+            Ok(Filter::text(field, operator, value))
+        }
+        QueryField::Number(field) => number(name, field, value, equals, position),
+        QueryField::Flag(field) => {
+            let value = match value.to_lowercase().as_str() {
+                "true" | "yes" | "1" => true,
+                "false" | "no" | "0" => false,
+                _ => return Err(invalid(name, value, position)),
+            };
+            Ok(Filter::flag(field, value))
+        }
+    }
+}
+
+fn anywhere(value: &str) -> Filter {
+    [TextField::Title, TextField::Artist, TextField::AlbumTitle]
+        .into_iter()
+        .map(|field| Filter::text(field, TextOperator::Contains, value))
+        .reduce(Filter::or)
+        .expect("fields are not empty")
+}
+
+/// Parses `n`, `min..max`, `>n`, `>=n`, `<n` and `<=n`.
+fn number(
+    name: &str,
+    field: NumberField,
+    value: &str,
+    equals: bool,
+    position: usize,
+) -> Result<Filter, AudioQueryError> {
+    let parse =
+        |text: &str| number_value(field, text.trim()).ok_or_else(|| invalid(name, value, position));
+    if equals {
+        return Ok(Filter::number(field, Comparison::Equal, parse(value)?));
+    }
+    if let Some((min, max)) = value.split_once("..") {
+        return Ok(
+            Filter::number(field, Comparison::GreaterOrEqual, parse(min)?).and(Filter::number(
+                field,
+                Comparison::LessOrEqual,
+                parse(max)?,
+            )),
+        );
+    }
+    let (comparison, value) = [
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+    ]
+    .into_iter()
+    .find_map(|(prefix, comparison)| value.strip_prefix(prefix).map(|value| (comparison, value)))
+    .unwrap_or((Comparison::Equal, value));
+    Ok(Filter::number(field, comparison, parse(value)?))
+}
+
+/// Durations may also be written `minutes:seconds`.
+fn number_value(field: NumberField, value: &str) -> Option<f64> {
+    if field == NumberField::Duration {
+        if let Some((minutes, seconds)) = value.split_once(':') {
+            let minutes = minutes.parse::<u32>().ok()?;
+            let seconds = seconds
+                .parse::<f64>()
+                .ok()
+                .filter(|seconds| *seconds < 60.0)?;
+            return Some(minutes as f64 * 60.0 + seconds);
+        }
+    }
+    value.parse::<f64>().ok().filter(|value| value.is_finite())
+}
+
+fn field(name: &str, position: usize) -> Result<QueryField, AudioQueryError> {
+    let field = match name.to_lowercase().as_str() {
+        "title" => QueryField::Text(TextField::Title),
+        "artist" => QueryField::Text(TextField::Artist),
+        "album" | "album_title" => QueryField::Text(TextField::AlbumTitle),
+        "album_artist" | "albumartist" => QueryField::Text(TextField::AlbumArtist),
+        "genre" => QueryField::Text(TextField::Genre),
+        "format" | "extension" => QueryField::Text(TextField::Format),
+        "path" => QueryField::Text(TextField::Path),
+        "lyrics" => QueryField::Text(TextField::Lyrics),
+        "year" => QueryField::Number(NumberField::Year),
+        "track" | "track_number" => QueryField::Number(NumberField::TrackNumber),
+        "disc" | "disc_number" => QueryField::Number(NumberField::DiscNumber),
+        "duration" => QueryField::Number(NumberField::Duration),
+        "bitrate" => QueryField::Number(NumberField::Bitrate),
+        "compilation" => QueryField::Flag(FlagField::Compilation),
+        "cover" => QueryField::Flag(FlagField::Cover),
+        _ => {
+            return Err(AudioQueryError::UnknownField {
+                name: name.to_owned(),
+                position,
+            })
+        }
+    };
+    Ok(field)
+}
+
+fn invalid(field: &str, value: &str, position: usize) -> AudioQueryError {
+    AudioQueryError::InvalidValue {
+        field: field.to_owned(),
+        value: value.to_owned(),
+        position,
+    }
+}
+
+fn unexpected(token: &Token, position: usize) -> AudioQueryError {
+    AudioQueryError::Unexpected {
+        token: token.describe(),
+        position,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(query: &str) -> Filter {
+        parse(query).unwrap().filter
+    }
+
+    fn artist(value: &str) -> Filter {
+        Filter::text(TextField::Artist, TextOperator::Contains, value)
+    }
+
+    fn year(comparison: Comparison, value: f64) -> Filter {
+        Filter::number(NumberField::Year, comparison, value)
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            filter("artist:a or artist:b artist:c"),
+            artist("a").or(Filter::And(vec![artist("b"), artist("c")]))
+        );
+        assert_eq!(
+            filter("artist:a or artist:b and artist:c"),
+            filter("artist:a or (artist:b artist:c)")
+        );
+        assert_eq!(
+            filter("(artist:a or artist:b) artist:c"),
+            Filter::And(vec![artist("a").or(artist("b")), artist("c")])
+        );
+        assert_eq!(
+            filter("not artist:a -artist:b artist:c"),
+            Filter::And(vec![!artist("a"), !artist("b"), artist("c")])
+        );
+        assert_eq!(
+            filter("- (artist:a or artist:b)"),
+            !artist("a").or(artist("b"))
+        );
+        assert_eq!(filter(""), Filter::All);
+    }
+
+    #[test]
+    fn parses_ranges_and_comparisons() {
+        assert_eq!(
+            filter("year:1990..1999"),
+            year(Comparison::GreaterOrEqual, 1990.0).and(year(Comparison::LessOrEqual, 1999.0))
+        );
+        assert_eq!(filter("year:>2000"), year(Comparison::Greater, 2000.0));
+        assert_eq!(
+            filter("year>=2000"),
+            year(Comparison::GreaterOrEqual, 2000.0)
+        );
+        assert_eq!(filter("year<2000"), year(Comparison::Less, 2000.0));
+        assert_eq!(filter("year=2000"), year(Comparison::Equal, 2000.0));
+        assert_eq!(
+            filter("duration:<=3:30"),
+            Filter::number(NumberField::Duration, Comparison::LessOrEqual, 210.0)
+        );
+        assert_eq!(
+            filter("compilation:yes missing:cover has:year"),
+            Filter::And(vec![
+                Filter::flag(FlagField::Compilation, true),
+                Filter::missing(QueryField::Flag(FlagField::Cover)),
+                !Filter::missing(QueryField::Number(NumberField::Year)),
+            ])
+        );
+    }
+
+    #[test]
+    fn quotes_keep_separators_and_keywords() {
+        assert_eq!(filter("artist:\"Miles Davis\""), artist("Miles Davis"));
+        assert_eq!(filter("artist:\"a:b (live)\""), artist("a:b (live)"));
+        assert_eq!(
+            filter("title=\"So What\""),
+            Filter::text(TextField::Title, TextOperator::Equals, "So What")
+        );
+        assert_eq!(filter("\"or\""), anywhere("or"));
+        // Only the unquoted part names the field
+        assert_eq!(filter("\"year:2000\""), anywhere("year:2000"));
+        assert_eq!(
+            filter("genre:jazz*"),
+            Filter::text(TextField::Genre, TextOperator::StartsWith, "jazz")
+        );
+    }
+
+    #[test]
+    fn parses_sort_and_limit() {
+        let query = parse("format:flac sort:-year sort:title limit:20").unwrap();
+        assert_eq!(
+            query.sort,
+            [
+                QuerySort {
+                    field: QueryField::Number(NumberField::Year),
+                    order: SortOrder::Descending,
+                },
+                QuerySort {
+                    field: QueryField::Text(TextField::Title),
+                    order: SortOrder::Ascending,
+                },
+            ]
+        );
+        assert_eq!(query.limit, Some(20));
+        assert_eq!(
+            query.filter,
+            Filter::text(TextField::Format, TextOperator::Contains, "flac")
+        );
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        let error = |query: &str| parse(query).unwrap_err();
+        assert_eq!(
+            error("year:1990 colour:red"),
+            AudioQueryError::UnknownField {
+                name: "colour".to_owned(),
+                position: 10,
+            }
+        );
+        assert_eq!(
+            error("é -year:abc"),
+            AudioQueryError::InvalidValue {
+                field: "year".to_owned(),
+                value: "abc".to_owned(),
+                position: 3,
+            }
+        );
+        assert_eq!(
+            error("artist:a limit:many"),
+            AudioQueryError::InvalidValue {
+                field: "limit".to_owned(),
+                value: "many".to_owned(),
+                position: 9,
+            }
+        );
+        assert_eq!(
+            error("artist:a or or"),
+            AudioQueryError::Unexpected {
+                token: "\"or\"".to_owned(),
+                position: 12,
+            }
+        );
+        assert_eq!(
+            error("artist:a)"),
+            AudioQueryError::Unexpected {
+                token: ")".to_owned(),
+                position: 8,
+            }
+        );
+        assert_eq!(error("(artist:a"), AudioQueryError::UnexpectedEnd(9));
+        assert_eq!(error("artist:a not"), AudioQueryError::UnexpectedEnd(12));
+        assert_eq!(
+            error("title:\"So What"),
+            AudioQueryError::UnterminatedQuote(6)
+        );
+        assert_eq!(
+            error("year:1990 colour:red").to_string(),
+            "Unknown field colour at position 10"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use super::{
+    fold, AudioQuery, Comparison, Filter, FlagField, NumberField, Predicate, QueryField, SortOrder,
+    TextField, TextOperator,
+};
+
+/// SQL function the database must provide, applying [`fold`] to its argument, so that text
+/// compares exactly as it does in memory.
+pub const FOLD_FUNCTION: &str = "earr_fold";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    Text(String),
+    Integer(i64),
+    Real(f64),
+}
+
+/// A query over the `tracks` table, whose positional parameters are bound in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlQuery {
+    pub condition: String,
+    pub order_by: String,
+    pub limit: Option<usize>,
+    pub params: Vec<SqlValue>,
+}
+
+impl SqlQuery {
+    /// The complete statement selecting `columns` from the matching tracks.
+    pub fn select(&self, columns: &str) -> String {
+        let mut statement = format!(
+            "SELECT {columns} FROM tracks WHERE {} ORDER BY {}",
+            self.condition, self.order_by
+        );
+        if let Some(limit) = self.limit {
+            statement.push_str(&format!(" LIMIT {limit}"));
+        }
+        statement
+    }
+}
+
+pub(super) fn translate(query: &AudioQuery) -> SqlQuery {
+    let mut params = Vec::new();
+    let condition = condition(&query.filter, &mut params);
+    let order_by = query
+        .sort
+        .iter()
+        .map(|sort| {
+            let order = match sort.order {
+                SortOrder::Ascending => "ASC",
+                SortOrder::Descending => "DESC",
+            };
+            match sort.field {
+                QueryField::Text(field) => {
+                    format!("{FOLD_FUNCTION}({}) {order}", text_column(field))
+                }
+                // Missing values come last in both orders
+                QueryField::Number(field) => {
+                    let column = number_column(field);
+                    format!("{column} IS NULL, {column} {order}")
+                }
+                QueryField::Flag(field) => format!("{} {order}", flag_expression(field)),
+            }
+        })
+        .chain(["path".to_owned()])
+        .collect::<Vec<_>>()
+        .join(", ");
+    SqlQuery {
+        condition,
+        order_by,
+        limit: query.limit,
+        params,
+    }
+}
+
+/// Every expression is either true or false, never NULL, so that negations match the
+/// audios the negated filter does not.
+fn condition(filter: &Filter, params: &mut Vec<SqlValue>) -> String {
+    let join = |filters: &[Filter], operator: &str, params: &mut Vec<SqlValue>| {
+        filters
+            .iter()
+            .map(|filter| format!("({})", condition(filter, params)))
+            .collect::<Vec<_>>()
+            .join(operator)
+    };
+    match filter {
+        Filter::All => "1".to_owned(),
+        Filter::And(filters) if filters.is_empty() => "1".to_owned(),
+        Filter::Or(filters) if filters.is_empty() => "0".to_owned(),
+        Filter::And(filters) => join(filters, " AND ", params),
+        Filter::Or(filters) => join(filters, " OR ", params),
+        Filter::Not(filter) => format!("NOT ({})", condition(filter, params)),
+        Filter::Predicate(predicate) => predicate_condition(predicate, params),
+    }
+}
+
+fn predicate_condition(predicate: &Predicate, params: &mut Vec<SqlValue>) -> String {
+    match predicate {
+        Predicate::Text {
+            field,
+            operator,
+            value,
+        } => {
+            let column = format!("{FOLD_FUNCTION}({})", text_column(*field));
+            let value = fold(value);
+            match operator {
+                TextOperator::Contains => {
+                    params.push(SqlValue::Text(value));
+                    format!("instr({column}, ?) > 0")
+                }
+                TextOperator::Equals => {
+                    params.push(SqlValue::Text(value));
+                    format!("{column} = ?")
+                }
+                TextOperator::StartsWith => {
+                    params.push(SqlValue::Integer(value.chars().count() as i64));
+                    params.push(SqlValue::Text(value));
+                    format!("substr({column}, 1, ?) = ?")
+                }
+            }
+        }
+        Predicate::Number {
+            field,
+            comparison,
+            value,
+        } => {
+            let column = number_column(*field);
+            let operator = match comparison {
+                Comparison::Equal => "=",
+                Comparison::Less => "<",
+                Comparison::LessOrEqual => "<=",
+                Comparison::Greater => ">",
+                Comparison::GreaterOrEqual => ">=",
+            };
+            params.push(SqlValue::Real(*value));
+            format!("{column} IS NOT NULL AND {column} {operator} ?")
+        }
+        Predicate::Flag { field, value } => {
+            params.push(SqlValue::Integer(i64::from(*value)));
+            format!("{} = ?", flag_expression(*field))
+        }
+        Predicate::Missing(QueryField::Text(field)) => {
+            params.push(SqlValue::Text(field.default_value()));
+            format!("{} = ?", text_column(*field))
+        }
+        Predicate::Missing(QueryField::Number(field)) => {
+            format!("{} IS NULL", number_column(*field))
+        }
+        Predicate::Missing(QueryField::Flag(field)) => {
+            format!("{} = 0", flag_expression(*field))
+        }
+    }
+}
+
+fn text_column(field: TextField) -> &'static str {
+    match field {
+        TextField::Title => "title",
+        TextField::Artist => "artist",
+        TextField::AlbumTitle => "album_title",
+        TextField::AlbumArtist => "album_artist",
+        TextField::Genre => "genre",
+        TextField::Format => "extension",
+        TextField::Path => "path",
+        // Only kept in the serialized audio
+        TextField::Lyrics => "COALESCE(json_extract(audio, '$.lyrics'), '')",
+    }
+}
+
+fn number_column(field: NumberField) -> &'static str {
+    match field {
+        NumberField::Year => "year",
+        NumberField::TrackNumber => "track_number",
+        NumberField::DiscNumber => "disc_number",
+        NumberField::Duration => "duration",
+        NumberField::Bitrate => "bitrate",
+    }
+}
+
+fn flag_expression(field: FlagField) -> &'static str {
+    match field {
+        FlagField::Compilation => "compilation",
+        FlagField::Cover => "(cover_id IS NOT NULL)",
+    }
+}
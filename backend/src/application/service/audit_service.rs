@@ -0,0 +1,394 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::domain::entity::audio::{
+    cover::Cover, field::AudioField, genre::Genre, title::Title, Audio,
+};
+
+/// Reads an album level field of a track.
+type AlbumFieldValue = fn(&Audio) -> String;
+
+pub struct AuditServiceOptions {
+    /// Covers narrower or shorter than this, in pixels, are reported.
+    pub min_cover_size: u32,
+}
+
+impl Default for AuditServiceOptions {
+    fn default() -> Self {
+        Self {
+            min_cover_size: 500,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AuditIssueKind {
+    /// The field is not tagged and its default value is used.
+    MissingField {
+        field: AudioField,
+    },
+    /// The field is not tagged and its value was inferred from the path.
+    InferredField {
+        field: AudioField,
+    },
+    /// Tracks of the same folder disagree on an album level field.
+    InconsistentAlbumField {
+        field: AudioField,
+        values: Vec<String>,
+    },
+    TrackNumberGap {
+        disc: Option<u16>,
+        missing: Vec<u16>,
+    },
+    DuplicateTrackNumber {
+        disc: Option<u16>,
+        track: u16,
+    },
+    MixedYears {
+        years: Vec<u16>,
+    },
+    LowResolutionCover {
+        width: u32,
+        height: u32,
+    },
+    SuspiciousEncoding {
+        field: AudioField,
+        value: String,
+    },
+}
+
+impl AuditIssueKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MissingField { .. } => "missing field",
+            Self::InferredField { .. } => "inferred field",
+            Self::InconsistentAlbumField { .. } => "inconsistent album field",
+            Self::TrackNumberGap { .. } => "track number gap",
+            Self::DuplicateTrackNumber { .. } => "duplicate track number",
+            Self::MixedYears { .. } => "mixed years",
+            Self::LowResolutionCover { .. } => "low resolution cover",
+            Self::SuspiciousEncoding { .. } => "suspicious encoding",
+        }
+    }
+}
+
+impl Display for AuditIssueKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let disc = |disc: &Option<u16>| {
+            disc.map(|disc| format!("disc {disc}, "))
+                .unwrap_or_default()
+        };
+        let join = |values: &mut dyn Iterator<Item = String>| values.collect::<Vec<_>>().join(", ");
+        match self {
+            Self::MissingField { field } | Self::InferredField { field } => write!(f, "{field}"),
+            Self::InconsistentAlbumField { field, values } => {
+                write!(f, "{field}: {}", values.join(" | "))
+            }
+            Self::TrackNumberGap {
+                disc: number,
+                missing,
+            } => write!(
+                f,
+                "{}missing {}",
+                disc(number),
+                join(&mut missing.iter().map(u16::to_string))
+            ),
+            Self::DuplicateTrackNumber {
+                disc: number,
+                track,
+            } => {
+                write!(f, "{}track {track}", disc(number))
+            }
+            Self::MixedYears { years } => {
+                write!(f, "{}", join(&mut years.iter().map(u16::to_string)))
+            }
+            Self::LowResolutionCover { width, height } => write!(f, "{width}x{height}"),
+            Self::SuspiciousEncoding { field, value } => write!(f, "{field}: {value}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditIssue {
+    /// The track, or the folder for album level issues.
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub kind: AuditIssueKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditReport {
+    pub track_count: usize,
+    pub issues: Vec<AuditIssue>,
+}
+
+impl AuditReport {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Number of issues of each kind.
+    pub fn counts(&self) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+        for issue in &self.issues {
+            *counts.entry(issue.kind.name()).or_default() += 1;
+        }
+        counts
+    }
+}
+
+/// Renders the report as a table of issues followed by a summary.
+impl Display for AuditReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let rows = self
+            .issues
+            .iter()
+            .map(|issue| {
+                [
+                    issue.path.display().to_string(),
+                    issue.kind.name().to_owned(),
+                    issue.kind.to_string(),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let header = ["PATH".to_owned(), "ISSUE".to_owned(), "DETAILS".to_owned()];
+        let mut widths = [0; 3];
+        for row in std::iter::once(&header).chain(&rows) {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        for row in std::iter::once(&header).chain(&rows) {
+            writeln!(
+                f,
+                "{:<path$}  {:<issue$}  {}",
+                row[0],
+                row[1],
+                row[2],
+                path = widths[0],
+                issue = widths[1]
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{} issues in {} tracks",
+            self.issues.len(),
+            self.track_count
+        )?;
+        for (name, count) in self.counts() {
+            writeln!(f, "  {name}: {count}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reports how complete and consistent the tags of a library are.
+#[derive(Default)]
+pub struct AuditService {
+    options: AuditServiceOptions,
+}
+
+impl AuditService {
+    pub fn new(options: AuditServiceOptions) -> Self {
+        Self { options }
+    }
+
+    pub fn audit<'a>(&self, audios: impl IntoIterator<Item = &'a Audio>) -> AuditReport {
+        let mut folders: BTreeMap<&Path, Vec<&Audio>> = BTreeMap::new();
+        let mut issues = Vec::new();
+        let mut track_count = 0;
+        for audio in audios {
+            track_count += 1;
+            issues.extend(Self::track_issues(audio));
+            let folder = audio.path().parent().unwrap_or(Path::new(""));
+            folders.entry(folder).or_default().push(audio);
+        }
+        for (folder, audios) in folders {
+            issues.extend(self.folder_issues(folder, &audios));
+        }
+        AuditReport {
+            track_count,
+            issues,
+        }
+    }
+
+    fn track_issues(audio: &Audio) -> Vec<AuditIssue> {
+        let missing = [
+            (AudioField::Title, *audio.title() == Title::default()),
+            (AudioField::Artist, audio.artist().is_unknown()),
+            (
+                AudioField::AlbumTitle,
+                *audio.album_title() == Title::default(),
+            ),
+            (AudioField::AlbumArtist, audio.album_artist().is_unknown()),
+            (AudioField::Genre, *audio.genre() == Genre::default()),
+            (AudioField::Year, audio.year().is_none()),
+            (AudioField::TrackNumber, audio.track_number().is_none()),
+            (
+                AudioField::AlbumCover,
+                *audio.album_cover() == Cover::default(),
+            ),
+        ];
+        let missing = missing.into_iter().filter_map(|(field, missing)| {
+            if audio.is_inferred(field) {
+                Some(AuditIssueKind::InferredField { field })
+            } else {
+                missing.then_some(AuditIssueKind::MissingField { field })
+            }
+        });
+
+        let texts = [
+            (
+                AudioField::Title,
+                audio.title().name(),
+                audio.title().has_encoding_issue(),
+            ),
+            (
+                AudioField::Artist,
+                audio.artist().name(),
+                audio.artist().has_encoding_issue(),
+            ),
+            (
+                AudioField::AlbumTitle,
+                audio.album_title().name(),
+                audio.album_title().has_encoding_issue(),
+            ),
+            (
+                AudioField::AlbumArtist,
+                audio.album_artist().name(),
+                audio.album_artist().has_encoding_issue(),
+            ),
+            (
+                AudioField::Genre,
+                audio.genre().name(),
+                audio.genre().has_encoding_issue(),
+            ),
+        ];
+        let suspicious = texts
+            .into_iter()
+            .filter(|(_, value, has_encoding_issue)| {
+                *has_encoding_issue || value.contains(char::REPLACEMENT_CHARACTER)
+            })
+            .map(|(field, value, _)| AuditIssueKind::SuspiciousEncoding {
+                field,
+                value: value.clone(),
+            });
+
+        missing
+            .chain(suspicious)
+            .map(|kind| AuditIssue {
+                path: audio.path().clone(),
+                kind,
+            })
+            .collect()
+    }
+
+    fn folder_issues(&self, folder: &Path, audios: &[&Audio]) -> Vec<AuditIssue> {
+        let mut kinds = Vec::new();
+
+        let distinct = |value: fn(&Audio) -> String| {
+            audios
+                .iter()
+                .map(|audio| value(audio))
+                .collect::<BTreeSet<_>>()
+        };
+        let album_fields: [(AudioField, AlbumFieldValue); 3] = [
+            (AudioField::AlbumTitle, |audio| {
+                audio.album_title().name().clone()
+            }),
+            (AudioField::AlbumArtist, |audio| {
+                audio.album_artist().name().clone()
+            }),
+            (AudioField::Compilation, |audio| {
+                audio.compilation().to_string()
+            }),
+        ];
+        for (field, value) in album_fields {
+            let values = distinct(value);
+            if values.len() > 1 {
+                kinds.push(AuditIssueKind::InconsistentAlbumField {
+                    field,
+                    values: values.into_iter().collect(),
+                });
+            }
+        }
+
+        let mut albums: BTreeMap<&str, Vec<&Audio>> = BTreeMap::new();
+        for audio in audios {
+            albums
+                .entry(audio.album_title().name())
+                .or_default()
+                .push(audio);
+        }
+        for tracks in albums.values() {
+            kinds.extend(Self::numbering_issues(tracks));
+            let years = tracks
+                .iter()
+                .filter_map(|audio| audio.year().map(|year| year.0))
+                .collect::<BTreeSet<_>>();
+            if years.len() > 1 {
+                kinds.push(AuditIssueKind::MixedYears {
+                    years: years.into_iter().collect(),
+                });
+            }
+        }
+
+        let mut covers: Vec<&Cover> = Vec::new();
+        for audio in audios {
+            let cover = audio.album_cover();
+            if *cover != Cover::default() && !covers.contains(&cover) {
+                covers.push(cover);
+            }
+        }
+        for (width, height) in covers.into_iter().filter_map(Cover::dimensions) {
+            if width < self.options.min_cover_size || height < self.options.min_cover_size {
+                kinds.push(AuditIssueKind::LowResolutionCover { width, height });
+            }
+        }
+
+        kinds
+            .into_iter()
+            .map(|kind| AuditIssue {
+                path: folder.to_path_buf(),
+                kind,
+            })
+            .collect()
+    }
+
+    fn numbering_issues(tracks: &[&Audio]) -> Vec<AuditIssueKind> {
+        let mut discs: BTreeMap<Option<u16>, Vec<u16>> = BTreeMap::new();
+        for audio in tracks {
+            if let Some(track) = audio.track_number() {
+                discs.entry(*audio.disc_number()).or_default().push(*track);
+            }
+        }
+
+        let mut kinds = Vec::new();
+        for (disc, mut numbers) in discs {
+            numbers.sort_unstable();
+            for pair in numbers.windows(2).filter(|pair| pair[0] == pair[1]) {
+                kinds.push(AuditIssueKind::DuplicateTrackNumber {
+                    disc,
+                    track: pair[0],
+                });
+            }
+            numbers.dedup();
+            let last = numbers.last().copied().unwrap_or_default();
+            let missing = (1..=last)
+                .filter(|number| numbers.binary_search(number).is_err())
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                kinds.push(AuditIssueKind::TrackNumberGap { disc, missing });
+            }
+        }
+        kinds
+    }
+}
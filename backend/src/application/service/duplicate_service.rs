@@ -0,0 +1,231 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    path::PathBuf,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::domain::{
+    entity::audio::{sort_key::SortKeyOptions, title::Title, Audio},
+    repository::LibraryFileRepository,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateStrategy {
+    /// Files with the exact same content.
+    FileHash,
+    /// Same title, artist and album, with durations within the tolerance.
+    Tags,
+    /// Same MusicBrainz recording ID.
+    #[serde(rename = "musicbrainz_id")]
+    MusicBrainzId,
+}
+
+pub struct DuplicateServiceOptions {
+    /// Strategies to run, in order. A group already found by a previous strategy is not
+    /// reported again.
+    pub strategies: Vec<DuplicateStrategy>,
+    pub duration_tolerance: Duration,
+    /// Extensions (lowercase) from most to least preferred when recommending the copy to keep.
+    /// Unlisted formats come last.
+    pub preferred_formats: Vec<String>,
+}
+
+impl Default for DuplicateServiceOptions {
+    fn default() -> Self {
+        Self {
+            strategies: vec![
+                DuplicateStrategy::FileHash,
+                DuplicateStrategy::MusicBrainzId,
+                DuplicateStrategy::Tags,
+            ],
+            duration_tolerance: Duration::from_secs(2),
+            preferred_formats: ["flac", "wav", "aiff", "m4a", "opus", "ogg", "mp3"]
+                .into_iter()
+                .map(str::to_owned)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    pub strategy: DuplicateStrategy,
+    /// The recommended copy to keep.
+    pub keep: Audio,
+    pub duplicates: Vec<Audio>,
+}
+
+/// Finds copies of the same tracks, e.g. in different formats or folders.
+pub struct DuplicateService<R> {
+    repository: R,
+    options: DuplicateServiceOptions,
+}
+
+impl<R> DuplicateService<R>
+where
+    R: LibraryFileRepository,
+    R::Error: Display,
+{
+    pub fn new(repository: R, options: DuplicateServiceOptions) -> Self {
+        Self {
+            repository,
+            options,
+        }
+    }
+
+    pub fn find(&self, audios: &[Audio]) -> Vec<DuplicateGroup> {
+        let mut reported: HashSet<Vec<PathBuf>> = HashSet::new();
+        let mut groups = Vec::new();
+        for strategy in &self.options.strategies {
+            let candidates = match strategy {
+                DuplicateStrategy::FileHash => self.by_file_hash(audios),
+                DuplicateStrategy::Tags => self.by_tags(audios),
+                DuplicateStrategy::MusicBrainzId => Self::by_musicbrainz_id(audios),
+            };
+            for candidate in candidates {
+                let mut paths = candidate
+                    .iter()
+                    .map(|audio| audio.path().clone())
+                    .collect::<Vec<_>>();
+                paths.sort();
+                if reported.insert(paths) {
+                    groups.push(self.recommend(*strategy, candidate));
+                }
+            }
+        }
+        groups
+    }
+
+    /// Only files sharing their size are hashed.
+    fn by_file_hash<'a>(&self, audios: &'a [Audio]) -> Vec<Vec<&'a Audio>> {
+        let mut by_size: HashMap<u64, Vec<&Audio>> = HashMap::new();
+        for audio in audios {
+            match self.repository.size(audio.path()) {
+                Ok(size) => by_size.entry(size).or_default().push(audio),
+                Err(err) => log::error!("Failed to read {}: {}", audio.path().display(), err),
+            }
+        }
+
+        let mut by_hash: BTreeMap<String, Vec<&Audio>> = BTreeMap::new();
+        for audio in by_size
+            .into_values()
+            .filter(|audios| audios.len() > 1)
+            .flatten()
+        {
+            match self.repository.hash(audio.path()) {
+                Ok(hash) => by_hash.entry(hash).or_default().push(audio),
+                Err(err) => log::error!("Failed to hash {}: {}", audio.path().display(), err),
+            }
+        }
+        Self::duplicates(by_hash)
+    }
+
+    /// Audios with an unknown title or artist are never considered duplicates.
+    fn by_tags<'a>(&self, audios: &'a [Audio]) -> Vec<Vec<&'a Audio>> {
+        let sort_key = SortKeyOptions {
+            articles: Vec::new(),
+            fold_diacritics: true,
+        };
+        let mut by_tags: BTreeMap<(String, String, String), Vec<&Audio>> = BTreeMap::new();
+        for audio in audios {
+            if *audio.title() == Title::default() || audio.artist().is_unknown() {
+                continue;
+            }
+            let key = (
+                sort_key.normalize(audio.title().name()),
+                sort_key.normalize(audio.artist().name()),
+                sort_key.normalize(audio.album_title().name()),
+            );
+            by_tags.entry(key).or_default().push(audio);
+        }
+
+        by_tags
+            .into_values()
+            .flat_map(|audios| self.split_by_duration(audios))
+            .filter(|audios| audios.len() > 1)
+            .collect()
+    }
+
+    /// Splits audios into runs of durations within the tolerance of each other. Audios of
+    /// unknown duration join the first run.
+    fn split_by_duration<'a>(&self, audios: Vec<&'a Audio>) -> Vec<Vec<&'a Audio>> {
+        let (mut known, unknown): (Vec<_>, Vec<_>) = audios
+            .into_iter()
+            .partition(|audio| audio.duration().is_some());
+        known.sort_by_key(|audio| *audio.duration());
+
+        let mut runs: Vec<Vec<&Audio>> = Vec::new();
+        let mut run_start = Duration::ZERO;
+        for audio in known {
+            let duration = audio.duration().unwrap_or_default();
+            match runs.last_mut() {
+                Some(run) if duration - run_start <= self.options.duration_tolerance => {
+                    run.push(audio)
+                }
+                _ => {
+                    run_start = duration;
+                    runs.push(vec![audio]);
+                }
+            }
+        }
+        match runs.first_mut() {
+            Some(run) => run.extend(unknown),
+            None => runs.push(unknown),
+        }
+        runs
+    }
+
+    fn by_musicbrainz_id(audios: &[Audio]) -> Vec<Vec<&Audio>> {
+        let mut by_id: BTreeMap<String, Vec<&Audio>> = BTreeMap::new();
+        for audio in audios {
+            if let Some(id) = audio.musicbrainz_recording_id() {
+                by_id.entry(id.to_lowercase()).or_default().push(audio);
+            }
+        }
+        Self::duplicates(by_id)
+    }
+
+    fn duplicates<K>(groups: BTreeMap<K, Vec<&Audio>>) -> Vec<Vec<&Audio>> {
+        groups
+            .into_values()
+            .filter(|audios| audios.len() > 1)
+            .collect()
+    }
+
+    /// Recommends keeping the preferred format, then the highest bitrate, then the copy with
+    /// the fewest inferred fields and finally the shortest path.
+    fn recommend(&self, strategy: DuplicateStrategy, mut audios: Vec<&Audio>) -> DuplicateGroup {
+        let format_rank = |audio: &Audio| {
+            let extension = audio
+                .path()
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            self.options
+                .preferred_formats
+                .iter()
+                .position(|format| *format == extension)
+                .unwrap_or(self.options.preferred_formats.len())
+        };
+        audios.sort_by_key(|audio| {
+            (
+                format_rank(audio),
+                Reverse(audio.bitrate().unwrap_or_default()),
+                audio.inferred_fields().len(),
+                audio.path().as_os_str().len(),
+                audio.path().clone(),
+            )
+        });
+        let mut audios = audios.into_iter().cloned();
+        DuplicateGroup {
+            strategy,
+            keep: audios.next().expect("duplicate groups have several audios"),
+            duplicates: audios.collect(),
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::{fmt::Display, time::Duration};
+
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+
+use crate::domain::{
+    entity::{
+        audio::Audio,
+        fingerprint::{Fingerprint, FingerprintError, TrackFingerprint, SAMPLE_RATE},
+    },
+    repository::{AudioDecoder, FingerprintRepository, LibraryFileRepository},
+};
+
+pub struct FingerprintServiceOptions {
+    /// Only the beginning of the audio is fingerprinted, like `fpcalc` does.
+    pub max_duration: Duration,
+    /// Minimum [`Fingerprint::similarity`] for two audios to be the same recording.
+    pub similarity_threshold: f64,
+    /// Audios whose durations differ by more than this are never compared.
+    pub max_duration_difference: Duration,
+}
+
+impl Default for FingerprintServiceOptions {
+    fn default() -> Self {
+        Self {
+            max_duration: Duration::from_secs(120),
+            similarity_threshold: 0.85,
+            max_duration_difference: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FingerprintServiceError {
+    #[error("Failed to read file: {0}")]
+    File(String),
+    #[error("Failed to decode audio: {0}")]
+    Decoder(String),
+    #[error("Failed to access fingerprint store: {0}")]
+    Repository(String),
+    #[error(transparent)]
+    Fingerprint(#[from] FingerprintError),
+}
+
+/// Computes acoustic fingerprints, fully offline, and uses them to find the same recording
+/// across differently tagged or transcoded files.
+pub struct FingerprintService<D, R, L> {
+    decoder: D,
+    repository: R,
+    files: L,
+    options: FingerprintServiceOptions,
+}
+
+impl<D, R, L> FingerprintService<D, R, L>
+where
+    D: AudioDecoder,
+    D::Error: Display,
+    R: FingerprintRepository,
+    R::Error: Display,
+    L: LibraryFileRepository,
+    L::Error: Display,
+{
+    pub fn new(decoder: D, repository: R, files: L, options: FingerprintServiceOptions) -> Self {
+        Self {
+            decoder,
+            repository,
+            files,
+            options,
+        }
+    }
+
+    /// Returns the fingerprint of `audio`, computing and storing it unless a fingerprint of
+    /// the current version of the file is already stored.
+    pub fn fingerprint(&self, audio: &Audio) -> Result<Fingerprint, FingerprintServiceError> {
+        let path = audio.path();
+        let file_error = |err: L::Error| FingerprintServiceError::File(err.to_string());
+        let size = self.files.size(path).map_err(file_error)?;
+        let modified = DateTime::<Utc>::from(self.files.modified(path).map_err(file_error)?);
+
+        let stored = self
+            .repository
+            .find(path)
+            .map_err(|err| FingerprintServiceError::Repository(err.to_string()))?;
+        if let Some(stored) = stored {
+            if stored.size == size && stored.modified == modified {
+                return Ok(stored.fingerprint);
+            }
+        }
+
+        let samples = self
+            .decoder
+            .decode(path, SAMPLE_RATE, self.options.max_duration)
+            .map_err(|err| FingerprintServiceError::Decoder(err.to_string()))?;
+        let fingerprint = Fingerprint::compute(&samples)?;
+        self.repository
+            .save(&TrackFingerprint {
+                path: path.clone(),
+                size,
+                modified,
+                fingerprint: fingerprint.clone(),
+            })
+            .map_err(|err| FingerprintServiceError::Repository(err.to_string()))?;
+        Ok(fingerprint)
+    }
+
+    /// Returns the candidates that are the same recording as `audio`, most similar first.
+    pub fn identify<'a>(
+        &self,
+        audio: &Audio,
+        candidates: &'a [Audio],
+    ) -> Result<Vec<(&'a Audio, f64)>, FingerprintServiceError> {
+        let fingerprint = self.fingerprint(audio)?;
+        let mut matches = self
+            .fingerprints(candidates)
+            .into_iter()
+            .filter(|(candidate, _)| {
+                candidate.path() != audio.path() && self.comparable(audio, candidate)
+            })
+            .map(|(candidate, candidate_fingerprint)| {
+                (candidate, fingerprint.similarity(&candidate_fingerprint))
+            })
+            .filter(|(_, similarity)| *similarity >= self.options.similarity_threshold)
+            .collect::<Vec<_>>();
+        matches.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        Ok(matches)
+    }
+
+    /// Groups audios that are the same recording. Audios that cannot be fingerprinted are
+    /// left out.
+    pub fn find_duplicates<'a>(&self, audios: &'a [Audio]) -> Vec<Vec<&'a Audio>> {
+        let fingerprints = self.fingerprints(audios);
+
+        // Union-find over the matching pairs
+        let mut parents = (0..fingerprints.len()).collect::<Vec<_>>();
+        fn root(parents: &mut [usize], mut index: usize) -> usize {
+            while parents[index] != index {
+                parents[index] = parents[parents[index]];
+                index = parents[index];
+            }
+            index
+        }
+        for (i, (audio, fingerprint)) in fingerprints.iter().enumerate() {
+            for (j, (other, other_fingerprint)) in fingerprints.iter().enumerate().skip(i + 1) {
+                if self.comparable(audio, other)
+                    && fingerprint.similarity(other_fingerprint)
+                        >= self.options.similarity_threshold
+                {
+                    let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+                    parents[b] = a;
+                }
+            }
+        }
+
+        let mut groups: Vec<Vec<&Audio>> = vec![Vec::new(); fingerprints.len()];
+        for (index, (audio, _)) in fingerprints.iter().enumerate() {
+            groups[root(&mut parents, index)].push(audio);
+        }
+        groups.retain(|group| group.len() > 1);
+        groups
+    }
+
+    fn fingerprints<'a>(&self, audios: &'a [Audio]) -> Vec<(&'a Audio, Fingerprint)> {
+        audios
+            .iter()
+            .filter_map(|audio| match self.fingerprint(audio) {
+                Ok(fingerprint) => Some((audio, fingerprint)),
+                Err(err) => {
+                    log::error!("Failed to fingerprint {}: {}", audio.path().display(), err);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn comparable(&self, audio: &Audio, other: &Audio) -> bool {
+        match (audio.duration(), other.duration()) {
+            (Some(duration), Some(other_duration)) => {
+                duration.abs_diff(*other_duration) <= self.options.max_duration_difference
+            }
+            _ => true,
+        }
+    }
+}
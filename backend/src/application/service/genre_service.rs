@@ -0,0 +1,185 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::domain::entity::audio::{
+    genre::{normalizer::GenreNormalizer, Genre},
+    Audio,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenreNode {
+    pub genre: Genre,
+    /// Tracks tagged with exactly this genre.
+    pub track_count: usize,
+    /// Tracks tagged with this genre or any of its descendants.
+    pub total_track_count: usize,
+    pub children: Vec<GenreNode>,
+}
+
+#[derive(Default)]
+pub struct GenreService {
+    normalizer: GenreNormalizer,
+}
+
+impl GenreService {
+    pub fn new(normalizer: GenreNormalizer) -> Self {
+        Self { normalizer }
+    }
+
+    /// Builds the genre tree of the given audios using the normalizer aliases and hierarchy.
+    /// Parent genres without tracks of their own are included so the tree can be browsed.
+    /// A genre is never nested under itself, even when the hierarchy has a cycle.
+    pub fn browse<'a>(&self, audios: impl IntoIterator<Item = &'a Audio>) -> Vec<GenreNode> {
+        let mut track_counts: HashMap<Genre, usize> = HashMap::new();
+        for audio in audios {
+            let genre = self.normalizer.normalize_genre(audio.genre());
+            *track_counts.entry(genre).or_default() += 1;
+        }
+
+        let mut children: BTreeMap<String, Vec<Genre>> = BTreeMap::new();
+        let mut roots: BTreeMap<String, Genre> = BTreeMap::new();
+        for genre in track_counts.keys() {
+            let mut current = genre.clone();
+            for ancestor in self.normalizer.ancestors(genre) {
+                let siblings = children.entry(ancestor.name().to_lowercase()).or_default();
+                if !siblings.contains(&current) {
+                    siblings.push(current);
+                }
+                current = ancestor;
+            }
+            roots.insert(current.name().to_lowercase(), current);
+        }
+
+        roots
+            .into_values()
+            .map(|genre| Self::build_node(genre, &track_counts, &children, &mut HashSet::new()))
+            .collect()
+    }
+
+    fn build_node(
+        genre: Genre,
+        track_counts: &HashMap<Genre, usize>,
+        children: &BTreeMap<String, Vec<Genre>>,
+        // Genres of the branch leading to this node
+        branch: &mut HashSet<String>,
+    ) -> GenreNode {
+        let key = genre.name().to_lowercase();
+        branch.insert(key.clone());
+        let mut child_nodes = Vec::new();
+        for child in children.get(&key).into_iter().flatten() {
+            if !branch.contains(&child.name().to_lowercase()) {
+                child_nodes.push(Self::build_node(
+                    child.clone(),
+                    track_counts,
+                    children,
+                    branch,
+                ));
+            }
+        }
+        branch.remove(&key);
+        child_nodes.sort_by_key(|node| node.genre.name().to_lowercase());
+
+        let track_count = track_counts.get(&genre).copied().unwrap_or_default();
+        let total_track_count = track_count
+            + child_nodes
+                .iter()
+                .map(|node| node.total_track_count)
+                .sum::<usize>();
+        GenreNode {
+            genre,
+            track_count,
+            total_track_count,
+            children: child_nodes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::domain::entity::audio::{artist::Artist, cover::Cover, title::Title, AudioBuilder};
+
+    use super::*;
+
+    fn audio(genre: &str) -> Audio {
+        AudioBuilder::default()
+            .title(Title::default())
+            .artist(Artist::default())
+            .year(None)
+            .album_title(Title::default())
+            .album_artist(Artist::default())
+            .album_cover(Cover::default())
+            .genre(genre.parse::<Genre>().unwrap())
+            .track_number(None)
+            .disc_number(None)
+            .compilation(false)
+            .path(PathBuf::from(format!("/music/{genre}.mp3")))
+            .build()
+            .unwrap()
+    }
+
+    /// Names, own and total track counts of the tree, depth first.
+    fn flatten(nodes: &[GenreNode], depth: usize, flat: &mut Vec<(String, usize, usize)>) {
+        for node in nodes {
+            flat.push((
+                format!("{}{}", "  ".repeat(depth), node.genre.name()),
+                node.track_count,
+                node.total_track_count,
+            ));
+            flatten(&node.children, depth + 1, flat);
+        }
+    }
+
+    fn browse(normalizer: GenreNormalizer, genres: &[&str]) -> Vec<(String, usize, usize)> {
+        let audios = genres.iter().map(|genre| audio(genre)).collect::<Vec<_>>();
+        let mut flat = Vec::new();
+        flatten(&GenreService::new(normalizer).browse(&audios), 0, &mut flat);
+        flat
+    }
+
+    fn expected(nodes: &[(&str, usize, usize)]) -> Vec<(String, usize, usize)> {
+        nodes
+            .iter()
+            .map(|(name, own, total)| (name.to_string(), *own, *total))
+            .collect()
+    }
+
+    #[test]
+    fn nests_genres_under_their_parents() {
+        let normalizer = GenreNormalizer::default()
+            .with_parent("Bebop", "Jazz")
+            .with_parent("hard bop", "Bebop")
+            .with_parent("Jazz", "Music");
+        assert_eq!(
+            browse(
+                normalizer,
+                &["Hard Bop", "Bebop", "Bebop", "Swing", "Rock", "Hip Hop"]
+            ),
+            expected(&[
+                ("Hip-Hop", 1, 1),
+                ("Music", 0, 3),
+                ("  Jazz", 0, 3),
+                ("    Bebop", 2, 3),
+                ("      Hard Bop", 1, 1),
+                ("Rock", 1, 1),
+                ("Swing", 1, 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn cuts_cycles_of_the_hierarchy() {
+        let normalizer = GenreNormalizer::default()
+            .with_parent("Bebop", "Jazz")
+            .with_parent("Jazz", "Bebop");
+        assert_eq!(
+            browse(normalizer, &["Bebop", "Jazz"]),
+            expected(&[
+                ("Bebop", 1, 2),
+                ("  Jazz", 1, 1),
+                ("Jazz", 1, 2),
+                ("  Bebop", 1, 1),
+            ])
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::PathBuf,
+    sync::{Arc, Mutex, RwLock, Weak},
+    thread,
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::domain::{
+    entity::{
+        audio::{sort_key::SortKeyOptions, Audio},
+        library_root::LibraryRoot,
+    },
+    repository::{AudioGathererRepository, LibraryRepository},
+};
+
+use super::{AlbumService, AlbumServiceOptions};
+
+mod library;
+mod listing;
+
+pub use library::{ArtistSummary, GenreSummary, Library};
+pub use listing::{
+    AlbumFilter, AlbumSort, ArtistFilter, ArtistSort, GenreFilter, GenreSort, Page, Paginated,
+    SortOrder, TrackFilter, TrackSort,
+};
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScanStatus {
+    pub scanning: bool,
+    /// The root being scanned, or last scanned, when not all of them are.
+    pub root: Option<String>,
+    /// Audios gathered so far by the running scan, or by the last one.
+    pub gathered: usize,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Why the last scan failed.
+    pub error: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum LibraryServiceError {
+    #[error("A scan is already running")]
+    ScanInProgress,
+    #[error("Unknown library root: {0}")]
+    UnknownRoot(String),
+    #[error("Failed to gather audios: {0}")]
+    Gatherer(String),
+    #[error("Failed to access library store: {0}")]
+    Repository(String),
+}
+
+struct RestrictedLibrary {
+    /// The snapshot the audios were taken from.
+    base: Weak<Library>,
+    library: Arc<Library>,
+}
+
+/// Keeps the library gathered from the filesystem in the store and serves snapshots of it.
+pub struct LibraryService<G, R> {
+    gatherer: G,
+    repository: R,
+    album_service: AlbumService,
+    sort_key: SortKeyOptions,
+    library: RwLock<Arc<Library>>,
+    /// Snapshots restricted to sets of directories.
+    restricted: Mutex<HashMap<Vec<PathBuf>, RestrictedLibrary>>,
+    status: Mutex<ScanStatus>,
+}
+
+impl<G, R> LibraryService<G, R>
+where
+    G: AudioGathererRepository,
+    G::Error: Display,
+    R: LibraryRepository,
+    R::Error: Display,
+{
+    /// Creates the service with the library last stored.
+    pub fn new(
+        gatherer: G,
+        repository: R,
+        options: AlbumServiceOptions,
+    ) -> Result<Self, LibraryServiceError> {
+        let audios = repository
+            .load()
+            .map_err(|err| LibraryServiceError::Repository(err.to_string()))?;
+        let sort_key = options.sort_key.clone();
+        let album_service = AlbumService::new(options);
+        let library = Library::new(album_service.group(audios), sort_key.clone());
+        Ok(Self {
+            gatherer,
+            repository,
+            album_service,
+            sort_key,
+            library: RwLock::new(Arc::new(library)),
+            restricted: Mutex::new(HashMap::new()),
+            status: Mutex::new(ScanStatus::default()),
+        })
+    }
+
+    /// The store the library is kept in, holding the audios of the current snapshot.
+    pub fn repository(&self) -> &R {
+        &self.repository
+    }
+
+    /// The current snapshot. It is not affected by later scans.
+    pub fn library(&self) -> Arc<Library> {
+        Arc::clone(&self.library.read().expect("library lock poisoned"))
+    }
+
+    /// The current snapshot with only the audios under one of the directories, or all of
+    /// them when there is none. Albums, artists and genres are made of these audios alone.
+    pub fn library_within(&self, roots: &[PathBuf]) -> Arc<Library> {
+        let library = self.library();
+        if roots.is_empty() {
+            return library;
+        }
+        let mut restricted = self.restricted.lock().expect("restricted lock poisoned");
+        if let Some(within) = restricted.get(roots) {
+            if Weak::ptr_eq(&within.base, &Arc::downgrade(&library)) {
+                return Arc::clone(&within.library);
+            }
+        }
+        let audios = library
+            .audios()
+            .filter(|audio| roots.iter().any(|root| audio.path().starts_with(root)))
+            .cloned()
+            .collect::<Vec<_>>();
+        let within = Arc::new(Library::new(
+            self.album_service.group(audios),
+            self.sort_key.clone(),
+        ));
+        restricted.insert(
+            roots.to_vec(),
+            RestrictedLibrary {
+                base: Arc::downgrade(&library),
+                library: Arc::clone(&within),
+            },
+        );
+        within
+    }
+
+    /// The roots audios are gathered from.
+    pub fn roots(&self) -> &[LibraryRoot] {
+        self.gatherer.roots()
+    }
+
+    pub fn status(&self) -> ScanStatus {
+        self.status.lock().expect("status lock poisoned").clone()
+    }
+
+    /// Gathers the audios again, stores them and replaces the snapshot.
+    pub fn scan(&self) -> Result<Arc<Library>, LibraryServiceError> {
+        self.begin_scan(None)?;
+        self.run_scan(None)
+    }
+
+    /// Gathers the audios of a single root again, keeping those of the other roots.
+    pub fn scan_root(&self, name: &str) -> Result<Arc<Library>, LibraryServiceError> {
+        self.begin_scan(Some(name))?;
+        self.run_scan(Some(name))
+    }
+
+    /// Starts a scan in a background thread, see [`Self::scan`]. Its progress is reported by
+    /// [`Self::status`].
+    pub fn start_scan(self: &Arc<Self>) -> Result<(), LibraryServiceError>
+    where
+        G: Send + Sync + 'static,
+        R: Send + Sync + 'static,
+    {
+        self.start(None)
+    }
+
+    /// Starts a scan of a single root in a background thread, see [`Self::scan_root`].
+    pub fn start_root_scan(self: &Arc<Self>, name: &str) -> Result<(), LibraryServiceError>
+    where
+        G: Send + Sync + 'static,
+        R: Send + Sync + 'static,
+    {
+        self.start(Some(name))
+    }
+
+    /// Scans each root that has an interval on its own, in a background thread per root.
+    /// A scheduled scan is skipped when another scan is running.
+    pub fn start_schedule(self: &Arc<Self>)
+    where
+        G: Send + Sync + 'static,
+        R: Send + Sync + 'static,
+    {
+        for root in self.roots() {
+            let Some(interval) = root.scan_interval() else {
+                continue;
+            };
+            let service = Arc::clone(self);
+            let name = root.name.clone();
+            thread::spawn(move || loop {
+                thread::sleep(interval);
+                match service.scan_root(&name) {
+                    Ok(library) => log::info!(
+                        "Scanned library root {}, {} audios in library",
+                        name,
+                        library.audio_count()
+                    ),
+                    Err(LibraryServiceError::ScanInProgress) => {
+                        log::info!("Skipped scan of library root {}, a scan is running", name)
+                    }
+                    Err(err) => log::error!("Failed to scan library root {}: {}", name, err),
+                }
+            });
+        }
+    }
+
+    fn start(self: &Arc<Self>, root: Option<&str>) -> Result<(), LibraryServiceError>
+    where
+        G: Send + Sync + 'static,
+        R: Send + Sync + 'static,
+    {
+        self.begin_scan(root)?;
+        let service = Arc::clone(self);
+        let root = root.map(str::to_string);
+        thread::spawn(move || {
+            if let Err(err) = service.run_scan(root.as_deref()) {
+                log::error!("Failed to scan library: {}", err);
+            }
+        });
+        Ok(())
+    }
+
+    fn begin_scan(&self, root: Option<&str>) -> Result<(), LibraryServiceError> {
+        if let Some(name) = root {
+            self.root(name)?;
+        }
+        let mut status = self.status.lock().expect("status lock poisoned");
+        if status.scanning {
+            return Err(LibraryServiceError::ScanInProgress);
+        }
+        *status = ScanStatus {
+            scanning: true,
+            root: root.map(str::to_string),
+            started_at: Some(Utc::now()),
+            ..ScanStatus::default()
+        };
+        Ok(())
+    }
+
+    fn root(&self, name: &str) -> Result<&LibraryRoot, LibraryServiceError> {
+        self.roots()
+            .iter()
+            .find(|root| root.name == name)
+            .ok_or_else(|| LibraryServiceError::UnknownRoot(name.to_string()))
+    }
+
+    fn run_scan(&self, root: Option<&str>) -> Result<Arc<Library>, LibraryServiceError> {
+        let result = self.gather(root).and_then(|audios| {
+            let audios = self.stamp_added(audios);
+            self.repository
+                .replace(&audios)
+                .map_err(|err| LibraryServiceError::Repository(err.to_string()))?;
+            let albums = self.album_service.group(audios);
+            Ok(Arc::new(Library::new(albums, self.sort_key.clone())))
+        });
+
+        if let Ok(library) = &result {
+            *self.library.write().expect("library lock poisoned") = Arc::clone(library);
+        }
+        let mut status = self.status.lock().expect("status lock poisoned");
+        status.scanning = false;
+        status.finished_at = Some(Utc::now());
+        status.error = result.as_ref().err().map(|err| err.to_string());
+        result
+    }
+
+    /// Keeps the date audios were added from the current snapshot. Audios gathered for the
+    /// first time are added now, unless the library is being filled from scratch or was
+    /// stored before dates were kept, in which case the file modification date stands in.
+    fn stamp_added(&self, audios: Vec<Audio>) -> Vec<Audio> {
+        let library = self.library();
+        let previous = library
+            .audios()
+            .map(|audio| (audio.id(), audio.added_at().or(*audio.modified_at())))
+            .collect::<HashMap<_, _>>();
+        let now = Utc::now();
+        audios
+            .into_iter()
+            .map(|audio| {
+                let added_at = match previous.get(&audio.id()) {
+                    Some(added_at) => added_at.or(*audio.modified_at()),
+                    None if previous.is_empty() => *audio.modified_at(),
+                    None => Some(now),
+                };
+                audio.with_added_at(added_at)
+            })
+            .collect()
+    }
+
+    /// Gathers the audios of every root, or those of a single root along with the audios of
+    /// the others from the current snapshot.
+    fn gather(&self, root: Option<&str>) -> Result<Vec<Audio>, LibraryServiceError> {
+        let (mut audios, gathered) = match root {
+            None => (Vec::new(), self.gatherer.gather()),
+            Some(name) => {
+                let root = self.root(name)?;
+                // Audios stored before roots were named are told apart by their path
+                let kept = self
+                    .library()
+                    .audios()
+                    .filter(|audio| match audio.root() {
+                        Some(audio_root) => *audio_root != root.name,
+                        None => !audio.path().starts_with(&root.path),
+                    })
+                    .cloned()
+                    .collect();
+                (kept, self.gatherer.gather_root(name))
+            }
+        };
+        let kept = audios.len();
+        for audio in gathered.map_err(|err| LibraryServiceError::Gatherer(err.to_string()))? {
+            audios.push(audio);
+            self.status.lock().expect("status lock poisoned").gathered = audios.len() - kept;
+        }
+        Ok(audios)
+    }
+}
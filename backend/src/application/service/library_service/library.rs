@@ -0,0 +1,339 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use crate::domain::entity::{
+    album::Album,
+    audio::{artist::Artist, cover::Cover, sort_key::SortKeyOptions, Audio},
+};
+
+use super::listing::{
+    in_year_range, AlbumFilter, AlbumSort, ArtistFilter, ArtistSort, GenreFilter, GenreSort, Page,
+    Paginated, SortOrder, TextMatcher, TrackFilter, TrackSort,
+};
+
+#[derive(Debug, Clone)]
+pub struct ArtistSummary {
+    pub artist: Artist,
+    /// Albums credited to the artist as album artist.
+    pub album_count: usize,
+    /// Tracks credited to the artist as track artist.
+    pub track_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GenreSummary {
+    pub name: String,
+    pub album_count: usize,
+    pub track_count: usize,
+}
+
+/// An immutable snapshot of the gathered library, with its albums, artists and genres.
+pub struct Library {
+    albums: Vec<Album>,
+    artists: Vec<ArtistSummary>,
+    genres: Vec<GenreSummary>,
+    sort_key: SortKeyOptions,
+    /// Album and track index of each audio id.
+    audio_index: HashMap<String, (usize, usize)>,
+    album_index: HashMap<String, usize>,
+    artist_index: HashMap<String, usize>,
+    covers: HashMap<String, Cover>,
+}
+
+impl Library {
+    /// Builds the snapshot from albums already grouped and ordered.
+    pub(crate) fn new(albums: Vec<Album>, sort_key: SortKeyOptions) -> Self {
+        let mut audio_index = HashMap::new();
+        let mut album_index = HashMap::new();
+        let mut covers = HashMap::new();
+        let mut artists: HashMap<String, ArtistSummary> = HashMap::new();
+        let mut genres: BTreeMap<String, GenreSummary> = BTreeMap::new();
+
+        for (album_position, album) in albums.iter().enumerate() {
+            album_index.insert(album.id(), album_position);
+            artists
+                .entry(album.artist().id())
+                .or_insert_with(|| ArtistSummary::new(album.artist()))
+                .album_count += 1;
+
+            let mut album_genres = Vec::new();
+            for (track_position, track) in album.tracks().iter().enumerate() {
+                audio_index.insert(track.id(), (album_position, track_position));
+                artists
+                    .entry(track.artist().id())
+                    .or_insert_with(|| ArtistSummary::new(track.artist()))
+                    .track_count += 1;
+
+                let genre = genres
+                    .entry(track.genre().name().clone())
+                    .or_insert_with(|| GenreSummary {
+                        name: track.genre().name().clone(),
+                        album_count: 0,
+                        track_count: 0,
+                    });
+                genre.track_count += 1;
+                if !album_genres.contains(track.genre().name()) {
+                    album_genres.push(track.genre().name().clone());
+                    genre.album_count += 1;
+                }
+
+                let cover = track.album_cover();
+                if !cover.is_default() {
+                    covers.entry(cover.id()).or_insert_with(|| cover.clone());
+                }
+            }
+        }
+
+        let mut artists = artists.into_values().collect::<Vec<_>>();
+        artists.sort_by_cached_key(|summary| summary.artist.sort_key(&sort_key));
+        let artist_index = artists
+            .iter()
+            .enumerate()
+            .map(|(position, summary)| (summary.artist.id(), position))
+            .collect();
+
+        Self {
+            albums,
+            artists,
+            genres: genres.into_values().collect(),
+            sort_key,
+            audio_index,
+            album_index,
+            artist_index,
+            covers,
+        }
+    }
+
+    pub fn albums(&self) -> &[Album] {
+        &self.albums
+    }
+
+    pub fn artists(&self) -> &[ArtistSummary] {
+        &self.artists
+    }
+
+    pub fn genres(&self) -> &[GenreSummary] {
+        &self.genres
+    }
+
+    /// Options artists and titles are ordered with.
+    pub fn sort_key_options(&self) -> &SortKeyOptions {
+        &self.sort_key
+    }
+
+    /// Every audio, ordered by album.
+    pub fn audios(&self) -> impl Iterator<Item = &Audio> {
+        self.albums.iter().flat_map(|album| album.tracks())
+    }
+
+    pub fn audio_count(&self) -> usize {
+        self.audio_index.len()
+    }
+
+    pub fn audio(&self, id: &str) -> Option<&Audio> {
+        let (album, track) = self.audio_index.get(id)?;
+        Some(&self.albums[*album].tracks()[*track])
+    }
+
+    /// The album the audio was grouped into.
+    pub fn album_of(&self, audio: &Audio) -> Option<&Album> {
+        let (album, _) = self.audio_index.get(&audio.id())?;
+        Some(&self.albums[*album])
+    }
+
+    pub fn album(&self, id: &str) -> Option<&Album> {
+        Some(&self.albums[*self.album_index.get(id)?])
+    }
+
+    pub fn artist(&self, id: &str) -> Option<&ArtistSummary> {
+        Some(&self.artists[*self.artist_index.get(id)?])
+    }
+
+    pub fn cover(&self, id: &str) -> Option<&Cover> {
+        self.covers.get(id)
+    }
+
+    pub fn tracks(
+        &self,
+        filter: &TrackFilter,
+        sort: TrackSort,
+        order: SortOrder,
+        page: Page,
+    ) -> Paginated<&Audio> {
+        let matcher = TextMatcher::new(filter.query.as_ref());
+        let candidates: Box<dyn Iterator<Item = &Audio>> = match &filter.album_id {
+            Some(album_id) => match self.album(album_id) {
+                Some(album) => Box::new(album.tracks().iter()),
+                None => Box::new(std::iter::empty()),
+            },
+            None => Box::new(self.audios()),
+        };
+        let mut tracks = candidates
+            .filter(|audio| {
+                matcher.matches([
+                    audio.title().name(),
+                    audio.artist().name(),
+                    audio.album_title().name(),
+                ])
+            })
+            .filter(|audio| {
+                filter.artist_id.as_ref().is_none_or(|artist_id| {
+                    audio.artist().id() == *artist_id || audio.album_artist().id() == *artist_id
+                })
+            })
+            .filter(|audio| Self::matches_genre(filter.genre.as_ref(), audio))
+            .filter(|audio| {
+                in_year_range(
+                    audio.year().map(|year| year.0),
+                    filter.min_year,
+                    filter.max_year,
+                )
+            })
+            .filter(|audio| {
+                filter
+                    .compilation
+                    .is_none_or(|compilation| *audio.compilation() == compilation)
+            })
+            .filter(|audio| {
+                filter
+                    .root
+                    .as_ref()
+                    .is_none_or(|root| audio.root().as_ref() == Some(root))
+            })
+            .collect::<Vec<_>>();
+
+        // Tracks are already ordered by album
+        match sort {
+            TrackSort::Album => {}
+            TrackSort::Title => {
+                tracks.sort_by_cached_key(|audio| audio.title().sort_key(&self.sort_key))
+            }
+            TrackSort::Artist => tracks.sort_by_cached_key(|audio| {
+                (
+                    audio.artist().sort_key(&self.sort_key),
+                    audio.title().sort_key(&self.sort_key),
+                )
+            }),
+            TrackSort::Year => tracks.sort_by_key(|audio| *audio.year()),
+            TrackSort::Duration => tracks.sort_by_key(|audio| *audio.duration()),
+            TrackSort::Path => tracks.sort_by(|a, b| a.path().cmp(b.path())),
+        }
+        Paginated::new(Self::ordered(tracks, order), page)
+    }
+
+    pub fn list_albums(
+        &self,
+        filter: &AlbumFilter,
+        sort: AlbumSort,
+        order: SortOrder,
+        page: Page,
+    ) -> Paginated<&Album> {
+        let matcher = TextMatcher::new(filter.query.as_ref());
+        let mut albums = self
+            .albums
+            .iter()
+            .filter(|album| matcher.matches([album.title().name(), album.artist().name()]))
+            .filter(|album| {
+                filter.artist_id.as_ref().is_none_or(|artist_id| {
+                    album.artist().id() == *artist_id
+                        || album
+                            .tracks()
+                            .iter()
+                            .any(|track| track.artist().id() == *artist_id)
+                })
+            })
+            .filter(|album| {
+                filter.genre.is_none()
+                    || album
+                        .tracks()
+                        .iter()
+                        .any(|track| Self::matches_genre(filter.genre.as_ref(), track))
+            })
+            .filter(|album| {
+                in_year_range(
+                    album.year().map(|year| year.0),
+                    filter.min_year,
+                    filter.max_year,
+                )
+            })
+            .filter(|album| {
+                filter
+                    .compilation
+                    .is_none_or(|compilation| *album.compilation() == compilation)
+            })
+            .collect::<Vec<_>>();
+
+        // Albums are already ordered by artist
+        match sort {
+            AlbumSort::Artist => {}
+            AlbumSort::Title => {
+                albums.sort_by_cached_key(|album| album.title().sort_key(&self.sort_key))
+            }
+            AlbumSort::Year => albums.sort_by_key(|album| *album.year()),
+        }
+        Paginated::new(Self::ordered(albums, order), page)
+    }
+
+    pub fn list_artists(
+        &self,
+        filter: &ArtistFilter,
+        sort: ArtistSort,
+        order: SortOrder,
+        page: Page,
+    ) -> Paginated<&ArtistSummary> {
+        let matcher = TextMatcher::new(filter.query.as_ref());
+        let mut artists = self
+            .artists
+            .iter()
+            .filter(|summary| matcher.matches([summary.artist.name()]))
+            .collect::<Vec<_>>();
+        match sort {
+            ArtistSort::Name => {}
+            ArtistSort::AlbumCount => artists.sort_by_key(|summary| summary.album_count),
+            ArtistSort::TrackCount => artists.sort_by_key(|summary| summary.track_count),
+        }
+        Paginated::new(Self::ordered(artists, order), page)
+    }
+
+    pub fn list_genres(
+        &self,
+        filter: &GenreFilter,
+        sort: GenreSort,
+        order: SortOrder,
+        page: Page,
+    ) -> Paginated<&GenreSummary> {
+        let matcher = TextMatcher::new(filter.query.as_ref());
+        let mut genres = self
+            .genres
+            .iter()
+            .filter(|genre| matcher.matches([&genre.name]))
+            .collect::<Vec<_>>();
+        match sort {
+            GenreSort::Name => {}
+            GenreSort::TrackCount => genres.sort_by_key(|genre| genre.track_count),
+        }
+        Paginated::new(Self::ordered(genres, order), page)
+    }
+
+    fn matches_genre(genre: Option<&String>, audio: &Audio) -> bool {
+        genre.is_none_or(|genre| audio.genre().name().eq_ignore_ascii_case(genre))
+    }
+
+    fn ordered<T>(mut items: Vec<T>, order: SortOrder) -> Vec<T> {
+        if order == SortOrder::Descending {
+            items.reverse();
+        }
+        items
+    }
+}
+
+impl ArtistSummary {
+    fn new(artist: &Artist) -> Self {
+        Self {
+            artist: artist.clone(),
+            album_count: 0,
+            track_count: 0,
+        }
+    }
+}
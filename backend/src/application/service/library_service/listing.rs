@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+
+use crate::domain::entity::audio::sort_key::SortKeyOptions;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Page {
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl Default for Page {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            limit: 50,
+        }
+    }
+}
+
+/// One page of a listing, along with the number of items matching the filter.
+#[derive(Debug, Clone, Serialize)]
+pub struct Paginated<T> {
+    pub total: usize,
+    pub offset: usize,
+    pub items: Vec<T>,
+}
+
+impl<T> Paginated<T> {
+    pub(super) fn new(items: Vec<T>, page: Page) -> Self {
+        let total = items.len();
+        Self {
+            total,
+            offset: page.offset,
+            items: items
+                .into_iter()
+                .skip(page.offset)
+                .take(page.limit)
+                .collect(),
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Paginated<U> {
+        Paginated {
+            total: self.total,
+            offset: self.offset,
+            items: self.items.into_iter().map(f).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackSort {
+    /// Album artist, album, disc and track number.
+    #[default]
+    Album,
+    Title,
+    Artist,
+    Year,
+    Duration,
+    Path,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlbumSort {
+    /// Artist, then title.
+    #[default]
+    Artist,
+    Title,
+    Year,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtistSort {
+    #[default]
+    Name,
+    AlbumCount,
+    TrackCount,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GenreSort {
+    #[default]
+    Name,
+    TrackCount,
+}
+
+/// Text queries match case and diacritics insensitively anywhere in the value.
+#[derive(Debug, Clone, Default)]
+pub struct TrackFilter {
+    /// Matched against the title, artist and album title.
+    pub query: Option<String>,
+    /// Matches the track artist or the album artist.
+    pub artist_id: Option<String>,
+    pub album_id: Option<String>,
+    pub genre: Option<String>,
+    pub min_year: Option<u16>,
+    pub max_year: Option<u16>,
+    pub compilation: Option<bool>,
+    /// Name of the library root the track was gathered from.
+    pub root: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AlbumFilter {
+    /// Matched against the title and artist.
+    pub query: Option<String>,
+    pub artist_id: Option<String>,
+    /// Albums with at least one track of the genre match.
+    pub genre: Option<String>,
+    pub min_year: Option<u16>,
+    pub max_year: Option<u16>,
+    pub compilation: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ArtistFilter {
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GenreFilter {
+    pub query: Option<String>,
+}
+
+/// Case and diacritics insensitive substring matcher.
+pub(super) struct TextMatcher {
+    options: SortKeyOptions,
+    query: Option<String>,
+}
+
+impl TextMatcher {
+    pub(super) fn new(query: Option<&String>) -> Self {
+        let options = SortKeyOptions {
+            articles: Vec::new(),
+            fold_diacritics: true,
+        };
+        let query = query
+            .map(|query| options.normalize(query))
+            .filter(|query| !query.is_empty());
+        Self { options, query }
+    }
+
+    /// Whether any of the values matches. Always true without a query.
+    pub(super) fn matches<'a>(&self, values: impl IntoIterator<Item = &'a String>) -> bool {
+        match &self.query {
+            Some(query) => values
+                .into_iter()
+                .any(|value| self.options.normalize(value).contains(query.as_str())),
+            None => true,
+        }
+    }
+}
+
+pub(super) fn in_year_range(
+    year: Option<u16>,
+    min_year: Option<u16>,
+    max_year: Option<u16>,
+) -> bool {
+    if min_year.is_none() && max_year.is_none() {
+        return true;
+    }
+    year.is_some_and(|year| {
+        min_year.is_none_or(|min_year| year >= min_year)
+            && max_year.is_none_or(|max_year| year <= max_year)
+    })
+}
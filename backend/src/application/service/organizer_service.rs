@@ -0,0 +1,217 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::domain::{entity::audio::Audio, repository::LibraryFileRepository};
+
+mod path_template;
+
+pub use path_template::{PathTemplate, PathTemplateError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrganizeMode {
+    #[default]
+    Move,
+    Copy,
+}
+
+#[derive(Debug, Clone)]
+pub struct OrganizerOptions {
+    pub template: PathTemplate,
+    pub mode: OrganizeMode,
+    /// Extensions (lowercase) of the files that follow the audios of their directory.
+    /// Sidecars sharing the file stem of an audio (e.g. lyrics) are renamed along with it,
+    /// the other ones (e.g. covers) move to the directory of the audio.
+    pub sidecar_extensions: Vec<String>,
+}
+
+impl OrganizerOptions {
+    pub fn new(template: PathTemplate) -> Self {
+        Self {
+            template,
+            mode: OrganizeMode::default(),
+            sidecar_extensions: [
+                "jpg", "jpeg", "png", "gif", "webp", "lrc", "txt", "cue", "log",
+            ]
+            .into_iter()
+            .map(str::to_owned)
+            .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileOperationKind {
+    Audio,
+    Sidecar,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileOperation {
+    pub kind: FileOperationKind,
+    pub source: PathBuf,
+    pub target: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct FileOperationReport {
+    pub operation: FileOperation,
+    /// `None` when the operation succeeded or was not executed.
+    pub error: Option<String>,
+}
+
+/// Moves or copies audios and their sidecar files to the paths given by a [`PathTemplate`].
+pub struct OrganizerService<R> {
+    repository: R,
+    options: OrganizerOptions,
+}
+
+impl<R> OrganizerService<R>
+where
+    R: LibraryFileRepository,
+    R::Error: Display,
+{
+    pub fn new(repository: R, options: OrganizerOptions) -> Self {
+        Self {
+            repository,
+            options,
+        }
+    }
+
+    /// Computes the operations needed to organize `audios` under `root`, without touching
+    /// any file. Targets already taken, on disk or by a previous operation, get a ` (N)`
+    /// suffix. Audios already at their target are left out.
+    pub fn plan(&self, root: &Path, audios: &[Audio]) -> Vec<FileOperation> {
+        // Images and text files may have been gathered with inferred metadata
+        let audios = audios
+            .iter()
+            .filter(|audio| !self.is_sidecar(audio.path()))
+            .collect::<Vec<_>>();
+        let audio_paths = audios
+            .iter()
+            .map(|audio| audio.path().clone())
+            .collect::<HashSet<_>>();
+        let mut taken = HashSet::new();
+        let mut directories_done = HashSet::new();
+        let mut directory_files: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+        let mut operations = Vec::new();
+
+        for audio in audios {
+            let source = audio.path();
+            let target = self.available(
+                root.join(self.options.template.render(audio)),
+                source,
+                &taken,
+            );
+            taken.insert(target.clone());
+            if target != *source {
+                operations.push(FileOperation {
+                    kind: FileOperationKind::Audio,
+                    source: source.clone(),
+                    target: target.clone(),
+                });
+            }
+
+            let Some(directory) = source.parent() else {
+                continue;
+            };
+            let files = directory_files
+                .entry(directory.to_path_buf())
+                .or_insert_with(|| self.repository.files(directory).unwrap_or_default());
+            let move_directory_sidecars = directories_done.insert(directory.to_path_buf());
+            let target_directory = target.parent().unwrap_or(root);
+            for sidecar in files
+                .iter()
+                .filter(|file| !audio_paths.contains(*file) && self.is_sidecar(file))
+            {
+                let sidecar_target = if sidecar.file_stem() == source.file_stem() {
+                    let mut file_name = target.file_stem().unwrap_or_default().to_os_string();
+                    if let Some(extension) = sidecar.extension() {
+                        file_name.push(".");
+                        file_name.push(extension);
+                    }
+                    target_directory.join(file_name)
+                } else if move_directory_sidecars
+                    && !files.iter().any(|file| {
+                        file.file_stem() == sidecar.file_stem() && audio_paths.contains(file)
+                    })
+                {
+                    target_directory.join(sidecar.file_name().unwrap_or_default())
+                } else {
+                    continue;
+                };
+                let sidecar_target = self.available(sidecar_target, sidecar, &taken);
+                taken.insert(sidecar_target.clone());
+                if sidecar_target != *sidecar {
+                    operations.push(FileOperation {
+                        kind: FileOperationKind::Sidecar,
+                        source: sidecar.clone(),
+                        target: sidecar_target,
+                    });
+                }
+            }
+        }
+        operations
+    }
+
+    /// Plans and, unless `dry_run`, executes the operations. A failing operation does not
+    /// stop the others.
+    pub fn organize(
+        &self,
+        root: &Path,
+        audios: &[Audio],
+        dry_run: bool,
+    ) -> Vec<FileOperationReport> {
+        self.plan(root, audios)
+            .into_iter()
+            .map(|operation| {
+                let error = if dry_run {
+                    None
+                } else {
+                    let result = match self.options.mode {
+                        OrganizeMode::Move => {
+                            self.repository.rename(&operation.source, &operation.target)
+                        }
+                        OrganizeMode::Copy => {
+                            self.repository.copy(&operation.source, &operation.target)
+                        }
+                    };
+                    result.err().map(|err| err.to_string())
+                };
+                FileOperationReport { operation, error }
+            })
+            .collect()
+    }
+
+    fn is_sidecar(&self, path: &Path) -> bool {
+        path.extension().is_some_and(|extension| {
+            let extension = extension.to_string_lossy().to_lowercase();
+            self.options.sidecar_extensions.contains(&extension)
+        })
+    }
+
+    /// `target`, or the first `stem (N).ext` variant not taken by another file.
+    fn available(&self, target: PathBuf, source: &Path, taken: &HashSet<PathBuf>) -> PathBuf {
+        let is_available = |path: &PathBuf| {
+            !taken.contains(path) && (path == source || !self.repository.exists(path))
+        };
+        if is_available(&target) {
+            return target;
+        }
+        let stem = target.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = target
+            .extension()
+            .map(|extension| format!(".{}", extension.to_string_lossy()))
+            .unwrap_or_default();
+        (2..)
+            .map(|index| target.with_file_name(format!("{stem} ({index}){extension}")))
+            .find(is_available)
+            .expect("an unused file name exists")
+    }
+}
@@ -0,0 +1,303 @@
+use std::{path::PathBuf, str::FromStr};
+
+use thiserror::Error;
+
+use crate::domain::entity::audio::Audio;
+
+/// Characters not allowed in file names on at least one supported platform.
+const ILLEGAL_CHARACTERS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// File names reserved by Windows, whatever the extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Most filesystems limit file names to 255 bytes.
+const MAX_COMPONENT_BYTES: usize = 255;
+
+/// A template of the path of an audio relative to the library root, such as
+/// `{album_artist}/{year} - {album_title}/{disc}-{track:02} {title}.{ext}`.
+///
+/// Supported placeholders are `title`, `artist`, `album_title` (or `album`), `album_artist`,
+/// `year`, `genre`, `disc`, `track` and `ext`. Numbers can be zero padded with `:0N`. When the
+/// template does not use `{ext}`, the extension of the audio is appended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplatePart {
+    Literal(String),
+    Placeholder(TemplateField, usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TemplateField {
+    Title,
+    Artist,
+    AlbumTitle,
+    AlbumArtist,
+    Year,
+    Genre,
+    DiscNumber,
+    TrackNumber,
+    Extension,
+}
+
+impl TemplateField {
+    fn from_placeholder(placeholder: &str) -> Option<Self> {
+        match placeholder {
+            "title" => Some(Self::Title),
+            "artist" => Some(Self::Artist),
+            "album" | "album_title" => Some(Self::AlbumTitle),
+            "album_artist" => Some(Self::AlbumArtist),
+            "year" => Some(Self::Year),
+            "genre" => Some(Self::Genre),
+            "disc" => Some(Self::DiscNumber),
+            "track" => Some(Self::TrackNumber),
+            "ext" => Some(Self::Extension),
+            _ => None,
+        }
+    }
+
+    fn value(self, audio: &Audio, width: usize) -> String {
+        let number = |number: Option<u16>| {
+            number
+                .map(|number| format!("{number:0width$}"))
+                .unwrap_or_default()
+        };
+        match self {
+            Self::Title => audio.title().name().clone(),
+            Self::Artist => audio.artist().name().clone(),
+            Self::AlbumTitle => audio.album_title().name().clone(),
+            Self::AlbumArtist => audio.album_artist().name().clone(),
+            Self::Year => number(audio.year().map(|year| year.0)),
+            Self::Genre => audio.genre().name().clone(),
+            Self::DiscNumber => number(*audio.disc_number()),
+            Self::TrackNumber => number(*audio.track_number()),
+            Self::Extension => extension(audio),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PathTemplateError {
+    #[error("Path template cannot be empty")]
+    Empty,
+    #[error("Unknown placeholder: {{{0}}}")]
+    UnknownPlaceholder(String),
+    #[error("Unclosed placeholder in template: {0}")]
+    UnclosedPlaceholder(String),
+    #[error("Invalid placeholder format: {0}")]
+    InvalidFormat(String),
+}
+
+impl FromStr for PathTemplate {
+    type Err = PathTemplateError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let template = s.trim().trim_matches('/');
+        if template.is_empty() {
+            return Err(PathTemplateError::Empty);
+        }
+
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(TemplatePart::Literal(rest[..start].to_owned()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| PathTemplateError::UnclosedPlaceholder(template.to_owned()))?;
+            let placeholder = &rest[start + 1..start + end];
+            let (name, format) = placeholder.split_once(':').unwrap_or((placeholder, ""));
+            let field = TemplateField::from_placeholder(name)
+                .ok_or_else(|| PathTemplateError::UnknownPlaceholder(name.to_owned()))?;
+            let width = match format {
+                "" => 0,
+                format => format
+                    .strip_prefix('0')
+                    .and_then(|width| width.parse().ok())
+                    .ok_or_else(|| PathTemplateError::InvalidFormat(placeholder.to_owned()))?,
+            };
+            parts.push(TemplatePart::Placeholder(field, width));
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(TemplatePart::Literal(rest.to_owned()));
+        }
+        Ok(Self { parts })
+    }
+}
+
+impl PathTemplate {
+    /// Renders the relative path of `audio`. Every component is sanitized, so values cannot
+    /// introduce directories or characters illegal in file names.
+    pub fn render(&self, audio: &Audio) -> PathBuf {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(literal) => rendered.push_str(literal),
+                TemplatePart::Placeholder(field, width) => {
+                    rendered.push_str(&replace_illegal(&field.value(audio, *width)))
+                }
+            }
+        }
+        let has_extension = self
+            .parts
+            .iter()
+            .any(|part| matches!(part, TemplatePart::Placeholder(TemplateField::Extension, _)));
+        if !has_extension && !extension(audio).is_empty() {
+            rendered.push('.');
+            rendered.push_str(&extension(audio));
+        }
+
+        let components = rendered.split('/').collect::<Vec<_>>();
+        let last = components.len() - 1;
+        components
+            .into_iter()
+            .enumerate()
+            .map(|(index, component)| sanitize_component(component, index == last))
+            .collect()
+    }
+}
+
+fn extension(audio: &Audio) -> String {
+    audio
+        .path()
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn replace_illegal(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if ILLEGAL_CHARACTERS.contains(&c) || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Cleans up a path component: separators left dangling by missing values and leading dots
+/// are trimmed, and names Windows cannot store are fixed.
+fn sanitize_component(component: &str, is_file_name: bool) -> String {
+    let (stem, extension) = match component.rsplit_once('.') {
+        Some((stem, extension)) if is_file_name && !extension.is_empty() => (stem, Some(extension)),
+        _ => (component, None),
+    };
+
+    let mut stem = replace_illegal(stem)
+        .trim_matches(|c: char| c.is_whitespace() || c == '-' || c == '_')
+        .trim_matches('.')
+        .to_owned();
+    if stem.is_empty() {
+        stem = "Unknown".to_owned();
+    }
+    if RESERVED_NAMES.contains(&stem.to_uppercase().as_str()) {
+        stem.insert(0, '_');
+    }
+
+    let extension = extension.map(|extension| format!(".{}", replace_illegal(extension)));
+    let max_stem_bytes = MAX_COMPONENT_BYTES - extension.as_ref().map_or(0, String::len);
+    if stem.len() > max_stem_bytes {
+        let mut end = max_stem_bytes;
+        while !stem.is_char_boundary(end) {
+            end -= 1;
+        }
+        stem = stem[..end].trim_end().to_owned();
+    }
+    stem + extension.as_deref().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::entity::audio::{
+        artist::Artist, cover::Cover, genre::Genre, year::Year, AudioBuilder,
+    };
+
+    use super::*;
+
+    fn audio(title: &str, album_artist: &str, path: &str) -> Audio {
+        AudioBuilder::default()
+            .title(title.parse().unwrap())
+            .artist(Artist::default())
+            .year(Some(Year::new(2001)))
+            .album_title("Discovery".parse().unwrap())
+            .album_artist(album_artist.parse().unwrap())
+            .album_cover(Cover::default())
+            .genre(Genre::default())
+            .track_number(Some(3))
+            .disc_number(None)
+            .compilation(false)
+            .path(PathBuf::from(path))
+            .build()
+            .unwrap()
+    }
+
+    fn render(template: &str, audio: &Audio) -> PathBuf {
+        template.parse::<PathTemplate>().unwrap().render(audio)
+    }
+
+    #[test]
+    fn renders_fields_with_padding() {
+        let audio = audio("Digital Love", "Daft Punk", "/music/a.MP3");
+        assert_eq!(
+            render(
+                "{album_artist}/{year} - {album}/{disc}-{track:02} {title}.{ext}",
+                &audio
+            ),
+            PathBuf::from("Daft Punk/2001 - Discovery/03 Digital Love.mp3")
+        );
+        // The extension is appended when the template does not place it
+        assert_eq!(
+            render("/{artist}/{track:03}/", &audio),
+            PathBuf::from("UNKNOWN/003.mp3")
+        );
+    }
+
+    #[test]
+    fn sanitizes_values() {
+        let illegal = audio("What?/Why: <Live>", "../..", "/music/a.flac");
+        assert_eq!(
+            render("{album_artist}/{title}", &illegal),
+            PathBuf::from("_/What__Why_ _Live.flac")
+        );
+        let reserved = audio("con", "Aux", "/music/a.flac");
+        assert_eq!(
+            render("{album_artist}/{title}", &reserved),
+            PathBuf::from("_Aux/_con.flac")
+        );
+        let long = "é".repeat(200);
+        let rendered = render("{title}", &audio(&long, "Daft Punk", "/music/a.flac"));
+        let file_name = rendered.to_str().unwrap();
+        assert!(file_name.len() <= MAX_COMPONENT_BYTES);
+        assert!(file_name.ends_with("é.flac"));
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert!(matches!(
+            " / ".parse::<PathTemplate>(),
+            Err(PathTemplateError::Empty)
+        ));
+        assert!(matches!(
+            "{band}".parse::<PathTemplate>(),
+            Err(PathTemplateError::UnknownPlaceholder(name)) if name == "band"
+        ));
+        assert!(matches!(
+            "{title".parse::<PathTemplate>(),
+            Err(PathTemplateError::UnclosedPlaceholder(_))
+        ));
+        assert!(matches!(
+            "{track:2}".parse::<PathTemplate>(),
+            Err(PathTemplateError::InvalidFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, RwLock, Weak},
+};
+
+use thiserror::Error;
+
+use crate::domain::{
+    entity::{
+        audio::Audio,
+        playlist::{playlist_id, Playlist, PlaylistEntry},
+    },
+    repository::PlaylistRepository,
+};
+
+use super::Library;
+
+/// A playlist file matched against the audios of the library.
+#[derive(Debug, Clone)]
+pub struct ResolvedPlaylist {
+    pub id: String,
+    pub path: PathBuf,
+    pub name: String,
+    /// Identifiers of the audios the entries point to, in playlist order.
+    pub tracks: Vec<String>,
+    /// Entries that point to no audio of the library, such as remote streams or files that
+    /// were moved.
+    pub unresolved: Vec<PlaylistEntry>,
+}
+
+#[derive(Error, Debug)]
+pub enum PlaylistServiceError {
+    #[error("Failed to access playlists: {0}")]
+    Repository(String),
+}
+
+struct ResolvedPlaylists {
+    playlists: Arc<Vec<ResolvedPlaylist>>,
+    /// The snapshot the playlists were last resolved against.
+    library: Weak<Library>,
+}
+
+/// Reads the playlist files of the library and writes new ones. Playlists are read again
+/// and resolved whenever the library snapshot changes, so a scan picks up edited files.
+pub struct PlaylistService<P> {
+    repository: P,
+    resolved: RwLock<ResolvedPlaylists>,
+}
+
+impl<P> PlaylistService<P>
+where
+    P: PlaylistRepository,
+    P::Error: Display,
+{
+    pub fn new(repository: P) -> Self {
+        Self {
+            repository,
+            resolved: RwLock::new(ResolvedPlaylists {
+                playlists: Arc::new(Vec::new()),
+                library: Weak::new(),
+            }),
+        }
+    }
+
+    /// Every playlist, resolved against the snapshot. Playlists that fail to be read are
+    /// logged and left out.
+    pub fn playlists(
+        &self,
+        library: &Arc<Library>,
+    ) -> Result<Arc<Vec<ResolvedPlaylist>>, PlaylistServiceError> {
+        let current = |resolved: &ResolvedPlaylists| {
+            Weak::ptr_eq(&resolved.library, &Arc::downgrade(library))
+        };
+        {
+            let resolved = self.resolved.read().expect("playlists lock poisoned");
+            if current(&resolved) {
+                return Ok(Arc::clone(&resolved.playlists));
+            }
+        }
+        let mut resolved = self.resolved.write().expect("playlists lock poisoned");
+        // Another request may have resolved them meanwhile
+        if current(&resolved) {
+            return Ok(Arc::clone(&resolved.playlists));
+        }
+        let paths = self
+            .repository
+            .find()
+            .map_err(|err| PlaylistServiceError::Repository(err.to_string()))?;
+        let resolver = Resolver::new(library);
+        let playlists = paths
+            .into_iter()
+            .filter_map(|path| match self.repository.read(&path) {
+                Ok(playlist) => Some(resolver.resolve(&path, playlist)),
+                Err(err) => {
+                    log::error!("Failed to read playlist {}: {}", path.display(), err);
+                    None
+                }
+            })
+            .collect();
+        *resolved = ResolvedPlaylists {
+            playlists: Arc::new(playlists),
+            library: Arc::downgrade(library),
+        };
+        Ok(Arc::clone(&resolved.playlists))
+    }
+
+    pub fn playlist(
+        &self,
+        library: &Arc<Library>,
+        id: &str,
+    ) -> Result<Option<ResolvedPlaylist>, PlaylistServiceError> {
+        Ok(self
+            .playlists(library)?
+            .iter()
+            .find(|playlist| playlist.id == id)
+            .cloned())
+    }
+
+    /// Reads and resolves a playlist file, which does not have to be in the library.
+    pub fn import(
+        &self,
+        library: &Library,
+        path: &Path,
+    ) -> Result<ResolvedPlaylist, PlaylistServiceError> {
+        let playlist = self
+            .repository
+            .read(path)
+            .map_err(|err| PlaylistServiceError::Repository(err.to_string()))?;
+        Ok(Resolver::new(library).resolve(path, playlist))
+    }
+
+    /// Writes the audios as a playlist in the format given by the extension of `path`,
+    /// with locations relative to it when possible.
+    pub fn export(
+        &self,
+        path: &Path,
+        name: &str,
+        audios: &[&Audio],
+    ) -> Result<Playlist, PlaylistServiceError> {
+        let playlist = Self::to_playlist(name, audios, path.parent());
+        self.repository
+            .write(path, &playlist)
+            .map_err(|err| PlaylistServiceError::Repository(err.to_string()))?;
+        Ok(playlist)
+    }
+
+    /// Builds a playlist of the audios. Locations are relative to `directory` if given and
+    /// absolute paths otherwise.
+    pub fn to_playlist(name: &str, audios: &[&Audio], directory: Option<&Path>) -> Playlist {
+        let entries = audios
+            .iter()
+            .map(|audio| {
+                let path = match directory {
+                    Some(directory) => relative_path(audio.path(), directory),
+                    None => normalize(audio.path()),
+                };
+                PlaylistEntry {
+                    location: path.to_string_lossy().into_owned(),
+                    title: Some(audio.title().name().clone()),
+                    artist: Some(audio.artist().name().clone()),
+                    album: Some(audio.album_title().name().clone()),
+                    duration: *audio.duration(),
+                }
+            })
+            .collect();
+        Playlist::new(name, entries)
+    }
+}
+
+/// Finds the audios playlist entries point to.
+struct Resolver {
+    by_path: HashMap<PathBuf, String>,
+    /// Playlists written on case-insensitive filesystems may not match the case of paths.
+    by_lowercase_path: HashMap<String, String>,
+}
+
+impl Resolver {
+    fn new(library: &Library) -> Self {
+        let mut by_path = HashMap::new();
+        let mut by_lowercase_path = HashMap::new();
+        for audio in library.audios() {
+            let path = normalize(audio.path());
+            by_lowercase_path.insert(path.to_string_lossy().to_lowercase(), audio.id());
+            by_path.insert(path, audio.id());
+        }
+        Self {
+            by_path,
+            by_lowercase_path,
+        }
+    }
+
+    fn resolve(&self, path: &Path, playlist: Playlist) -> ResolvedPlaylist {
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let mut tracks = Vec::new();
+        let mut unresolved = Vec::new();
+        for entry in playlist.entries {
+            match self.find(directory, &entry) {
+                Some(id) => tracks.push(id.clone()),
+                None => unresolved.push(entry),
+            }
+        }
+        ResolvedPlaylist {
+            id: playlist_id(path),
+            path: path.to_path_buf(),
+            name: playlist.name,
+            tracks,
+            unresolved,
+        }
+    }
+
+    fn find(&self, directory: &Path, entry: &PlaylistEntry) -> Option<&String> {
+        let path = normalize(&directory.join(entry.path()?));
+        self.by_path.get(&path).or_else(|| {
+            self.by_lowercase_path
+                .get(&path.to_string_lossy().to_lowercase())
+        })
+    }
+}
+
+/// Resolves `.` and `..` lexically, without following symbolic links, as the paths of
+/// gathered audios are not canonical either.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// `path` relative to `directory`, or `path` itself when one is absolute and the other is
+/// not.
+fn relative_path(path: &Path, directory: &Path) -> PathBuf {
+    let path = normalize(path);
+    let directory = normalize(directory);
+    if path.is_absolute() != directory.is_absolute() {
+        return path;
+    }
+    let path_components = path.components().collect::<Vec<_>>();
+    let directory_components = directory.components().collect::<Vec<_>>();
+    let common = path_components
+        .iter()
+        .zip(&directory_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut relative = PathBuf::new();
+    for _ in common..directory_components.len() {
+        relative.push("..");
+    }
+    relative.extend(&path_components[common..]);
+    relative
+}
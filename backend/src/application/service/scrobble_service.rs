@@ -0,0 +1,690 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    slice,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::Duration,
+};
+
+use chrono::Utc;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::domain::{
+    entity::scrobble::{Listen, QueuedListen, ScrobbleError, ScrobblerAccount, ScrobblerKind},
+    repository::{ScrobbleRepository, Scrobbler},
+};
+
+pub struct ScrobbleServiceOptions {
+    /// Delay before the first retry of a listen that failed to be submitted, doubled on each
+    /// failure.
+    pub retry_delay: Duration,
+    pub max_retry_delay: Duration,
+    /// Most listens submitted at once, Last.fm accepts 50.
+    pub batch_size: usize,
+    /// How often the queue is flushed when no listen wakes it up.
+    pub interval: Duration,
+}
+
+impl Default for ScrobbleServiceOptions {
+    fn default() -> Self {
+        Self {
+            retry_delay: Duration::from_secs(60),
+            max_retry_delay: Duration::from_secs(6 * 60 * 60),
+            batch_size: 50,
+            interval: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ScrobbleServiceError {
+    #[error("Not connected to {0}")]
+    NotConnected(ScrobblerKind),
+    #[error(transparent)]
+    Scrobble(#[from] ScrobbleError),
+    #[error("Failed to access scrobbles: {0}")]
+    Repository(String),
+}
+
+/// Outcome of a flush of the queue.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FlushReport {
+    pub submitted: usize,
+    /// Listens rejected by the service, which are not sent again.
+    pub rejected: usize,
+    /// Listens that failed to be submitted and are retried later.
+    pub postponed: usize,
+    /// Listens kept until their account is connected again, the service having refused
+    /// its credentials.
+    pub paused: usize,
+}
+
+/// Sends what users listen to to their scrobbling services. Listens go through a queue
+/// persisted in the repository, so that they are submitted once services are reachable again.
+pub struct ScrobbleService<S, R> {
+    scrobbler: S,
+    repository: R,
+    options: ScrobbleServiceOptions,
+    /// Set when listens are queued, to flush right away rather than on the next interval.
+    pending: Mutex<bool>,
+    wake: Condvar,
+}
+
+impl<S, R> ScrobbleService<S, R>
+where
+    S: Scrobbler,
+    R: ScrobbleRepository,
+    R::Error: Display,
+{
+    pub fn new(scrobbler: S, repository: R, options: ScrobbleServiceOptions) -> Self {
+        Self {
+            scrobbler,
+            repository,
+            options,
+            pending: Mutex::new(false),
+            wake: Condvar::new(),
+        }
+    }
+
+    pub fn accounts(&self, user_id: &str) -> Result<Vec<ScrobblerAccount>, ScrobbleServiceError> {
+        self.repository
+            .accounts(user_id)
+            .map_err(|err| ScrobbleServiceError::Repository(err.to_string()))
+    }
+
+    /// Why the paused accounts of the user were paused, by kind of service. Listens are
+    /// still queued for them, and submitted once they are connected again.
+    pub fn paused(
+        &self,
+        user_id: &str,
+    ) -> Result<BTreeMap<ScrobblerKind, String>, ScrobbleServiceError> {
+        self.repository
+            .paused_accounts(user_id)
+            .map_err(|err| ScrobbleServiceError::Repository(err.to_string()))
+    }
+
+    /// Checks the credentials of the account and saves it, replacing the one of its kind.
+    /// A paused account is resumed along with its queue.
+    pub fn connect(
+        &self,
+        user_id: &str,
+        account: ScrobblerAccount,
+    ) -> Result<ScrobblerAccount, ScrobbleServiceError> {
+        self.scrobbler.validate(&account)?;
+        self.repository
+            .save_account(user_id, &account)
+            .map_err(|err| ScrobbleServiceError::Repository(err.to_string()))?;
+        self.wake();
+        Ok(account)
+    }
+
+    /// Logs in a Last.fm compatible service, see [`Scrobbler::last_fm_session`].
+    pub fn last_fm_session(
+        &self,
+        url: &str,
+        api_key: &str,
+        api_secret: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<String, ScrobbleServiceError> {
+        Ok(self
+            .scrobbler
+            .last_fm_session(url, api_key, api_secret, username, password)?)
+    }
+
+    /// Removes the account along with the listens queued for it.
+    pub fn disconnect(
+        &self,
+        user_id: &str,
+        kind: ScrobblerKind,
+    ) -> Result<(), ScrobbleServiceError> {
+        if !self
+            .accounts(user_id)?
+            .iter()
+            .any(|account| account.kind() == kind)
+        {
+            return Err(ScrobbleServiceError::NotConnected(kind));
+        }
+        self.repository
+            .delete_account(user_id, kind)
+            .map_err(|err| ScrobbleServiceError::Repository(err.to_string()))
+    }
+
+    pub fn queue(&self, user_id: &str) -> Result<Vec<QueuedListen>, ScrobbleServiceError> {
+        self.repository
+            .queued(user_id)
+            .map_err(|err| ScrobbleServiceError::Repository(err.to_string()))
+    }
+
+    /// Tells every service of the user what is playing, but the paused ones. It is not
+    /// queued as it would be outdated by the time it is sent, so failures are only logged.
+    pub fn now_playing(&self, user_id: &str, listen: &Listen) -> Result<(), ScrobbleServiceError> {
+        let paused = self.paused(user_id)?;
+        for account in self.accounts(user_id)? {
+            if paused.contains_key(&account.kind()) {
+                continue;
+            }
+            if let Err(err) = self.scrobbler.now_playing(&account, listen) {
+                log::warn!("Failed to send now playing to {}: {}", account.kind(), err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues a listen for every service of the user, to be submitted in the background.
+    pub fn scrobble(&self, user_id: &str, listen: &Listen) -> Result<(), ScrobbleServiceError> {
+        let accounts = self.accounts(user_id)?;
+        for account in &accounts {
+            self.repository
+                .enqueue(user_id, account.kind(), listen)
+                .map_err(|err| ScrobbleServiceError::Repository(err.to_string()))?;
+        }
+        if !accounts.is_empty() {
+            self.wake();
+        }
+        Ok(())
+    }
+
+    /// Submits the queued listens that are due. Listens are sent in batches for each user and
+    /// service. When a service fails, the listens are postponed with an exponential backoff.
+    /// When it rejects a batch, its listens are sent one by one so that only the rejected
+    /// ones are dropped. When it refuses the credentials, the account is paused and its
+    /// listens are kept until it is connected again.
+    pub fn flush(&self) -> Result<FlushReport, ScrobbleServiceError> {
+        let due = self
+            .repository
+            .due(Utc::now(), usize::MAX)
+            .map_err(|err| ScrobbleServiceError::Repository(err.to_string()))?;
+        let mut groups = BTreeMap::<(String, ScrobblerKind), Vec<QueuedListen>>::new();
+        for listen in due {
+            groups
+                .entry((listen.user_id.clone(), listen.kind))
+                .or_default()
+                .push(listen);
+        }
+
+        let mut accounts = HashMap::<String, Vec<ScrobblerAccount>>::new();
+        let mut paused = HashMap::<String, BTreeMap<ScrobblerKind, String>>::new();
+        let mut report = FlushReport::default();
+        for ((user_id, kind), listens) in groups {
+            if !accounts.contains_key(&user_id) {
+                accounts.insert(user_id.clone(), self.accounts(&user_id)?);
+                paused.insert(user_id.clone(), self.paused(&user_id)?);
+            }
+            let account = accounts[&user_id]
+                .iter()
+                .find(|account| account.kind() == kind);
+            let Some(account) = account else {
+                // The account was removed since, along with its queue
+                for listen in &listens {
+                    self.dequeue(listen)?;
+                }
+                continue;
+            };
+            if paused[&user_id].contains_key(&kind) {
+                report.paused += listens.len();
+                continue;
+            }
+            self.flush_account(&user_id, account, &listens, &mut report)?;
+        }
+        Ok(report)
+    }
+
+    /// Flushes the queue in a background thread, whenever listens are queued and at each
+    /// interval to retry failed ones.
+    pub fn start(self: &Arc<Self>)
+    where
+        S: Send + Sync + 'static,
+        R: Send + Sync + 'static,
+    {
+        let service = Arc::clone(self);
+        thread::spawn(move || loop {
+            match service.flush() {
+                Ok(report) if report.submitted + report.rejected + report.postponed > 0 => {
+                    log::info!(
+                        "Scrobbled {} listens, {} rejected, {} postponed, {} paused",
+                        report.submitted,
+                        report.rejected,
+                        report.postponed,
+                        report.paused
+                    )
+                }
+                Ok(_) => {}
+                Err(err) => log::error!("Failed to flush scrobbles: {}", err),
+            }
+            let pending = service.pending.lock().expect("pending lock poisoned");
+            let (mut pending, _) = service
+                .wake
+                .wait_timeout_while(pending, service.options.interval, |pending| !*pending)
+                .expect("pending lock poisoned");
+            *pending = false;
+        });
+    }
+
+    /// Submits the listens of an account. Returns the error that stopped the submission
+    /// when the service failed or refused the credentials, after holding the listens left.
+    fn flush_account(
+        &self,
+        user_id: &str,
+        account: &ScrobblerAccount,
+        listens: &[QueuedListen],
+        report: &mut FlushReport,
+    ) -> Result<Option<ScrobbleError>, ScrobbleServiceError> {
+        let batch_size = self.options.batch_size.max(1);
+        for (index, batch) in listens.chunks(batch_size).enumerate() {
+            let start = index * batch_size;
+            let submitted = batch
+                .iter()
+                .map(|queued| queued.listen.clone())
+                .collect::<Vec<_>>();
+            match self.scrobbler.submit(account, &submitted) {
+                Ok(()) => {
+                    for listen in batch {
+                        self.dequeue(listen)?;
+                    }
+                    report.submitted += batch.len();
+                }
+                Err(err @ ScrobbleError::Unauthorized(_)) => {
+                    log::warn!(
+                        "Paused scrobbling to {} for user {}: {}",
+                        account.kind(),
+                        user_id,
+                        err
+                    );
+                    self.repository
+                        .pause_account(user_id, account.kind(), &err.to_string())
+                        .map_err(|err| ScrobbleServiceError::Repository(err.to_string()))?;
+                    self.hold(&listens[start..], &err, report)?;
+                    return Ok(Some(err));
+                }
+                Err(err) if err.is_retryable() => {
+                    // The service would fail the next batches as well
+                    self.hold(&listens[start..], &err, report)?;
+                    return Ok(Some(err));
+                }
+                Err(_) if batch.len() > 1 => {
+                    for (offset, listen) in batch.iter().enumerate() {
+                        let single = slice::from_ref(listen);
+                        if let Some(err) = self.flush_account(user_id, account, single, report)? {
+                            self.hold(&listens[start + offset + 1..], &err, report)?;
+                            return Ok(Some(err));
+                        }
+                    }
+                }
+                Err(err) => {
+                    log::warn!(
+                        "Dropped listen of {} by {}: {}",
+                        batch[0].listen.title,
+                        batch[0].listen.artist,
+                        err
+                    );
+                    self.dequeue(&batch[0])?;
+                    report.rejected += 1;
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Keeps listens that could not be submitted: until the account is connected again when
+    /// the credentials were refused, else until a later attempt.
+    fn hold(
+        &self,
+        listens: &[QueuedListen],
+        err: &ScrobbleError,
+        report: &mut FlushReport,
+    ) -> Result<(), ScrobbleServiceError> {
+        if let ScrobbleError::Unauthorized(_) = err {
+            report.paused += listens.len();
+            return Ok(());
+        }
+        for listen in listens {
+            self.postpone(listen, err)?;
+            report.postponed += 1;
+        }
+        Ok(())
+    }
+
+    /// Flushes the queue right away rather than on the next interval.
+    fn wake(&self) {
+        *self.pending.lock().expect("pending lock poisoned") = true;
+        self.wake.notify_one();
+    }
+
+    fn postpone(
+        &self,
+        listen: &QueuedListen,
+        err: &ScrobbleError,
+    ) -> Result<(), ScrobbleServiceError> {
+        let attempts = listen.attempts.saturating_add(1);
+        let delay = self
+            .options
+            .retry_delay
+            .saturating_mul(2u32.saturating_pow(attempts - 1))
+            .min(self.options.max_retry_delay);
+        let listen = QueuedListen {
+            attempts,
+            next_attempt_at: Utc::now() + chrono::Duration::seconds(delay.as_secs() as i64),
+            last_error: Some(err.to_string()),
+            ..listen.clone()
+        };
+        self.repository
+            .reschedule(&listen)
+            .map_err(|err| ScrobbleServiceError::Repository(err.to_string()))
+    }
+
+    fn dequeue(&self, listen: &QueuedListen) -> Result<(), ScrobbleServiceError> {
+        self.repository
+            .dequeue(listen.id)
+            .map_err(|err| ScrobbleServiceError::Repository(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use chrono::TimeZone;
+
+    use crate::infrastructure::repository::scrobble_repository::SqliteScrobbleRepository;
+
+    use super::*;
+
+    /// Answers submissions with the scripted results, then accepts them.
+    #[derive(Default)]
+    struct FakeScrobbler {
+        results: Mutex<VecDeque<Result<(), ScrobbleError>>>,
+        /// Titles of the listens of each submission.
+        submitted: Mutex<Vec<Vec<String>>>,
+    }
+
+    impl FakeScrobbler {
+        fn script(results: impl IntoIterator<Item = Result<(), ScrobbleError>>) -> Self {
+            Self {
+                results: Mutex::new(results.into_iter().collect()),
+                ..Self::default()
+            }
+        }
+    }
+
+    impl Scrobbler for &FakeScrobbler {
+        fn validate(&self, _: &ScrobblerAccount) -> Result<(), ScrobbleError> {
+            Ok(())
+        }
+
+        fn now_playing(&self, _: &ScrobblerAccount, _: &Listen) -> Result<(), ScrobbleError> {
+            Ok(())
+        }
+
+        fn submit(&self, _: &ScrobblerAccount, listens: &[Listen]) -> Result<(), ScrobbleError> {
+            self.submitted
+                .lock()
+                .unwrap()
+                .push(listens.iter().map(|listen| listen.title.clone()).collect());
+            self.results.lock().unwrap().pop_front().unwrap_or(Ok(()))
+        }
+
+        fn last_fm_session(
+            &self,
+            _: &str,
+            _: &str,
+            _: &str,
+            _: &str,
+            _: &str,
+        ) -> Result<String, ScrobbleError> {
+            Ok("session".to_string())
+        }
+    }
+
+    fn account() -> ScrobblerAccount {
+        ScrobblerAccount::ListenBrainz {
+            url: "http://127.0.0.1:1".to_string(),
+            token: "token".to_string(),
+        }
+    }
+
+    fn listen(title: &str, listened_at: i64) -> Listen {
+        Listen {
+            artist: "Artist".to_string(),
+            title: title.to_string(),
+            album: None,
+            album_artist: None,
+            track_number: None,
+            duration: None,
+            recording_mbid: None,
+            listened_at: Utc.timestamp_opt(listened_at, 0).unwrap(),
+        }
+    }
+
+    fn service(
+        scrobbler: &FakeScrobbler,
+        repository: SqliteScrobbleRepository,
+    ) -> ScrobbleService<&FakeScrobbler, SqliteScrobbleRepository> {
+        ScrobbleService::new(
+            scrobbler,
+            repository,
+            ScrobbleServiceOptions {
+                batch_size: 2,
+                ..ScrobbleServiceOptions::default()
+            },
+        )
+    }
+
+    fn scrobble(
+        service: &ScrobbleService<&FakeScrobbler, SqliteScrobbleRepository>,
+        titles: &[&str],
+    ) {
+        for (index, title) in titles.iter().enumerate() {
+            service
+                .scrobble("user", &listen(title, index as i64))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn pauses_accounts_whose_credentials_are_refused() {
+        let scrobbler =
+            FakeScrobbler::script([Err(ScrobbleError::Unauthorized("Invalid token".into()))]);
+        let service = service(
+            &scrobbler,
+            SqliteScrobbleRepository::open(":memory:").unwrap(),
+        );
+        service.connect("user", account()).unwrap();
+        scrobble(&service, &["a", "b", "c"]);
+
+        let report = service.flush().unwrap();
+        assert_eq!(report.paused, 3);
+        assert_eq!(
+            service.paused("user").unwrap()[&ScrobblerKind::ListenBrainz],
+            "Scrobbling service refused the credentials: Invalid token"
+        );
+        let queue = service.queue("user").unwrap();
+        assert_eq!(queue.len(), 3);
+        assert!(queue.iter().all(|listen| listen.attempts == 0));
+
+        // Paused accounts are left alone until they are connected again
+        assert_eq!(service.flush().unwrap().paused, 3);
+        assert_eq!(scrobbler.submitted.lock().unwrap().len(), 1);
+
+        service.connect("user", account()).unwrap();
+        assert!(service.paused("user").unwrap().is_empty());
+        assert_eq!(service.flush().unwrap().submitted, 3);
+        assert!(service.queue("user").unwrap().is_empty());
+    }
+
+    #[test]
+    fn postpones_listens_with_backoff_when_unavailable() {
+        let scrobbler = FakeScrobbler::script([
+            Err(ScrobbleError::Unavailable("offline".into())),
+            Err(ScrobbleError::Unavailable("offline".into())),
+        ]);
+        let service = service(
+            &scrobbler,
+            SqliteScrobbleRepository::open(":memory:").unwrap(),
+        );
+        service.connect("user", account()).unwrap();
+        scrobble(&service, &["a", "b", "c"]);
+
+        let before = Utc::now();
+        let report = service.flush().unwrap();
+        assert_eq!(report.postponed, 3);
+        // The service failed the first batch, so the second one is not sent
+        assert_eq!(scrobbler.submitted.lock().unwrap().len(), 1);
+        let queue = service.queue("user").unwrap();
+        for listen in &queue {
+            assert_eq!(listen.attempts, 1);
+            assert_eq!(
+                listen.last_error.as_deref(),
+                Some("Scrobbling service is unavailable: offline")
+            );
+            let delay = listen.next_attempt_at - before;
+            assert!(
+                delay >= chrono::Duration::seconds(59) && delay <= chrono::Duration::seconds(61)
+            );
+        }
+
+        // Nothing is due until the delay passed
+        assert_eq!(service.flush().unwrap(), FlushReport::default());
+        assert_eq!(scrobbler.submitted.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let scrobbler = FakeScrobbler::script(
+            (0..3).map(|_| Err(ScrobbleError::Unavailable("offline".into()))),
+        );
+        let service = ScrobbleService::new(
+            &scrobbler,
+            SqliteScrobbleRepository::open(":memory:").unwrap(),
+            ScrobbleServiceOptions {
+                retry_delay: Duration::ZERO,
+                ..ScrobbleServiceOptions::default()
+            },
+        );
+        service.connect("user", account()).unwrap();
+        scrobble(&service, &["a"]);
+        for attempts in 1..=3 {
+            service.flush().unwrap();
+            assert_eq!(service.queue("user").unwrap()[0].attempts, attempts);
+        }
+
+        let service = ScrobbleService::new(
+            &scrobbler,
+            SqliteScrobbleRepository::open(":memory:").unwrap(),
+            ScrobbleServiceOptions {
+                retry_delay: Duration::from_secs(60),
+                max_retry_delay: Duration::from_secs(90),
+                ..ScrobbleServiceOptions::default()
+            },
+        );
+        let queued = QueuedListen {
+            id: 1,
+            user_id: "user".to_string(),
+            kind: ScrobblerKind::ListenBrainz,
+            listen: listen("a", 0),
+            attempts: 1,
+            next_attempt_at: Utc::now(),
+            last_error: None,
+        };
+        service.repository.save_account("user", &account()).unwrap();
+        service
+            .repository
+            .enqueue("user", ScrobblerKind::ListenBrainz, &queued.listen)
+            .unwrap();
+        let before = Utc::now();
+        service
+            .postpone(&queued, &ScrobbleError::Unavailable("offline".into()))
+            .unwrap();
+        // The second attempt waits twice the delay, capped to the maximum
+        let rescheduled = &service.queue("user").unwrap()[0];
+        assert_eq!(rescheduled.attempts, 2);
+        let delay = rescheduled.next_attempt_at - before;
+        assert!(delay >= chrono::Duration::seconds(89) && delay <= chrono::Duration::seconds(91));
+    }
+
+    #[test]
+    fn splits_rejected_batches_to_drop_only_rejected_listens() {
+        let scrobbler = FakeScrobbler::script([
+            Err(ScrobbleError::Rejected("invalid".into())),
+            Ok(()),
+            Err(ScrobbleError::Rejected("invalid".into())),
+        ]);
+        let service = service(
+            &scrobbler,
+            SqliteScrobbleRepository::open(":memory:").unwrap(),
+        );
+        service.connect("user", account()).unwrap();
+        scrobble(&service, &["a", "b", "c"]);
+
+        let report = service.flush().unwrap();
+        assert_eq!(
+            report,
+            FlushReport {
+                submitted: 2,
+                rejected: 1,
+                ..FlushReport::default()
+            }
+        );
+        assert_eq!(
+            *scrobbler.submitted.lock().unwrap(),
+            [vec!["a", "b"], vec!["a"], vec!["b"], vec!["c"]]
+        );
+        assert!(service.queue("user").unwrap().is_empty());
+    }
+
+    #[test]
+    fn stops_splitting_when_the_service_fails() {
+        let scrobbler = FakeScrobbler::script([
+            Err(ScrobbleError::Rejected("invalid".into())),
+            Err(ScrobbleError::Unavailable("offline".into())),
+        ]);
+        let service = service(
+            &scrobbler,
+            SqliteScrobbleRepository::open(":memory:").unwrap(),
+        );
+        service.connect("user", account()).unwrap();
+        scrobble(&service, &["a", "b", "c"]);
+
+        let report = service.flush().unwrap();
+        assert_eq!(report.postponed, 3);
+        assert_eq!(scrobbler.submitted.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn queued_listens_survive_a_restart() {
+        let dir = std::env::temp_dir().join(format!("earr-scrobbles-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("earr.sqlite3");
+
+        let offline = FakeScrobbler::script([Err(ScrobbleError::Unavailable("offline".into()))]);
+        let options = || ScrobbleServiceOptions {
+            retry_delay: Duration::ZERO,
+            ..ScrobbleServiceOptions::default()
+        };
+        let service = ScrobbleService::new(
+            &offline,
+            SqliteScrobbleRepository::open(&path).unwrap(),
+            options(),
+        );
+        service.connect("user", account()).unwrap();
+        scrobble(&service, &["a", "b"]);
+        assert_eq!(service.flush().unwrap().postponed, 2);
+        drop(service);
+
+        let online = FakeScrobbler::default();
+        let service = ScrobbleService::new(
+            &online,
+            SqliteScrobbleRepository::open(&path).unwrap(),
+            options(),
+        );
+        assert_eq!(service.queue("user").unwrap().len(), 2);
+        assert_eq!(service.flush().unwrap().submitted, 2);
+        assert_eq!(*online.submitted.lock().unwrap(), [vec!["a", "b"]]);
+        assert!(service.queue("user").unwrap().is_empty());
+
+        drop(service);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,276 @@
+use std::sync::{Arc, RwLock, Weak};
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::domain::entity::audio::Audio;
+
+use super::{Library, Page, Paginated};
+
+mod index;
+mod query;
+
+use index::SearchIndex;
+pub use query::SearchQuery;
+
+/// Text fields of audios that are indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchField {
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Genre,
+    Lyrics,
+}
+
+impl SearchField {
+    pub const ALL: [Self; 6] = [
+        Self::Title,
+        Self::Artist,
+        Self::Album,
+        Self::AlbumArtist,
+        Self::Genre,
+        Self::Lyrics,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /// How much a match in the field counts towards the ranking.
+    fn weight(self) -> f64 {
+        match self {
+            Self::Title => 3.0,
+            Self::Artist | Self::Album => 2.0,
+            Self::AlbumArtist => 1.5,
+            Self::Genre => 1.0,
+            Self::Lyrics => 0.5,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SearchServiceError {
+    #[error("Invalid year: {0}")]
+    InvalidYear(String),
+    #[error("Unterminated quote")]
+    UnterminatedQuote,
+}
+
+/// Number of audios affected by an index update.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct IndexUpdate {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchHit<'a> {
+    pub audio: &'a Audio,
+    pub score: f64,
+}
+
+struct IndexedLibrary {
+    index: SearchIndex,
+    /// The snapshot the index was last updated with.
+    library: Weak<Library>,
+}
+
+/// Full-text search over the tracks of the library, see [`SearchQuery`] for the syntax.
+/// The index follows the library snapshots, reindexing only the audios that changed.
+pub struct SearchService {
+    indexed: RwLock<IndexedLibrary>,
+}
+
+impl Default for SearchService {
+    fn default() -> Self {
+        Self {
+            indexed: RwLock::new(IndexedLibrary {
+                index: SearchIndex::default(),
+                library: Weak::new(),
+            }),
+        }
+    }
+}
+
+impl SearchService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Brings the index up to date with the snapshot.
+    pub fn update(&self, library: &Arc<Library>) -> IndexUpdate {
+        let current =
+            |indexed: &IndexedLibrary| Weak::ptr_eq(&indexed.library, &Arc::downgrade(library));
+        if current(&self.indexed.read().expect("search index lock poisoned")) {
+            return IndexUpdate::default();
+        }
+        let mut indexed = self.indexed.write().expect("search index lock poisoned");
+        // Another search may have updated it meanwhile
+        if current(&indexed) {
+            return IndexUpdate::default();
+        }
+        let update = indexed.index.update(library.audios());
+        indexed.library = Arc::downgrade(library);
+        update
+    }
+
+    /// Returns the tracks of `library` matching the query, best matches first. The index is
+    /// kept up to date with the whole snapshot `indexed`, of which `library` may only hold
+    /// the part a user sees.
+    pub fn search<'a>(
+        &self,
+        indexed: &Arc<Library>,
+        library: &'a Library,
+        query: &str,
+        page: Page,
+    ) -> Result<Paginated<SearchHit<'a>>, SearchServiceError> {
+        let query = SearchQuery::parse(query)?;
+        self.update(indexed);
+
+        let indexed = self.indexed.read().expect("search index lock poisoned");
+        let mut hits = if query.is_empty() {
+            Vec::new()
+        } else {
+            indexed
+                .index
+                .search(&query)
+                .into_iter()
+                .filter_map(|(id, score)| library.audio(id).map(|audio| SearchHit { audio, score }))
+                .collect::<Vec<_>>()
+        };
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.audio.path().cmp(b.audio.path()))
+        });
+        Ok(Paginated {
+            total: hits.len(),
+            offset: page.offset,
+            items: hits
+                .into_iter()
+                .skip(page.offset)
+                .take(page.limit)
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{
+        application::service::{AlbumService, AlbumServiceOptions},
+        domain::entity::audio::{
+            artist::Artist, cover::Cover, genre::Genre, title::Title, AudioBuilder,
+        },
+    };
+
+    use super::*;
+
+    fn audio(path: &str, title: &str, lyrics: Option<&str>) -> Audio {
+        AudioBuilder::default()
+            .title(title.parse().unwrap())
+            .artist(Artist::default())
+            .year(None)
+            .album_title(Title::default())
+            .album_artist(Artist::default())
+            .album_cover(Cover::default())
+            .genre(Genre::default())
+            .track_number(None)
+            .disc_number(None)
+            .compilation(false)
+            .lyrics(lyrics.map(str::to_owned))
+            .path(PathBuf::from(path))
+            .build()
+            .unwrap()
+    }
+
+    fn library(audios: Vec<Audio>) -> Arc<Library> {
+        let options = AlbumServiceOptions::default();
+        let sort_key = options.sort_key.clone();
+        Arc::new(Library::new(
+            AlbumService::new(options).group(audios),
+            sort_key,
+        ))
+    }
+
+    fn titles(hits: &Paginated<SearchHit>) -> Vec<String> {
+        hits.items
+            .iter()
+            .map(|hit| hit.audio.title().name().clone())
+            .collect()
+    }
+
+    #[test]
+    fn ranks_and_paginates_hits() {
+        let library = library(vec![
+            audio("/c", "Sunrise", Some("sunset")),
+            audio("/b", "Sunset", None),
+            audio("/a", "Sunset Boulevard", None),
+        ]);
+        let service = SearchService::new();
+
+        let hits = service
+            .search(&library, &library, "sunset", Page::default())
+            .unwrap();
+        assert_eq!(hits.total, 3);
+        // Title matches rank before lyrics ones, equal scores by path
+        assert_eq!(titles(&hits), vec!["Sunset Boulevard", "Sunset", "Sunrise"]);
+
+        let page = Page {
+            offset: 1,
+            limit: 1,
+        };
+        let hits = service.search(&library, &library, "sunset", page).unwrap();
+        assert_eq!(hits.total, 3);
+        assert_eq!(titles(&hits), vec!["Sunset"]);
+    }
+
+    #[test]
+    fn follows_library_snapshots() {
+        let service = SearchService::new();
+        let first = library(vec![audio("/1", "Old", None), audio("/2", "Kept", None)]);
+        assert_eq!(service.update(&first).added, 2);
+        assert_eq!(service.update(&first), IndexUpdate::default());
+
+        let second = library(vec![audio("/2", "Kept", None), audio("/3", "New", None)]);
+        assert_eq!(
+            service.update(&second),
+            IndexUpdate {
+                added: 1,
+                updated: 0,
+                removed: 1,
+            }
+        );
+        let search = |query| {
+            titles(
+                &service
+                    .search(&second, &second, query, Page::default())
+                    .unwrap(),
+            )
+        };
+        assert!(search("old").is_empty());
+        assert_eq!(search("new"), vec!["New"]);
+    }
+
+    #[test]
+    fn only_returns_tracks_of_the_visible_library() {
+        let service = SearchService::new();
+        let indexed = library(vec![
+            audio("/a/1", "Song", None),
+            audio("/b/2", "Song", None),
+        ]);
+        let visible = library(vec![audio("/b/2", "Song", None)]);
+
+        let hits = service
+            .search(&indexed, &visible, "song", Page::default())
+            .unwrap();
+        assert_eq!(hits.total, 1);
+        assert_eq!(hits.items[0].audio.path(), &PathBuf::from("/b/2"));
+    }
+}
@@ -0,0 +1,477 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet},
+    hash::{Hash, Hasher},
+};
+
+use crate::domain::entity::audio::{sort_key::SortKeyOptions, Audio};
+
+use super::{
+    query::{QueryTerm, SearchQuery, TermKind},
+    IndexUpdate, SearchField,
+};
+
+/// Weight of words matched by prefix or with typos, relative to exact matches.
+const PREFIX_WEIGHT: f64 = 0.6;
+const FUZZY_WEIGHT: f64 = 0.4;
+/// Words shorter than this are not matched with typos.
+const MIN_FUZZY_LENGTH: usize = 4;
+/// Words at least this long are matched with up to two typos instead of one.
+const TWO_TYPOS_LENGTH: usize = 8;
+
+/// Splits a text into lowercase words without diacritics.
+pub(super) fn tokenize(text: &str) -> Vec<String> {
+    let options = SortKeyOptions {
+        articles: Vec::new(),
+        fold_diacritics: true,
+    };
+    options
+        .normalize(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+struct Document {
+    /// Hash of the indexed values, used to tell whether an audio changed.
+    fingerprint: u64,
+    year: Option<u16>,
+    /// Words of each field, in order.
+    fields: [Vec<String>; SearchField::ALL.len()],
+}
+
+/// Number of occurrences of a word in each field of a document.
+type Occurrences = [u32; SearchField::ALL.len()];
+
+/// Inverted index of the text fields of audios, by audio id.
+#[derive(Default)]
+pub(super) struct SearchIndex {
+    documents: HashMap<String, Document>,
+    postings: BTreeMap<String, HashMap<String, Occurrences>>,
+}
+
+impl SearchIndex {
+    /// Indexes the audios that are new or changed and drops the ones that are gone.
+    pub fn update<'a>(&mut self, audios: impl IntoIterator<Item = &'a Audio>) -> IndexUpdate {
+        let mut update = IndexUpdate::default();
+        let mut seen = HashSet::new();
+        for audio in audios {
+            let id = audio.id();
+            let texts = Self::texts(audio);
+            let year = audio.year().map(|year| year.0);
+            let fingerprint = Self::fingerprint(&texts, year);
+            match self.documents.get(&id) {
+                Some(document) if document.fingerprint == fingerprint => {}
+                Some(_) => {
+                    self.remove(&id);
+                    self.insert(id.clone(), &texts, year, fingerprint);
+                    update.updated += 1;
+                }
+                None => {
+                    self.insert(id.clone(), &texts, year, fingerprint);
+                    update.added += 1;
+                }
+            }
+            seen.insert(id);
+        }
+        let removed = self
+            .documents
+            .keys()
+            .filter(|id| !seen.contains(*id))
+            .cloned()
+            .collect::<Vec<_>>();
+        for id in &removed {
+            self.remove(id);
+        }
+        update.removed = removed.len();
+        update
+    }
+
+    /// Ids of the audios matching every term of the query, with their score.
+    pub fn search(&self, query: &SearchQuery) -> HashMap<&str, f64> {
+        let (excluded, required): (Vec<_>, Vec<_>) =
+            query.terms.iter().partition(|term| term.negated);
+
+        let mut scores: Option<HashMap<&str, f64>> = None;
+        for term in required {
+            let matches = self.matches(term);
+            scores = Some(match scores {
+                None => matches,
+                Some(scores) => scores
+                    .into_iter()
+                    .filter_map(|(id, score)| matches.get(id).map(|other| (id, score + other)))
+                    .collect(),
+            });
+        }
+        // Queries made only of exclusions start from every audio
+        let mut scores =
+            scores.unwrap_or_else(|| self.documents.keys().map(|id| (id.as_str(), 0.0)).collect());
+        for term in excluded {
+            let matches = self.matches(term);
+            scores.retain(|id, _| !matches.contains_key(id));
+        }
+        scores
+    }
+
+    fn matches(&self, term: &QueryTerm) -> HashMap<&str, f64> {
+        match &term.kind {
+            TermKind::Word { field, word } => self.word_matches(*field, word),
+            TermKind::Phrase { field, words } => self.phrase_matches(*field, words),
+            TermKind::Year(years) => self
+                .documents
+                .iter()
+                .filter(|(_, document)| document.year.is_some_and(|year| years.contains(&year)))
+                .map(|(id, _)| (id.as_str(), 0.0))
+                .collect(),
+        }
+    }
+
+    /// Scores documents by the best of the exact, prefix and fuzzy matches of the word.
+    fn word_matches(&self, field: Option<SearchField>, word: &str) -> HashMap<&str, f64> {
+        let mut variants = Vec::new();
+        if self.postings.contains_key(word) {
+            variants.push((word, 1.0));
+        }
+        variants.extend(
+            self.postings
+                .range::<str, _>((std::ops::Bound::Excluded(word), std::ops::Bound::Unbounded))
+                .map(|(token, _)| token.as_str())
+                .take_while(|token| token.starts_with(word))
+                .map(|token| (token, PREFIX_WEIGHT)),
+        );
+        let length = word.chars().count();
+        if length >= MIN_FUZZY_LENGTH {
+            let max_distance = if length >= TWO_TYPOS_LENGTH { 2 } else { 1 };
+            variants.extend(
+                self.postings
+                    .keys()
+                    .filter(|token| !token.starts_with(word))
+                    .filter_map(|token| {
+                        let distance = edit_distance(word, token, max_distance)?;
+                        Some((token.as_str(), FUZZY_WEIGHT / distance as f64))
+                    }),
+            );
+        }
+
+        let mut scores: HashMap<&str, f64> = HashMap::new();
+        for (token, weight) in variants {
+            let postings = &self.postings[token];
+            let idf = self.idf(postings.len());
+            for (id, occurrences) in postings {
+                let score = weight * idf * Self::field_score(field, occurrences);
+                if score > 0.0 {
+                    let best = scores.entry(id.as_str()).or_default();
+                    *best = best.max(score);
+                }
+            }
+        }
+        scores
+    }
+
+    /// Scores documents where the words appear in sequence within a field.
+    fn phrase_matches(&self, field: Option<SearchField>, words: &[String]) -> HashMap<&str, f64> {
+        let Some(postings) = words
+            .iter()
+            .map(|word| self.postings.get(word))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return HashMap::new();
+        };
+        let idf = postings
+            .iter()
+            .map(|postings| self.idf(postings.len()))
+            .sum::<f64>();
+        let shortest = postings
+            .iter()
+            .min_by_key(|postings| postings.len())
+            .expect("phrases have several words");
+        shortest
+            .keys()
+            .filter_map(|id| {
+                let document = &self.documents[id];
+                let weight = SearchField::ALL
+                    .into_iter()
+                    .filter(|candidate| field.is_none_or(|field| field == *candidate))
+                    .filter(|candidate| {
+                        document.fields[candidate.index()]
+                            .windows(words.len())
+                            .any(|window| window == words)
+                    })
+                    .map(SearchField::weight)
+                    .max_by(f64::total_cmp)?;
+                Some((id.as_str(), idf * weight))
+            })
+            .collect()
+    }
+
+    /// Saturating sum of the occurrences in each field, weighted by field.
+    fn field_score(field: Option<SearchField>, occurrences: &Occurrences) -> f64 {
+        SearchField::ALL
+            .into_iter()
+            .filter(|candidate| field.is_none_or(|field| field == *candidate))
+            .map(|candidate| {
+                let count = occurrences[candidate.index()] as f64;
+                candidate.weight() * count / (count + 1.0)
+            })
+            .sum()
+    }
+
+    /// Rarer words weigh more.
+    fn idf(&self, document_count: usize) -> f64 {
+        let total = self.documents.len() as f64;
+        let count = document_count as f64;
+        (1.0 + (total - count + 0.5) / (count + 0.5)).ln()
+    }
+
+    fn texts(audio: &Audio) -> [Vec<String>; SearchField::ALL.len()] {
+        SearchField::ALL.map(|field| match field {
+            SearchField::Title => tokenize(audio.title().name()),
+            SearchField::Artist => tokenize(audio.artist().name()),
+            SearchField::Album => tokenize(audio.album_title().name()),
+            SearchField::AlbumArtist => tokenize(audio.album_artist().name()),
+            SearchField::Genre => tokenize(audio.genre().name()),
+            SearchField::Lyrics => audio.lyrics().as_deref().map(tokenize).unwrap_or_default(),
+        })
+    }
+
+    fn fingerprint(texts: &[Vec<String>], year: Option<u16>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        texts.hash(&mut hasher);
+        year.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn insert(
+        &mut self,
+        id: String,
+        texts: &[Vec<String>; SearchField::ALL.len()],
+        year: Option<u16>,
+        fingerprint: u64,
+    ) {
+        for field in SearchField::ALL {
+            for word in &texts[field.index()] {
+                let occurrences = self
+                    .postings
+                    .entry(word.clone())
+                    .or_default()
+                    .entry(id.clone())
+                    .or_default();
+                occurrences[field.index()] += 1;
+            }
+        }
+        self.documents.insert(
+            id,
+            Document {
+                fingerprint,
+                year,
+                fields: texts.clone(),
+            },
+        );
+    }
+
+    fn remove(&mut self, id: &str) {
+        let Some(document) = self.documents.remove(id) else {
+            return;
+        };
+        for word in document.fields.iter().flatten() {
+            if let Some(postings) = self.postings.get_mut(word) {
+                postings.remove(id);
+                if postings.is_empty() {
+                    self.postings.remove(word);
+                }
+            }
+        }
+    }
+}
+
+/// Edit distance between two words, counting a transposition of adjacent characters as a
+/// single edit, `None` when it exceeds `max`.
+fn edit_distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut before_previous: Vec<usize> = Vec::new();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for i in 0..a.len() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for j in 0..b.len() {
+            let substitution = previous[j] + usize::from(a[i] != b[j]);
+            let mut distance = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+            if i > 0 && j > 0 && a[i] == b[j - 1] && a[i - 1] == b[j] {
+                distance = distance.min(before_previous[j - 1] + 1);
+            }
+            current[j + 1] = distance;
+        }
+        // Later rows cannot go below the minimum of the last two
+        let min = current.iter().chain(&previous).min().copied();
+        if min.is_some_and(|min| min > max) {
+            return None;
+        }
+        before_previous = std::mem::replace(&mut previous, current);
+    }
+    let distance = previous[b.len()];
+    (distance <= max).then_some(distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::domain::entity::audio::{
+        artist::Artist, cover::Cover, genre::Genre, title::Title, year::Year, AudioBuilder,
+    };
+
+    use super::*;
+
+    fn audio(path: &str, title: &str, artist: &str, year: Option<u16>) -> Audio {
+        AudioBuilder::default()
+            .title(title.parse::<Title>().unwrap_or_default())
+            .artist(artist.parse::<Artist>().unwrap_or_default())
+            .year(year.map(Year))
+            .album_title(Title::default())
+            .album_artist(Artist::default())
+            .album_cover(Cover::default())
+            .genre(Genre::default())
+            .track_number(None)
+            .disc_number(None)
+            .compilation(false)
+            .path(PathBuf::from(path))
+            .build()
+            .unwrap()
+    }
+
+    fn index(audios: &[Audio]) -> SearchIndex {
+        let mut index = SearchIndex::default();
+        index.update(audios);
+        index
+    }
+
+    /// Titles of the matching audios, best matches first.
+    fn search(index: &SearchIndex, audios: &[Audio], query: &str) -> Vec<String> {
+        let scores = index.search(&SearchQuery::parse(query).unwrap());
+        let mut hits = audios
+            .iter()
+            .filter_map(|audio| Some((scores.get(audio.id().as_str())?, audio)))
+            .collect::<Vec<_>>();
+        hits.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        hits.into_iter()
+            .map(|(_, audio)| audio.title().name().clone())
+            .collect()
+    }
+
+    #[test]
+    fn measures_edit_distance() {
+        assert_eq!(edit_distance("beatles", "beatles", 2), Some(0));
+        assert_eq!(edit_distance("beatles", "beetles", 2), Some(1));
+        assert_eq!(edit_distance("beatles", "beatle", 2), Some(1));
+        // A transposition is a single edit
+        assert_eq!(edit_distance("beatles", "baetles", 1), Some(1));
+        assert_eq!(edit_distance("kitten", "sitting", 3), Some(3));
+        assert_eq!(edit_distance("kitten", "sitting", 2), None);
+        assert_eq!(edit_distance("abba", "abbafoo", 2), None);
+    }
+
+    #[test]
+    fn matches_exactly_by_prefix_and_with_typos() {
+        let audios = [
+            audio("/1", "Beat It", "Michael Jackson", None),
+            audio("/2", "Beatnik", "Unknown", None),
+            audio("/3", "Yesterday", "Beatles", None),
+        ];
+        let index = index(&audios);
+
+        // Exact matches rank before prefix ones
+        assert_eq!(
+            search(&index, &audios, "beat"),
+            vec!["Beat It", "Beatnik", "Yesterday"]
+        );
+        assert_eq!(search(&index, &audios, "yestrday"), vec!["Yesterday"]);
+        // Long words allow two typos, short ones a single one
+        assert_eq!(search(&index, &audios, "yesturdy"), vec!["Yesterday"]);
+        assert!(search(&index, &audios, "yestrdy").is_empty());
+        assert_eq!(search(&index, &audios, "jakcson"), vec!["Beat It"]);
+        // Words too short are not matched with typos
+        assert!(search(&index, &audios, "bet").is_empty());
+    }
+
+    #[test]
+    fn folds_diacritics() {
+        let audios = [
+            audio("/1", "Café del Mar", "Energy 52", None),
+            audio("/2", "Cafe Society", "Unknown", None),
+        ];
+        let index = index(&audios);
+
+        assert_eq!(search(&index, &audios, "café").len(), 2);
+        assert_eq!(search(&index, &audios, "CAFE del").len(), 1);
+    }
+
+    #[test]
+    fn scopes_terms_to_fields_and_years() {
+        let audios = [
+            audio("/1", "Love", "Nobody", Some(1995)),
+            audio("/2", "Song", "Love", Some(2005)),
+            audio("/3", "Love Song", "Somebody", Some(2010)),
+        ];
+        let index = index(&audios);
+
+        assert_eq!(search(&index, &audios, "title:love").len(), 2);
+        assert_eq!(search(&index, &audios, "artist:love"), vec!["Song"]);
+        assert_eq!(search(&index, &audios, "love year:>2000").len(), 2);
+        assert_eq!(
+            search(&index, &audios, "love year:1990..1999"),
+            vec!["Love"]
+        );
+        assert_eq!(
+            search(&index, &audios, r#"title:"love song""#),
+            vec!["Love Song"]
+        );
+        assert_eq!(search(&index, &audios, "love -song"), vec!["Love"]);
+        assert_eq!(search(&index, &audios, "-love").len(), 0);
+    }
+
+    #[test]
+    fn ranks_title_matches_first() {
+        let audios = [
+            audio("/1", "Other", "Wonder", None),
+            audio("/2", "Wonder", "Other", None),
+        ];
+        let index = index(&audios);
+
+        assert_eq!(search(&index, &audios, "wonder"), vec!["Wonder", "Other"]);
+    }
+
+    #[test]
+    fn updates_only_changed_audios() {
+        let first = audio("/1", "First", "Artist", None);
+        let second = audio("/2", "Second", "Artist", None);
+        let mut index = SearchIndex::default();
+        assert_eq!(
+            index.update([&first, &second]),
+            IndexUpdate {
+                added: 2,
+                updated: 0,
+                removed: 0,
+            }
+        );
+        assert_eq!(index.update([&first, &second]), IndexUpdate::default());
+
+        let renamed = audio("/1", "Renamed", "Artist", None);
+        let third = audio("/3", "Third", "Artist", None);
+        assert_eq!(
+            index.update([&renamed, &third]),
+            IndexUpdate {
+                added: 1,
+                updated: 1,
+                removed: 1,
+            }
+        );
+        let audios = [renamed, third];
+        assert!(search(&index, &audios, "first").is_empty());
+        assert_eq!(search(&index, &audios, "renamed"), vec!["Renamed"]);
+        assert!(!index.postings.contains_key("second"));
+        assert_eq!(index.documents.len(), 2);
+    }
+}
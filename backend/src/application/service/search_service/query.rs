@@ -0,0 +1,247 @@
+use std::ops::RangeInclusive;
+
+use super::{index::tokenize, SearchField, SearchServiceError};
+
+/// A parsed search query. Terms are separated by whitespace and must all match:
+///
+/// - `word` matches any field, exactly, by prefix or with a typo
+/// - `"several words"` matches the words in sequence
+/// - `field:word` and `field:"several words"` only match the field, one of `title`,
+///   `artist`, `album`, `album_artist`, `genre` and `lyrics`
+/// - `year:2001`, `year:>2000`, `year:<=1999` and `year:1990..1999` match the year
+/// - a leading `-` excludes the tracks matching the term
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchQuery {
+    pub(super) terms: Vec<QueryTerm>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct QueryTerm {
+    pub negated: bool,
+    pub kind: TermKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum TermKind {
+    Word {
+        field: Option<SearchField>,
+        word: String,
+    },
+    Phrase {
+        field: Option<SearchField>,
+        words: Vec<String>,
+    },
+    Year(RangeInclusive<u16>),
+}
+
+impl SearchQuery {
+    pub fn parse(query: &str) -> Result<Self, SearchServiceError> {
+        let mut terms = Vec::new();
+        let mut chars = query.chars().peekable();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.peek().is_none() {
+                break;
+            }
+            let negated = chars.next_if_eq(&'-').is_some();
+
+            let mut value = String::new();
+            let mut field = None;
+            let mut quoted = false;
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                match c {
+                    '"' => {
+                        quoted = true;
+                        loop {
+                            match chars.next() {
+                                Some('"') => break,
+                                Some(c) => value.push(c),
+                                None => return Err(SearchServiceError::UnterminatedQuote),
+                            }
+                        }
+                    }
+                    // Colons that do not follow a known field name are part of the text
+                    ':' if field.is_none() && !quoted => match FieldName::parse(&value) {
+                        Some(name) => {
+                            field = Some(name);
+                            value.clear();
+                        }
+                        None => value.push(c),
+                    },
+                    c => value.push(c),
+                }
+            }
+
+            let kind = match field {
+                Some(FieldName::Year) => Some(TermKind::Year(parse_years(&value)?)),
+                Some(FieldName::Text(field)) => Self::text(Some(field), &value),
+                None => Self::text(None, &value),
+            };
+            if let Some(kind) = kind {
+                terms.push(QueryTerm { negated, kind });
+            }
+        }
+        Ok(Self { terms })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// A trailing `*` is accepted, every word being matched by prefix anyway.
+    fn text(field: Option<SearchField>, value: &str) -> Option<TermKind> {
+        let mut words = tokenize(value.trim_end_matches('*'));
+        match words.len() {
+            0 => None,
+            1 => Some(TermKind::Word {
+                field,
+                word: words.remove(0),
+            }),
+            _ => Some(TermKind::Phrase { field, words }),
+        }
+    }
+}
+
+enum FieldName {
+    Text(SearchField),
+    Year,
+}
+
+impl FieldName {
+    fn parse(name: &str) -> Option<Self> {
+        let field = match name.to_lowercase().as_str() {
+            "year" => return Some(Self::Year),
+            "title" => SearchField::Title,
+            "artist" => SearchField::Artist,
+            "album" => SearchField::Album,
+            "album_artist" | "albumartist" => SearchField::AlbumArtist,
+            "genre" => SearchField::Genre,
+            "lyrics" => SearchField::Lyrics,
+            _ => return None,
+        };
+        Some(Self::Text(field))
+    }
+}
+
+fn parse_years(value: &str) -> Result<RangeInclusive<u16>, SearchServiceError> {
+    let invalid = || SearchServiceError::InvalidYear(value.to_owned());
+    let year = |value: &str| value.trim().parse::<u16>().map_err(|_| invalid());
+    if let Some((min, max)) = value.split_once("..") {
+        return Ok(year(min)?..=year(max)?);
+    }
+    let range = if let Some(min) = value.strip_prefix(">=") {
+        year(min)?..=u16::MAX
+    } else if let Some(max) = value.strip_prefix("<=") {
+        0..=year(max)?
+    } else if let Some(min) = value.strip_prefix('>') {
+        year(min)?.checked_add(1).ok_or_else(invalid)?..=u16::MAX
+    } else if let Some(max) = value.strip_prefix('<') {
+        0..=year(max)?.checked_sub(1).ok_or_else(invalid)?
+    } else {
+        let year = year(value)?;
+        year..=year
+    };
+    Ok(range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(query: &str) -> Vec<QueryTerm> {
+        SearchQuery::parse(query).unwrap().terms
+    }
+
+    fn word(field: Option<SearchField>, word: &str) -> TermKind {
+        TermKind::Word {
+            field,
+            word: word.to_owned(),
+        }
+    }
+
+    fn kinds(query: &str) -> Vec<TermKind> {
+        terms(query).into_iter().map(|term| term.kind).collect()
+    }
+
+    #[test]
+    fn parses_field_scoped_terms() {
+        assert_eq!(
+            kinds("artist:foo year:>2000"),
+            vec![
+                word(Some(SearchField::Artist), "foo"),
+                TermKind::Year(2001..=u16::MAX),
+            ]
+        );
+        assert_eq!(
+            kinds("Album_Artist:foo albumartist:bar"),
+            vec![
+                word(Some(SearchField::AlbumArtist), "foo"),
+                word(Some(SearchField::AlbumArtist), "bar"),
+            ]
+        );
+        // Unknown fields are part of the text
+        assert_eq!(
+            kinds("mood:calm"),
+            vec![TermKind::Phrase {
+                field: None,
+                words: vec!["mood".to_owned(), "calm".to_owned()],
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_years() {
+        assert_eq!(kinds("year:2001"), vec![TermKind::Year(2001..=2001)]);
+        assert_eq!(kinds("year:>=2001"), vec![TermKind::Year(2001..=u16::MAX)]);
+        assert_eq!(kinds("year:<=1999"), vec![TermKind::Year(0..=1999)]);
+        assert_eq!(kinds("year:<2000"), vec![TermKind::Year(0..=1999)]);
+        assert_eq!(kinds("year:1990..1999"), vec![TermKind::Year(1990..=1999)]);
+        assert!(matches!(
+            SearchQuery::parse("year:nineties"),
+            Err(SearchServiceError::InvalidYear(value)) if value == "nineties"
+        ));
+        assert!(SearchQuery::parse("year:<0").is_err());
+    }
+
+    #[test]
+    fn parses_phrases_and_exclusions() {
+        let terms = terms(r#"-"Hey Jude" title:"let it be" beat*"#);
+        assert_eq!(
+            terms,
+            vec![
+                QueryTerm {
+                    negated: true,
+                    kind: TermKind::Phrase {
+                        field: None,
+                        words: vec!["hey".to_owned(), "jude".to_owned()],
+                    },
+                },
+                QueryTerm {
+                    negated: false,
+                    kind: TermKind::Phrase {
+                        field: Some(SearchField::Title),
+                        words: vec!["let".to_owned(), "it".to_owned(), "be".to_owned()],
+                    },
+                },
+                QueryTerm {
+                    negated: false,
+                    kind: word(None, "beat"),
+                },
+            ]
+        );
+        assert!(matches!(
+            SearchQuery::parse(r#"title:"let it"#),
+            Err(SearchServiceError::UnterminatedQuote)
+        ));
+        assert!(SearchQuery::parse("  * - ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn folds_case_and_diacritics() {
+        assert_eq!(kinds("CAFÉ"), vec![word(None, "cafe")]);
+        assert_eq!(
+            kinds("genre:Música"),
+            vec![word(Some(SearchField::Genre), "musica")]
+        );
+    }
+}
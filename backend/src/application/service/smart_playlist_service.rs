@@ -0,0 +1,201 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use thiserror::Error;
+
+use crate::domain::{
+    entity::{audio::Audio, play_stats::PlayStats, smart_playlist::SmartPlaylist, user::User},
+    repository::SmartPlaylistRepository,
+};
+
+use super::Library;
+
+/// How long evaluated tracks are reused while the library and play statistics do not
+/// change, as rules on relative dates change over time.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Error, Debug)]
+pub enum SmartPlaylistServiceError {
+    #[error("Smart playlist not found: {0}")]
+    NotFound(String),
+    #[error("Not allowed to change smart playlist: {0}")]
+    Forbidden(String),
+    #[error("Smart playlist name is empty")]
+    EmptyName,
+    #[error("Smart playlist limit must be positive")]
+    InvalidLimit,
+    #[error("Failed to access smart playlists: {0}")]
+    Repository(String),
+}
+
+struct Evaluation {
+    /// The definition the tracks were evaluated for.
+    playlist: SmartPlaylist,
+    library: Weak<Library>,
+    stats: Weak<HashMap<String, PlayStats>>,
+    evaluated_at: Instant,
+    tracks: Arc<Vec<String>>,
+}
+
+/// Stores smart playlists and evaluates them against the library.
+pub struct SmartPlaylistService<R> {
+    repository: R,
+    /// Evaluations by playlist and user identifiers, as users see different parts of the
+    /// library and have their own statistics.
+    evaluations: Mutex<HashMap<(String, String), Evaluation>>,
+}
+
+impl<R> SmartPlaylistService<R>
+where
+    R: SmartPlaylistRepository,
+    R::Error: Display,
+{
+    pub fn new(repository: R) -> Self {
+        Self {
+            repository,
+            evaluations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Playlists the user sees: their own, the public and the shared ones.
+    pub fn playlists(&self, user: &User) -> Result<Vec<SmartPlaylist>, SmartPlaylistServiceError> {
+        Ok(self
+            .repository
+            .list()
+            .map_err(|err| SmartPlaylistServiceError::Repository(err.to_string()))?
+            .into_iter()
+            .filter(|playlist| playlist.is_visible_to(user))
+            .collect())
+    }
+
+    /// A playlist the user sees, as if others did not exist.
+    pub fn playlist(
+        &self,
+        user: &User,
+        id: &str,
+    ) -> Result<SmartPlaylist, SmartPlaylistServiceError> {
+        self.repository
+            .find(id)
+            .map_err(|err| SmartPlaylistServiceError::Repository(err.to_string()))?
+            .filter(|playlist| playlist.is_visible_to(user))
+            .ok_or_else(|| SmartPlaylistServiceError::NotFound(id.to_string()))
+    }
+
+    /// Stores a new playlist owned by the user, who must be allowed to create playlists.
+    pub fn create(
+        &self,
+        user: &User,
+        playlist: SmartPlaylist,
+    ) -> Result<SmartPlaylist, SmartPlaylistServiceError> {
+        if !user.role.can_edit() {
+            return Err(SmartPlaylistServiceError::Forbidden(playlist.name));
+        }
+        let playlist = playlist.owned_by(user);
+        validate(&playlist)?;
+        self.repository
+            .save(&playlist)
+            .map_err(|err| SmartPlaylistServiceError::Repository(err.to_string()))?;
+        Ok(playlist)
+    }
+
+    /// Replaces the definition of an existing playlist the user may change, keeping its
+    /// owner.
+    pub fn update(
+        &self,
+        user: &User,
+        playlist: SmartPlaylist,
+    ) -> Result<SmartPlaylist, SmartPlaylistServiceError> {
+        let existing = self.editable(user, &playlist.id)?;
+        let playlist = SmartPlaylist {
+            owner: existing.owner,
+            ..playlist
+        };
+        validate(&playlist)?;
+        self.repository
+            .save(&playlist)
+            .map_err(|err| SmartPlaylistServiceError::Repository(err.to_string()))?;
+        Ok(playlist)
+    }
+
+    pub fn delete(&self, user: &User, id: &str) -> Result<(), SmartPlaylistServiceError> {
+        self.editable(user, id)?;
+        self.repository
+            .delete(id)
+            .map_err(|err| SmartPlaylistServiceError::Repository(err.to_string()))?;
+        self.evaluations
+            .lock()
+            .expect("evaluations lock poisoned")
+            .retain(|(playlist_id, _), _| playlist_id != id);
+        Ok(())
+    }
+
+    /// Identifiers of the audios of the playlist for a user, given the part of the library
+    /// they see and their statistics of tracks by identifier. The result is reused until the
+    /// library snapshot, the statistics or the definition change, or for
+    /// [`REFRESH_INTERVAL`] at most.
+    pub fn tracks(
+        &self,
+        user_id: &str,
+        library: &Arc<Library>,
+        playlist: &SmartPlaylist,
+        stats: &Arc<HashMap<String, PlayStats>>,
+    ) -> Arc<Vec<String>> {
+        let mut evaluations = self.evaluations.lock().expect("evaluations lock poisoned");
+        let key = (playlist.id.clone(), user_id.to_string());
+        if let Some(evaluation) = evaluations.get(&key) {
+            let current = evaluation.playlist == *playlist
+                && Weak::ptr_eq(&evaluation.library, &Arc::downgrade(library))
+                && Weak::ptr_eq(&evaluation.stats, &Arc::downgrade(stats))
+                && evaluation.evaluated_at.elapsed() < REFRESH_INTERVAL;
+            if current {
+                return Arc::clone(&evaluation.tracks);
+            }
+        }
+        let tracks = Arc::new(
+            playlist
+                .evaluate(
+                    library.audios(),
+                    |audio| stats.get(&audio.id()).copied().unwrap_or_default(),
+                    Utc::now(),
+                )
+                .into_iter()
+                .map(Audio::id)
+                .collect::<Vec<_>>(),
+        );
+        evaluations.insert(
+            key,
+            Evaluation {
+                playlist: playlist.clone(),
+                library: Arc::downgrade(library),
+                stats: Arc::downgrade(stats),
+                evaluated_at: Instant::now(),
+                tracks: Arc::clone(&tracks),
+            },
+        );
+        tracks
+    }
+
+    fn editable(&self, user: &User, id: &str) -> Result<SmartPlaylist, SmartPlaylistServiceError> {
+        let playlist = self.playlist(user, id)?;
+        if playlist.is_editable_by(user) {
+            Ok(playlist)
+        } else {
+            Err(SmartPlaylistServiceError::Forbidden(playlist.name))
+        }
+    }
+}
+
+fn validate(playlist: &SmartPlaylist) -> Result<(), SmartPlaylistServiceError> {
+    if playlist.name.trim().is_empty() {
+        return Err(SmartPlaylistServiceError::EmptyName);
+    }
+    if playlist.limit == Some(0) {
+        return Err(SmartPlaylistServiceError::InvalidLimit);
+    }
+    Ok(())
+}
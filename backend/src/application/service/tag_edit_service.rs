@@ -0,0 +1,512 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+    path::PathBuf,
+};
+
+use thiserror::Error;
+
+use crate::domain::{
+    entity::{
+        audio::{
+            artist::Artist, cover::Cover, field::AudioField, genre::Genre, tag_change::TagChange,
+            title::Title, year::Year, Audio, AudioBuilder,
+        },
+        edit_journal::{EditJournal, EditJournalEntry},
+    },
+    repository::{AudioTagWriter, EditJournalRepository},
+};
+
+/// An edit applied to every audio of a batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagEdit {
+    SetTitle(Title),
+    SetArtist(Artist),
+    SetAlbumTitle(Title),
+    SetAlbumArtist(Artist),
+    SetYear(Option<Year>),
+    SetGenre(Genre),
+    /// Replaces `from` with `to`, leaving audios with other genres untouched.
+    ReplaceGenre {
+        from: Genre,
+        to: Genre,
+    },
+    SetDiscNumber(Option<u16>),
+    SetCompilation(bool),
+    SetCover(Cover),
+    /// Numbers the tracks of each disc from 1, following their current disc and track
+    /// numbers, then their paths.
+    RenumberTracks,
+}
+
+#[derive(Debug)]
+pub enum FileEditResult {
+    Edited(Vec<TagChange>),
+    Unchanged,
+    Failed(String),
+}
+
+#[derive(Debug)]
+pub struct FileEditReport {
+    pub path: PathBuf,
+    pub result: FileEditResult,
+}
+
+#[derive(Debug)]
+pub struct BatchEditReport {
+    /// The journal of the applied changes, `None` for dry runs and batches changing nothing.
+    pub journal: Option<EditJournal>,
+    pub files: Vec<FileEditReport>,
+}
+
+impl BatchEditReport {
+    pub fn failures(&self) -> impl Iterator<Item = &FileEditReport> {
+        self.files
+            .iter()
+            .filter(|file| matches!(file.result, FileEditResult::Failed(_)))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum TagEditServiceError {
+    #[error("Journal not found: {0}")]
+    JournalNotFound(String),
+    #[error("Failed to access journal: {0}")]
+    Journal(String),
+}
+
+/// Applies edits across many audios as one operation, recording the previous raw tag values
+/// in an [`EditJournal`] so the whole batch can be undone.
+pub struct TagEditService<W, J> {
+    writer: W,
+    journal_repository: J,
+}
+
+impl<W, J> TagEditService<W, J>
+where
+    W: AudioTagWriter,
+    W::Error: Display,
+    J: EditJournalRepository,
+    J::Error: Display,
+{
+    pub fn new(writer: W, journal_repository: J) -> Self {
+        Self {
+            writer,
+            journal_repository,
+        }
+    }
+
+    /// Applies `edits` to every audio. With `dry_run`, changes are computed and reported but
+    /// no file is written and no journal is saved.
+    ///
+    /// The journal is saved as pending before the first file is written, then updated once
+    /// the batch is done. A file failing does not stop the batch; the journal then only holds
+    /// the files written.
+    pub fn apply(
+        &self,
+        description: &str,
+        audios: &[Audio],
+        edits: &[TagEdit],
+        dry_run: bool,
+    ) -> Result<BatchEditReport, TagEditServiceError> {
+        let track_numbers = if edits.contains(&TagEdit::RenumberTracks) {
+            Self::renumber(audios)
+        } else {
+            HashMap::new()
+        };
+
+        // Changes are all computed first, so that they are journaled before any write
+        let planned = audios
+            .iter()
+            .map(|audio| {
+                Self::edited(audio, edits, track_numbers.get(audio.path()).copied())
+                    .and_then(|edited| self.writer.diff(&edited).map_err(|err| err.to_string()))
+            })
+            .collect::<Vec<_>>();
+        let pending = planned
+            .iter()
+            .zip(audios)
+            .filter_map(|(changes, audio)| {
+                let changes = changes
+                    .as_ref()
+                    .ok()
+                    .filter(|changes| !changes.is_empty())?;
+                Some(EditJournalEntry {
+                    path: audio.path().clone(),
+                    changes: changes.clone(),
+                })
+            })
+            .collect::<Vec<_>>();
+        let journal = if dry_run || pending.is_empty() {
+            None
+        } else {
+            let journal = EditJournal::pending(description, pending);
+            self.journal_repository
+                .save(&journal)
+                .map_err(|err| TagEditServiceError::Journal(err.to_string()))?;
+            Some(journal)
+        };
+
+        let mut entries = Vec::new();
+        let mut files = Vec::new();
+        for (audio, changes) in audios.iter().zip(planned) {
+            let result = changes
+                .and_then(|changes| {
+                    if changes.is_empty() {
+                        return Ok(FileEditResult::Unchanged);
+                    }
+                    if !dry_run {
+                        self.writer
+                            .apply(audio.path(), &changes)
+                            .map_err(|err| err.to_string())?;
+                        entries.push(EditJournalEntry {
+                            path: audio.path().clone(),
+                            changes: changes.clone(),
+                        });
+                    }
+                    Ok(FileEditResult::Edited(changes))
+                })
+                .unwrap_or_else(FileEditResult::Failed);
+            files.push(FileEditReport {
+                path: audio.path().clone(),
+                result,
+            });
+        }
+
+        // The pending journal is kept when it cannot be updated, as it covers every write
+        let journal = match journal {
+            Some(journal) if entries.is_empty() => {
+                self.journal_repository
+                    .delete(journal.id())
+                    .map_err(|err| TagEditServiceError::Journal(err.to_string()))?;
+                None
+            }
+            Some(journal) => {
+                let journal = journal.applied(entries);
+                self.journal_repository
+                    .save(&journal)
+                    .map_err(|err| TagEditServiceError::Journal(err.to_string()))?;
+                Some(journal)
+            }
+            None => None,
+        };
+        Ok(BatchEditReport { journal, files })
+    }
+
+    /// Journals of the batches that can be undone, the newest first.
+    pub fn journals(&self) -> Result<Vec<EditJournal>, TagEditServiceError> {
+        self.journal_repository
+            .list()
+            .map_err(|err| TagEditServiceError::Journal(err.to_string()))
+    }
+
+    /// Restores the tag values recorded in the journal `id`. The journal is deleted once
+    /// every file was restored, and kept otherwise so the undo can be retried.
+    pub fn undo(&self, id: &str) -> Result<BatchEditReport, TagEditServiceError> {
+        let journal = self
+            .journal_repository
+            .find(id)
+            .map_err(|err| TagEditServiceError::Journal(err.to_string()))?
+            .ok_or_else(|| TagEditServiceError::JournalNotFound(id.to_owned()))?;
+
+        let files = journal
+            .entries()
+            .iter()
+            .rev()
+            .map(|entry| {
+                let changes = entry
+                    .changes
+                    .iter()
+                    .rev()
+                    .map(TagChange::reversed)
+                    .collect::<Vec<_>>();
+                let result = match self.writer.apply(&entry.path, &changes) {
+                    Ok(()) => FileEditResult::Edited(changes),
+                    Err(err) => FileEditResult::Failed(err.to_string()),
+                };
+                FileEditReport {
+                    path: entry.path.clone(),
+                    result,
+                }
+            })
+            .collect();
+
+        let report = BatchEditReport {
+            journal: None,
+            files,
+        };
+        if report.failures().next().is_none() {
+            self.journal_repository
+                .delete(id)
+                .map_err(|err| TagEditServiceError::Journal(err.to_string()))?;
+        }
+        Ok(report)
+    }
+
+    fn renumber(audios: &[Audio]) -> HashMap<PathBuf, u16> {
+        let mut sorted = audios.iter().collect::<Vec<_>>();
+        sorted.sort_by_key(|audio| (*audio.disc_number(), *audio.track_number(), audio.path()));
+
+        let mut next_numbers: HashMap<Option<u16>, u16> = HashMap::new();
+        sorted
+            .into_iter()
+            .map(|audio| {
+                let next_number = next_numbers.entry(*audio.disc_number()).or_insert(1);
+                let track_number = *next_number;
+                *next_number += 1;
+                (audio.path().clone(), track_number)
+            })
+            .collect()
+    }
+
+    /// The audio with the edits applied. Edited fields are no longer inferred, so that the
+    /// writer writes them.
+    fn edited(
+        audio: &Audio,
+        edits: &[TagEdit],
+        track_number: Option<u16>,
+    ) -> Result<Audio, String> {
+        let mut builder: AudioBuilder = audio.edit();
+        let mut inferred_fields: BTreeSet<AudioField> = audio.inferred_fields().clone();
+        let mut edited = |field: AudioField| {
+            inferred_fields.remove(&field);
+        };
+        for edit in edits {
+            match edit {
+                TagEdit::SetTitle(title) => {
+                    builder.title(title.clone());
+                    edited(AudioField::Title);
+                }
+                TagEdit::SetArtist(artist) => {
+                    builder.artist(artist.clone());
+                    edited(AudioField::Artist);
+                }
+                TagEdit::SetAlbumTitle(album_title) => {
+                    builder.album_title(album_title.clone());
+                    edited(AudioField::AlbumTitle);
+                }
+                TagEdit::SetAlbumArtist(album_artist) => {
+                    builder.album_artist(album_artist.clone());
+                    edited(AudioField::AlbumArtist);
+                }
+                TagEdit::SetYear(year) => {
+                    builder.year(*year);
+                    edited(AudioField::Year);
+                }
+                TagEdit::SetGenre(genre) => {
+                    builder.genre(genre.clone());
+                    edited(AudioField::Genre);
+                }
+                TagEdit::ReplaceGenre { from, to } => {
+                    if audio.genre() == from {
+                        builder.genre(to.clone());
+                        edited(AudioField::Genre);
+                    }
+                }
+                TagEdit::SetDiscNumber(disc_number) => {
+                    builder.disc_number(*disc_number);
+                    edited(AudioField::DiscNumber);
+                }
+                TagEdit::SetCompilation(compilation) => {
+                    builder.compilation(*compilation);
+                    edited(AudioField::Compilation);
+                }
+                TagEdit::SetCover(cover) => {
+                    builder.album_cover(cover.clone());
+                    edited(AudioField::AlbumCover);
+                }
+                TagEdit::RenumberTracks => {
+                    builder.track_number(track_number);
+                    edited(AudioField::TrackNumber);
+                }
+            }
+        }
+        builder
+            .inferred_fields(inferred_fields)
+            .build()
+            .map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::{Cell, RefCell},
+        collections::HashMap,
+        path::Path,
+    };
+
+    use crate::domain::entity::{audio::tag_change::TagValue, edit_journal::EditJournalState};
+
+    use super::*;
+
+    fn audio(path: &str) -> Audio {
+        AudioBuilder::default()
+            .title("Old".parse().unwrap())
+            .artist(Artist::default())
+            .year(None)
+            .album_title(Title::default())
+            .album_artist(Artist::default())
+            .album_cover(Cover::default())
+            .genre(Genre::default())
+            .track_number(None)
+            .disc_number(None)
+            .compilation(false)
+            .path(PathBuf::from(path))
+            .build()
+            .unwrap()
+    }
+
+    /// Writes titles, failing on files whose name contains "locked".
+    #[derive(Default)]
+    struct FakeWriter {
+        written: RefCell<HashMap<PathBuf, String>>,
+    }
+
+    impl AudioTagWriter for FakeWriter {
+        type Error = String;
+
+        fn diff(&self, audio: &Audio) -> Result<Vec<TagChange>, String> {
+            let before = self
+                .written
+                .borrow()
+                .get(audio.path())
+                .cloned()
+                .unwrap_or_else(|| "Old".to_owned());
+            if &before == audio.title().name() {
+                return Ok(Vec::new());
+            }
+            Ok(vec![TagChange {
+                field: AudioField::Title,
+                before: Some(TagValue::Text(vec![before])),
+                after: Some(TagValue::Text(vec![audio.title().name().clone()])),
+            }])
+        }
+
+        fn apply(&self, path: &Path, changes: &[TagChange]) -> Result<(), String> {
+            if path.to_string_lossy().contains("locked") {
+                return Err("locked".to_owned());
+            }
+            for change in changes {
+                if let Some(TagValue::Text(values)) = &change.after {
+                    self.written
+                        .borrow_mut()
+                        .insert(path.to_path_buf(), values[0].clone());
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Keeps journals in memory, failing the saves after the first `saves_left` ones.
+    struct MemoryJournals {
+        journals: RefCell<HashMap<String, EditJournal>>,
+        saves_left: Cell<usize>,
+    }
+
+    impl MemoryJournals {
+        fn new(saves_left: usize) -> Self {
+            Self {
+                journals: RefCell::new(HashMap::new()),
+                saves_left: Cell::new(saves_left),
+            }
+        }
+    }
+
+    impl EditJournalRepository for &MemoryJournals {
+        type Error = String;
+
+        fn save(&self, journal: &EditJournal) -> Result<(), String> {
+            if self.saves_left.get() == 0 {
+                return Err("disk full".to_owned());
+            }
+            self.saves_left.set(self.saves_left.get() - 1);
+            self.journals
+                .borrow_mut()
+                .insert(journal.id().clone(), journal.clone());
+            Ok(())
+        }
+
+        fn find(&self, id: &str) -> Result<Option<EditJournal>, String> {
+            Ok(self.journals.borrow().get(id).cloned())
+        }
+
+        fn list(&self) -> Result<Vec<EditJournal>, String> {
+            Ok(self.journals.borrow().values().cloned().collect())
+        }
+
+        fn delete(&self, id: &str) -> Result<(), String> {
+            self.journals.borrow_mut().remove(id);
+            Ok(())
+        }
+    }
+
+    fn set_title() -> Vec<TagEdit> {
+        vec![TagEdit::SetTitle("New".parse().unwrap())]
+    }
+
+    #[test]
+    fn journals_written_files_and_undoes_them() {
+        let journals = MemoryJournals::new(usize::MAX);
+        let service = TagEditService::new(FakeWriter::default(), &journals);
+        let audios = [audio("/a.flac"), audio("/locked.flac")];
+
+        let report = service
+            .apply("Retitle", &audios, &set_title(), false)
+            .unwrap();
+        assert_eq!(report.failures().count(), 1);
+        let journal = report.journal.unwrap();
+        assert_eq!(journal.state(), &EditJournalState::Applied);
+        assert_eq!(journal.entries().len(), 1);
+        assert_eq!(service.writer.written.borrow()[Path::new("/a.flac")], "New");
+        assert_eq!(service.journals().unwrap(), std::slice::from_ref(&journal));
+
+        let undo = service.undo(journal.id()).unwrap();
+        assert_eq!(undo.failures().count(), 0);
+        assert_eq!(service.writer.written.borrow()[Path::new("/a.flac")], "Old");
+        assert!(journals.journals.borrow().is_empty());
+    }
+
+    #[test]
+    fn keeps_the_pending_journal_when_it_cannot_be_updated() {
+        let journals = MemoryJournals::new(1);
+        let service = TagEditService::new(FakeWriter::default(), &journals);
+
+        let result = service.apply("Retitle", &[audio("/a.flac")], &set_title(), false);
+        assert!(matches!(result, Err(TagEditServiceError::Journal(_))));
+        let journal = journals.journals.borrow().values().next().cloned().unwrap();
+        assert_eq!(journal.state(), &EditJournalState::Pending);
+        assert_eq!(journal.entries().len(), 1);
+    }
+
+    #[test]
+    fn writes_nothing_when_the_journal_cannot_be_saved() {
+        let journals = MemoryJournals::new(0);
+        let service = TagEditService::new(FakeWriter::default(), &journals);
+
+        let result = service.apply("Retitle", &[audio("/a.flac")], &set_title(), false);
+        assert!(result.is_err());
+        assert!(service.writer.written.borrow().is_empty());
+    }
+
+    #[test]
+    fn dry_runs_write_and_journal_nothing() {
+        let journals = MemoryJournals::new(usize::MAX);
+        let service = TagEditService::new(FakeWriter::default(), &journals);
+
+        let report = service
+            .apply("Retitle", &[audio("/a.flac")], &set_title(), true)
+            .unwrap();
+        assert!(report.journal.is_none());
+        assert!(matches!(report.files[0].result, FileEditResult::Edited(_)));
+        assert!(service.writer.written.borrow().is_empty());
+        assert!(journals.journals.borrow().is_empty());
+    }
+
+    #[test]
+    fn journal_ids_are_unique() {
+        let first = EditJournal::pending("A", Vec::new());
+        let second = EditJournal::pending("B", Vec::new());
+        assert_ne!(first.id(), second.id());
+    }
+}
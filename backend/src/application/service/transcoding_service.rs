@@ -0,0 +1,282 @@
+use std::{
+    fmt::Display,
+    io::{self, Read, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::domain::{
+    entity::{
+        audio::Audio,
+        transcode_profile::{AudioCodec, TranscodeProfile},
+    },
+    repository::{AudioTranscoder, LibraryFileRepository, TranscodeCacheRepository},
+};
+
+pub struct TranscodingServiceOptions {
+    pub profiles: Vec<TranscodeProfile>,
+    /// Transcodes running at once. Serving cached outputs is not limited.
+    pub max_concurrent: usize,
+    /// Size of the cache of transcoded outputs, in bytes.
+    pub max_cache_size: u64,
+}
+
+impl Default for TranscodingServiceOptions {
+    fn default() -> Self {
+        Self {
+            profiles: vec![
+                TranscodeProfile::new("opus", AudioCodec::Opus, 128),
+                TranscodeProfile::new("mobile", AudioCodec::Opus, 64),
+                TranscodeProfile::new("mp3", AudioCodec::Mp3, 192),
+                TranscodeProfile::new("aac", AudioCodec::Aac, 160),
+            ],
+            max_concurrent: thread::available_parallelism()
+                .map(|parallelism| parallelism.get())
+                .unwrap_or(2),
+            max_cache_size: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum TranscodingServiceError {
+    #[error("Unknown transcode profile: {0}")]
+    UnknownProfile(String),
+    #[error("Too many transcodes running")]
+    Busy,
+    #[error("Failed to read file: {0}")]
+    File(String),
+    #[error("Failed to start transcoding: {0}")]
+    Transcoder(String),
+    #[error("Failed to access transcode cache: {0}")]
+    Cache(String),
+}
+
+pub enum TranscodedOutput {
+    /// A complete output, which can be served with range requests.
+    Cached(PathBuf),
+    /// An output produced while it is read.
+    Live(Box<dyn Read + Send>),
+}
+
+pub struct Transcoded {
+    pub profile: TranscodeProfile,
+    pub output: TranscodedOutput,
+}
+
+/// Transcodes audios for clients that cannot play the original format or bitrate. Complete
+/// outputs are cached.
+pub struct TranscodingService<T, C, L> {
+    transcoder: T,
+    cache: Arc<C>,
+    files: L,
+    options: TranscodingServiceOptions,
+    running: Arc<AtomicUsize>,
+}
+
+impl<T, C, L> TranscodingService<T, C, L>
+where
+    T: AudioTranscoder,
+    T::Error: Display,
+    C: TranscodeCacheRepository + Send + Sync + 'static,
+    C::Error: Display,
+    L: LibraryFileRepository,
+    L::Error: Display,
+{
+    pub fn new(transcoder: T, cache: C, files: L, options: TranscodingServiceOptions) -> Self {
+        Self {
+            transcoder,
+            cache: Arc::new(cache),
+            files,
+            options,
+            running: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn profiles(&self) -> &[TranscodeProfile] {
+        &self.options.profiles
+    }
+
+    /// Returns the output of `audio` transcoded with the profile named `profile_name`,
+    /// starting at `offset`. Outputs starting at an offset are never cached.
+    pub fn transcode(
+        &self,
+        audio: &Audio,
+        profile_name: &str,
+        offset: Duration,
+    ) -> Result<Transcoded, TranscodingServiceError> {
+        let profile = self
+            .options
+            .profiles
+            .iter()
+            .find(|profile| profile.name == profile_name)
+            .ok_or_else(|| TranscodingServiceError::UnknownProfile(profile_name.to_string()))?
+            .clone();
+
+        let key = if offset.is_zero() {
+            let key = self.cache_key(audio, &profile)?;
+            let cached = self
+                .cache
+                .find(&key)
+                .map_err(|err| TranscodingServiceError::Cache(err.to_string()))?;
+            if let Some(path) = cached {
+                return Ok(Transcoded {
+                    profile,
+                    output: TranscodedOutput::Cached(path),
+                });
+            }
+            Some(key)
+        } else {
+            None
+        };
+
+        let permit = Permit::acquire(&self.running, self.options.max_concurrent)
+            .ok_or(TranscodingServiceError::Busy)?;
+        let output = self
+            .transcoder
+            .transcode(audio.path(), &profile, offset)
+            .map_err(|err| TranscodingServiceError::Transcoder(err.to_string()))?;
+
+        let writer = key.as_ref().and_then(|key| match self.cache.create(key) {
+            Ok(writer) => Some(writer),
+            Err(err) => {
+                log::error!("Failed to cache transcoded output: {}", err);
+                None
+            }
+        });
+        let output: Box<dyn Read + Send> = match (key, writer) {
+            (Some(key), Some(writer)) => Box::new(CachingOutput {
+                output,
+                writer: Some(writer),
+                cache: Arc::clone(&self.cache),
+                key,
+                max_cache_size: self.options.max_cache_size,
+                _permit: permit,
+            }),
+            _ => Box::new(LiveOutput {
+                output,
+                _permit: permit,
+            }),
+        };
+        Ok(Transcoded {
+            profile,
+            output: TranscodedOutput::Live(output),
+        })
+    }
+
+    /// Outputs are keyed by the version of the source file and the encoding settings.
+    fn cache_key(
+        &self,
+        audio: &Audio,
+        profile: &TranscodeProfile,
+    ) -> Result<String, TranscodingServiceError> {
+        let file_error = |err: L::Error| TranscodingServiceError::File(err.to_string());
+        let path = audio.path();
+        let size = self.files.size(path).map_err(file_error)?;
+        let modified = self
+            .files
+            .modified(path)
+            .map_err(file_error)?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        let mut hasher = Sha256::new();
+        hasher.update(path.as_os_str().as_encoded_bytes());
+        hasher.update([0]);
+        hasher.update(size.to_le_bytes());
+        hasher.update(modified.as_nanos().to_le_bytes());
+        hasher.update(profile.codec.extension());
+        hasher.update(profile.bitrate.to_le_bytes());
+        Ok(format!(
+            "{:x}.{}",
+            hasher.finalize(),
+            profile.codec.extension()
+        ))
+    }
+}
+
+/// A slot among the transcodes allowed to run at once, released when dropped.
+struct Permit(Arc<AtomicUsize>);
+
+impl Permit {
+    fn acquire(running: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        running
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (count < max).then_some(count + 1)
+            })
+            .ok()
+            .map(|_| Self(Arc::clone(running)))
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+struct LiveOutput<R> {
+    output: R,
+    _permit: Permit,
+}
+
+impl<R: Read> Read for LiveOutput<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.output.read(buf)
+    }
+}
+
+/// Copies the output into the cache while it is read, and commits it once complete. An
+/// output that fails or is not read to the end is discarded.
+struct CachingOutput<R, C: TranscodeCacheRepository> {
+    output: R,
+    writer: Option<C::Writer>,
+    cache: Arc<C>,
+    key: String,
+    max_cache_size: u64,
+    _permit: Permit,
+}
+
+impl<R, C> Read for CachingOutput<R, C>
+where
+    R: Read,
+    C: TranscodeCacheRepository,
+    C::Error: Display,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = match self.output.read(buf) {
+            Ok(read) => read,
+            Err(err) => {
+                self.writer = None;
+                return Err(err);
+            }
+        };
+        if read > 0 {
+            if let Some(writer) = &mut self.writer {
+                if let Err(err) = writer.write_all(&buf[..read]) {
+                    log::error!("Failed to cache transcoded output: {}", err);
+                    self.writer = None;
+                }
+            }
+        } else if !buf.is_empty() {
+            if let Some(writer) = self.writer.take() {
+                let result = self
+                    .cache
+                    .commit(&self.key, writer)
+                    .and_then(|()| self.cache.evict(self.max_cache_size));
+                if let Err(err) = result {
+                    log::error!("Failed to cache transcoded output: {}", err);
+                }
+            }
+        }
+        Ok(read)
+    }
+}
@@ -0,0 +1,313 @@
+use std::{collections::HashMap, fmt::Display, path::PathBuf, sync::Mutex, time::Duration};
+
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::domain::{
+    entity::user::{ApiToken, Role, User, UserError},
+    repository::UserRepository,
+};
+
+/// How often the last use of a token is recorded, rather than on every request.
+const TOKEN_USE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Error, Debug)]
+pub enum UserServiceError {
+    #[error("User not found: {0}")]
+    NotFound(String),
+    #[error("Token not found: {0}")]
+    TokenNotFound(String),
+    #[error("Username is already taken: {0}")]
+    UsernameTaken(String),
+    #[error("Wrong username or password")]
+    InvalidCredentials,
+    #[error("No Subsonic password was created for the user")]
+    NoSubsonicPassword,
+    #[error("There must remain an administrator")]
+    LastAdmin,
+    #[error(transparent)]
+    User(#[from] UserError),
+    #[error("Failed to access users: {0}")]
+    Repository(String),
+}
+
+/// Changes to a user. Fields that are `None` are left as they are.
+#[derive(Debug, Clone, Default)]
+pub struct UserUpdate {
+    pub password: Option<String>,
+    pub role: Option<Role>,
+    pub library_roots: Option<Vec<PathBuf>>,
+}
+
+/// Manages accounts and authenticates them by password or API token.
+pub struct UserService<R> {
+    repository: R,
+    /// Digest of the password each user last authenticated with, along with the hash it was
+    /// verified against. Argon2 is slow by design and Subsonic clients send the password
+    /// with every request, so it is only verified again when either changes.
+    verified: Mutex<HashMap<String, (String, Vec<u8>)>>,
+}
+
+impl<R> UserService<R>
+where
+    R: UserRepository,
+    R::Error: Display,
+{
+    pub fn new(repository: R) -> Self {
+        Self {
+            repository,
+            verified: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Creates an administrator when there is no user yet, which is returned.
+    pub fn bootstrap(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<Option<User>, UserServiceError> {
+        if !self.users()?.is_empty() {
+            return Ok(None);
+        }
+        self.create(username, password, Role::Admin, Vec::new())
+            .map(Some)
+    }
+
+    pub fn authenticate(&self, username: &str, password: &str) -> Result<User, UserServiceError> {
+        let user = self
+            .repository
+            .find_user_by_name(username)
+            .map_err(|err| UserServiceError::Repository(err.to_string()))?
+            .ok_or(UserServiceError::InvalidCredentials)?;
+        let digest = Sha256::digest(password.as_bytes()).to_vec();
+        let mut verified = self.verified.lock().expect("verified lock poisoned");
+        let known = verified
+            .get(&user.id)
+            .is_some_and(|(hash, known)| *hash == user.password_hash && *known == digest);
+        if !known {
+            if !user.verify_password(password) {
+                return Err(UserServiceError::InvalidCredentials);
+            }
+            verified.insert(user.id.clone(), (user.password_hash.clone(), digest));
+        }
+        Ok(user)
+    }
+
+    /// Authenticates a Subsonic client with either the Subsonic password of the user or
+    /// their account password.
+    pub fn authenticate_subsonic(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<User, UserServiceError> {
+        let user = self.find_by_name(username)?;
+        if user.verify_subsonic_password(password) {
+            return Ok(user);
+        }
+        self.authenticate(username, password)
+    }
+
+    /// Authenticates a Subsonic client with the `token` made of the MD5 of the Subsonic
+    /// password of the user followed by `salt`.
+    pub fn authenticate_subsonic_token(
+        &self,
+        username: &str,
+        token: &str,
+        salt: &str,
+    ) -> Result<User, UserServiceError> {
+        let user = self.find_by_name(username)?;
+        if user.subsonic_password.is_none() {
+            return Err(UserServiceError::NoSubsonicPassword);
+        }
+        if !user.verify_subsonic_token(token, salt) {
+            return Err(UserServiceError::InvalidCredentials);
+        }
+        Ok(user)
+    }
+
+    /// Authenticates the user a token secret was given to.
+    pub fn authenticate_token(&self, secret: &str) -> Result<User, UserServiceError> {
+        let mut token = self
+            .repository
+            .find_token(&ApiToken::hash_secret(secret))
+            .map_err(|err| UserServiceError::Repository(err.to_string()))?
+            .ok_or(UserServiceError::InvalidCredentials)?;
+        let user = self
+            .repository
+            .find_user(&token.user_id)
+            .map_err(|err| UserServiceError::Repository(err.to_string()))?
+            .ok_or(UserServiceError::InvalidCredentials)?;
+        let now = Utc::now();
+        let stale = token.last_used_at.is_none_or(|last_used_at| {
+            (now - last_used_at).to_std().unwrap_or_default() >= TOKEN_USE_INTERVAL
+        });
+        if stale {
+            token.last_used_at = Some(now);
+            self.repository
+                .save_token(&token)
+                .map_err(|err| UserServiceError::Repository(err.to_string()))?;
+        }
+        Ok(user)
+    }
+
+    pub fn users(&self) -> Result<Vec<User>, UserServiceError> {
+        self.repository
+            .list_users()
+            .map_err(|err| UserServiceError::Repository(err.to_string()))
+    }
+
+    pub fn user(&self, id: &str) -> Result<User, UserServiceError> {
+        self.repository
+            .find_user(id)
+            .map_err(|err| UserServiceError::Repository(err.to_string()))?
+            .ok_or_else(|| UserServiceError::NotFound(id.to_string()))
+    }
+
+    pub fn create(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+        library_roots: Vec<PathBuf>,
+    ) -> Result<User, UserServiceError> {
+        let existing = self
+            .repository
+            .find_user_by_name(username.trim())
+            .map_err(|err| UserServiceError::Repository(err.to_string()))?;
+        if existing.is_some() {
+            return Err(UserServiceError::UsernameTaken(username.trim().to_string()));
+        }
+        let user = User {
+            library_roots,
+            ..User::new(username, password, role)?
+        };
+        self.save(&user)?;
+        Ok(user)
+    }
+
+    pub fn update(&self, id: &str, update: UserUpdate) -> Result<User, UserServiceError> {
+        let mut user = self.user(id)?;
+        if let Some(password) = &update.password {
+            user.set_password(password)?;
+        }
+        if let Some(role) = update.role {
+            if user.role.is_admin() && !role.is_admin() {
+                self.ensure_other_admin(id)?;
+            }
+            user.role = role;
+        }
+        if let Some(library_roots) = update.library_roots {
+            user.library_roots = library_roots;
+        }
+        self.save(&user)?;
+        Ok(user)
+    }
+
+    /// Changes the password of a user who knows the current one.
+    pub fn change_password(
+        &self,
+        id: &str,
+        current: &str,
+        password: &str,
+    ) -> Result<User, UserServiceError> {
+        if !self.user(id)?.verify_password(current) {
+            return Err(UserServiceError::InvalidCredentials);
+        }
+        self.update(
+            id,
+            UserUpdate {
+                password: Some(password.to_string()),
+                ..UserUpdate::default()
+            },
+        )
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), UserServiceError> {
+        if self.user(id)?.role.is_admin() {
+            self.ensure_other_admin(id)?;
+        }
+        self.repository
+            .delete_user(id)
+            .map_err(|err| UserServiceError::Repository(err.to_string()))?;
+        self.verified
+            .lock()
+            .expect("verified lock poisoned")
+            .remove(id);
+        Ok(())
+    }
+
+    /// Replaces the Subsonic password of the user with a new one, which is returned.
+    pub fn reset_subsonic_password(&self, user_id: &str) -> Result<String, UserServiceError> {
+        let mut user = self.user(user_id)?;
+        let password = user.generate_subsonic_password();
+        self.save(&user)?;
+        Ok(password)
+    }
+
+    /// Removes the Subsonic password of the user, whose clients then need their account
+    /// password or an API key.
+    pub fn delete_subsonic_password(&self, user_id: &str) -> Result<(), UserServiceError> {
+        let mut user = self.user(user_id)?;
+        user.subsonic_password = None;
+        self.save(&user)
+    }
+
+    /// Creates a token for the user, returned with the secret to authenticate with.
+    pub fn create_token(
+        &self,
+        user_id: &str,
+        name: &str,
+    ) -> Result<(ApiToken, String), UserServiceError> {
+        self.user(user_id)?;
+        let (token, secret) = ApiToken::generate(user_id, name);
+        self.repository
+            .save_token(&token)
+            .map_err(|err| UserServiceError::Repository(err.to_string()))?;
+        Ok((token, secret))
+    }
+
+    pub fn tokens(&self, user_id: &str) -> Result<Vec<ApiToken>, UserServiceError> {
+        self.repository
+            .list_tokens(user_id)
+            .map_err(|err| UserServiceError::Repository(err.to_string()))
+    }
+
+    pub fn revoke_token(&self, user_id: &str, token_id: &str) -> Result<(), UserServiceError> {
+        if !self
+            .tokens(user_id)?
+            .iter()
+            .any(|token| token.id == token_id)
+        {
+            return Err(UserServiceError::TokenNotFound(token_id.to_string()));
+        }
+        self.repository
+            .delete_token(token_id)
+            .map_err(|err| UserServiceError::Repository(err.to_string()))
+    }
+
+    fn find_by_name(&self, username: &str) -> Result<User, UserServiceError> {
+        self.repository
+            .find_user_by_name(username)
+            .map_err(|err| UserServiceError::Repository(err.to_string()))?
+            .ok_or(UserServiceError::InvalidCredentials)
+    }
+
+    fn save(&self, user: &User) -> Result<(), UserServiceError> {
+        self.repository
+            .save_user(user)
+            .map_err(|err| UserServiceError::Repository(err.to_string()))
+    }
+
+    fn ensure_other_admin(&self, id: &str) -> Result<(), UserServiceError> {
+        let other_admin = self
+            .users()?
+            .iter()
+            .any(|user| user.id != id && user.role.is_admin());
+        if other_admin {
+            Ok(())
+        } else {
+            Err(UserServiceError::LastAdmin)
+        }
+    }
+}
@@ -1,9 +1,9 @@
 use dotenvy::dotenv;
 use earr::{
+    application::service::AlbumService,
     domain::repository::AudioGathererRepository,
     infrastructure::repository::audio_gatherer_repository::{
-        audio_parser::{AudiotagsAudioParser, FfmpegAudioParser, ResilientAudioParser},
-        FilesystemAudioGathererRepository,
+        audio_parser::ResilientAudioParser, FilesystemAudioGathererRepository,
     },
 };
 
@@ -27,12 +27,10 @@ fn main() {
             println!("Processed {} audios", idx + 1);
         }
     }
-    
-    let audios = audio_gatherer_repository
-        .gather()
-        .unwrap()
-        .collect::<Vec<_>>();
-    // dbg!(audios);
+
+    let audios = audio_gatherer_repository.gather().unwrap();
+    let albums = AlbumService::default().group(audios);
+    println!("Found {} albums", albums.len());
 
     // let ffmpeg_audios = FilesystemAudioGathererRepository::<FfmpegAudioParser>::new(&music_dir)
     //     .gather()
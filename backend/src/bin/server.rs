@@ -0,0 +1,270 @@
+use std::{env, fmt::Display, path::PathBuf, process, sync::Arc};
+
+use dotenvy::dotenv;
+use earr::{
+    application::service::{
+        ActivityService, AlbumServiceOptions, FingerprintService, FingerprintServiceOptions,
+        GenreService, LibraryService, PlaylistService, ScrobbleService, ScrobbleServiceOptions,
+        SmartPlaylistService, TagEditService, TranscodingService, TranscodingServiceOptions,
+        UserService,
+    },
+    infrastructure::{
+        config::{Config, ConfigReport},
+        http::{self, AppState},
+        logging::Logger,
+        repository::{
+            activity_repository::SqliteActivityRepository,
+            audio_decoder::FfmpegAudioDecoder,
+            audio_gatherer_repository::{
+                audio_parser::FfmpegAudioParser, FilesystemAudioGathererRepository,
+            },
+            audio_transcoder::FfmpegAudioTranscoder,
+            edit_journal_repository::FilesystemEditJournalRepository,
+            fingerprint_repository::FilesystemFingerprintRepository,
+            library_file_repository::FilesystemLibraryFileRepository,
+            library_repository::SqliteLibraryRepository,
+            playlist_repository::FilesystemPlaylistRepository,
+            scrobble_repository::SqliteScrobbleRepository,
+            scrobbler::HttpScrobbler,
+            smart_playlist_repository::FilesystemSmartPlaylistRepository,
+            transcode_cache_repository::FilesystemTranscodeCacheRepository,
+            user_repository::SqliteUserRepository,
+        },
+    },
+};
+use tokio::{net::TcpListener, signal};
+
+const USAGE: &str = "Usage: server [--config PATH] [config check]";
+
+enum Command {
+    Serve,
+    CheckConfig,
+}
+
+#[tokio::main]
+async fn main() {
+    dotenv().ok();
+
+    let (command, config_path) = match parse_args(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    let config = match Config::load(config_path.as_deref()) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
+    let report = config.validate();
+    if let Command::CheckConfig = command {
+        check_config(&config, &report);
+    }
+    if !report.is_valid() {
+        eprintln!("Invalid configuration:");
+        for error in &report.errors {
+            eprintln!("  {}", error);
+        }
+        eprintln!("Run `server config check` for details");
+        process::exit(1);
+    }
+    if let Err(err) = Logger::init(config.logging.level, config.logging.file.as_deref()) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+    for warning in &report.warnings {
+        log::warn!("{}", warning);
+    }
+
+    let roots = config.roots;
+    let database = &config.storage.database;
+    let cache_dir = &config.storage.cache_dir;
+    let data_dir = &config.storage.data_dir;
+
+    let ffmpeg = FfmpegAudioParser::new(&config.ffmpeg.ffprobe, &config.ffmpeg.ffmpeg);
+    let gatherer = FilesystemAudioGathererRepository::from_roots(roots.clone(), &ffmpeg)
+        .with_genre_normalizer(config.genres.normalizer());
+    let repository = or_exit(
+        SqliteLibraryRepository::open(database),
+        "Failed to open the library database",
+    );
+    let library = Arc::new(or_exit(
+        LibraryService::new(gatherer, repository, AlbumServiceOptions::default()),
+        "Failed to load the library",
+    ));
+    if library.library().audio_count() == 0 {
+        or_exit(library.start_scan(), "Failed to start scanning the library");
+    }
+    library.start_schedule();
+
+    let transcoding = Arc::new(TranscodingService::new(
+        FfmpegAudioTranscoder::new(&config.ffmpeg.ffmpeg),
+        FilesystemTranscodeCacheRepository::new(cache_dir.join("transcodes")),
+        FilesystemLibraryFileRepository,
+        TranscodingServiceOptions::default(),
+    ));
+
+    let playlists = Arc::new(PlaylistService::new(
+        FilesystemPlaylistRepository::with_paths(roots.iter().map(|root| &root.path)),
+    ));
+
+    let smart_playlists = Arc::new(SmartPlaylistService::new(
+        FilesystemSmartPlaylistRepository::new(data_dir.join("smart_playlists")),
+    ));
+
+    let activity = Arc::new(ActivityService::new(or_exit(
+        SqliteActivityRepository::open(database),
+        "Failed to open the activity database",
+    )));
+
+    let users = Arc::new(UserService::new(or_exit(
+        SqliteUserRepository::open(database),
+        "Failed to open the user database",
+    )));
+    // The first administrator is created from the environment, later ones through the API
+    if let Some(admin) = &config.admin {
+        let user = users.bootstrap(&admin.username, &admin.password);
+        if let Some(user) = or_exit(user, "Failed to create the administrator") {
+            log::info!("Created administrator {}", user.username);
+        }
+    }
+    let existing = or_exit(users.users(), "Failed to read users");
+    if existing.is_empty() {
+        log::warn!("No user exists, set EARR_USERNAME and EARR_PASSWORD to create one");
+    }
+    // Activity recorded before there were users belongs to the first administrator
+    let first_admin = existing
+        .iter()
+        .filter(|user| user.role.is_admin())
+        .min_by_key(|user| user.created_at);
+    if let Some(admin) = first_admin {
+        match activity.adopt_unowned(&admin.id) {
+            Ok(0) => {}
+            Ok(adopted) => log::info!(
+                "Gave {} plays and annotations recorded before users to {}",
+                adopted,
+                admin.username
+            ),
+            Err(err) => log::error!("{}", err),
+        }
+    }
+
+    // Listens queued while offline are submitted once the server is up again
+    let scrobbles = Arc::new(ScrobbleService::new(
+        HttpScrobbler::default(),
+        or_exit(
+            SqliteScrobbleRepository::open(database),
+            "Failed to open the scrobbling database",
+        ),
+        ScrobbleServiceOptions::default(),
+    ));
+    scrobbles.start();
+
+    // Journals of batch edits are kept until undone, also across restarts
+    let tag_writer = || config.tags.writer(&config.ffmpeg);
+    let tag_edits = Arc::new(TagEditService::new(
+        tag_writer(),
+        FilesystemEditJournalRepository::new(data_dir.join("edit_journals")),
+    ));
+
+    // Fingerprints are recomputed when their file changes, so they are only cached
+    let fingerprints = Arc::new(FingerprintService::new(
+        FfmpegAudioDecoder::new(&config.ffmpeg.ffmpeg),
+        FilesystemFingerprintRepository::new(cache_dir.join("fingerprints")),
+        FilesystemLibraryFileRepository,
+        FingerprintServiceOptions::default(),
+    ));
+
+    let state = AppState::new(
+        library,
+        transcoding,
+        playlists,
+        smart_playlists,
+        activity,
+        users,
+        scrobbles,
+        Arc::new(tag_writer()),
+        tag_edits,
+        fingerprints,
+        Arc::new(GenreService::new(config.genres.normalizer())),
+    );
+
+    let address = &config.server.address;
+    let listener = or_exit(
+        TcpListener::bind(address).await,
+        &format!("Failed to listen on {}", address),
+    );
+    log::info!("Listening on {}", address);
+    let served = axum::serve(listener, http::router(state))
+        .with_graceful_shutdown(async {
+            signal::ctrl_c().await.ok();
+        })
+        .await;
+    or_exit(served, "Server failed");
+}
+
+/// Ends the process when starting the server failed, as it cannot run without what
+/// failed.
+fn or_exit<T, E: Display>(result: Result<T, E>, context: &str) -> T {
+    result.unwrap_or_else(|err| {
+        eprintln!("{}: {}", context, err);
+        process::exit(1);
+    })
+}
+
+fn parse_args(
+    mut args: impl Iterator<Item = String>,
+) -> Result<(Command, Option<PathBuf>), String> {
+    let mut command = Command::Serve;
+    let mut config_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => {
+                let path = args.next().ok_or("--config requires a path")?;
+                config_path = Some(PathBuf::from(path));
+            }
+            "config" => match args.next().as_deref() {
+                Some("check") => command = Command::CheckConfig,
+                _ => return Err("Unknown config command".to_string()),
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+    Ok((command, config_path))
+}
+
+/// Prints the configuration in effect along with its problems, then exits with a failure
+/// when it has errors.
+fn check_config(config: &Config, report: &ConfigReport) -> ! {
+    match &config.source {
+        Some(source) => println!("# Configuration read from {}", source.display()),
+        None => println!("# No configuration file, using defaults"),
+    }
+    println!("# Environment variables applied\n");
+    match toml::to_string(config) {
+        Ok(content) => println!("{}", content),
+        Err(err) => eprintln!("Failed to print configuration: {}", err),
+    }
+    for warning in &report.warnings {
+        println!("warning: {}", warning);
+    }
+    for error in &report.errors {
+        println!("error: {}", error);
+    }
+    if report.is_valid() {
+        println!("Configuration is valid");
+        process::exit(0);
+    }
+    println!(
+        "Configuration is invalid, {} error(s) found",
+        report.errors.len()
+    );
+    process::exit(1);
+}
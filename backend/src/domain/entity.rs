@@ -1 +1,12 @@
+pub mod activity;
+pub mod album;
 pub mod audio;
+pub mod edit_journal;
+pub mod fingerprint;
+pub mod library_root;
+pub mod play_stats;
+pub mod playlist;
+pub mod scrobble;
+pub mod smart_playlist;
+pub mod transcode_profile;
+pub mod user;
@@ -0,0 +1,133 @@
+use std::{fmt, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// A rating from 1 to 5 stars. Items that are not rated have no rating rather than 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub struct Rating(u8);
+
+#[derive(Debug, Error)]
+#[error("Rating must be between 1 and 5 stars: {0}")]
+pub struct RatingError(u8);
+
+impl Rating {
+    pub const MAX: u8 = 5;
+
+    pub fn stars(&self) -> u8 {
+        self.0
+    }
+
+    /// Reads the rating of an ID3 POPM frame, from 1 to 255 with 0 meaning unrated, using
+    /// the ranges Windows Media Player and most taggers write.
+    pub fn from_popularimeter(rating: u8) -> Option<Self> {
+        match rating {
+            0 => None,
+            1..=31 => Some(Self(1)),
+            32..=95 => Some(Self(2)),
+            96..=159 => Some(Self(3)),
+            160..=223 => Some(Self(4)),
+            224..=255 => Some(Self(5)),
+        }
+    }
+
+    /// Reads a FMPS rating, a fraction from 0.0 to 1.0. A rating below half a star is
+    /// considered unrated.
+    pub fn from_fraction(rating: f64) -> Option<Self> {
+        if !(0.0..=1.0).contains(&rating) {
+            return None;
+        }
+        let stars = (rating * f64::from(Self::MAX)).round() as u8;
+        Self::try_from(stars).ok()
+    }
+}
+
+impl TryFrom<u8> for Rating {
+    type Error = RatingError;
+
+    fn try_from(stars: u8) -> Result<Self, Self::Error> {
+        if (1..=Self::MAX).contains(&stars) {
+            Ok(Self(stars))
+        } else {
+            Err(RatingError(stars))
+        }
+    }
+}
+
+impl From<Rating> for u8 {
+    fn from(rating: Rating) -> Self {
+        rating.0
+    }
+}
+
+impl fmt::Display for Rating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// What ratings and favorites apply to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    Track,
+    Album,
+    Artist,
+}
+
+impl ItemKind {
+    pub const ALL: [Self; 3] = [Self::Track, Self::Album, Self::Artist];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Track => "track",
+            Self::Album => "album",
+            Self::Artist => "artist",
+        }
+    }
+}
+
+impl fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A listening of an audio.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Play {
+    pub audio_id: String,
+    pub played_at: DateTime<Utc>,
+    /// How long the audio was listened to, when the client reports it.
+    pub listened: Option<Duration>,
+    /// Name of the application the audio was played with.
+    pub client: Option<String>,
+}
+
+/// The rating and favorite status of a track, album or artist.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub kind: ItemKind,
+    pub id: String,
+    pub rating: Option<Rating>,
+    /// When the item was marked as a favorite, if it is one.
+    pub favorited_at: Option<DateTime<Utc>>,
+}
+
+impl Annotation {
+    pub fn new(kind: ItemKind, id: &str) -> Self {
+        Self {
+            kind,
+            id: id.to_string(),
+            rating: None,
+            favorited_at: None,
+        }
+    }
+
+    /// Whether the annotation holds nothing and does not need to be kept.
+    pub fn is_empty(&self) -> bool {
+        self.rating.is_none() && self.favorited_at.is_none()
+    }
+}
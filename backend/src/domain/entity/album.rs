@@ -0,0 +1,49 @@
+use std::path::Path;
+
+use derive_getters::Getters;
+use sha2::{Digest, Sha256};
+
+use super::audio::{artist::Artist, hex_prefix, title::Title, year::Year, Audio};
+
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+pub struct Album {
+    title: Title,
+    artist: Artist,
+    year: Option<Year>,
+    compilation: bool,
+    tracks: Vec<Audio>,
+}
+
+impl Album {
+    /// Creates an album with its tracks ordered by disc and track number.
+    pub fn new(title: Title, artist: Artist, compilation: bool, mut tracks: Vec<Audio>) -> Self {
+        tracks.sort_by_key(|track| (*track.disc_number(), *track.track_number()));
+        let year = tracks.iter().filter_map(|track| *track.year()).min();
+        Self {
+            title,
+            artist,
+            year,
+            compilation,
+            tracks,
+        }
+    }
+
+    /// Stable identifier derived from the artist, the title and the folder of the album.
+    pub fn id(&self) -> String {
+        let folder = self
+            .tracks
+            .first()
+            .and_then(|track| track.path().parent())
+            .unwrap_or(Path::new(""));
+        let mut hasher = Sha256::new();
+        for part in [
+            self.artist.name().as_bytes(),
+            self.title.name().as_bytes(),
+            folder.as_os_str().as_encoded_bytes(),
+        ] {
+            hasher.update(part);
+            hasher.update([0]);
+        }
+        hex_prefix(&hasher.finalize())
+    }
+}
@@ -1,18 +1,35 @@
-use std::fmt::{self, Formatter};
+use std::{collections::BTreeSet, path::PathBuf, time::Duration};
+
+use chrono::{DateTime, Utc};
 
 use derivative::Derivative;
 use derive_builder::Builder;
 use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::activity::Rating;
 
-use self::{artist::Artist, cover::Cover, genre::Genre, title::Title, year::Year};
+use self::{
+    artist::Artist,
+    cover::Cover,
+    field::AudioField,
+    genre::{normalizer::GenreNormalizer, Genre},
+    title::Title,
+    year::Year,
+};
 
 pub mod artist;
 pub mod cover;
+pub mod field;
 pub mod genre;
+pub mod sort_key;
+pub mod tag_change;
+pub mod text;
 pub mod title;
 pub mod year;
 
-#[derive(Derivative, Builder, Getters, Clone)]
+#[derive(Derivative, Builder, Getters, Clone, Serialize, Deserialize)]
 #[derivative(Debug, PartialEq, Hash, Eq)]
 pub struct Audio {
     title: Title,
@@ -20,7 +37,140 @@ pub struct Audio {
     year: Option<Year>,
     album_title: Title,
     album_artist: Artist,
+    /// Covers are stored apart from the other fields, see [`Cover::id`].
     #[derivative(PartialEq = "ignore", Hash = "ignore", Debug = "ignore")]
+    #[serde(skip)]
     album_cover: Cover,
     genre: Genre,
+    track_number: Option<u16>,
+    disc_number: Option<u16>,
+    compilation: bool,
+    path: PathBuf,
+    #[builder(default)]
+    duration: Option<Duration>,
+    /// Average bitrate in kbit/s.
+    #[builder(default)]
+    bitrate: Option<u32>,
+    #[builder(default)]
+    musicbrainz_recording_id: Option<String>,
+    /// Unsynchronized lyrics.
+    #[derivative(Debug = "ignore")]
+    #[builder(default)]
+    lyrics: Option<String>,
+    /// Rating found in the tags of the file, see [`Rating::from_popularimeter`].
+    #[builder(default)]
+    rating: Option<Rating>,
+    /// Last modification of the file when it was gathered.
+    #[derivative(PartialEq = "ignore", Hash = "ignore")]
+    #[builder(default)]
+    modified_at: Option<DateTime<Utc>>,
+    /// When the audio first appeared in the library, kept across scans.
+    #[derivative(PartialEq = "ignore", Hash = "ignore")]
+    #[builder(default)]
+    added_at: Option<DateTime<Utc>>,
+    /// Fields whose values were inferred from the file path instead of read from tags.
+    #[derivative(PartialEq = "ignore", Hash = "ignore")]
+    #[builder(default)]
+    inferred_fields: BTreeSet<AudioField>,
+    /// Name of the library root the audio was gathered from, see
+    /// [`super::library_root::LibraryRoot`]. Audios stored before roots were named have none.
+    #[derivative(PartialEq = "ignore", Hash = "ignore")]
+    #[builder(default)]
+    #[serde(default)]
+    root: Option<String>,
+}
+
+impl Audio {
+    /// Returns a builder initialized with this audio, used to edit its metadata.
+    pub fn edit(&self) -> AudioBuilder {
+        let mut builder = AudioBuilder::default();
+        builder
+            .title(self.title.clone())
+            .artist(self.artist.clone())
+            .year(self.year)
+            .album_title(self.album_title.clone())
+            .album_artist(self.album_artist.clone())
+            .album_cover(self.album_cover.clone())
+            .genre(self.genre.clone())
+            .track_number(self.track_number)
+            .disc_number(self.disc_number)
+            .compilation(self.compilation)
+            .path(self.path.clone())
+            .duration(self.duration)
+            .bitrate(self.bitrate)
+            .musicbrainz_recording_id(self.musicbrainz_recording_id.clone())
+            .lyrics(self.lyrics.clone())
+            .rating(self.rating)
+            .modified_at(self.modified_at)
+            .added_at(self.added_at)
+            .inferred_fields(self.inferred_fields.clone())
+            .root(self.root.clone());
+        builder
+    }
+
+    /// Stable identifier derived from the path of the audio.
+    pub fn id(&self) -> String {
+        let hash = Sha256::digest(self.path.as_os_str().as_encoded_bytes());
+        hex_prefix(&hash)
+    }
+
+    pub fn with_album_cover(self, album_cover: Cover) -> Self {
+        Self {
+            album_cover,
+            ..self
+        }
+    }
+
+    pub fn with_added_at(self, added_at: Option<DateTime<Utc>>) -> Self {
+        Self { added_at, ..self }
+    }
+
+    pub fn with_root(self, root: &str) -> Self {
+        Self {
+            root: Some(root.to_string()),
+            ..self
+        }
+    }
+
+    pub fn is_inferred(&self, field: AudioField) -> bool {
+        self.inferred_fields.contains(&field)
+    }
+
+    /// Whether any text field looks double encoded.
+    pub fn has_encoding_issue(&self) -> bool {
+        self.title.has_encoding_issue()
+            || self.artist.has_encoding_issue()
+            || self.album_title.has_encoding_issue()
+            || self.album_artist.has_encoding_issue()
+            || self.genre.has_encoding_issue()
+    }
+
+    /// Normalizes the genre from its raw tag value with a configured normalizer rather than
+    /// the default one.
+    pub fn normalize_genre(self, normalizer: &GenreNormalizer) -> Self {
+        Self {
+            genre: self.genre.renormalize(normalizer),
+            ..self
+        }
+    }
+
+    /// Repairs double-encoded text fields. Raw tag values are preserved.
+    pub fn repair_encoding(self) -> Self {
+        Self {
+            title: self.title.repair_encoding(),
+            artist: self.artist.repair_encoding(),
+            album_title: self.album_title.repair_encoding(),
+            album_artist: self.album_artist.repair_encoding(),
+            genre: self.genre.repair_encoding(),
+            ..self
+        }
+    }
+}
+
+/// First 128 bits of a hash, in hexadecimal, used as identifier.
+pub(crate) fn hex_prefix(hash: &[u8]) -> String {
+    hash.iter()
+        .take(16)
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
 }
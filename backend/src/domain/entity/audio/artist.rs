@@ -1,9 +1,23 @@
 use std::str::FromStr;
 
+use derivative::Derivative;
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Artist(pub String);
+use super::{sort_key::SortKeyOptions, text};
+
+#[derive(Derivative, Getters, Clone, Serialize, Deserialize)]
+#[derivative(Debug, PartialEq, Eq, Hash)]
+pub struct Artist {
+    name: String,
+    #[derivative(PartialEq = "ignore", Hash = "ignore")]
+    sort_name: Option<String>,
+    /// Value exactly as read from the tag, kept for writeback.
+    #[derivative(PartialEq = "ignore", Hash = "ignore")]
+    raw: String,
+}
 
 #[derive(Debug, Error)]
 pub enum ArtistError {
@@ -13,18 +27,81 @@ pub enum ArtistError {
 
 impl Default for Artist {
     fn default() -> Self {
-        Self("UNKNOWN".to_string())
+        Self::new("UNKNOWN")
     }
 }
 
 impl FromStr for Artist {
     type Err = ArtistError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let trimmed = s.trim();
-        if trimmed.is_empty() {
+        let normalized = text::normalize(s);
+        if normalized.is_empty() {
             return Err(ArtistError::Empty);
         }
 
-        Ok(Self(trimmed.to_string()))
+        Ok(Self {
+            name: normalized,
+            sort_name: None,
+            raw: s.to_string(),
+        })
+    }
+}
+
+impl Artist {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            sort_name: None,
+            raw: name.to_string(),
+        }
+    }
+
+    pub fn various() -> Self {
+        Self::new("Various Artists")
+    }
+
+    /// Stable identifier derived from the artist name.
+    pub fn id(&self) -> String {
+        super::hex_prefix(&Sha256::digest(self.name.as_bytes()))
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Attaches the sort name read from tags such as `TSOP` or `ARTISTSORT`.
+    /// Blank sort names are ignored.
+    pub fn with_sort_name(self, sort_name: &str) -> Self {
+        let sort_name = text::normalize(sort_name);
+        if sort_name.is_empty() {
+            return self;
+        }
+        Self {
+            sort_name: Some(sort_name),
+            ..self
+        }
+    }
+
+    /// Key used to order artists, preferring the tagged sort name.
+    pub fn sort_key(&self, options: &SortKeyOptions) -> String {
+        match &self.sort_name {
+            Some(sort_name) => options.normalize(sort_name),
+            None => options.generate(&self.name),
+        }
+    }
+
+    pub fn has_encoding_issue(&self) -> bool {
+        text::has_encoding_issue(&self.name)
+    }
+
+    /// Repairs double-encoded text (see [`text::repair_encoding`]), keeping the raw value.
+    pub fn repair_encoding(self) -> Self {
+        Self {
+            name: text::repair_encoding(&self.name).unwrap_or(self.name),
+            sort_name: self
+                .sort_name
+                .map(|sort_name| text::repair_encoding(&sort_name).unwrap_or(sort_name)),
+            raw: self.raw,
+        }
     }
 }
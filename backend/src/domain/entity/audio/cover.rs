@@ -1,9 +1,20 @@
+use std::sync::Arc;
+
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 
+use super::hex_prefix;
+
 static DEFAULT_COVER: &[u8] = include_bytes!("./cover/default_cover.png");
 
+/// Shared by every audio without a cover.
+static DEFAULT: Lazy<Cover> = Lazy::new(|| Cover(Arc::from(DEFAULT_COVER)));
+
+/// Image data of a cover. Clones share the same bytes, so the tracks of an album hold a
+/// single copy.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Cover(pub Vec<u8>);
+pub struct Cover(Arc<[u8]>);
 
 #[derive(Debug, Error)]
 pub enum CoverError {
@@ -13,7 +24,7 @@ pub enum CoverError {
 
 impl Default for Cover {
     fn default() -> Self {
-        Self(DEFAULT_COVER.to_vec())
+        DEFAULT.clone()
     }
 }
 
@@ -22,7 +33,79 @@ impl Cover {
         if cover.is_empty() {
             return Err(CoverError::Empty);
         }
-        Ok(Self(cover))
+        Ok(Self(Arc::from(cover)))
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Whether this is the placeholder used for audios without a cover.
+    pub fn is_default(&self) -> bool {
+        Arc::ptr_eq(&self.0, &DEFAULT.0) || *self.0 == *DEFAULT_COVER
+    }
+
+    /// Stable identifier derived from the image content, shared by the tracks of an album.
+    pub fn id(&self) -> String {
+        hex_prefix(&Sha256::digest(&self.0))
+    }
+
+    /// MIME type guessed from the image signature.
+    pub fn mime_type(&self) -> &'static str {
+        let data = self.data();
+        if data.starts_with(b"\x89PNG") {
+            "image/png"
+        } else if data.starts_with(&[0xFF, 0xD8]) {
+            "image/jpeg"
+        } else if data.starts_with(b"GIF8") {
+            "image/gif"
+        } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+            "image/webp"
+        } else if data.starts_with(b"BM") {
+            "image/bmp"
+        } else {
+            "application/octet-stream"
+        }
+    }
+
+    /// Width and height of PNG and JPEG covers, read from the image header.
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        let data = self.data();
+        let be_u16 = |offset: usize| {
+            data.get(offset..offset + 2)
+                .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as u32)
+        };
+        let be_u32 = |offset: usize| {
+            data.get(offset..offset + 4)
+                .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        };
+
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            // The IHDR chunk comes first
+            return Some((be_u32(16)?, be_u32(20)?));
+        }
+        if !data.starts_with(&[0xFF, 0xD8]) {
+            return None;
+        }
+        // Walks the JPEG segments up to a start of frame marker
+        let mut offset = 2;
+        while offset + 4 <= data.len() {
+            if data[offset] != 0xFF {
+                return None;
+            }
+            let marker = data[offset + 1];
+            if marker == 0xFF {
+                offset += 1;
+                continue;
+            }
+            let is_start_of_frame =
+                matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+            if is_start_of_frame {
+                return Some((be_u16(offset + 7)?, be_u16(offset + 5)?));
+            }
+            offset += 2 + be_u16(offset + 2)? as usize;
+        }
+        None
     }
 }
 
@@ -0,0 +1,53 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+/// Identifies a metadata field of an [`Audio`](super::Audio).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioField {
+    Title,
+    Artist,
+    Year,
+    AlbumTitle,
+    AlbumArtist,
+    AlbumCover,
+    Genre,
+    TrackNumber,
+    DiscNumber,
+    Compilation,
+}
+
+impl Display for AudioField {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Title => "title",
+            Self::Artist => "artist",
+            Self::Year => "year",
+            Self::AlbumTitle => "album_title",
+            Self::AlbumArtist => "album_artist",
+            Self::AlbumCover => "album_cover",
+            Self::Genre => "genre",
+            Self::TrackNumber => "track_number",
+            Self::DiscNumber => "disc_number",
+            Self::Compilation => "compilation",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl AudioField {
+    /// Fields that can be written back into audio files.
+    pub const WRITABLE: [AudioField; 10] = [
+        Self::Title,
+        Self::Artist,
+        Self::Year,
+        Self::AlbumTitle,
+        Self::AlbumArtist,
+        Self::AlbumCover,
+        Self::Genre,
+        Self::TrackNumber,
+        Self::DiscNumber,
+        Self::Compilation,
+    ];
+}
@@ -1,9 +1,28 @@
 use std::str::FromStr;
 
+use derivative::Derivative;
+use derive_getters::Getters;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Genre(pub String);
+use self::normalizer::GenreNormalizer;
+
+use super::text;
+
+pub mod id3v1;
+pub mod normalizer;
+
+static DEFAULT_NORMALIZER: Lazy<GenreNormalizer> = Lazy::new(GenreNormalizer::default);
+
+#[derive(Derivative, Getters, Clone, Serialize, Deserialize)]
+#[derivative(Debug, PartialEq, Eq, Hash)]
+pub struct Genre {
+    name: String,
+    /// Value exactly as read from the tag, kept for writeback.
+    #[derivative(PartialEq = "ignore", Hash = "ignore")]
+    raw: String,
+}
 
 #[derive(Debug, Error)]
 pub enum GenreError {
@@ -13,18 +32,84 @@ pub enum GenreError {
 
 impl Default for Genre {
     fn default() -> Self {
-        Self("UNKNOWN".to_string())
+        Self::new("UNKNOWN")
     }
 }
 
 impl FromStr for Genre {
     type Err = GenreError;
+    /// Parses the primary genre of a raw tag value using the default [`GenreNormalizer`].
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let trimmed = s.trim();
-        if trimmed.is_empty() {
-            return Err(GenreError::Empty);
+        Self::parse_with(s, &DEFAULT_NORMALIZER)
+    }
+}
+
+impl Genre {
+    pub(crate) fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            raw: name.to_string(),
         }
+    }
+
+    /// Parses the primary genre of a raw tag value using the given normalizer.
+    pub fn parse_with(raw: &str, normalizer: &GenreNormalizer) -> Result<Self, GenreError> {
+        // Text normalization drops control characters, among which the ID3v2.4 separator
+        let parts = raw.split('\0').map(text::normalize).collect::<Vec<_>>();
+        normalizer
+            .normalize(&parts.join("\0"))
+            .map(|name| Self {
+                name,
+                raw: raw.to_string(),
+            })
+            .ok_or(GenreError::Empty)
+    }
+
+    /// Parses the raw value again with another normalizer, keeping the genre when the
+    /// normalizer finds none in it.
+    pub fn renormalize(&self, normalizer: &GenreNormalizer) -> Self {
+        Self::parse_with(&self.raw, normalizer).unwrap_or_else(|_| self.clone())
+    }
+
+    /// Returns a genre with the same raw value but a different normalized name.
+    pub(crate) fn renamed(&self, name: String) -> Self {
+        Self {
+            name,
+            raw: self.raw.clone(),
+        }
+    }
+
+    pub fn has_encoding_issue(&self) -> bool {
+        text::has_encoding_issue(&self.name)
+    }
+
+    /// Repairs double-encoded text (see [`text::repair_encoding`]), keeping the raw value.
+    pub fn repair_encoding(self) -> Self {
+        Self {
+            name: text::repair_encoding(&self.name).unwrap_or(self.name),
+            raw: self.raw,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_id3v24_values_before_normalizing_text() {
+        let genre = "Rock\0Pop".parse::<Genre>().unwrap();
+        assert_eq!(genre.name(), "Rock");
+        assert_eq!(genre.raw(), "Rock\0Pop");
+        assert_eq!(
+            GenreNormalizer::default().normalize_all("Rock\0Pop"),
+            ["Rock", "Pop"]
+        );
+    }
 
-        Ok(Self(trimmed.to_string()))
+    #[test]
+    fn normalizes_text_of_each_genre() {
+        let genre = "\u{200B}Cafe\u{301} Music \0Jazz".parse::<Genre>().unwrap();
+        assert_eq!(genre.name(), "Caf\u{e9} Music");
     }
 }
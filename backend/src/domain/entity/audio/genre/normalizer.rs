@@ -0,0 +1,243 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{id3v1, Genre};
+
+/// Separators used to store several genres in a single tag value.
+const SEPARATORS: &[char] = &[';', '/', ',', '\0'];
+
+/// Normalizes raw genre tag values into canonical genre names.
+///
+/// Handles ID3v1 numeric codes (`17`, `(17)`), ID3v2 `(n)Refinement` values, multi-genre
+/// values such as `Rock/Pop`, whitespace and case differences and a configurable alias table.
+/// It also holds an optional genre hierarchy used when browsing the library.
+#[derive(Debug, Clone)]
+pub struct GenreNormalizer {
+    aliases: HashMap<String, String>,
+    parents: HashMap<String, String>,
+}
+
+impl Default for GenreNormalizer {
+    fn default() -> Self {
+        Self::empty()
+            .with_alias("Hip Hop", "Hip-Hop")
+            .with_alias("HipHop", "Hip-Hop")
+            .with_alias("Rap", "Hip-Hop")
+            .with_alias("RnB", "R&B")
+            .with_alias("R and B", "R&B")
+            .with_alias("Rhythm and Blues", "R&B")
+            .with_alias("Rock and Roll", "Rock & Roll")
+            .with_alias("Rock n Roll", "Rock & Roll")
+            .with_alias("Rock'n'Roll", "Rock & Roll")
+            .with_alias("Electronica", "Electronic")
+            .with_alias("Synth-Pop", "SynthPop")
+            .with_alias("Synth Pop", "SynthPop")
+    }
+}
+
+impl GenreNormalizer {
+    /// A normalizer without aliases nor hierarchy.
+    pub fn empty() -> Self {
+        Self {
+            aliases: HashMap::new(),
+            parents: HashMap::new(),
+        }
+    }
+
+    /// Maps `alias` (compared ignoring case and whitespace) to `canonical`.
+    pub fn with_alias(mut self, alias: &str, canonical: &str) -> Self {
+        self.aliases
+            .insert(Self::key(alias), collapse_whitespace(canonical));
+        self
+    }
+
+    /// Declares `parent` as the parent genre of `child`.
+    pub fn with_parent(mut self, child: &str, parent: &str) -> Self {
+        self.parents
+            .insert(Self::key(child), collapse_whitespace(parent));
+        self
+    }
+
+    /// Returns every genre contained in a raw tag value, in order and without duplicates.
+    pub fn normalize_all(&self, raw: &str) -> Vec<String> {
+        let raw = raw.trim();
+        let parts = match parse_id3v2_codes(raw) {
+            Some(parts) => parts,
+            None if self.is_known(raw) => vec![raw.to_string()],
+            None => raw.split(SEPARATORS).map(str::to_string).collect(),
+        };
+
+        let mut seen = HashSet::new();
+        parts
+            .iter()
+            .filter_map(|part| self.normalize_single(part))
+            .filter(|genre| seen.insert(Self::key(genre)))
+            .collect()
+    }
+
+    /// Returns the primary genre of a raw tag value.
+    pub fn normalize(&self, raw: &str) -> Option<String> {
+        self.normalize_all(raw).into_iter().next()
+    }
+
+    /// Re-applies this normalizer to an already parsed genre.
+    pub fn normalize_genre(&self, genre: &Genre) -> Genre {
+        self.normalize(genre.name())
+            .map(|name| genre.renamed(name))
+            .unwrap_or_else(|| genre.clone())
+    }
+
+    pub fn parent(&self, genre: &Genre) -> Option<Genre> {
+        self.parents
+            .get(&Self::key(genre.name()))
+            .map(|parent| Genre::new(parent))
+    }
+
+    /// Returns the parents of a genre, closest first. Cycles in the hierarchy are cut.
+    pub fn ancestors(&self, genre: &Genre) -> Vec<Genre> {
+        let mut seen = HashSet::from([Self::key(genre.name())]);
+        let mut ancestors = Vec::new();
+        let mut current = genre.clone();
+        while let Some(parent) = self.parent(&current) {
+            if !seen.insert(Self::key(parent.name())) {
+                break;
+            }
+            ancestors.push(parent.clone());
+            current = parent;
+        }
+        ancestors
+    }
+
+    /// Genres of the hierarchy that are their own ancestor, in lower case and ordered.
+    pub fn cyclic_genres(&self) -> Vec<String> {
+        let mut cyclic = self
+            .parents
+            .keys()
+            .filter(|child| {
+                let mut seen = HashSet::new();
+                let mut current = (*child).clone();
+                while let Some(parent) = self.parents.get(&current) {
+                    current = Self::key(parent);
+                    if current == **child {
+                        return true;
+                    }
+                    if !seen.insert(current.clone()) {
+                        return false;
+                    }
+                }
+                false
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        cyclic.sort();
+        cyclic
+    }
+
+    fn normalize_single(&self, raw: &str) -> Option<String> {
+        let collapsed = collapse_whitespace(raw);
+        if collapsed.is_empty() {
+            return None;
+        }
+        // Codes resolve to names that aliases still apply to
+        let name = match collapsed.parse::<usize>() {
+            Ok(code) => id3v1::genre_name(code)?.to_string(),
+            Err(_) => collapsed,
+        };
+        if let Some(canonical) = self.aliases.get(&Self::key(&name)) {
+            return Some(canonical.clone());
+        }
+        if let Some(canonical) = id3v1::canonical_name(&name) {
+            return Some(canonical.to_string());
+        }
+        Some(normalize_case(&name))
+    }
+
+    fn is_known(&self, raw: &str) -> bool {
+        let collapsed = collapse_whitespace(raw);
+        self.aliases.contains_key(&Self::key(&collapsed))
+            || id3v1::canonical_name(&collapsed).is_some()
+    }
+
+    fn key(genre: &str) -> String {
+        collapse_whitespace(genre).to_lowercase()
+    }
+}
+
+/// Parses ID3v2 `TCON` values made of `(n)` references, optionally followed by a refinement,
+/// e.g. `(17)`, `(28)(31)` or `(4)Eurodisco`. `RX` and `CR` stand for Remix and Cover, and
+/// `((` escapes a literal parenthesis. Returns `None` if the value does not use that syntax.
+fn parse_id3v2_codes(raw: &str) -> Option<Vec<String>> {
+    if raw.starts_with("((") {
+        return Some(vec![raw[1..].to_string()]);
+    }
+    if !raw.starts_with('(') {
+        return None;
+    }
+
+    let mut genres = Vec::new();
+    let mut rest = raw;
+    while let Some(reference) = rest.strip_prefix('(').filter(|rest| !rest.starts_with('(')) {
+        let (code, remaining) = reference.split_once(')')?;
+        let genre = match code {
+            "RX" => "Remix".to_string(),
+            "CR" => "Cover".to_string(),
+            code => code.parse().ok().and_then(id3v1::genre_name)?.to_string(),
+        };
+        genres.push(genre);
+        rest = remaining;
+    }
+
+    let refinement = rest.strip_prefix('(').unwrap_or(rest).trim();
+    if !refinement.is_empty() {
+        // The refinement is a more specific description of the referenced genres
+        return Some(vec![refinement.to_string()]);
+    }
+    Some(genres)
+}
+
+fn collapse_whitespace(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Capitalizes every word of values written entirely in lower case, leaving names with
+/// deliberate casing such as "IDM" or "SynthPop" untouched.
+fn normalize_case(value: &str) -> String {
+    if value != value.to_lowercase() {
+        return value.to_string();
+    }
+
+    value
+        .split(' ')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_aliases_to_numeric_codes() {
+        let normalizer = GenreNormalizer::default();
+        assert_eq!(normalizer.normalize("15").as_deref(), Some("Hip-Hop"));
+        assert_eq!(normalizer.normalize("(15)").as_deref(), Some("Hip-Hop"));
+        assert_eq!(normalizer.normalize("Rap").as_deref(), Some("Hip-Hop"));
+    }
+
+    #[test]
+    fn configured_aliases_replace_default_ones() {
+        let normalizer = GenreNormalizer::default().with_alias("rap", "Rap");
+        let genre = Genre::parse_with("(15)", &normalizer).unwrap();
+        assert_eq!(genre.name(), "Rap");
+        assert_eq!(
+            "(15)".parse::<Genre>().unwrap().renormalize(&normalizer),
+            genre
+        );
+    }
+}
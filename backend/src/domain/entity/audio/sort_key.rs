@@ -0,0 +1,46 @@
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// Options used to derive the key that artists and titles are ordered by.
+#[derive(Debug, Clone)]
+pub struct SortKeyOptions {
+    /// Leading articles that are ignored when no explicit sort name is tagged,
+    /// so that "The Beatles" sorts under B.
+    pub articles: Vec<String>,
+    /// Whether diacritics are removed so that "Émilie" sorts next to "Emilie".
+    pub fold_diacritics: bool,
+}
+
+impl Default for SortKeyOptions {
+    fn default() -> Self {
+        Self {
+            articles: ["the", "a", "an"].map(String::from).to_vec(),
+            fold_diacritics: true,
+        }
+    }
+}
+
+impl SortKeyOptions {
+    /// Generates a sort key for a value that has no explicit sort name.
+    pub fn generate(&self, value: &str) -> String {
+        let key = self.normalize(value);
+        self.articles
+            .iter()
+            .map(|article| self.normalize(article))
+            .find_map(|article| {
+                key.strip_prefix(&article)
+                    .filter(|rest| rest.starts_with(char::is_whitespace))
+                    .map(|rest| rest.trim_start().to_string())
+                    .filter(|rest| !rest.is_empty())
+            })
+            .unwrap_or(key)
+    }
+
+    /// Normalizes an explicit sort name so it compares consistently with generated keys.
+    pub fn normalize(&self, value: &str) -> String {
+        let value = value.trim().to_lowercase();
+        if !self.fold_diacritics {
+            return value;
+        }
+        value.nfd().filter(|c| !is_combining_mark(*c)).collect()
+    }
+}
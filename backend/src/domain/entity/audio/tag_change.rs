@@ -0,0 +1,57 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use super::field::AudioField;
+
+/// A raw tag value as stored in an audio file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TagValue {
+    /// One or more text values. Multi-value fields hold one entry per value.
+    Text(Vec<String>),
+    Picture(Vec<u8>),
+}
+
+impl Display for TagValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text(values) => write!(f, "{}", values.join("; ")),
+            Self::Picture(data) => write!(f, "<image, {} bytes>", data.len()),
+        }
+    }
+}
+
+/// A change of a single tag field. `None` means the field is absent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagChange {
+    pub field: AudioField,
+    pub before: Option<TagValue>,
+    pub after: Option<TagValue>,
+}
+
+impl TagChange {
+    /// The change that undoes this one.
+    pub fn reversed(&self) -> Self {
+        Self {
+            field: self.field,
+            before: self.after.clone(),
+            after: self.before.clone(),
+        }
+    }
+}
+
+impl Display for TagChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let display = |value: &Option<TagValue>| match value {
+            Some(value) => value.to_string(),
+            None => "<none>".to_string(),
+        };
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.field,
+            display(&self.before),
+            display(&self.after)
+        )
+    }
+}
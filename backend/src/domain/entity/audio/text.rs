@@ -0,0 +1,112 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// Characters that take no space when rendered and only make equal names compare different.
+const ZERO_WIDTH: &[char] = &['\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}', '\u{FEFF}'];
+
+/// Characters that Windows-1252 places in the 0x80..=0x9F range, where Latin-1 has controls.
+const WINDOWS_1252: [(char, u8); 27] = [
+    ('€', 0x80),
+    ('‚', 0x82),
+    ('ƒ', 0x83),
+    ('„', 0x84),
+    ('…', 0x85),
+    ('†', 0x86),
+    ('‡', 0x87),
+    ('ˆ', 0x88),
+    ('‰', 0x89),
+    ('Š', 0x8A),
+    ('‹', 0x8B),
+    ('Œ', 0x8C),
+    ('Ž', 0x8E),
+    ('‘', 0x91),
+    ('’', 0x92),
+    ('“', 0x93),
+    ('”', 0x94),
+    ('•', 0x95),
+    ('–', 0x96),
+    ('—', 0x97),
+    ('˜', 0x98),
+    ('™', 0x99),
+    ('š', 0x9A),
+    ('›', 0x9B),
+    ('œ', 0x9C),
+    ('ž', 0x9E),
+    ('Ÿ', 0x9F),
+];
+
+/// Normalizes a text tag value: NFC composition, removal of control and zero-width
+/// characters (line breaks and tabs become spaces) and trimming.
+pub fn normalize(raw: &str) -> String {
+    raw.nfc()
+        .filter(|c| !ZERO_WIDTH.contains(c))
+        .map(|c| if c.is_whitespace() { ' ' } else { c })
+        .filter(|c| !c.is_control())
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Detects UTF-8 text that was decoded as Latin-1/Windows-1252 and encoded again
+/// (e.g. "BeyoncÃ©" for "Beyoncé") and returns the repaired text.
+///
+/// Returns `None` when the text does not look double encoded.
+pub fn repair_encoding(text: &str) -> Option<String> {
+    if text.is_ascii() {
+        return None;
+    }
+
+    let bytes = text
+        .chars()
+        .map(|c| match c as u32 {
+            code @ 0..=0xFF => Some(code as u8),
+            _ => WINDOWS_1252
+                .iter()
+                .find(|(windows_char, _)| *windows_char == c)
+                .map(|(_, byte)| *byte),
+        })
+        .collect::<Option<Vec<u8>>>()?;
+
+    let repaired = String::from_utf8(bytes).ok()?;
+    if repaired == text {
+        return None;
+    }
+    Some(normalize(&repaired))
+}
+
+/// Whether the text looks like it suffered a double-encoding mistake.
+pub fn has_encoding_issue(text: &str) -> bool {
+    repair_encoding(text).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_text_tags() {
+        // "e" followed by a combining acute accent
+        assert_eq!(normalize("Beyonce\u{301}"), "Beyoncé");
+        assert_eq!(normalize("  Daft\u{200B} Punk\u{FEFF} "), "Daft Punk");
+        assert_eq!(normalize("One\nMore\tTime\u{7}"), "One More Time");
+        assert_eq!(normalize(" \u{200D} "), "");
+    }
+
+    #[test]
+    fn repairs_double_encoded_text() {
+        assert_eq!(repair_encoding("BeyoncÃ©").as_deref(), Some("Beyoncé"));
+        assert_eq!(repair_encoding("Sigur RÃ³s").as_deref(), Some("Sigur Rós"));
+        // The right single quotation mark is encoded as E2 80 99, 0x80 being the euro sign
+        // in Windows-1252
+        assert_eq!(repair_encoding("Donâ€™t").as_deref(), Some("Don’t"));
+    }
+
+    #[test]
+    fn leaves_correct_text_alone() {
+        assert_eq!(repair_encoding("Daft Punk"), None);
+        assert_eq!(repair_encoding("Beyoncé"), None);
+        assert_eq!(repair_encoding("Björk – Jóga"), None);
+        assert_eq!(repair_encoding("坂本龍一"), None);
+        assert!(has_encoding_issue("MotÃ¶rhead"));
+        assert!(!has_encoding_issue("Motörhead"));
+    }
+}
@@ -1,9 +1,22 @@
 use std::str::FromStr;
 
+use derivative::Derivative;
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Title(pub String);
+use super::{sort_key::SortKeyOptions, text};
+
+#[derive(Derivative, Getters, Clone, Serialize, Deserialize)]
+#[derivative(Debug, PartialEq, Eq, Hash)]
+pub struct Title {
+    name: String,
+    #[derivative(PartialEq = "ignore", Hash = "ignore")]
+    sort_name: Option<String>,
+    /// Value exactly as read from the tag, kept for writeback.
+    #[derivative(PartialEq = "ignore", Hash = "ignore")]
+    raw: String,
+}
 
 #[derive(Debug, Error)]
 pub enum TitleError {
@@ -13,18 +26,68 @@ pub enum TitleError {
 
 impl Default for Title {
     fn default() -> Self {
-        Self("UNKNOWN".to_string())
+        Self::new("UNKNOWN")
     }
 }
 
 impl FromStr for Title {
     type Err = TitleError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let trimmed = s.trim();
-        if trimmed.is_empty() {
+        let normalized = text::normalize(s);
+        if normalized.is_empty() {
             return Err(TitleError::Empty);
         }
 
-        Ok(Self(trimmed.to_string()))
+        Ok(Self {
+            name: normalized,
+            sort_name: None,
+            raw: s.to_string(),
+        })
+    }
+}
+
+impl Title {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            sort_name: None,
+            raw: name.to_string(),
+        }
+    }
+
+    /// Attaches the sort name read from tags such as `TSOT` or `TITLESORT`.
+    /// Blank sort names are ignored.
+    pub fn with_sort_name(self, sort_name: &str) -> Self {
+        let sort_name = text::normalize(sort_name);
+        if sort_name.is_empty() {
+            return self;
+        }
+        Self {
+            sort_name: Some(sort_name),
+            ..self
+        }
+    }
+
+    /// Key used to order titles, preferring the tagged sort name.
+    pub fn sort_key(&self, options: &SortKeyOptions) -> String {
+        match &self.sort_name {
+            Some(sort_name) => options.normalize(sort_name),
+            None => options.generate(&self.name),
+        }
+    }
+
+    pub fn has_encoding_issue(&self) -> bool {
+        text::has_encoding_issue(&self.name)
+    }
+
+    /// Repairs double-encoded text (see [`text::repair_encoding`]), keeping the raw value.
+    pub fn repair_encoding(self) -> Self {
+        Self {
+            name: text::repair_encoding(&self.name).unwrap_or(self.name),
+            sort_name: self
+                .sort_name
+                .map(|sort_name| text::repair_encoding(&sort_name).unwrap_or(sort_name)),
+            raw: self.raw,
+        }
     }
 }
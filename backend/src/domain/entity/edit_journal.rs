@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{DateTime, Utc};
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+
+use super::audio::tag_change::TagChange;
+
+/// Record of the tag changes applied by a batch edit, used to undo it.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, Serialize, Deserialize)]
+pub struct EditJournal {
+    id: String,
+    created_at: DateTime<Utc>,
+    description: String,
+    entries: Vec<EditJournalEntry>,
+    #[serde(default)]
+    state: EditJournalState,
+}
+
+/// Whether the files of a journal were written. A journal is saved as pending with every
+/// planned change before the first write, so that an interrupted batch can still be undone.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EditJournalState {
+    /// Files may have been written, entries hold every planned change.
+    Pending,
+    /// Entries hold the changes of the files written.
+    #[default]
+    Applied,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditJournalEntry {
+    pub path: PathBuf,
+    /// Applied changes, holding the raw tag values found before the edit.
+    pub changes: Vec<TagChange>,
+}
+
+impl EditJournal {
+    /// A journal of changes about to be applied.
+    pub fn pending(description: &str, entries: Vec<EditJournalEntry>) -> Self {
+        let created_at = Utc::now();
+        // Batches may start within the same millisecond, hence the random suffix
+        let mut suffix = [0; 4];
+        OsRng.fill_bytes(&mut suffix);
+        let suffix = suffix
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        Self {
+            id: format!("{}-{}", created_at.format("%Y%m%dT%H%M%S%.3fZ"), suffix),
+            created_at,
+            description: description.to_owned(),
+            entries,
+            state: EditJournalState::Pending,
+        }
+    }
+
+    /// The journal once applied, holding the changes of the files actually written.
+    pub fn applied(self, entries: Vec<EditJournalEntry>) -> Self {
+        Self {
+            entries,
+            state: EditJournalState::Applied,
+            ..self
+        }
+    }
+}
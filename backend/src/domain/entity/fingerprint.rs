@@ -0,0 +1,118 @@
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+mod chromaprint;
+mod compression;
+
+pub use chromaprint::SAMPLE_RATE;
+
+/// Items of a fingerprint are this far apart, in seconds.
+const ITEM_DURATION: f64 = 4096.0 / 3.0 / SAMPLE_RATE as f64;
+/// Largest shift between two fingerprints searched when comparing them, about 10 seconds.
+const MAX_ALIGNMENT_OFFSET: isize = 80;
+/// Fewest overlapping items for a comparison to be meaningful, about 2.5 seconds.
+const MIN_OVERLAP: usize = 20;
+
+/// A Chromaprint-compatible acoustic fingerprint. It displays and parses as the compressed
+/// base64 form printed by `fpcalc`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Fingerprint {
+    raw: Vec<u32>,
+}
+
+/// The fingerprint of a file, with the file size and modification time it was computed for
+/// so that it can be recomputed when the file changes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackFingerprint {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: DateTime<Utc>,
+    pub fingerprint: Fingerprint,
+}
+
+#[derive(Debug, Error)]
+pub enum FingerprintError {
+    #[error("Audio is too short to be fingerprinted")]
+    TooShort,
+    #[error("Invalid fingerprint")]
+    Invalid,
+}
+
+impl Fingerprint {
+    /// Computes the fingerprint of mono samples at [`SAMPLE_RATE`].
+    pub fn compute(samples: &[i16]) -> Result<Self, FingerprintError> {
+        let raw = chromaprint::compute(samples);
+        if raw.is_empty() {
+            return Err(FingerprintError::TooShort);
+        }
+        Ok(Self { raw })
+    }
+
+    pub fn raw(&self) -> &[u32] {
+        &self.raw
+    }
+
+    /// Duration of the audio covered by the fingerprint.
+    pub fn duration_secs(&self) -> f64 {
+        self.raw.len() as f64 * ITEM_DURATION
+    }
+
+    /// Similarity from 0 to 1 of the best alignment of both fingerprints, 1 meaning every
+    /// bit matches. Unrelated recordings score around 0.5, while transcodes of the same
+    /// recording usually score above 0.85.
+    pub fn similarity(&self, other: &Fingerprint) -> f64 {
+        (-MAX_ALIGNMENT_OFFSET..=MAX_ALIGNMENT_OFFSET)
+            .filter_map(|offset| {
+                let (a, b) = if offset >= 0 {
+                    (self.raw.get(offset as usize..)?, other.raw.as_slice())
+                } else {
+                    (self.raw.as_slice(), other.raw.get(offset.unsigned_abs()..)?)
+                };
+                let overlap = a.len().min(b.len());
+                if overlap < MIN_OVERLAP {
+                    return None;
+                }
+                let errors = a
+                    .iter()
+                    .zip(b)
+                    .map(|(a, b)| (a ^ b).count_ones())
+                    .sum::<u32>();
+                Some(1.0 - errors as f64 / (overlap * 32) as f64)
+            })
+            .fold(0.0, f64::max)
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", compression::encode(&self.raw))
+    }
+}
+
+impl FromStr for Fingerprint {
+    type Err = FingerprintError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let raw = compression::decode(s)?;
+        if raw.is_empty() {
+            return Err(FingerprintError::Invalid);
+        }
+        Ok(Self { raw })
+    }
+}
+
+impl TryFrom<String> for Fingerprint {
+    type Error = FingerprintError;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Fingerprint> for String {
+    fn from(value: Fingerprint) -> Self {
+        value.to_string()
+    }
+}
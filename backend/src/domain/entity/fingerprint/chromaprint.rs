@@ -0,0 +1,290 @@
+use std::f64::consts::PI;
+
+/// Sample rate audio must be decoded to before computing a fingerprint.
+pub const SAMPLE_RATE: u32 = 11025;
+
+const FRAME_SIZE: usize = 4096;
+/// Frames overlap by two thirds.
+const FRAME_STEP: usize = FRAME_SIZE / 3;
+const MIN_FREQUENCY: f64 = 28.0;
+const MAX_FREQUENCY: f64 = 3520.0;
+const BANDS: usize = 12;
+const CHROMA_FILTER: [f64; 5] = [0.25, 0.75, 1.0, 0.75, 0.25];
+const NORMALIZATION_THRESHOLD: f64 = 0.01;
+const MAX_FILTER_WIDTH: usize = 16;
+
+/// Filter type, first band (y), band count (height) and row count (width).
+type Filter = (u8, usize, usize, usize);
+
+/// Filters and quantization thresholds of the classifiers of the default Chromaprint
+/// algorithm (`TEST2`).
+const CLASSIFIERS: [(Filter, [f64; 3]); 16] = [
+    ((0, 4, 3, 15), [1.98215, 2.35817, 2.63523]),
+    ((4, 4, 6, 15), [-1.03809, -0.651211, -0.282167]),
+    ((1, 0, 4, 16), [-0.298702, 0.119262, 0.558497]),
+    ((3, 8, 2, 12), [-0.105439, 0.0153946, 0.135898]),
+    ((3, 4, 4, 8), [-0.142891, 0.0258736, 0.200632]),
+    ((4, 0, 3, 5), [-0.826319, -0.590612, -0.368214]),
+    ((1, 2, 2, 9), [-0.557409, -0.233035, 0.0534525]),
+    ((2, 7, 3, 4), [-0.0646826, 0.00620476, 0.0784847]),
+    ((2, 6, 2, 16), [-0.192387, -0.029699, 0.215855]),
+    ((2, 1, 3, 2), [-0.0397818, -0.00568076, 0.0292026]),
+    ((5, 10, 1, 15), [-0.53823, -0.369934, -0.190235]),
+    ((3, 6, 2, 10), [-0.124877, 0.0296483, 0.139239]),
+    ((2, 1, 1, 14), [-0.101475, 0.0225617, 0.231971]),
+    ((3, 5, 6, 4), [-0.0799915, -0.00729616, 0.063262]),
+    ((1, 9, 2, 12), [-0.272556, 0.019424, 0.302559]),
+    ((3, 4, 2, 14), [-0.164292, -0.0321188, 0.0846339]),
+];
+
+/// Computes the raw Chromaprint fingerprint of mono samples at [`SAMPLE_RATE`].
+pub fn compute(samples: &[i16]) -> Vec<u32> {
+    let chroma = chroma(samples);
+    let image = IntegralImage::new(normalize(filter(chroma)));
+    if image.rows < MAX_FILTER_WIDTH {
+        return Vec::new();
+    }
+    (0..=image.rows - MAX_FILTER_WIDTH)
+        .map(|offset| {
+            CLASSIFIERS.iter().fold(0, |bits, (filter, thresholds)| {
+                let value = apply_filter(&image, offset, *filter);
+                (bits << 2) | gray_code(quantize(value, thresholds))
+            })
+        })
+        .collect()
+}
+
+/// Energy of each of the 12 pitch classes, frame by frame.
+fn chroma(samples: &[i16]) -> Vec<[f64; BANDS]> {
+    let window = (0..FRAME_SIZE)
+        .map(|i| {
+            (0.54 - 0.46 * (2.0 * PI * i as f64 / (FRAME_SIZE - 1) as f64).cos()) / i16::MAX as f64
+        })
+        .collect::<Vec<_>>();
+
+    let frequency_index =
+        |frequency: f64| (FRAME_SIZE as f64 * frequency / SAMPLE_RATE as f64).round() as usize;
+    let min_index = frequency_index(MIN_FREQUENCY).max(1);
+    let max_index = frequency_index(MAX_FREQUENCY).min(FRAME_SIZE / 2);
+    let notes = (min_index..max_index)
+        .map(|i| {
+            let frequency = i as f64 * SAMPLE_RATE as f64 / FRAME_SIZE as f64;
+            let octave = (frequency / (440.0 / 16.0)).log2();
+            (BANDS as f64 * (octave - octave.floor())) as usize
+        })
+        .collect::<Vec<_>>();
+
+    let fft = Fft::new(FRAME_SIZE);
+    let frame_count = if samples.len() < FRAME_SIZE {
+        0
+    } else {
+        (samples.len() - FRAME_SIZE) / FRAME_STEP + 1
+    };
+    (0..frame_count)
+        .map(|frame| {
+            let start = frame * FRAME_STEP;
+            let input = samples[start..start + FRAME_SIZE]
+                .iter()
+                .zip(&window)
+                .map(|(sample, window)| *sample as f64 * window)
+                .collect::<Vec<_>>();
+            let spectrum = fft.power_spectrum(&input);
+            let mut features = [0.0; BANDS];
+            for (i, note) in (min_index..max_index).zip(&notes) {
+                features[*note] += spectrum[i];
+            }
+            features
+        })
+        .collect()
+}
+
+fn filter(chroma: Vec<[f64; BANDS]>) -> Vec<[f64; BANDS]> {
+    chroma
+        .windows(CHROMA_FILTER.len())
+        .map(|frames| {
+            let mut features = [0.0; BANDS];
+            for (frame, coefficient) in frames.iter().zip(CHROMA_FILTER) {
+                for (feature, value) in features.iter_mut().zip(frame) {
+                    *feature += value * coefficient;
+                }
+            }
+            features
+        })
+        .collect()
+}
+
+fn normalize(chroma: Vec<[f64; BANDS]>) -> Vec<[f64; BANDS]> {
+    chroma
+        .into_iter()
+        .map(|mut features| {
+            let norm = features
+                .iter()
+                .map(|value| value * value)
+                .sum::<f64>()
+                .sqrt();
+            for feature in &mut features {
+                *feature = if norm < NORMALIZATION_THRESHOLD {
+                    0.0
+                } else {
+                    *feature / norm
+                };
+            }
+            features
+        })
+        .collect()
+}
+
+fn quantize(value: f64, thresholds: &[f64; 3]) -> u32 {
+    match value {
+        value if value < thresholds[0] => 0,
+        value if value < thresholds[1] => 1,
+        value if value < thresholds[2] => 2,
+        _ => 3,
+    }
+}
+
+fn gray_code(value: u32) -> u32 {
+    [0, 1, 3, 2][value as usize]
+}
+
+/// Applies a Haar-like filter covering rows `x..x + width` and bands `y..y + height`.
+fn apply_filter(image: &IntegralImage, x: usize, (kind, y, height, width): Filter) -> f64 {
+    let area = |x1, y1, x2, y2| image.area(x1, y1, x2, y2);
+    let (a, b) = match kind {
+        0 => (area(x, y, x + width, y + height), 0.0),
+        1 => {
+            let h = height / 2;
+            (
+                area(x, y + h, x + width, y + height),
+                area(x, y, x + width, y + h),
+            )
+        }
+        2 => {
+            let w = width / 2;
+            (
+                area(x + w, y, x + width, y + height),
+                area(x, y, x + w, y + height),
+            )
+        }
+        3 => {
+            let (w, h) = (width / 2, height / 2);
+            (
+                area(x, y + h, x + w, y + height) + area(x + w, y, x + width, y + h),
+                area(x, y, x + w, y + h) + area(x + w, y + h, x + width, y + height),
+            )
+        }
+        4 => {
+            let h = height / 3;
+            (
+                area(x, y + h, x + width, y + 2 * h),
+                area(x, y, x + width, y + h) + area(x, y + 2 * h, x + width, y + height),
+            )
+        }
+        _ => {
+            let w = width / 3;
+            (
+                area(x + w, y, x + 2 * w, y + height),
+                area(x, y, x + w, y + height) + area(x + 2 * w, y, x + width, y + height),
+            )
+        }
+    };
+    (1.0 + a).ln() - (1.0 + b).ln()
+}
+
+struct IntegralImage {
+    rows: usize,
+    sums: Vec<[f64; BANDS]>,
+}
+
+impl IntegralImage {
+    fn new(mut sums: Vec<[f64; BANDS]>) -> Self {
+        for row in 0..sums.len() {
+            for band in 0..BANDS {
+                let mut sum = sums[row][band];
+                if row > 0 {
+                    sum += sums[row - 1][band];
+                }
+                if band > 0 {
+                    sum += sums[row][band - 1];
+                }
+                if row > 0 && band > 0 {
+                    sum -= sums[row - 1][band - 1];
+                }
+                sums[row][band] = sum;
+            }
+        }
+        Self {
+            rows: sums.len(),
+            sums,
+        }
+    }
+
+    /// Sum of rows `r1..r2` and bands `c1..c2`.
+    fn area(&self, r1: usize, c1: usize, r2: usize, c2: usize) -> f64 {
+        if r1 == r2 || c1 == c2 {
+            return 0.0;
+        }
+        let at = |row: usize, band: usize| self.sums[row][band];
+        let mut area = at(r2 - 1, c2 - 1);
+        if r1 > 0 {
+            area -= at(r1 - 1, c2 - 1);
+        }
+        if c1 > 0 {
+            area -= at(r2 - 1, c1 - 1);
+        }
+        if r1 > 0 && c1 > 0 {
+            area += at(r1 - 1, c1 - 1);
+        }
+        area
+    }
+}
+
+/// Radix-2 FFT of real frames.
+struct Fft {
+    size: usize,
+    twiddles: Vec<(f64, f64)>,
+}
+
+impl Fft {
+    fn new(size: usize) -> Self {
+        let twiddles = (0..size / 2)
+            .map(|k| {
+                let angle = -2.0 * PI * k as f64 / size as f64;
+                (angle.cos(), angle.sin())
+            })
+            .collect();
+        Self { size, twiddles }
+    }
+
+    /// Squared magnitudes of bins `0..=size / 2`.
+    fn power_spectrum(&self, input: &[f64]) -> Vec<f64> {
+        let bits = self.size.trailing_zeros();
+        let mut re = vec![0.0; self.size];
+        let mut im = vec![0.0; self.size];
+        for (i, value) in input.iter().enumerate() {
+            re[i.reverse_bits() >> (usize::BITS - bits)] = *value;
+        }
+
+        let mut length = 2;
+        while length <= self.size {
+            let stride = self.size / length;
+            for start in (0..self.size).step_by(length) {
+                for k in 0..length / 2 {
+                    let (cos, sin) = self.twiddles[k * stride];
+                    let (even, odd) = (start + k, start + k + length / 2);
+                    let odd_re = re[odd] * cos - im[odd] * sin;
+                    let odd_im = re[odd] * sin + im[odd] * cos;
+                    re[odd] = re[even] - odd_re;
+                    im[odd] = im[even] - odd_im;
+                    re[even] += odd_re;
+                    im[even] += odd_im;
+                }
+            }
+            length *= 2;
+        }
+
+        (0..=self.size / 2)
+            .map(|i| re[i] * re[i] + im[i] * im[i])
+            .collect()
+    }
+}
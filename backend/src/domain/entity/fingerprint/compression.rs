@@ -0,0 +1,127 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+use super::FingerprintError;
+
+/// Identifier of the default Chromaprint algorithm.
+const ALGORITHM: u8 = 1;
+const MAX_NORMAL_VALUE: u8 = 7;
+
+/// Encodes a raw fingerprint the way `fpcalc` prints it: the bit positions changing between
+/// consecutive items, packed in 3 bits (5 more bits for large gaps), in URL-safe base64.
+pub fn encode(raw: &[u32]) -> String {
+    let mut gaps = Vec::new();
+    let mut previous = 0;
+    for item in raw {
+        let mut changed = item ^ previous;
+        let (mut bit, mut last_bit) = (1, 0);
+        while changed != 0 {
+            if changed & 1 != 0 {
+                gaps.push(bit - last_bit);
+                last_bit = bit;
+            }
+            changed >>= 1;
+            bit += 1;
+        }
+        gaps.push(0);
+        previous = *item;
+    }
+
+    let length = raw.len() as u32;
+    let mut bytes = vec![
+        ALGORITHM,
+        (length >> 16) as u8,
+        (length >> 8) as u8,
+        length as u8,
+    ];
+    let normal = gaps.iter().map(|gap| (*gap).min(MAX_NORMAL_VALUE));
+    bytes.extend(pack(normal, 3));
+    let exceptional = gaps
+        .iter()
+        .filter(|gap| **gap >= MAX_NORMAL_VALUE)
+        .map(|gap| gap - MAX_NORMAL_VALUE);
+    bytes.extend(pack(exceptional, 5));
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn decode(encoded: &str) -> Result<Vec<u32>, FingerprintError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(encoded.trim())
+        .map_err(|_| FingerprintError::Invalid)?;
+    if bytes.len() < 4 || bytes[0] != ALGORITHM {
+        return Err(FingerprintError::Invalid);
+    }
+    let length = u32::from_be_bytes([0, bytes[1], bytes[2], bytes[3]]) as usize;
+
+    let mut gaps = unpack(&bytes[4..], 3);
+    let mut items = 0;
+    let gap_count = gaps
+        .iter()
+        .position(|gap| {
+            items += usize::from(*gap == 0);
+            items == length
+        })
+        .map_or(0, |position| position + 1);
+    if items < length {
+        return Err(FingerprintError::Invalid);
+    }
+    gaps.truncate(gap_count);
+
+    let exceptional_offset = 4 + (gap_count * 3).div_ceil(8);
+    let mut exceptional =
+        unpack(bytes.get(exceptional_offset..).unwrap_or_default(), 5).into_iter();
+    for gap in gaps.iter_mut().filter(|gap| **gap == MAX_NORMAL_VALUE) {
+        *gap += exceptional.next().ok_or(FingerprintError::Invalid)?;
+    }
+
+    let mut raw = Vec::with_capacity(length);
+    let (mut changed, mut last_bit, mut previous) = (0u32, 0u32, 0u32);
+    for gap in gaps {
+        if gap == 0 {
+            previous ^= changed;
+            raw.push(previous);
+            changed = 0;
+            last_bit = 0;
+        } else {
+            last_bit += gap as u32;
+            if last_bit > 32 {
+                return Err(FingerprintError::Invalid);
+            }
+            changed |= 1 << (last_bit - 1);
+        }
+    }
+    Ok(raw)
+}
+
+/// Packs values of `width` bits, least significant bits first.
+fn pack(values: impl Iterator<Item = u8>, width: u32) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let (mut buffer, mut buffered) = (0u32, 0);
+    for value in values {
+        buffer |= (value as u32) << buffered;
+        buffered += width;
+        while buffered >= 8 {
+            bytes.push(buffer as u8);
+            buffer >>= 8;
+            buffered -= 8;
+        }
+    }
+    if buffered > 0 {
+        bytes.push(buffer as u8);
+    }
+    bytes
+}
+
+fn unpack(bytes: &[u8], width: u32) -> Vec<u8> {
+    let mut values = Vec::new();
+    let (mut buffer, mut buffered) = (0u32, 0);
+    for byte in bytes {
+        buffer |= (*byte as u32) << buffered;
+        buffered += 8;
+        while buffered >= width {
+            values.push((buffer & ((1 << width) - 1)) as u8);
+            buffer >>= width;
+            buffered -= width;
+        }
+    }
+    values
+}
@@ -0,0 +1,218 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Readers of audio metadata, chained so that each fills the fields the previous ones
+/// could not read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParserKind {
+    /// Tags read by the audiotags and id3 libraries.
+    Tags,
+    /// Tags and stream properties read by ffprobe.
+    Ffmpeg,
+    /// Fields inferred from the path of the file.
+    Path,
+}
+
+impl ParserKind {
+    /// Tag parsers first, with path inference last so it only fills fields missing from tags.
+    pub const DEFAULT_CHAIN: [Self; 3] = [Self::Tags, Self::Ffmpeg, Self::Path];
+}
+
+/// Extensions of the audio files gathered by default.
+pub const DEFAULT_EXTENSIONS: [&str; 22] = [
+    "aac", "aif", "aifc", "aiff", "alac", "ape", "dff", "dsf", "flac", "m4a", "m4b", "mka", "mp2",
+    "mp3", "mp4", "mpc", "oga", "ogg", "opus", "tta", "wav", "wv",
+];
+
+/// Name of the files listing what to leave out of a directory, with the syntax of
+/// `.gitignore` files.
+pub const IGNORE_FILE_NAME: &str = ".earrignore";
+
+/// How symbolic links met while scanning are treated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymlinkPolicy {
+    /// Links are left out.
+    #[default]
+    Skip,
+    /// Links are followed wherever they lead.
+    Follow,
+    /// Links are followed when they lead inside the root.
+    WithinRoot,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum LibraryRootError {
+    #[error("Library root name is empty")]
+    EmptyName,
+    #[error("Library root {0} is declared twice")]
+    DuplicateName(String),
+    #[error("Library root {0} has no parser")]
+    NoParser(String),
+    #[error("Library root {0} has a scan interval of zero")]
+    ZeroInterval(String),
+    #[error("Library roots {0} and {1} overlap, their audios would be gathered twice")]
+    Overlap(String, String),
+}
+
+/// A directory audios are gathered from, with how it is gathered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LibraryRoot {
+    /// Unique among roots, it is kept with the audios gathered from the root.
+    pub name: String,
+    pub path: PathBuf,
+    #[serde(default = "default_parsers")]
+    pub parsers: Vec<ParserKind>,
+    /// Patterns such as `{album_artist}/{album}/{track} - {title}` the path parser infers
+    /// fields with, the first matching one being used. Built-in ones are used when empty.
+    #[serde(default)]
+    pub path_patterns: Vec<String>,
+    /// Lowercase extensions of the files gathered, any file is when empty.
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+    /// Globs of the files gathered relative to the root, any file is when empty.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Globs of the directories and files left out, relative to the root.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Whether the [`IGNORE_FILE_NAME`] files of the directories are honored.
+    #[serde(default = "enabled")]
+    pub ignore_files: bool,
+    /// Whether directories and files whose name starts with a dot are left out.
+    #[serde(default = "enabled")]
+    pub skip_hidden: bool,
+    /// How deep directories are scanned, the files right in the root being at depth 1.
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    #[serde(default)]
+    pub symlinks: SymlinkPolicy,
+    /// How often the root is scanned on its own, in seconds. It is only scanned along with
+    /// the others when not set.
+    #[serde(default)]
+    pub scan_interval_secs: Option<u64>,
+}
+
+fn default_parsers() -> Vec<ParserKind> {
+    ParserKind::DEFAULT_CHAIN.to_vec()
+}
+
+fn default_extensions() -> Vec<String> {
+    DEFAULT_EXTENSIONS
+        .iter()
+        .map(|extension| extension.to_string())
+        .collect()
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl LibraryRoot {
+    /// A root with the default parsers and scan rules, and no schedule.
+    pub fn new(name: &str, path: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.to_string(),
+            path: path.into(),
+            parsers: default_parsers(),
+            path_patterns: Vec::new(),
+            extensions: default_extensions(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            ignore_files: true,
+            skip_hidden: true,
+            max_depth: None,
+            symlinks: SymlinkPolicy::default(),
+            scan_interval_secs: None,
+        }
+    }
+
+    /// A root named after its directory.
+    pub fn from_path(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+        Self::new(&name, path)
+    }
+
+    pub fn scan_interval(&self) -> Option<Duration> {
+        self.scan_interval_secs.map(Duration::from_secs)
+    }
+
+    /// Checks the roots are usable together.
+    pub fn validate_all(roots: &[Self]) -> Result<(), LibraryRootError> {
+        let mut names = HashSet::new();
+        for root in roots {
+            if root.name.trim().is_empty() {
+                return Err(LibraryRootError::EmptyName);
+            }
+            if !names.insert(root.name.as_str()) {
+                return Err(LibraryRootError::DuplicateName(root.name.clone()));
+            }
+            if root.parsers.is_empty() {
+                return Err(LibraryRootError::NoParser(root.name.clone()));
+            }
+            if root.scan_interval_secs == Some(0) {
+                return Err(LibraryRootError::ZeroInterval(root.name.clone()));
+            }
+        }
+        Self::check_overlaps(roots.iter().map(|root| (root, root.path.as_path())))
+    }
+
+    /// Checks that no root is inside another, comparing the paths given for each root
+    /// component by component.
+    pub fn check_overlaps<'a>(
+        roots: impl IntoIterator<Item = (&'a Self, &'a Path)>,
+    ) -> Result<(), LibraryRootError> {
+        let roots = roots.into_iter().collect::<Vec<_>>();
+        for (index, (root, path)) in roots.iter().enumerate() {
+            for (other, other_path) in &roots[index + 1..] {
+                if path.starts_with(other_path) || other_path.starts_with(path) {
+                    return Err(LibraryRootError::Overlap(
+                        root.name.clone(),
+                        other.name.clone(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_overlapping_roots() {
+        let overlap = |first: &str, second: &str| {
+            LibraryRoot::validate_all(&[
+                LibraryRoot::new("first", first),
+                LibraryRoot::new("second", second),
+            ])
+        };
+        assert!(overlap("/music/rock", "/music/jazz").is_ok());
+        assert!(overlap("/music/rock", "/music/rockabilly").is_ok());
+        for (first, second) in [
+            ("/music", "/music"),
+            ("/music", "/music/"),
+            ("/music", "/music/rock"),
+            ("/music/rock", "/music"),
+        ] {
+            assert!(matches!(
+                overlap(first, second),
+                Err(LibraryRootError::Overlap(..))
+            ));
+        }
+    }
+}
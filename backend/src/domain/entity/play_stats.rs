@@ -0,0 +1,13 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::activity::Rating;
+
+/// How much a track has been listened to and how it was rated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct PlayStats {
+    pub play_count: u32,
+    pub last_played: Option<DateTime<Utc>>,
+    pub rating: Option<Rating>,
+    pub favorited_at: Option<DateTime<Utc>>,
+}
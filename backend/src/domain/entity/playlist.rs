@@ -0,0 +1,393 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use percent_encoding::percent_decode_str;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use super::audio::hex_prefix;
+
+mod m3u;
+mod pls;
+mod xspf;
+
+/// File formats playlists are read from and written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaylistFormat {
+    /// Extended M3U.
+    M3u,
+    /// Extended M3U, always in UTF-8.
+    M3u8,
+    Pls,
+    Xspf,
+}
+
+impl PlaylistFormat {
+    pub const ALL: [Self; 4] = [Self::M3u, Self::M3u8, Self::Pls, Self::Xspf];
+
+    /// The format matching the extension of the path, if any.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        Self::ALL
+            .into_iter()
+            .find(|format| format.extension().eq_ignore_ascii_case(extension))
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::M3u => "m3u",
+            Self::M3u8 => "m3u8",
+            Self::Pls => "pls",
+            Self::Xspf => "xspf",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::M3u | Self::M3u8 => "audio/x-mpegurl",
+            Self::Pls => "audio/x-scpls",
+            Self::Xspf => "application/xspf+xml",
+        }
+    }
+}
+
+impl Display for PlaylistFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+/// An entry of a playlist, with the metadata the file gives for it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PlaylistEntry {
+    /// Path, absolute or relative to the playlist, or URL of a remote stream. Paths use
+    /// the separators they were written with.
+    pub location: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+impl PlaylistEntry {
+    pub fn new(location: &str) -> Self {
+        Self {
+            location: location.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Whether the location is a URL other than a `file:` one.
+    pub fn is_remote(&self) -> bool {
+        self.location.split_once("://").is_some_and(|(scheme, _)| {
+            !scheme.eq_ignore_ascii_case("file")
+                && scheme.len() > 1
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '+')
+        })
+    }
+
+    /// The path of the entry with `/` separators, decoding `file:` URIs. `None` for remote
+    /// entries.
+    pub fn path(&self) -> Option<PathBuf> {
+        if self.is_remote() {
+            return None;
+        }
+        let location = file_uri_path(&self.location).unwrap_or_else(|| self.location.clone());
+        Some(PathBuf::from(location.replace('\\', "/")))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Playlist {
+    pub name: String,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+#[derive(Debug, Error)]
+pub enum PlaylistError {
+    #[error("Invalid XSPF playlist: {0}")]
+    Xspf(String),
+}
+
+impl Playlist {
+    pub fn new(name: &str, entries: Vec<PlaylistEntry>) -> Self {
+        Self {
+            name: name.to_string(),
+            entries,
+        }
+    }
+
+    /// Parses a playlist file. `name` is used when the file does not name the playlist.
+    pub fn parse(data: &[u8], format: PlaylistFormat, name: &str) -> Result<Self, PlaylistError> {
+        let text = decode_text(data);
+        let mut playlist = match format {
+            PlaylistFormat::M3u | PlaylistFormat::M3u8 => m3u::parse(&text),
+            PlaylistFormat::Pls => pls::parse(&text),
+            PlaylistFormat::Xspf => xspf::parse(&text)?,
+        };
+        if playlist.name.trim().is_empty() {
+            playlist.name = name.to_string();
+        }
+        Ok(playlist)
+    }
+
+    /// Writes the playlist as UTF-8, whatever the format.
+    pub fn render(&self, format: PlaylistFormat) -> String {
+        match format {
+            PlaylistFormat::M3u | PlaylistFormat::M3u8 => m3u::render(self),
+            PlaylistFormat::Pls => pls::render(self),
+            PlaylistFormat::Xspf => xspf::render(self),
+        }
+    }
+
+    /// Total of the known entry durations.
+    pub fn duration(&self) -> Duration {
+        self.entries.iter().filter_map(|entry| entry.duration).sum()
+    }
+}
+
+/// Stable identifier derived from the path of a playlist file.
+pub fn playlist_id(path: &Path) -> String {
+    let hash = Sha256::digest(path.as_os_str().as_encoded_bytes());
+    hex_prefix(&hash)
+}
+
+/// The decoded path of a `file:` URI.
+fn file_uri_path(uri: &str) -> Option<String> {
+    let path = uri
+        .get(..7)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("file://"))
+        .map(|_| &uri[7..])?;
+    // Skips the host, usually empty or `localhost`
+    let path = &path[path.find('/').unwrap_or(path.len())..];
+    // `file:///C:/Music` is a Windows path
+    let path = match path.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => &path[1..],
+        _ => path,
+    };
+    Some(percent_decode_str(path).decode_utf8_lossy().into_owned())
+}
+
+/// Strips the BOM, and falls back to Latin-1 for text that is not valid UTF-8, which older
+/// players write in plain `.m3u` and `.pls` files.
+fn decode_text(data: &[u8]) -> String {
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    match std::str::from_utf8(data) {
+        Ok(text) => text.to_string(),
+        Err(_) => data.iter().map(|&byte| byte as char).collect(),
+    }
+}
+
+/// Values must not break the lines that structure the text formats.
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(location: &str, title: &str, artist: &str, seconds: u64) -> PlaylistEntry {
+        PlaylistEntry {
+            title: Some(title.to_string()),
+            artist: Some(artist.to_string()),
+            duration: Some(Duration::from_secs(seconds)),
+            ..PlaylistEntry::new(location)
+        }
+    }
+
+    fn parse(text: &str, format: PlaylistFormat) -> Playlist {
+        Playlist::parse(text.as_bytes(), format, "Fallback").unwrap()
+    }
+
+    #[test]
+    fn format_from_path() {
+        let format = |path: &str| PlaylistFormat::from_path(Path::new(path));
+        assert_eq!(format("Mix.M3U8"), Some(PlaylistFormat::M3u8));
+        assert_eq!(format("mix.pls"), Some(PlaylistFormat::Pls));
+        assert_eq!(format("mix.xspf"), Some(PlaylistFormat::Xspf));
+        assert_eq!(format("mix.txt"), None);
+        assert_eq!(format("mix"), None);
+    }
+
+    #[test]
+    fn entry_locations() {
+        assert!(PlaylistEntry::new("http://radio.example/stream").is_remote());
+        assert!(!PlaylistEntry::new("file:///music/a.flac").is_remote());
+        assert!(!PlaylistEntry::new("C:\\Music\\a.mp3").is_remote());
+        assert_eq!(PlaylistEntry::new("https://radio.example").path(), None);
+        assert_eq!(
+            PlaylistEntry::new("file:///music/Some%20Song.flac").path(),
+            Some(PathBuf::from("/music/Some Song.flac"))
+        );
+        assert_eq!(
+            PlaylistEntry::new("file:///C:/Music/a.mp3").path(),
+            Some(PathBuf::from("C:/Music/a.mp3"))
+        );
+        assert_eq!(
+            PlaylistEntry::new("Artist\\Album\\a.mp3").path(),
+            Some(PathBuf::from("Artist/Album/a.mp3"))
+        );
+    }
+
+    #[test]
+    fn parses_extended_m3u() {
+        let playlist = parse(
+            "\u{feff}#EXTM3U\n#PLAYLIST:Road Trip\n\n#EXTINF:215,Artist - Song\n#EXTALB:Album\n\
+             a.mp3\n#EXTINF:-1,Untitled\nhttp://radio.example/stream\nplain.flac\n",
+            PlaylistFormat::M3u,
+        );
+        assert_eq!(playlist.name, "Road Trip");
+        assert_eq!(
+            playlist.entries,
+            vec![
+                PlaylistEntry {
+                    album: Some("Album".to_string()),
+                    ..entry("a.mp3", "Song", "Artist", 215)
+                },
+                PlaylistEntry {
+                    title: Some("Untitled".to_string()),
+                    ..PlaylistEntry::new("http://radio.example/stream")
+                },
+                PlaylistEntry::new("plain.flac"),
+            ]
+        );
+        assert_eq!(playlist.duration(), Duration::from_secs(215));
+    }
+
+    #[test]
+    fn falls_back_to_latin1_and_given_name() {
+        let playlist = Playlist::parse(b"caf\xe9.mp3\n", PlaylistFormat::M3u, "Fallback").unwrap();
+        assert_eq!(playlist.name, "Fallback");
+        assert_eq!(playlist.entries, vec![PlaylistEntry::new("café.mp3")]);
+    }
+
+    #[test]
+    fn renders_extended_m3u() {
+        let playlist = Playlist::new(
+            "Road\nTrip",
+            vec![
+                PlaylistEntry {
+                    album: Some("Album".to_string()),
+                    ..entry("a.mp3", "Song", "Artist", 215)
+                },
+                PlaylistEntry::new("b.mp3"),
+            ],
+        );
+        let text = playlist.render(PlaylistFormat::M3u8);
+        assert_eq!(
+            text,
+            "#EXTM3U\n#PLAYLIST:Road Trip\n#EXTINF:215,Artist - Song\n#EXTALB:Album\na.mp3\nb.mp3\n"
+        );
+        assert_eq!(parse(&text, PlaylistFormat::M3u8).entries, playlist.entries);
+    }
+
+    #[test]
+    fn parses_pls_by_entry_number() {
+        let playlist = parse(
+            "[playlist]\nX-GNOME-Title=Radio\nFile2=b.mp3\nTitle2=Second\nLength2=-1\n\
+             File1=a.mp3\nTitle1=First\nLength1=180\nTitle3=No file\nNumberOfEntries=3\n",
+            PlaylistFormat::Pls,
+        );
+        assert_eq!(playlist.name, "Radio");
+        assert_eq!(
+            playlist.entries,
+            vec![
+                PlaylistEntry {
+                    title: Some("First".to_string()),
+                    duration: Some(Duration::from_secs(180)),
+                    ..PlaylistEntry::new("a.mp3")
+                },
+                PlaylistEntry {
+                    title: Some("Second".to_string()),
+                    ..PlaylistEntry::new("b.mp3")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn renders_pls() {
+        let playlist = Playlist::new(
+            "",
+            vec![
+                entry("a.mp3", "Song", "Artist", 180),
+                PlaylistEntry::new("b.mp3"),
+            ],
+        );
+        assert_eq!(
+            playlist.render(PlaylistFormat::Pls),
+            "[playlist]\nFile1=a.mp3\nTitle1=Artist - Song\nLength1=180\n\
+             File2=b.mp3\nLength2=-1\nNumberOfEntries=2\nVersion=2\n"
+        );
+    }
+
+    #[test]
+    fn parses_xspf() {
+        let playlist = parse(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<playlist version="1" xmlns="http://xspf.org/ns/0/">
+  <title>Mix &amp; Match</title>
+  <trackList>
+    <track>
+      <location>file:///music/Some%20Song.flac</location>
+      <title>Song</title>
+      <creator>Artist</creator>
+      <album><![CDATA[Album]]></album>
+      <duration>215000</duration>
+    </track>
+    <track><location>http://radio.example/stream</location></track>
+  </trackList>
+</playlist>"#,
+            PlaylistFormat::Xspf,
+        );
+        assert_eq!(playlist.name, "Mix & Match");
+        assert_eq!(
+            playlist.entries,
+            vec![
+                PlaylistEntry {
+                    album: Some("Album".to_string()),
+                    ..entry("/music/Some Song.flac", "Song", "Artist", 215)
+                },
+                PlaylistEntry::new("http://radio.example/stream"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_xspf() {
+        let result = Playlist::parse(
+            b"<playlist><trackList></playlist>",
+            PlaylistFormat::Xspf,
+            "Fallback",
+        );
+        assert!(matches!(result, Err(PlaylistError::Xspf(_))));
+    }
+
+    #[test]
+    fn xspf_round_trip() {
+        let playlist = Playlist::new(
+            "Mix & Match",
+            vec![
+                PlaylistEntry {
+                    album: Some("<Album>".to_string()),
+                    ..entry("/music/Some Song #1.flac", "Song", "Artist", 215)
+                },
+                PlaylistEntry::new("relative/b.mp3"),
+                PlaylistEntry::new("http://radio.example/stream?id=1&format=mp3"),
+            ],
+        );
+        let xml = playlist.render(PlaylistFormat::Xspf);
+        assert!(xml.contains("<location>file:///music/Some%20Song%20%231.flac</location>"));
+        assert!(xml.contains("<album>&lt;Album&gt;</album>"));
+        assert_eq!(parse(&xml, PlaylistFormat::Xspf), playlist);
+    }
+}
@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use super::{single_line, Playlist, PlaylistEntry};
+
+/// Parses extended M3U. Plain M3U is a subset of it, with only locations.
+pub(super) fn parse(text: &str) -> Playlist {
+    let mut playlist = Playlist::default();
+    // Metadata of the next entry
+    let mut info: Option<PlaylistEntry> = None;
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            let mut entry = parse_extinf(extinf);
+            entry.album = info.take().and_then(|info| info.album);
+            info = Some(entry);
+        } else if let Some(name) = line.strip_prefix("#PLAYLIST:") {
+            playlist.name = name.trim().to_string();
+        } else if let Some(album) = line.strip_prefix("#EXTALB:") {
+            info.get_or_insert_with(PlaylistEntry::default).album = Some(album.trim().to_string());
+        } else if !line.starts_with('#') {
+            playlist.entries.push(PlaylistEntry {
+                location: line.to_string(),
+                ..info.take().unwrap_or_default()
+            });
+        }
+    }
+    playlist
+}
+
+/// `#EXTINF:<seconds> [attributes],<artist> - <title>`, the duration being -1 when unknown.
+fn parse_extinf(extinf: &str) -> PlaylistEntry {
+    let (duration, display) = extinf.split_once(',').unwrap_or((extinf, ""));
+    let duration = duration
+        .split_whitespace()
+        .next()
+        .and_then(|duration| duration.parse::<f64>().ok())
+        .filter(|duration| duration.is_finite() && *duration > 0.0)
+        .map(Duration::from_secs_f64);
+    let display = display.trim();
+    let (artist, title) = match display.split_once(" - ") {
+        Some((artist, title)) => (Some(artist.trim()), title.trim()),
+        None => (None, display),
+    };
+    PlaylistEntry {
+        title: Some(title.to_string()).filter(|title| !title.is_empty()),
+        artist: artist
+            .filter(|artist| !artist.is_empty())
+            .map(str::to_string),
+        duration,
+        ..PlaylistEntry::default()
+    }
+}
+
+pub(super) fn render(playlist: &Playlist) -> String {
+    let mut text = String::from("#EXTM3U\n");
+    if !playlist.name.is_empty() {
+        text.push_str(&format!("#PLAYLIST:{}\n", single_line(&playlist.name)));
+    }
+    for entry in &playlist.entries {
+        if entry.title.is_some() || entry.artist.is_some() || entry.duration.is_some() {
+            let duration = entry
+                .duration
+                .map(|duration| duration.as_secs_f64().round() as i64)
+                .unwrap_or(-1);
+            let display = match (&entry.artist, &entry.title) {
+                (Some(artist), Some(title)) => format!("{artist} - {title}"),
+                (Some(artist), None) => format!("{artist} - "),
+                (None, title) => title.clone().unwrap_or_default(),
+            };
+            text.push_str(&format!("#EXTINF:{duration},{}\n", single_line(&display)));
+        }
+        if let Some(album) = &entry.album {
+            text.push_str(&format!("#EXTALB:{}\n", single_line(album)));
+        }
+        text.push_str(&single_line(&entry.location));
+        text.push('\n');
+    }
+    text
+}
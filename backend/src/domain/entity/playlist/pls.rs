@@ -0,0 +1,72 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use super::{single_line, Playlist, PlaylistEntry};
+
+/// Parses the `[playlist]` section of a PLS file. Entries are numbered by their keys, so
+/// they are sorted by number rather than by position, and numbers without a `File` key are
+/// ignored.
+pub(super) fn parse(text: &str) -> Playlist {
+    let mut entries = BTreeMap::<u32, PlaylistEntry>::new();
+    let mut name = String::new();
+    for line in text.lines().map(str::trim) {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+        if key == "x-gnome-title" || key == "title" {
+            name = value.to_string();
+            continue;
+        }
+        let Some((field, number)) = ["file", "title", "length"].into_iter().find_map(|field| {
+            let number = key.strip_prefix(field)?.parse::<u32>().ok()?;
+            Some((field, number))
+        }) else {
+            continue;
+        };
+        let entry = entries.entry(number).or_default();
+        match field {
+            "file" => entry.location = value.to_string(),
+            "title" => entry.title = Some(value.to_string()).filter(|title| !title.is_empty()),
+            _ => {
+                entry.duration = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|length| length.is_finite() && *length > 0.0)
+                    .map(Duration::from_secs_f64)
+            }
+        }
+    }
+    Playlist {
+        name,
+        entries: entries
+            .into_values()
+            .filter(|entry| !entry.location.is_empty())
+            .collect(),
+    }
+}
+
+pub(super) fn render(playlist: &Playlist) -> String {
+    let mut text = String::from("[playlist]\n");
+    for (number, entry) in (1..).zip(&playlist.entries) {
+        text.push_str(&format!("File{number}={}\n", single_line(&entry.location)));
+        let title = match (&entry.artist, &entry.title) {
+            (Some(artist), Some(title)) => Some(format!("{artist} - {title}")),
+            (None, Some(title)) => Some(title.clone()),
+            (_, None) => None,
+        };
+        if let Some(title) = title {
+            text.push_str(&format!("Title{number}={}\n", single_line(&title)));
+        }
+        let length = entry
+            .duration
+            .map(|duration| duration.as_secs_f64().round() as i64)
+            .unwrap_or(-1);
+        text.push_str(&format!("Length{number}={length}\n"));
+    }
+    text.push_str(&format!(
+        "NumberOfEntries={}\nVersion=2\n",
+        playlist.entries.len()
+    ));
+    text
+}
@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use quick_xml::{escape::escape, escape::resolve_predefined_entity, events::Event, Reader};
+
+use super::{file_uri_path, Playlist, PlaylistEntry, PlaylistError};
+
+/// Characters escaped in the paths of `location` URIs. Slashes are kept as separators.
+const PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b']')
+    .add(b'\\')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// Parses the `trackList` and `title` of an XSPF playlist. Other elements, such as
+/// extensions and metadata links, are ignored.
+pub(super) fn parse(text: &str) -> Result<Playlist, PlaylistError> {
+    let mut reader = Reader::from_str(text);
+    let mut playlist = Playlist::default();
+    // Local names of the open elements
+    let mut elements = Vec::<String>::new();
+    let mut value = String::new();
+    let mut entry: Option<PlaylistEntry> = None;
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|err| PlaylistError::Xspf(err.to_string()))?;
+        match event {
+            Event::Start(start) => {
+                let name = String::from_utf8_lossy(start.local_name().as_ref()).into_owned();
+                if name == "track" && elements.last().is_some_and(|parent| parent == "trackList") {
+                    entry = Some(PlaylistEntry::default());
+                }
+                elements.push(name);
+                value.clear();
+            }
+            Event::Text(text) => value.push_str(
+                &text
+                    .xml_content()
+                    .map_err(|err| PlaylistError::Xspf(err.to_string()))?,
+            ),
+            Event::CData(data) => value.push_str(
+                &data
+                    .xml_content()
+                    .map_err(|err| PlaylistError::Xspf(err.to_string()))?,
+            ),
+            Event::GeneralRef(reference) => {
+                let character = reference
+                    .resolve_char_ref()
+                    .map_err(|err| PlaylistError::Xspf(err.to_string()))?;
+                match character {
+                    Some(character) => value.push(character),
+                    None => {
+                        let name = reference
+                            .decode()
+                            .map_err(|err| PlaylistError::Xspf(err.to_string()))?;
+                        let resolved = resolve_predefined_entity(&name).ok_or_else(|| {
+                            PlaylistError::Xspf(format!("Unknown entity &{name};"))
+                        })?;
+                        value.push_str(resolved);
+                    }
+                }
+            }
+            Event::End(_) => {
+                let name = elements.pop().unwrap_or_default();
+                let parent = elements.last().map(String::as_str);
+                let text = value.trim();
+                match (parent, name.as_str(), &mut entry) {
+                    (Some("playlist"), "title", _) => playlist.name = text.to_string(),
+                    (Some("trackList"), "track", entry) => {
+                        if let Some(entry) = entry.take().filter(|entry| !entry.location.is_empty())
+                        {
+                            playlist.entries.push(entry);
+                        }
+                    }
+                    // Only the first location is used, the others being alternatives
+                    (Some("track"), "location", Some(entry)) if entry.location.is_empty() => {
+                        entry.location = uri_to_location(text)
+                    }
+                    (Some("track"), "title", Some(entry)) => entry.title = non_empty(text),
+                    (Some("track"), "creator", Some(entry)) => entry.artist = non_empty(text),
+                    (Some("track"), "album", Some(entry)) => entry.album = non_empty(text),
+                    (Some("track"), "duration", Some(entry)) => {
+                        entry.duration = text.parse().ok().map(Duration::from_millis)
+                    }
+                    _ => {}
+                }
+                value.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if !elements.is_empty() {
+        return Err(PlaylistError::Xspf(
+            "Unexpected end of document".to_string(),
+        ));
+    }
+    Ok(playlist)
+}
+
+pub(super) fn render(playlist: &Playlist) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n",
+    );
+    if !playlist.name.is_empty() {
+        xml.push_str(&format!("  <title>{}</title>\n", escape(&playlist.name)));
+    }
+    xml.push_str("  <trackList>\n");
+    for entry in &playlist.entries {
+        xml.push_str("    <track>\n");
+        xml.push_str(&format!(
+            "      <location>{}</location>\n",
+            escape(location_to_uri(entry))
+        ));
+        let elements = [
+            ("title", entry.title.clone()),
+            ("creator", entry.artist.clone()),
+            ("album", entry.album.clone()),
+            (
+                "duration",
+                entry
+                    .duration
+                    .map(|duration| duration.as_millis().to_string()),
+            ),
+        ];
+        for (name, value) in elements {
+            if let Some(value) = value {
+                xml.push_str(&format!("      <{name}>{}</{name}>\n", escape(&value)));
+            }
+        }
+        xml.push_str("    </track>\n");
+    }
+    xml.push_str("  </trackList>\n</playlist>\n");
+    xml
+}
+
+/// Turns `file:` URIs and relative references into paths. Other URLs are kept as they are.
+fn uri_to_location(uri: &str) -> String {
+    let entry = PlaylistEntry::new(uri);
+    if entry.is_remote() {
+        return entry.location;
+    }
+    file_uri_path(uri).unwrap_or_else(|| percent_decode_str(uri).decode_utf8_lossy().into_owned())
+}
+
+fn location_to_uri(entry: &PlaylistEntry) -> String {
+    let Some(path) = entry.path() else {
+        return entry.location.clone();
+    };
+    let path = path.to_string_lossy();
+    let encoded = utf8_percent_encode(&path, PATH).to_string();
+    match path.as_bytes() {
+        [b'/', ..] => format!("file://{encoded}"),
+        [drive, b':', ..] if drive.is_ascii_alphabetic() => format!("file:///{encoded}"),
+        _ => encoded,
+    }
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    Some(text.to_string()).filter(|text| !text.is_empty())
+}
@@ -0,0 +1,227 @@
+use std::{fmt, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::audio::{title::Title, Audio};
+
+pub const DEFAULT_LISTENBRAINZ_URL: &str = "https://api.listenbrainz.org";
+pub const DEFAULT_LAST_FM_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+
+/// Tracks shorter than this are never scrobbled.
+const MIN_DURATION: Duration = Duration::from_secs(30);
+/// Tracks listened to for this long are scrobbled, even if it is less than half of them.
+const MIN_LISTENED: Duration = Duration::from_secs(240);
+
+/// The protocol of a scrobbling service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ScrobblerKind {
+    /// The ListenBrainz API, also served by Maloja and other self-hosted services.
+    #[serde(rename = "listenbrainz")]
+    ListenBrainz,
+    /// The Last.fm API 2.0, also served by Libre.fm and other compatible services.
+    #[serde(rename = "lastfm")]
+    LastFm,
+}
+
+impl ScrobblerKind {
+    pub const ALL: [Self; 2] = [Self::ListenBrainz, Self::LastFm];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ListenBrainz => "listenbrainz",
+            Self::LastFm => "lastfm",
+        }
+    }
+}
+
+impl fmt::Display for ScrobblerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The credentials of a user on a scrobbling service. `url` is the root of the API, which
+/// is the public service by default.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum ScrobblerAccount {
+    #[serde(rename = "listenbrainz")]
+    ListenBrainz { url: String, token: String },
+    #[serde(rename = "lastfm")]
+    LastFm {
+        url: String,
+        api_key: String,
+        api_secret: String,
+        /// Obtained by logging in with the API key, see `auth.getMobileSession`.
+        session_key: String,
+    },
+}
+
+impl ScrobblerAccount {
+    pub fn kind(&self) -> ScrobblerKind {
+        match self {
+            Self::ListenBrainz { .. } => ScrobblerKind::ListenBrainz,
+            Self::LastFm { .. } => ScrobblerKind::LastFm,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        match self {
+            Self::ListenBrainz { url, .. } | Self::LastFm { url, .. } => url,
+        }
+    }
+}
+
+/// A listening of a track as sent to scrobbling services. It holds the metadata itself so
+/// that queued listens are still sent once the track left the library.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Listen {
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub track_number: Option<u16>,
+    pub duration: Option<Duration>,
+    pub recording_mbid: Option<String>,
+    pub listened_at: DateTime<Utc>,
+}
+
+impl Listen {
+    /// Unknown album fields are left out, while unknown titles are sent as such since
+    /// services require them. The album artist stands in for an unknown artist.
+    pub fn new(audio: &Audio, listened_at: DateTime<Utc>) -> Self {
+        let known_album = *audio.album_title() != Title::default();
+        let artist = if audio.artist().is_unknown() {
+            audio.album_artist()
+        } else {
+            audio.artist()
+        };
+        Self {
+            artist: artist.name().clone(),
+            title: audio.title().name().clone(),
+            album: known_album.then(|| audio.album_title().name().clone()),
+            album_artist: (known_album && !audio.album_artist().is_unknown())
+                .then(|| audio.album_artist().name().clone()),
+            track_number: *audio.track_number(),
+            duration: *audio.duration(),
+            recording_mbid: audio.musicbrainz_recording_id().clone(),
+            listened_at,
+        }
+    }
+
+    /// Whether the listen counts as a scrobble, following the Last.fm rules: the track lasts
+    /// at least 30 seconds and was listened to for half of it or 4 minutes. Listens of
+    /// unknown length count.
+    pub fn counts(&self, listened: Option<Duration>) -> bool {
+        let Some(duration) = self.duration else {
+            return true;
+        };
+        if duration < MIN_DURATION {
+            return false;
+        }
+        listened.is_none_or(|listened| listened >= (duration / 2).min(MIN_LISTENED))
+    }
+}
+
+/// A listen waiting to be submitted to a service of a user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedListen {
+    pub id: i64,
+    pub user_id: String,
+    pub kind: ScrobblerKind,
+    pub listen: Listen,
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum ScrobbleError {
+    #[error("Scrobbling service is unavailable: {0}")]
+    Unavailable(String),
+    #[error("Scrobbling service refused the credentials: {0}")]
+    Unauthorized(String),
+    #[error("Scrobbling service rejected the listens: {0}")]
+    Rejected(String),
+}
+
+impl ScrobbleError {
+    /// Whether sending again later may succeed by itself. Refused credentials need the user
+    /// to connect again, while rejected listens are rejected again.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Unavailable(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use chrono::TimeZone;
+
+    use crate::domain::entity::audio::{artist::Artist, cover::Cover, genre::Genre, AudioBuilder};
+
+    use super::*;
+
+    /// Empty names stand for unknown ones.
+    fn audio(artist: &str, album: &str, album_artist: &str, duration: Option<u64>) -> Audio {
+        AudioBuilder::default()
+            .title("Song".parse::<Title>().unwrap())
+            .artist(artist.parse::<Artist>().unwrap_or_default())
+            .year(None)
+            .album_title(album.parse::<Title>().unwrap_or_default())
+            .album_artist(album_artist.parse::<Artist>().unwrap())
+            .album_cover(Cover::default())
+            .genre(Genre::default())
+            .track_number(Some(3))
+            .disc_number(None)
+            .compilation(false)
+            .path(PathBuf::from("/music/song.flac"))
+            .duration(duration.map(Duration::from_secs))
+            .build()
+            .unwrap()
+    }
+
+    fn listen(duration: Option<u64>) -> Listen {
+        let listened_at = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        Listen::new(&audio("Artist", "Album", "Band", duration), listened_at)
+    }
+
+    #[test]
+    fn counts_half_or_four_minutes() {
+        let seconds = |seconds| Some(Duration::from_secs(seconds));
+        let short = listen(Some(29));
+        assert!(!short.counts(None));
+        assert!(!short.counts(seconds(29)));
+
+        let song = listen(Some(200));
+        assert!(song.counts(seconds(100)));
+        assert!(!song.counts(seconds(99)));
+        assert!(song.counts(None));
+
+        let long = listen(Some(1200));
+        assert!(long.counts(seconds(240)));
+        assert!(!long.counts(seconds(239)));
+
+        assert!(listen(None).counts(seconds(1)));
+    }
+
+    #[test]
+    fn new_leaves_out_unknown_album() {
+        let listened_at = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let known = listen(Some(200));
+        assert_eq!(known.artist, "Artist");
+        assert_eq!(known.album.as_deref(), Some("Album"));
+        assert_eq!(known.album_artist.as_deref(), Some("Band"));
+        assert_eq!(known.track_number, Some(3));
+
+        let without_album = Listen::new(&audio("Artist", "", "Band", None), listened_at);
+        assert_eq!(without_album.album, None);
+        assert_eq!(without_album.album_artist, None);
+
+        let without_artist = Listen::new(&audio("", "Album", "Band", None), listened_at);
+        assert_eq!(without_artist.artist, "Band");
+    }
+}
@@ -0,0 +1,240 @@
+use std::cmp::Ordering;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+    audio::{hex_prefix, Audio},
+    play_stats::PlayStats,
+    user::User,
+};
+
+mod rule;
+
+pub use rule::{
+    Comparison, DateCondition, DateField, FlagField, NumberField, Rule, RuleField, TextField,
+    TextOperator,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleSort {
+    pub field: RuleField,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+/// A playlist made of the audios matching a rule, evaluated again as the library and play
+/// statistics change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmartPlaylist {
+    pub id: String,
+    pub name: String,
+    pub rule: Rule,
+    /// Audios equal on every key are ordered by path.
+    #[serde(default)]
+    pub sort: Vec<RuleSort>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Identifier of the user who created the playlist. Playlists without one are shared
+    /// by every user and only administrators change them.
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// Whether users other than the owner see the playlist.
+    #[serde(default)]
+    pub public: bool,
+}
+
+impl SmartPlaylist {
+    /// Creates a playlist with a new identifier, unlimited and ordered by path.
+    pub fn new(name: &str, rule: Rule) -> Self {
+        let now = Utc::now();
+        let seed = format!("{name}\0{}", now.timestamp_nanos_opt().unwrap_or_default());
+        Self {
+            id: hex_prefix(&Sha256::digest(seed.as_bytes())),
+            name: name.to_string(),
+            rule,
+            sort: Vec::new(),
+            limit: None,
+            owner: None,
+            public: false,
+        }
+    }
+
+    pub fn owned_by(self, user: &User) -> Self {
+        Self {
+            owner: Some(user.id.clone()),
+            ..self
+        }
+    }
+
+    pub fn is_visible_to(&self, user: &User) -> bool {
+        self.public || self.owner.as_ref().is_none_or(|owner| *owner == user.id)
+    }
+
+    pub fn is_editable_by(&self, user: &User) -> bool {
+        user.role.is_admin() || self.owner.as_ref() == Some(&user.id)
+    }
+
+    pub fn sort_by(mut self, field: RuleField, descending: bool) -> Self {
+        self.sort.push(RuleSort { field, descending });
+        self
+    }
+
+    pub fn limit(self, limit: usize) -> Self {
+        Self {
+            limit: Some(limit),
+            ..self
+        }
+    }
+
+    /// Returns the matching audios, ordered and limited. Relative dates are measured from
+    /// `now`.
+    pub fn evaluate<'a>(
+        &self,
+        audios: impl IntoIterator<Item = &'a Audio>,
+        stats: impl Fn(&Audio) -> PlayStats,
+        now: DateTime<Utc>,
+    ) -> Vec<&'a Audio> {
+        let mut matched = audios
+            .into_iter()
+            .map(|audio| (audio, stats(audio)))
+            .filter(|(audio, stats)| self.rule.matches(audio, stats, now))
+            .collect::<Vec<_>>();
+        matched.sort_by(|(a, a_stats), (b, b_stats)| {
+            self.sort
+                .iter()
+                .map(|sort| {
+                    let a = sort.field.sort_value(a, a_stats);
+                    let b = sort.field.sort_value(b, b_stats);
+                    match (a, b) {
+                        // Missing values come last in both orders
+                        (None, None) => Ordering::Equal,
+                        (None, Some(_)) => Ordering::Greater,
+                        (Some(_), None) => Ordering::Less,
+                        (Some(a), Some(b)) => {
+                            let ordering = a.partial_cmp(&b).unwrap_or(Ordering::Equal);
+                            if sort.descending {
+                                ordering.reverse()
+                            } else {
+                                ordering
+                            }
+                        }
+                    }
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| a.path().cmp(b.path()))
+        });
+        let limit = self.limit.unwrap_or(usize::MAX);
+        matched
+            .into_iter()
+            .take(limit)
+            .map(|(audio, _)| audio)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use chrono::TimeZone;
+
+    use crate::domain::entity::audio::{
+        artist::Artist, cover::Cover, genre::Genre, title::Title, year::Year, AudioBuilder,
+    };
+
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+    }
+
+    fn audio(path: &str, genre: &str, year: Option<u16>, added_days_ago: Option<i64>) -> Audio {
+        AudioBuilder::default()
+            .title(Title::default())
+            .artist("Miles Davis".parse::<Artist>().unwrap())
+            .year(year.map(Year::new))
+            .album_title(Title::default())
+            .album_artist(Artist::default())
+            .album_cover(Cover::default())
+            .genre(genre.parse::<Genre>().unwrap())
+            .track_number(None)
+            .disc_number(None)
+            .compilation(false)
+            .path(PathBuf::from(path))
+            .duration(Some(Duration::from_secs(300)))
+            .added_at(added_days_ago.map(|days| now() - chrono::Duration::days(days)))
+            .build()
+            .unwrap()
+    }
+
+    fn rule(json: &str) -> Rule {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn matches(rule: &Rule, audio: &Audio) -> bool {
+        rule.matches(audio, &PlayStats::default(), now())
+    }
+
+    #[test]
+    fn parses_and_matches_nested_rules() {
+        let rule = rule(
+            r#"{"all": [{"text": {"field": "genre", "operator": "contains", "value": "JAZZ"}},
+            {"date": {"field": "added", "operator": "in_the_last", "days": 30}}]}"#,
+        );
+        assert!(matches(&rule, &audio("/a.mp3", "Jazz", None, Some(3))));
+        assert!(!matches(&rule, &audio("/b.mp3", "Jazz", None, Some(60))));
+        assert!(!matches(&rule, &audio("/c.mp3", "Rock", None, Some(3))));
+        assert!(!matches(&rule, &audio("/d.mp3", "Jazz", None, None)));
+
+        let not = Rule::Not(Box::new(rule));
+        assert!(matches(&not, &audio("/c.mp3", "Rock", None, Some(3))));
+        assert!(matches(
+            &Rule::All(Vec::new()),
+            &audio("/a.mp3", "Jazz", None, None)
+        ));
+        assert!(!matches(
+            &Rule::Any(Vec::new()),
+            &audio("/a.mp3", "Jazz", None, None)
+        ));
+    }
+
+    #[test]
+    fn missing_values_never_match_except_not_in_the_last() {
+        let year = rule(r#"{"number": {"field": "year", "comparison": "less", "value": 2000}}"#);
+        assert!(matches(&year, &audio("/a.mp3", "Jazz", Some(1959), None)));
+        assert!(!matches(&year, &audio("/a.mp3", "Jazz", None, None)));
+
+        let stale =
+            rule(r#"{"date": {"field": "last_played", "operator": "not_in_the_last", "days": 7}}"#);
+        assert!(matches(&stale, &audio("/a.mp3", "Jazz", None, None)));
+        let stats = PlayStats {
+            last_played: Some(now() - chrono::Duration::days(1)),
+            ..PlayStats::default()
+        };
+        assert!(!stale.matches(&audio("/a.mp3", "Jazz", None, None), &stats, now()));
+    }
+
+    #[test]
+    fn sorts_missing_values_last_and_limits() {
+        let audios = [
+            audio("/a.mp3", "Jazz", None, None),
+            audio("/b.mp3", "Jazz", Some(1959), None),
+            audio("/c.mp3", "Jazz", Some(1970), None),
+            audio("/d.mp3", "Jazz", Some(1970), None),
+        ];
+        let paths = |playlist: &SmartPlaylist| {
+            playlist
+                .evaluate(&audios, |_| PlayStats::default(), now())
+                .iter()
+                .map(|audio| audio.path().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        let playlist = SmartPlaylist::new("Jazz", Rule::default())
+            .sort_by(RuleField::Number(NumberField::Year), true);
+        // Ties are broken by path
+        assert_eq!(paths(&playlist), ["/c.mp3", "/d.mp3", "/b.mp3", "/a.mp3"]);
+        assert_eq!(paths(&playlist.limit(2)), ["/c.mp3", "/d.mp3"]);
+    }
+}
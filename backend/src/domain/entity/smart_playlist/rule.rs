@@ -0,0 +1,285 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::entity::{
+    audio::{sort_key::SortKeyOptions, Audio},
+    play_stats::PlayStats,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextField {
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Genre,
+    /// Lowercase file extension.
+    Format,
+    Path,
+    Lyrics,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NumberField {
+    Year,
+    TrackNumber,
+    DiscNumber,
+    /// In seconds.
+    Duration,
+    /// In kbit/s.
+    Bitrate,
+    PlayCount,
+    /// Stars given by the user, from 1 to 5.
+    Rating,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlagField {
+    Compilation,
+    /// Whether the audio has a cover.
+    Cover,
+    /// Whether the user marked the track as a favorite.
+    Favorite,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateField {
+    /// When the audio first appeared in the library.
+    Added,
+    /// Last modification of the file.
+    Modified,
+    LastPlayed,
+}
+
+/// Any field, written as its plain name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RuleField {
+    Text(TextField),
+    Number(NumberField),
+    Flag(FlagField),
+    Date(DateField),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextOperator {
+    Contains,
+    Equals,
+    StartsWith,
+    EndsWith,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparison {
+    Equal,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "operator", rename_all = "snake_case")]
+pub enum DateCondition {
+    /// Between the given number of days ago and now.
+    InTheLast {
+        days: u32,
+    },
+    /// Longer ago than the given number of days, or never, so that tracks never played
+    /// count as not played recently.
+    NotInTheLast {
+        days: u32,
+    },
+    Before {
+        date: DateTime<Utc>,
+    },
+    After {
+        date: DateTime<Utc>,
+    },
+}
+
+/// A tree of conditions on the metadata, play statistics and dates of audios, for example
+/// `{"all": [{"text": {"field": "genre", "operator": "contains", "value": "jazz"}},
+/// {"date": {"field": "added", "operator": "in_the_last", "days": 30}}]}`. Conditions on
+/// a number or date the audio does not have never match, except for
+/// [`DateCondition::NotInTheLast`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    /// Matches when every rule does, so always when empty.
+    All(Vec<Rule>),
+    /// Matches when a rule does, so never when empty.
+    Any(Vec<Rule>),
+    Not(Box<Rule>),
+    /// Compares case and diacritics insensitively.
+    Text {
+        field: TextField,
+        operator: TextOperator,
+        value: String,
+    },
+    Number {
+        field: NumberField,
+        comparison: Comparison,
+        value: f64,
+    },
+    Flag {
+        field: FlagField,
+        value: bool,
+    },
+    Date {
+        field: DateField,
+        #[serde(flatten)]
+        condition: DateCondition,
+    },
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self::All(Vec::new())
+    }
+}
+
+impl Rule {
+    pub fn matches(&self, audio: &Audio, stats: &PlayStats, now: DateTime<Utc>) -> bool {
+        match self {
+            Self::All(rules) => rules.iter().all(|rule| rule.matches(audio, stats, now)),
+            Self::Any(rules) => rules.iter().any(|rule| rule.matches(audio, stats, now)),
+            Self::Not(rule) => !rule.matches(audio, stats, now),
+            Self::Text {
+                field,
+                operator,
+                value,
+            } => {
+                let text = fold(&field.value(audio));
+                let value = fold(value);
+                match operator {
+                    TextOperator::Contains => text.contains(&value),
+                    TextOperator::Equals => text == value,
+                    TextOperator::StartsWith => text.starts_with(&value),
+                    TextOperator::EndsWith => text.ends_with(&value),
+                }
+            }
+            Self::Number {
+                field,
+                comparison,
+                value,
+            } => field
+                .value(audio, stats)
+                .is_some_and(|number| match comparison {
+                    Comparison::Equal => number == *value,
+                    Comparison::Less => number < *value,
+                    Comparison::LessOrEqual => number <= *value,
+                    Comparison::Greater => number > *value,
+                    Comparison::GreaterOrEqual => number >= *value,
+                }),
+            Self::Flag { field, value } => field.value(audio, stats) == *value,
+            Self::Date { field, condition } => {
+                let date = field.value(audio, stats);
+                let days_ago = |days: u32| now - Duration::days(i64::from(days));
+                match condition {
+                    DateCondition::InTheLast { days } => {
+                        date.is_some_and(|date| date >= days_ago(*days) && date <= now)
+                    }
+                    DateCondition::NotInTheLast { days } => {
+                        date.is_none_or(|date| date < days_ago(*days))
+                    }
+                    DateCondition::Before { date: before } => {
+                        date.is_some_and(|date| date < *before)
+                    }
+                    DateCondition::After { date: after } => date.is_some_and(|date| date > *after),
+                }
+            }
+        }
+    }
+}
+
+impl TextField {
+    fn value(&self, audio: &Audio) -> String {
+        match self {
+            Self::Title => audio.title().name().clone(),
+            Self::Artist => audio.artist().name().clone(),
+            Self::Album => audio.album_title().name().clone(),
+            Self::AlbumArtist => audio.album_artist().name().clone(),
+            Self::Genre => audio.genre().name().clone(),
+            Self::Format => audio
+                .path()
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+            Self::Path => audio.path().to_string_lossy().into_owned(),
+            Self::Lyrics => audio.lyrics().clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl NumberField {
+    fn value(&self, audio: &Audio, stats: &PlayStats) -> Option<f64> {
+        match self {
+            Self::Year => audio.year().map(|year| year.0 as f64),
+            Self::TrackNumber => audio.track_number().map(f64::from),
+            Self::DiscNumber => audio.disc_number().map(f64::from),
+            Self::Duration => audio.duration().map(|duration| duration.as_secs_f64()),
+            Self::Bitrate => audio.bitrate().map(f64::from),
+            Self::PlayCount => Some(f64::from(stats.play_count)),
+            Self::Rating => stats.rating.map(|rating| f64::from(rating.stars())),
+        }
+    }
+}
+
+impl FlagField {
+    fn value(&self, audio: &Audio, stats: &PlayStats) -> bool {
+        match self {
+            Self::Compilation => *audio.compilation(),
+            Self::Cover => !audio.album_cover().is_default(),
+            Self::Favorite => stats.favorited_at.is_some(),
+        }
+    }
+}
+
+impl DateField {
+    fn value(&self, audio: &Audio, stats: &PlayStats) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Added => *audio.added_at(),
+            Self::Modified => *audio.modified_at(),
+            Self::LastPlayed => stats.last_played,
+        }
+    }
+}
+
+/// A value audios are ordered by.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub(super) enum SortValue {
+    Number(f64),
+    Text(String),
+}
+
+impl RuleField {
+    /// `None` when the audio has no value, texts being folded and dates being timestamps.
+    pub(super) fn sort_value(&self, audio: &Audio, stats: &PlayStats) -> Option<SortValue> {
+        match self {
+            Self::Text(field) => Some(SortValue::Text(fold(&field.value(audio)))),
+            Self::Number(field) => field.value(audio, stats).map(SortValue::Number),
+            Self::Flag(field) => Some(SortValue::Number(f64::from(u8::from(
+                field.value(audio, stats),
+            )))),
+            Self::Date(field) => field
+                .value(audio, stats)
+                .map(|date| SortValue::Number(date.timestamp_millis() as f64)),
+        }
+    }
+}
+
+/// Lower cases and removes diacritics.
+fn fold(text: &str) -> String {
+    SortKeyOptions {
+        articles: Vec::new(),
+        fold_diacritics: true,
+    }
+    .normalize(text)
+}
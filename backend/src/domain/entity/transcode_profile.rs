@@ -0,0 +1,57 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+/// Codecs audio can be transcoded to for streaming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioCodec {
+    /// Opus in an Ogg container.
+    Opus,
+    Mp3,
+    /// AAC in an ADTS stream, which unlike MP4 can be written progressively.
+    Aac,
+}
+
+impl AudioCodec {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Opus => "opus",
+            Self::Mp3 => "mp3",
+            Self::Aac => "aac",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Opus => "audio/ogg",
+            Self::Mp3 => "audio/mpeg",
+            Self::Aac => "audio/aac",
+        }
+    }
+}
+
+impl Display for AudioCodec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+/// A named codec and bitrate clients can request.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TranscodeProfile {
+    pub name: String,
+    pub codec: AudioCodec,
+    /// In kbit/s.
+    pub bitrate: u32,
+}
+
+impl TranscodeProfile {
+    pub fn new(name: &str, codec: AudioCodec, bitrate: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            codec,
+            bitrate,
+        }
+    }
+}
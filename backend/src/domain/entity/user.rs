@@ -0,0 +1,223 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use argon2::{
+    password_hash::{rand_core::OsRng, rand_core::RngCore, SaltString},
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+};
+use chrono::{DateTime, Utc};
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use super::audio::hex_prefix;
+
+/// Fewest characters of a password.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// What a user may do. Every user browses, streams and keeps their own listening activity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Also manages users.
+    Admin,
+    /// Also scans the library and creates playlists.
+    User,
+    ReadOnly,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Admin => "admin",
+            Self::User => "user",
+            Self::ReadOnly => "read_only",
+        }
+    }
+
+    pub fn is_admin(&self) -> bool {
+        *self == Self::Admin
+    }
+
+    /// Whether the user may scan the library and create playlists.
+    pub fn can_edit(&self) -> bool {
+        *self != Self::ReadOnly
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum UserError {
+    #[error("Username is empty")]
+    EmptyUsername,
+    #[error("Password must have at least {MIN_PASSWORD_LENGTH} characters")]
+    WeakPassword,
+    #[error("Failed to hash password: {0}")]
+    Hash(String),
+}
+
+/// An account of the server. Passwords are only kept as Argon2 hashes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct User {
+    pub id: String,
+    pub username: String,
+    pub password_hash: String,
+    pub role: Role,
+    /// Directories the user is restricted to, as the paths audios are gathered with. Users
+    /// without any see the whole library.
+    #[serde(default)]
+    pub library_roots: Vec<PathBuf>,
+    pub created_at: DateTime<Utc>,
+    /// Password of Subsonic clients, apart from the account password. Subsonic tokens are
+    /// the MD5 of the password and a salt, so it is kept in clear, and generated by the
+    /// server not to expose a password chosen by the user.
+    #[serde(default)]
+    pub subsonic_password: Option<String>,
+}
+
+impl User {
+    /// Creates a user with a new identifier.
+    pub fn new(username: &str, password: &str, role: Role) -> Result<Self, UserError> {
+        let username = username.trim();
+        if username.is_empty() {
+            return Err(UserError::EmptyUsername);
+        }
+        let created_at = Utc::now();
+        let seed = format!(
+            "{username}\0{}",
+            created_at.timestamp_nanos_opt().unwrap_or_default()
+        );
+        Ok(Self {
+            id: hex_prefix(&Sha256::digest(seed.as_bytes())),
+            username: username.to_string(),
+            password_hash: hash_password(password)?,
+            role,
+            library_roots: Vec::new(),
+            created_at,
+            subsonic_password: None,
+        })
+    }
+
+    pub fn set_password(&mut self, password: &str) -> Result<(), UserError> {
+        self.password_hash = hash_password(password)?;
+        Ok(())
+    }
+
+    pub fn verify_password(&self, password: &str) -> bool {
+        PasswordHash::new(&self.password_hash).is_ok_and(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+    }
+
+    /// Replaces the Subsonic password with a new random one, which is returned.
+    pub fn generate_subsonic_password(&mut self) -> String {
+        let mut bytes = [0; 12];
+        OsRng.fill_bytes(&mut bytes);
+        let password = hex(&bytes);
+        self.subsonic_password = Some(password.clone());
+        password
+    }
+
+    pub fn verify_subsonic_password(&self, password: &str) -> bool {
+        self.subsonic_password.as_deref() == Some(password)
+    }
+
+    /// Whether `token` is the MD5 of the Subsonic password followed by `salt`.
+    pub fn verify_subsonic_token(&self, token: &str, salt: &str) -> bool {
+        self.subsonic_password.as_ref().is_some_and(|password| {
+            let expected = Md5::digest(format!("{password}{salt}").as_bytes());
+            token.eq_ignore_ascii_case(&hex(&expected))
+        })
+    }
+
+    /// Whether the file is in the part of the library the user may access.
+    pub fn can_access(&self, path: &Path) -> bool {
+        self.library_roots.is_empty()
+            || self.library_roots.iter().any(|root| path.starts_with(root))
+    }
+}
+
+fn hash_password(password: &str) -> Result<String, UserError> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(UserError::WeakPassword);
+    }
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|err| UserError::Hash(err.to_string()))
+}
+
+/// A secret letting applications act as a user without their password. Only the hash of
+/// the secret is kept, the secret itself being shown once when the token is created.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: String,
+    pub user_id: String,
+    /// What the token is for, such as the application using it.
+    pub name: String,
+    pub secret_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl ApiToken {
+    /// Creates a token for the user, returned with its secret.
+    pub fn generate(user_id: &str, name: &str) -> (Self, String) {
+        let mut bytes = [0; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let secret = hex(&bytes);
+        let secret_hash = Self::hash_secret(&secret);
+        let token = Self {
+            // Secrets are random, so part of their hash identifies them without revealing them
+            id: secret_hash[..16].to_string(),
+            user_id: user_id.to_string(),
+            name: name.trim().to_string(),
+            secret_hash,
+            created_at: Utc::now(),
+            last_used_at: None,
+        };
+        (token, secret)
+    }
+
+    /// Secrets are long and random, so a plain hash is enough to store them.
+    pub fn hash_secret(secret: &str) -> String {
+        hex(&Sha256::digest(secret.as_bytes()))
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_subsonic_tokens() {
+        let mut user = User::new("admin", "correct-horse-battery", Role::Admin).unwrap();
+        assert!(!user.verify_subsonic_token("26719a1196d2a940705a59634eb18eab", "c19b2d"));
+
+        // Example of the Subsonic API documentation
+        user.subsonic_password = Some("sesame".to_string());
+        assert!(user.verify_subsonic_token("26719a1196d2a940705a59634eb18eab", "c19b2d"));
+        assert!(user.verify_subsonic_token("26719A1196D2A940705A59634EB18EAB", "c19b2d"));
+        assert!(!user.verify_subsonic_token("26719a1196d2a940705a59634eb18eab", "c19b2e"));
+
+        let password = user.generate_subsonic_password();
+        assert_ne!(password, "sesame");
+        assert!(user.verify_subsonic_password(&password));
+        assert!(!user.verify_subsonic_password("correct-horse-battery"));
+    }
+}
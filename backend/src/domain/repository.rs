@@ -1,3 +1,31 @@
+mod activity_repository;
+mod audio_decoder;
 mod audio_gatherer_repository;
+mod audio_tag_writer;
+mod audio_transcoder;
+mod edit_journal_repository;
+mod fingerprint_repository;
+mod library_file_repository;
+mod library_repository;
+mod playlist_repository;
+mod scrobble_repository;
+mod scrobbler;
+mod smart_playlist_repository;
+mod transcode_cache_repository;
+mod user_repository;
 
+pub use activity_repository::ActivityRepository;
+pub use audio_decoder::AudioDecoder;
 pub use audio_gatherer_repository::AudioGathererRepository;
+pub use audio_tag_writer::AudioTagWriter;
+pub use audio_transcoder::AudioTranscoder;
+pub use edit_journal_repository::EditJournalRepository;
+pub use fingerprint_repository::FingerprintRepository;
+pub use library_file_repository::LibraryFileRepository;
+pub use library_repository::LibraryRepository;
+pub use playlist_repository::PlaylistRepository;
+pub use scrobble_repository::ScrobbleRepository;
+pub use scrobbler::Scrobbler;
+pub use smart_playlist_repository::SmartPlaylistRepository;
+pub use transcode_cache_repository::TranscodeCacheRepository;
+pub use user_repository::UserRepository;
@@ -0,0 +1,32 @@
+use std::collections::HashMap;
+
+use crate::domain::entity::{
+    activity::{Annotation, ItemKind, Play},
+    play_stats::PlayStats,
+};
+
+/// Keeps what users did with the library: plays, ratings and favorites. Each user has their
+/// own, identified by `user_id`.
+pub trait ActivityRepository {
+    type Error;
+    fn record_play(&self, user_id: &str, play: &Play) -> Result<(), Self::Error>;
+    /// Plays from the most recent, skipping `offset` and returning `limit` at most.
+    fn plays(&self, user_id: &str, offset: usize, limit: usize) -> Result<Vec<Play>, Self::Error>;
+    fn play_total(&self, user_id: &str) -> Result<usize, Self::Error>;
+    /// Statistics of every audio that was played, rated or marked as a favorite, by audio
+    /// identifier.
+    fn track_stats(&self, user_id: &str) -> Result<HashMap<String, PlayStats>, Self::Error>;
+    fn annotation(
+        &self,
+        user_id: &str,
+        kind: ItemKind,
+        id: &str,
+    ) -> Result<Option<Annotation>, Self::Error>;
+    /// Annotations of every item of the kind.
+    fn annotations(&self, user_id: &str, kind: ItemKind) -> Result<Vec<Annotation>, Self::Error>;
+    /// Creates or replaces the annotation of the item, removing it when it is empty.
+    fn save_annotation(&self, user_id: &str, annotation: &Annotation) -> Result<(), Self::Error>;
+    /// Gives the plays and annotations recorded before there were users to the user,
+    /// returning how many were given.
+    fn adopt_unowned(&self, user_id: &str) -> Result<usize, Self::Error>;
+}
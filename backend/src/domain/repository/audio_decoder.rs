@@ -0,0 +1,13 @@
+use std::{path::Path, time::Duration};
+
+pub trait AudioDecoder {
+    type Error;
+    /// Decodes at most `max_duration` of the audio at `path` into mono 16-bit samples at
+    /// `sample_rate`.
+    fn decode(
+        &self,
+        path: &Path,
+        sample_rate: u32,
+        max_duration: Duration,
+    ) -> Result<Vec<i16>, Self::Error>;
+}
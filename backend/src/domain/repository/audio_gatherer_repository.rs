@@ -1,7 +1,12 @@
-use crate::domain::entity::audio::Audio;
+use crate::domain::entity::{audio::Audio, library_root::LibraryRoot};
 
 pub trait AudioGathererRepository {
     type Error;
     // TODO: Make this return Result<Impl Iterator<Item = Audio>, Self::Error> in rust 1.75.0 28 December, 2023
+    /// Gathers the audios of every root.
     fn gather(&self) -> Result<Box<dyn Iterator<Item = Audio>>, Self::Error>;
+    /// Gathers the audios of the root with that name.
+    fn gather_root(&self, name: &str) -> Result<Box<dyn Iterator<Item = Audio>>, Self::Error>;
+    /// The roots audios are gathered from.
+    fn roots(&self) -> &[LibraryRoot];
 }
@@ -0,0 +1,24 @@
+use std::path::Path;
+
+use crate::domain::entity::audio::{tag_change::TagChange, Audio};
+
+/// Writes edited metadata back into audio files.
+pub trait AudioTagWriter {
+    type Error;
+
+    /// Returns the tag changes needed for the file of `audio` to match its metadata,
+    /// without modifying the file. Inferred and unknown values are never written.
+    fn diff(&self, audio: &Audio) -> Result<Vec<TagChange>, Self::Error>;
+
+    /// Applies the `after` value of every change to the file at `path`.
+    fn apply(&self, path: &Path, changes: &[TagChange]) -> Result<(), Self::Error>;
+
+    /// Writes the metadata of `audio` into its file, returning the applied changes.
+    fn write(&self, audio: &Audio) -> Result<Vec<TagChange>, Self::Error> {
+        let changes = self.diff(audio)?;
+        if !changes.is_empty() {
+            self.apply(audio.path(), &changes)?;
+        }
+        Ok(changes)
+    }
+}
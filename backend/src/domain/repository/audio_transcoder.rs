@@ -0,0 +1,16 @@
+use std::{io::Read, path::Path, time::Duration};
+
+use crate::domain::entity::transcode_profile::TranscodeProfile;
+
+pub trait AudioTranscoder {
+    type Error;
+    /// Produced progressively while reading. Reading fails if transcoding does.
+    type Output: Read + Send + 'static;
+    /// Starts transcoding the audio at `path`, skipping its first `offset`.
+    fn transcode(
+        &self,
+        path: &Path,
+        profile: &TranscodeProfile,
+        offset: Duration,
+    ) -> Result<Self::Output, Self::Error>;
+}
@@ -0,0 +1,10 @@
+use crate::domain::entity::edit_journal::EditJournal;
+
+pub trait EditJournalRepository {
+    type Error;
+    fn save(&self, journal: &EditJournal) -> Result<(), Self::Error>;
+    fn find(&self, id: &str) -> Result<Option<EditJournal>, Self::Error>;
+    /// Returns every stored journal, newest first.
+    fn list(&self) -> Result<Vec<EditJournal>, Self::Error>;
+    fn delete(&self, id: &str) -> Result<(), Self::Error>;
+}
@@ -0,0 +1,9 @@
+use std::path::Path;
+
+use crate::domain::entity::fingerprint::TrackFingerprint;
+
+pub trait FingerprintRepository {
+    type Error;
+    fn save(&self, track_fingerprint: &TrackFingerprint) -> Result<(), Self::Error>;
+    fn find(&self, path: &Path) -> Result<Option<TrackFingerprint>, Self::Error>;
+}
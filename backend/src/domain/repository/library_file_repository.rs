@@ -0,0 +1,20 @@
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Access to the files of the library, used to reorganize it.
+pub trait LibraryFileRepository {
+    type Error;
+    fn exists(&self, path: &Path) -> bool;
+    fn size(&self, path: &Path) -> Result<u64, Self::Error>;
+    fn modified(&self, path: &Path) -> Result<SystemTime, Self::Error>;
+    /// Returns the hex encoded SHA-256 digest of the content of the file.
+    fn hash(&self, path: &Path) -> Result<String, Self::Error>;
+    /// Returns the files, not directories, directly inside `directory`.
+    fn files(&self, directory: &Path) -> Result<Vec<PathBuf>, Self::Error>;
+    /// Copies `source` to `target`, creating missing parent directories.
+    fn copy(&self, source: &Path, target: &Path) -> Result<(), Self::Error>;
+    /// Moves `source` to `target`, creating missing parent directories.
+    fn rename(&self, source: &Path, target: &Path) -> Result<(), Self::Error>;
+}
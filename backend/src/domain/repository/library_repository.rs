@@ -0,0 +1,9 @@
+use crate::domain::entity::audio::Audio;
+
+/// Persists the gathered library so it is available without rescanning.
+pub trait LibraryRepository {
+    type Error;
+    /// Replaces the whole stored library.
+    fn replace(&self, audios: &[Audio]) -> Result<(), Self::Error>;
+    fn load(&self) -> Result<Vec<Audio>, Self::Error>;
+}
@@ -0,0 +1,15 @@
+use std::path::{Path, PathBuf};
+
+use crate::domain::entity::playlist::Playlist;
+
+/// Playlist files stored along the audios of the library.
+pub trait PlaylistRepository {
+    type Error;
+    /// Returns the paths of every playlist file.
+    fn find(&self) -> Result<Vec<PathBuf>, Self::Error>;
+    /// Reads a playlist in the format given by the extension of its path.
+    fn read(&self, path: &Path) -> Result<Playlist, Self::Error>;
+    /// Writes a playlist in the format given by the extension of its path, creating missing
+    /// parent directories.
+    fn write(&self, path: &Path, playlist: &Playlist) -> Result<(), Self::Error>;
+}
@@ -0,0 +1,42 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::domain::entity::scrobble::{Listen, QueuedListen, ScrobblerAccount, ScrobblerKind};
+
+/// Keeps the scrobbling accounts of users and the listens waiting to be submitted.
+pub trait ScrobbleRepository {
+    type Error;
+    /// Creates or replaces the account of the user for the kind of service, which is not
+    /// paused anymore.
+    fn save_account(&self, user_id: &str, account: &ScrobblerAccount) -> Result<(), Self::Error>;
+    fn accounts(&self, user_id: &str) -> Result<Vec<ScrobblerAccount>, Self::Error>;
+    /// Stops submitting to the account until it is saved again, for the reason given.
+    fn pause_account(
+        &self,
+        user_id: &str,
+        kind: ScrobblerKind,
+        reason: &str,
+    ) -> Result<(), Self::Error>;
+    /// Why the paused accounts of the user were paused, by kind of service.
+    fn paused_accounts(
+        &self,
+        user_id: &str,
+    ) -> Result<BTreeMap<ScrobblerKind, String>, Self::Error>;
+    /// Removes the account along with the listens queued for it.
+    fn delete_account(&self, user_id: &str, kind: ScrobblerKind) -> Result<(), Self::Error>;
+    /// Queues a listen, to be attempted right away.
+    fn enqueue(
+        &self,
+        user_id: &str,
+        kind: ScrobblerKind,
+        listen: &Listen,
+    ) -> Result<(), Self::Error>;
+    /// Queued listens to attempt at `now`, the oldest listens first.
+    fn due(&self, now: DateTime<Utc>, limit: usize) -> Result<Vec<QueuedListen>, Self::Error>;
+    /// Every listen queued for the user, the oldest first.
+    fn queued(&self, user_id: &str) -> Result<Vec<QueuedListen>, Self::Error>;
+    /// Saves the attempts, next attempt and last error of a queued listen.
+    fn reschedule(&self, listen: &QueuedListen) -> Result<(), Self::Error>;
+    fn dequeue(&self, id: i64) -> Result<(), Self::Error>;
+}
@@ -0,0 +1,20 @@
+use crate::domain::entity::scrobble::{Listen, ScrobbleError, ScrobblerAccount};
+
+/// Client of scrobbling services. Errors tell whether sending again may succeed.
+pub trait Scrobbler {
+    /// Checks the credentials of the account.
+    fn validate(&self, account: &ScrobblerAccount) -> Result<(), ScrobbleError>;
+    fn now_playing(&self, account: &ScrobblerAccount, listen: &Listen)
+        -> Result<(), ScrobbleError>;
+    /// Submits listens, which are all accepted or all rejected.
+    fn submit(&self, account: &ScrobblerAccount, listens: &[Listen]) -> Result<(), ScrobbleError>;
+    /// Logs in a Last.fm compatible service and returns the session key to scrobble with.
+    fn last_fm_session(
+        &self,
+        url: &str,
+        api_key: &str,
+        api_secret: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<String, ScrobbleError>;
+}
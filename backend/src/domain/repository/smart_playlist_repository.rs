@@ -0,0 +1,11 @@
+use crate::domain::entity::smart_playlist::SmartPlaylist;
+
+pub trait SmartPlaylistRepository {
+    type Error;
+    /// Creates the playlist or replaces the one with the same id.
+    fn save(&self, playlist: &SmartPlaylist) -> Result<(), Self::Error>;
+    fn find(&self, id: &str) -> Result<Option<SmartPlaylist>, Self::Error>;
+    /// Returns every stored playlist, ordered by name.
+    fn list(&self) -> Result<Vec<SmartPlaylist>, Self::Error>;
+    fn delete(&self, id: &str) -> Result<(), Self::Error>;
+}
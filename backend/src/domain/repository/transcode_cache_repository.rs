@@ -0,0 +1,15 @@
+use std::{io::Write, path::PathBuf};
+
+/// Stores transcoded outputs so they are not transcoded again.
+pub trait TranscodeCacheRepository {
+    type Error;
+    type Writer: Write + Send + 'static;
+    /// Returns the path of a complete output and marks it as recently used.
+    fn find(&self, key: &str) -> Result<Option<PathBuf>, Self::Error>;
+    /// Starts writing an output, which [`Self::find`] ignores until it is committed. Dropping
+    /// the writer without committing it discards the output.
+    fn create(&self, key: &str) -> Result<Self::Writer, Self::Error>;
+    fn commit(&self, key: &str, writer: Self::Writer) -> Result<(), Self::Error>;
+    /// Removes the least recently used outputs until the cache is at most `max_size` bytes.
+    fn evict(&self, max_size: u64) -> Result<(), Self::Error>;
+}
@@ -0,0 +1,21 @@
+use crate::domain::entity::user::{ApiToken, User};
+
+/// Stores user accounts and their API tokens.
+pub trait UserRepository {
+    type Error;
+    /// Creates the user or replaces the one with the same id.
+    fn save_user(&self, user: &User) -> Result<(), Self::Error>;
+    fn find_user(&self, id: &str) -> Result<Option<User>, Self::Error>;
+    /// Usernames are compared case-insensitively.
+    fn find_user_by_name(&self, username: &str) -> Result<Option<User>, Self::Error>;
+    /// Returns every user, ordered by username.
+    fn list_users(&self) -> Result<Vec<User>, Self::Error>;
+    /// Deletes the user along with their tokens.
+    fn delete_user(&self, id: &str) -> Result<(), Self::Error>;
+    /// Creates the token or replaces the one with the same id.
+    fn save_token(&self, token: &ApiToken) -> Result<(), Self::Error>;
+    fn find_token(&self, secret_hash: &str) -> Result<Option<ApiToken>, Self::Error>;
+    /// Returns the tokens of the user, the most recent first.
+    fn list_tokens(&self, user_id: &str) -> Result<Vec<ApiToken>, Self::Error>;
+    fn delete_token(&self, id: &str) -> Result<(), Self::Error>;
+}
@@ -1 +1,4 @@
-pub mod repository;
\ No newline at end of file
+pub mod config;
+pub mod http;
+pub mod logging;
+pub mod repository;
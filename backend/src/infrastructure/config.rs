@@ -0,0 +1,550 @@
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs, io,
+    net::ToSocketAddrs,
+    path::{Path, PathBuf},
+};
+
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::domain::entity::{
+    audio::genre::normalizer::GenreNormalizer,
+    library_root::{LibraryRoot, ParserKind},
+    user::MIN_PASSWORD_LENGTH,
+};
+
+use super::repository::{
+    audio_gatherer_repository::{audio_parser::PathPattern, ScanFilter},
+    audio_tag_writer::FilesystemAudioTagWriter,
+};
+
+/// File the configuration is read from when no other is given, if it exists.
+pub const DEFAULT_CONFIG_FILE: &str = "earr.toml";
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read configuration file {0}: {1}")]
+    Read(PathBuf, io::Error),
+    #[error("Invalid configuration file {0}: {1}")]
+    Parse(PathBuf, toml::de::Error),
+    #[error("Invalid environment variable {0}: {1}")]
+    Env(&'static str, String),
+}
+
+/// Settings of the server, read from a TOML file and overridden by environment variables.
+/// Every section is optional, missing settings take their default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub storage: StorageConfig,
+    pub ffmpeg: FfmpegConfig,
+    pub logging: LoggingConfig,
+    pub genres: GenreConfig,
+    pub tags: TagConfig,
+    pub roots: Vec<LibraryRoot>,
+    /// Administrator created when there is no user yet, only read from `EARR_USERNAME` and
+    /// `EARR_PASSWORD` so the password is never written in a file.
+    #[serde(skip)]
+    pub admin: Option<AdminConfig>,
+    /// File the configuration was read from, if any.
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address and port the server listens on. Overridden by `EARR_ADDRESS`.
+    pub address: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1:3000".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// SQLite database of the library, users and activity. Overridden by `EARR_DATABASE`.
+    pub database: PathBuf,
+    /// Directory of the files that can be regenerated, such as transcodes. Overridden by
+    /// `EARR_CACHE_DIR`.
+    pub cache_dir: PathBuf,
+    /// Directory of the files created by users, such as smart playlists. Overridden by
+    /// `EARR_DATA_DIR`.
+    pub data_dir: PathBuf,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            database: PathBuf::from("earr.sqlite3"),
+            cache_dir: PathBuf::from("cache"),
+            data_dir: PathBuf::from("data"),
+        }
+    }
+}
+
+/// Executables run to read and transcode audios, looked up in `PATH` when given by name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FfmpegConfig {
+    /// Overridden by `EARR_FFMPEG`.
+    pub ffmpeg: PathBuf,
+    /// Overridden by `EARR_FFPROBE`.
+    pub ffprobe: PathBuf,
+}
+
+impl Default for FfmpegConfig {
+    fn default() -> Self {
+        Self {
+            ffmpeg: PathBuf::from("ffmpeg"),
+            ffprobe: PathBuf::from("ffprobe"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Most verbose level logged, from `off` to `trace`. Overridden by `EARR_LOG_LEVEL`.
+    pub level: LevelFilter,
+    /// File logs are appended to, the standard error when not set. Overridden by
+    /// `EARR_LOG_FILE`.
+    pub file: Option<PathBuf>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::Info,
+            file: None,
+        }
+    }
+}
+
+/// Genre names added to the default aliases of [`GenreNormalizer`], compared ignoring case
+/// and whitespace.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GenreConfig {
+    /// Canonical names of genres, by alias. They replace the default aliases.
+    pub aliases: BTreeMap<String, String>,
+    /// Parent genres, by child genre.
+    pub parents: BTreeMap<String, String>,
+}
+
+impl GenreConfig {
+    pub fn normalizer(&self) -> GenreNormalizer {
+        let normalizer = self.aliases.iter().fold(
+            GenreNormalizer::default(),
+            |normalizer, (alias, canonical)| normalizer.with_alias(alias, canonical),
+        );
+        self.parents
+            .iter()
+            .fold(normalizer, |normalizer, (child, parent)| {
+                normalizer.with_parent(child, parent)
+            })
+    }
+}
+
+/// How tags are written back into audio files.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TagConfig {
+    /// Text separating the values of artists, album artists and genres, e.g. `"; "`. They
+    /// are written as a single value when not set.
+    pub multi_value_separator: Option<String>,
+}
+
+impl TagConfig {
+    pub fn writer(&self, ffmpeg: &FfmpegConfig) -> FilesystemAudioTagWriter {
+        let writer = FilesystemAudioTagWriter::new(&ffmpeg.ffprobe, &ffmpeg.ffmpeg);
+        match &self.multi_value_separator {
+            Some(separator) => writer.with_multi_value_separator(separator),
+            None => writer,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct AdminConfig {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for AdminConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminConfig")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Problems found in a configuration. Errors prevent the server from starting, warnings
+/// only limit what it can do.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigReport {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl ConfigReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl Config {
+    /// Reads the configuration from the given file, else from the file of `EARR_CONFIG`,
+    /// else from [`DEFAULT_CONFIG_FILE`] when it exists, then applies the environment.
+    ///
+    /// Roots are replaced by the JSON list of `EARR_LIBRARY_ROOTS`. When neither declares
+    /// any, one is created for each directory of `MUSIC_DIR`, separated like `PATH`.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let path = match path {
+            Some(path) => Some(path.to_path_buf()),
+            None => env_var("EARR_CONFIG")
+                .map(PathBuf::from)
+                .or_else(|| Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|path| path.exists())),
+        };
+        let mut config = match path {
+            Some(path) => {
+                let mut config = Self::from_file(&path)?;
+                config.source = Some(path);
+                config
+            }
+            None => Self::default(),
+        };
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let content =
+            fs::read_to_string(path).map_err(|err| ConfigError::Read(path.to_path_buf(), err))?;
+        toml::from_str(&content).map_err(|err| ConfigError::Parse(path.to_path_buf(), err))
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        if let Some(address) = env_var("EARR_ADDRESS") {
+            self.server.address = address;
+        }
+        if let Some(database) = env_var("EARR_DATABASE") {
+            self.storage.database = PathBuf::from(database);
+        }
+        if let Some(cache_dir) = env_var("EARR_CACHE_DIR") {
+            self.storage.cache_dir = PathBuf::from(cache_dir);
+        }
+        if let Some(data_dir) = env_var("EARR_DATA_DIR") {
+            self.storage.data_dir = PathBuf::from(data_dir);
+        }
+        if let Some(ffmpeg) = env_var("EARR_FFMPEG") {
+            self.ffmpeg.ffmpeg = PathBuf::from(ffmpeg);
+        }
+        if let Some(ffprobe) = env_var("EARR_FFPROBE") {
+            self.ffmpeg.ffprobe = PathBuf::from(ffprobe);
+        }
+        if let Some(level) = env_var("EARR_LOG_LEVEL") {
+            self.logging.level = level.parse().map_err(|_| {
+                ConfigError::Env("EARR_LOG_LEVEL", format!("unknown level {level}"))
+            })?;
+        }
+        if let Some(file) = env_var("EARR_LOG_FILE") {
+            self.logging.file = Some(PathBuf::from(file));
+        }
+        let username = env_var("EARR_USERNAME");
+        let password = env_var("EARR_PASSWORD");
+        if username.is_some() || password.is_some() {
+            self.admin = Some(AdminConfig {
+                username: username.unwrap_or_default(),
+                password: password.unwrap_or_default(),
+            });
+        }
+        if let Some(roots) = env_var("EARR_LIBRARY_ROOTS") {
+            self.roots = serde_json::from_str(&roots)
+                .map_err(|err| ConfigError::Env("EARR_LIBRARY_ROOTS", err.to_string()))?;
+        }
+        if self.roots.is_empty() {
+            if let Some(music_dir) = env::var_os("MUSIC_DIR").filter(|dir| !dir.is_empty()) {
+                self.roots = env::split_paths(&music_dir)
+                    .map(LibraryRoot::from_path)
+                    .collect();
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the configuration against the system it runs on.
+    pub fn validate(&self) -> ConfigReport {
+        let mut report = ConfigReport::default();
+
+        if let Err(err) = self.server.address.to_socket_addrs() {
+            report.errors.push(format!(
+                "server.address: {} is not a valid address: {}",
+                self.server.address, err
+            ));
+        }
+
+        if self.roots.is_empty() {
+            report.errors.push(
+                "roots: no library root is configured, declare one in the configuration file, \
+                 EARR_LIBRARY_ROOTS or MUSIC_DIR"
+                    .to_string(),
+            );
+        }
+        if let Err(err) = LibraryRoot::validate_all(&self.roots) {
+            report.errors.push(format!("roots: {}", err));
+        } else {
+            // Symbolic links and `..` can hide roots inside one another
+            let canonical = self
+                .roots
+                .iter()
+                .filter_map(|root| Some((root, fs::canonicalize(&root.path).ok()?)))
+                .collect::<Vec<_>>();
+            let overlaps = LibraryRoot::check_overlaps(
+                canonical.iter().map(|(root, path)| (*root, path.as_path())),
+            );
+            if let Err(err) = overlaps {
+                report.errors.push(format!("roots: {}", err));
+            }
+        }
+        for root in &self.roots {
+            if !root.path.is_dir() {
+                let problem = if root.path.exists() {
+                    "is not a directory"
+                } else {
+                    "does not exist"
+                };
+                report.errors.push(format!(
+                    "roots.{}.path: {} {}",
+                    root.name,
+                    root.path.display(),
+                    problem
+                ));
+            }
+            if let Err(err) = ScanFilter::new(root) {
+                report.errors.push(format!("roots.{}: {}", root.name, err));
+            }
+            for pattern in &root.path_patterns {
+                if let Err(err) = pattern.parse::<PathPattern>() {
+                    report.errors.push(format!(
+                        "roots.{}.path_patterns: {}: {}",
+                        root.name, pattern, err
+                    ));
+                }
+            }
+        }
+
+        // Genres nested under themselves could not be browsed as a tree
+        let cyclic = self.genres.normalizer().cyclic_genres();
+        if !cyclic.is_empty() {
+            report.errors.push(format!(
+                "genres.parents: {} would be their own parent genre",
+                cyclic.join(", ")
+            ));
+        }
+
+        if let Some(separator) = &self.tags.multi_value_separator {
+            if separator.trim().is_empty() {
+                report.errors.push(
+                    "tags.multi_value_separator: the separator must not be blank".to_string(),
+                );
+            }
+        }
+
+        // Parsers after ffmpeg in a chain fill what it cannot read, so a missing ffprobe
+        // only degrades metadata
+        let uses_ffprobe = self
+            .roots
+            .iter()
+            .any(|root| root.parsers.contains(&ParserKind::Ffmpeg));
+        if uses_ffprobe && find_executable(&self.ffmpeg.ffprobe).is_none() {
+            report.warnings.push(format!(
+                "ffmpeg.ffprobe: {} is not an executable, the ffmpeg parser will fail",
+                self.ffmpeg.ffprobe.display()
+            ));
+        }
+        if find_executable(&self.ffmpeg.ffmpeg).is_none() {
+            report.warnings.push(format!(
+                "ffmpeg.ffmpeg: {} is not an executable, covers will not be extracted and \
+                 tracks will not be transcoded",
+                self.ffmpeg.ffmpeg.display()
+            ));
+        }
+
+        // The administrator is only created with the first start, but the server refuses
+        // to start rather than failing to create it
+        if let Some(admin) = &self.admin {
+            if admin.username.trim().is_empty() {
+                report
+                    .errors
+                    .push("EARR_USERNAME: the administrator needs a username".to_string());
+            }
+            if admin.password.is_empty() {
+                report
+                    .errors
+                    .push("EARR_PASSWORD: the administrator needs a password".to_string());
+            } else if admin.password.chars().count() < MIN_PASSWORD_LENGTH {
+                report.errors.push(format!(
+                    "EARR_PASSWORD: the password must have at least {} characters",
+                    MIN_PASSWORD_LENGTH
+                ));
+            }
+        }
+
+        check_file("storage.database", &self.storage.database, &mut report);
+        check_dir("storage.cache_dir", &self.storage.cache_dir, &mut report);
+        check_dir("storage.data_dir", &self.storage.data_dir, &mut report);
+        if let Some(file) = &self.logging.file {
+            check_file("logging.file", file, &mut report);
+        }
+
+        report
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// A file is created when missing, but not its directory.
+fn check_file(setting: &str, path: &Path, report: &mut ConfigReport) {
+    if path.is_dir() {
+        report
+            .errors
+            .push(format!("{}: {} is a directory", setting, path.display()));
+        return;
+    }
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty());
+    if let Some(parent) = parent {
+        if !parent.is_dir() {
+            report.errors.push(format!(
+                "{}: directory {} does not exist",
+                setting,
+                parent.display()
+            ));
+        }
+    }
+}
+
+/// A directory is created with its parents when missing.
+fn check_dir(setting: &str, path: &Path, report: &mut ConfigReport) {
+    if path.exists() && !path.is_dir() {
+        report.errors.push(format!(
+            "{}: {} is not a directory",
+            setting,
+            path.display()
+        ));
+    }
+}
+
+/// Resolves an executable as the system would run it, from `PATH` when given by name.
+fn find_executable(program: &Path) -> Option<PathBuf> {
+    if program.components().count() > 1 {
+        return program.is_file().then(|| program.to_path_buf());
+    }
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn admin_errors(username: &str, password: &str) -> Vec<String> {
+        let config = Config {
+            admin: Some(AdminConfig {
+                username: username.to_string(),
+                password: password.to_string(),
+            }),
+            ..Config::default()
+        };
+        config
+            .validate()
+            .errors
+            .into_iter()
+            .filter(|error| error.starts_with("EARR_"))
+            .collect()
+    }
+
+    #[test]
+    fn validates_administrator_credentials() {
+        assert!(admin_errors("admin", "correct-horse-battery").is_empty());
+        assert_eq!(
+            admin_errors("admin", "short"),
+            ["EARR_PASSWORD: the password must have at least 8 characters"]
+        );
+        assert_eq!(admin_errors(" ", "").len(), 2);
+    }
+
+    #[test]
+    fn rejects_invalid_path_patterns() {
+        let mut root = LibraryRoot::new("music", env::temp_dir());
+        root.path_patterns = vec!["{artist}/{title}".to_string(), "{artsit}".to_string()];
+        let config = Config {
+            roots: vec![root],
+            ..Config::default()
+        };
+        assert_eq!(
+            config.validate().errors,
+            ["roots.music.path_patterns: {artsit}: Unknown placeholder: {artsit}"]
+        );
+    }
+
+    #[test]
+    fn rejects_roots_overlapping_through_their_canonical_paths() {
+        let dir = env::temp_dir().join(format!("earr-config-{}", std::process::id()));
+        fs::create_dir_all(dir.join("music/rock")).unwrap();
+        let config = Config {
+            roots: vec![
+                LibraryRoot::new("music", dir.join("music")),
+                LibraryRoot::new("rock", dir.join("music/rock/../rock")),
+            ],
+            ..Config::default()
+        };
+        let errors = config.validate().errors;
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(errors.contains(
+            &"roots: Library roots music and rock overlap, their audios would be gathered twice"
+                .to_string()
+        ));
+    }
+
+    #[test]
+    fn rejects_cyclic_genre_parents() {
+        let genre_errors = |parents: &[(&str, &str)]| {
+            let config = Config {
+                genres: GenreConfig {
+                    parents: parents
+                        .iter()
+                        .map(|(child, parent)| (child.to_string(), parent.to_string()))
+                        .collect(),
+                    ..GenreConfig::default()
+                },
+                ..Config::default()
+            };
+            config
+                .validate()
+                .errors
+                .into_iter()
+                .filter(|error| error.starts_with("genres."))
+                .collect::<Vec<_>>()
+        };
+        assert!(genre_errors(&[("Bebop", "Jazz"), ("Hard Bop", "Bebop")]).is_empty());
+        assert_eq!(
+            genre_errors(&[("Bebop", "Jazz"), ("Jazz", "bebop"), ("Swing", "Jazz")]),
+            ["genres.parents: bebop, jazz would be their own parent genre"]
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use axum::{
+    middleware,
+    routing::{delete, get, post, put},
+    Router,
+};
+
+use crate::{
+    application::service::{
+        self, ActivityService, FingerprintService, GenreService, LibraryService, PlaylistService,
+        ResolvedPlaylist, ScrobbleService, SearchService, SmartPlaylistService, TagEditService,
+        TranscodingService, UserService,
+    },
+    domain::entity::user::User,
+    infrastructure::repository::{
+        activity_repository::SqliteActivityRepository,
+        audio_decoder::FfmpegAudioDecoder,
+        audio_gatherer_repository::{
+            audio_parser::resilient_audio_parser::ResilientAudioParser,
+            FilesystemAudioGathererRepository,
+        },
+        audio_tag_writer::FilesystemAudioTagWriter,
+        audio_transcoder::FfmpegAudioTranscoder,
+        edit_journal_repository::FilesystemEditJournalRepository,
+        fingerprint_repository::FilesystemFingerprintRepository,
+        library_file_repository::FilesystemLibraryFileRepository,
+        library_repository::SqliteLibraryRepository,
+        playlist_repository::FilesystemPlaylistRepository,
+        scrobble_repository::SqliteScrobbleRepository,
+        scrobbler::HttpScrobbler,
+        smart_playlist_repository::FilesystemSmartPlaylistRepository,
+        transcode_cache_repository::FilesystemTranscodeCacheRepository,
+        user_repository::SqliteUserRepository,
+    },
+};
+
+mod activity;
+mod audit;
+mod auth;
+mod dto;
+mod duplicate;
+mod error;
+mod fingerprint;
+mod library;
+mod organize;
+mod playlist;
+mod scan;
+mod scrobble;
+mod search;
+mod smart_playlist;
+mod stream;
+mod subsonic;
+mod tag;
+mod transcode;
+mod user;
+
+pub use error::ApiError;
+
+pub type Library = LibraryService<
+    FilesystemAudioGathererRepository<ResilientAudioParser>,
+    SqliteLibraryRepository,
+>;
+
+pub type Activity = ActivityService<SqliteActivityRepository>;
+
+pub type Playlists = PlaylistService<FilesystemPlaylistRepository>;
+
+pub type SmartPlaylists = SmartPlaylistService<FilesystemSmartPlaylistRepository>;
+
+pub type Users = UserService<SqliteUserRepository>;
+
+pub type Scrobbles = ScrobbleService<HttpScrobbler, SqliteScrobbleRepository>;
+
+pub type Fingerprints = FingerprintService<
+    FfmpegAudioDecoder,
+    FilesystemFingerprintRepository,
+    FilesystemLibraryFileRepository,
+>;
+
+pub type TagEdits = TagEditService<FilesystemAudioTagWriter, FilesystemEditJournalRepository>;
+
+pub type Transcoding = TranscodingService<
+    FfmpegAudioTranscoder,
+    FilesystemTranscodeCacheRepository,
+    FilesystemLibraryFileRepository,
+>;
+
+/// State shared by the request handlers.
+#[derive(Clone)]
+pub struct AppState {
+    pub library: Arc<Library>,
+    pub transcoding: Arc<Transcoding>,
+    pub search: Arc<SearchService>,
+    pub genres: Arc<GenreService>,
+    pub playlists: Arc<Playlists>,
+    pub smart_playlists: Arc<SmartPlaylists>,
+    pub activity: Arc<Activity>,
+    pub users: Arc<Users>,
+    pub scrobbles: Arc<Scrobbles>,
+    pub tag_writer: Arc<FilesystemAudioTagWriter>,
+    pub tag_edits: Arc<TagEdits>,
+    pub fingerprints: Arc<Fingerprints>,
+    /// Canonical paths of the directories files may be served from.
+    library_roots: Arc<Vec<PathBuf>>,
+    /// The user making the request, set once it is authenticated.
+    user: Option<Arc<User>>,
+}
+
+impl AppState {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        library: Arc<Library>,
+        transcoding: Arc<Transcoding>,
+        playlists: Arc<Playlists>,
+        smart_playlists: Arc<SmartPlaylists>,
+        activity: Arc<Activity>,
+        users: Arc<Users>,
+        scrobbles: Arc<Scrobbles>,
+        tag_writer: Arc<FilesystemAudioTagWriter>,
+        tag_edits: Arc<TagEdits>,
+        fingerprints: Arc<Fingerprints>,
+        genres: Arc<GenreService>,
+    ) -> Self {
+        // A root that cannot be resolved matches no canonical file path, so nothing in it
+        // is served
+        let library_roots = library
+            .roots()
+            .iter()
+            .map(|root| fs::canonicalize(&root.path).unwrap_or_else(|_| root.path.clone()))
+            .collect();
+        Self {
+            library,
+            transcoding,
+            search: Arc::new(SearchService::new()),
+            genres,
+            playlists,
+            smart_playlists,
+            activity,
+            users,
+            scrobbles,
+            tag_writer,
+            tag_edits,
+            fingerprints,
+            library_roots: Arc::new(library_roots),
+            user: None,
+        }
+    }
+
+    /// The state of a request made by the user.
+    pub fn with_user(self, user: Arc<User>) -> Self {
+        Self {
+            user: Some(user),
+            ..self
+        }
+    }
+
+    /// The user making the request. Handlers are only given the state of authenticated
+    /// requests.
+    pub fn user(&self) -> &User {
+        self.user
+            .as_deref()
+            .expect("state of an unauthenticated request")
+    }
+
+    /// The current snapshot of the part of the library the user may access.
+    pub fn snapshot(&self) -> Arc<service::Library> {
+        self.library.library_within(&self.user().library_roots)
+    }
+
+    /// Playlist files in the part of the library the user may access, without the tracks
+    /// they may not.
+    pub fn playlist_files(&self) -> Result<Vec<ResolvedPlaylist>, ApiError> {
+        let library = self.library.library();
+        let user = self.user();
+        Ok(self
+            .playlists
+            .playlists(&library)?
+            .iter()
+            .filter(|playlist| user.can_access(&playlist.path))
+            .map(|playlist| ResolvedPlaylist {
+                tracks: playlist
+                    .tracks
+                    .iter()
+                    .filter(|id| {
+                        library
+                            .audio(id)
+                            .is_some_and(|audio| user.can_access(audio.path()))
+                    })
+                    .cloned()
+                    .collect(),
+                ..playlist.clone()
+            })
+            .collect())
+    }
+
+    /// Fails unless the user may scan the library and create playlists.
+    pub fn check_can_edit(&self) -> Result<(), ApiError> {
+        if self.user().role.can_edit() {
+            Ok(())
+        } else {
+            Err(ApiError::Forbidden)
+        }
+    }
+
+    pub fn check_admin(&self) -> Result<(), ApiError> {
+        if self.user().role.is_admin() {
+            Ok(())
+        } else {
+            Err(ApiError::Forbidden)
+        }
+    }
+}
+
+/// Routes of the JSON API, all under `/api` and authenticated, and of the Subsonic API under
+/// `/rest`.
+pub fn router(state: AppState) -> Router {
+    let api = Router::new()
+        .route("/tracks", get(library::tracks))
+        .route("/tracks/{id}", get(library::track))
+        .route("/tracks/{id}/stream", get(stream::stream))
+        .route("/tracks/{id}/transcode", get(transcode::transcode))
+        .route("/tracks/{id}/plays", post(activity::record_play))
+        .route("/tracks/{id}/stats", get(activity::track_stats))
+        .route("/tracks/{id}/now-playing", post(scrobble::now_playing))
+        .route("/tracks/{id}/tags", get(tag::diff).post(tag::write))
+        .route("/tracks/{id}/matches", get(fingerprint::matches))
+        .route("/history", get(activity::history))
+        .route("/annotations/{kind}", get(activity::annotations))
+        .route(
+            "/annotations/{kind}/{id}",
+            get(activity::annotation)
+                .patch(activity::update_annotation)
+                .delete(activity::delete_annotation),
+        )
+        .route("/ratings/import", post(activity::import_ratings))
+        .route("/tag-edits", get(tag::journals).post(tag::edit))
+        .route("/tag-edits/{id}/undo", post(tag::undo))
+        .route("/transcoding/profiles", get(transcode::profiles))
+        .route("/albums", get(library::albums))
+        .route("/albums/{id}", get(library::album))
+        .route("/artists", get(library::artists))
+        .route("/artists/{id}", get(library::artist))
+        .route("/genres", get(library::genres))
+        .route("/search", get(search::search))
+        .route("/audit", get(audit::audit))
+        .route("/duplicates", get(duplicate::duplicates))
+        .route("/duplicates/acoustic", get(fingerprint::duplicates))
+        .route("/query", get(library::query))
+        .route("/playlists", get(playlist::playlists))
+        .route("/playlists/{id}", get(playlist::playlist))
+        .route("/playlists/{id}/export", get(playlist::export))
+        .route(
+            "/smart-playlists",
+            get(smart_playlist::playlists).post(smart_playlist::create),
+        )
+        .route(
+            "/smart-playlists/{id}",
+            get(smart_playlist::playlist)
+                .put(smart_playlist::update)
+                .delete(smart_playlist::delete),
+        )
+        .route("/smart-playlists/{id}/export", get(smart_playlist::export))
+        .route("/covers/{id}", get(library::cover))
+        .route("/scan", get(scan::status))
+        .route("/scan", post(scan::start))
+        .route("/roots", get(scan::roots))
+        .route("/organize", post(organize::organize))
+        .route("/me", get(user::me))
+        .route("/me/password", put(user::change_password))
+        .route("/me/tokens", get(user::tokens).post(user::create_token))
+        .route("/me/tokens/{id}", delete(user::revoke_token))
+        .route(
+            "/me/subsonic-password",
+            post(user::reset_subsonic_password).delete(user::delete_subsonic_password),
+        )
+        .route("/me/scrobblers", get(scrobble::scrobblers))
+        .route(
+            "/me/scrobblers/{kind}",
+            put(scrobble::connect).delete(scrobble::disconnect),
+        )
+        .route("/me/scrobbles/queue", get(scrobble::queue))
+        .route("/users", get(user::users).post(user::create))
+        .route(
+            "/users/{id}",
+            get(user::user).patch(user::update).delete(user::delete),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::authenticate,
+        ));
+    Router::new()
+        .nest("/api", api)
+        .nest("/rest", subsonic::router())
+        .with_state(state)
+}
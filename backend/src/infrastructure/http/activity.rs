@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    application::service::Paginated,
+    domain::entity::{
+        activity::{Annotation, ItemKind, Play, Rating},
+        play_stats::PlayStats,
+    },
+};
+
+use super::{dto::PlayDto, library::page, scrobble, ApiError, AppState};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PlayInput {
+    /// Now when not given.
+    played_at: Option<DateTime<Utc>>,
+    /// In seconds.
+    listened: Option<f64>,
+    client: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryParams {
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+/// Changes to an annotation. Fields that are not given are left as they are.
+#[derive(Debug, Deserialize)]
+pub struct AnnotationInput {
+    /// From 1 to 5 stars, 0 removing the rating.
+    rating: Option<u8>,
+    favorite: Option<bool>,
+}
+
+pub async fn record_play(
+    state: AppState,
+    Path(id): Path<String>,
+    input: Option<Json<PlayInput>>,
+) -> Result<(StatusCode, Json<PlayDto>), ApiError> {
+    let Json(input) = input.unwrap_or_default();
+    let listened = input
+        .listened
+        .map(Duration::try_from_secs_f64)
+        .transpose()
+        .map_err(|_| ApiError::BadRequest("listened must be a positive number of seconds"))?;
+    let library = state.snapshot();
+    let play = state.activity.record_play(
+        &state.user().id,
+        &library,
+        Play {
+            audio_id: id,
+            played_at: input.played_at.unwrap_or_else(Utc::now),
+            listened,
+            client: input.client,
+        },
+    )?;
+    if let Some(audio) = library.audio(&play.audio_id) {
+        scrobble::scrobble_play(&state, audio, &play);
+    }
+    Ok((StatusCode::CREATED, Json(PlayDto::new(&library, play))))
+}
+
+/// Plays from the most recent.
+pub async fn history(
+    state: AppState,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<Paginated<PlayDto>>, ApiError> {
+    let library = state.snapshot();
+    let history = state
+        .activity
+        .history(&state.user().id, page(params.offset, params.limit))?;
+    Ok(Json(Paginated {
+        total: history.total,
+        offset: history.offset,
+        items: history
+            .items
+            .into_iter()
+            .map(|play| PlayDto::new(&library, play))
+            .collect(),
+    }))
+}
+
+pub async fn track_stats(
+    state: AppState,
+    Path(id): Path<String>,
+) -> Result<Json<PlayStats>, ApiError> {
+    state
+        .snapshot()
+        .audio(&id)
+        .ok_or(ApiError::NotFound("Track"))?;
+    let stats = state.activity.stats(&state.user().id)?;
+    Ok(Json(stats.get(&id).copied().unwrap_or_default()))
+}
+
+/// Items of the kind that are rated or favorites.
+pub async fn annotations(
+    state: AppState,
+    Path(kind): Path<ItemKind>,
+) -> Result<Json<Vec<Annotation>>, ApiError> {
+    Ok(Json(state.activity.annotations(&state.user().id, kind)?))
+}
+
+pub async fn annotation(
+    state: AppState,
+    Path((kind, id)): Path<(ItemKind, String)>,
+) -> Result<Json<Annotation>, ApiError> {
+    Ok(Json(state.activity.annotation(
+        &state.user().id,
+        kind,
+        &id,
+    )?))
+}
+
+pub async fn update_annotation(
+    state: AppState,
+    Path((kind, id)): Path<(ItemKind, String)>,
+    Json(input): Json<AnnotationInput>,
+) -> Result<Json<Annotation>, ApiError> {
+    let library = state.snapshot();
+    let user_id = &state.user().id;
+    let mut annotation = state.activity.annotation(user_id, kind, &id)?;
+    if let Some(rating) = input.rating {
+        let rating = match rating {
+            0 => None,
+            stars => Some(
+                Rating::try_from(stars)
+                    .map_err(|_| ApiError::BadRequest("rating must be between 0 and 5"))?,
+            ),
+        };
+        annotation = state.activity.rate(user_id, &library, kind, &id, rating)?;
+    }
+    if let Some(favorite) = input.favorite {
+        annotation = state
+            .activity
+            .favorite(user_id, &library, kind, &id, favorite)?;
+    }
+    Ok(Json(annotation))
+}
+
+/// Removes the rating and favorite mark of an item.
+pub async fn delete_annotation(
+    state: AppState,
+    Path((kind, id)): Path<(ItemKind, String)>,
+) -> Result<StatusCode, ApiError> {
+    let library = state.snapshot();
+    let user_id = &state.user().id;
+    state.activity.rate(user_id, &library, kind, &id, None)?;
+    state
+        .activity
+        .favorite(user_id, &library, kind, &id, false)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Rates tracks from the POPM and FMPS ratings of their tags, unless already rated.
+pub async fn import_ratings(state: AppState) -> Result<Json<Value>, ApiError> {
+    let library = state.snapshot();
+    let imported = state
+        .activity
+        .import_tag_ratings(&state.user().id, &library)?;
+    Ok(Json(json!({ "imported": imported })))
+}
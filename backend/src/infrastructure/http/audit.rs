@@ -0,0 +1,51 @@
+use std::io;
+
+use axum::{
+    extract::Query,
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use tokio::task;
+
+use crate::application::service::{AuditService, AuditServiceOptions};
+
+use super::{ApiError, AppState};
+
+#[derive(Debug, Deserialize)]
+pub struct AuditParams {
+    #[serde(default)]
+    format: AuditFormat,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditFormat {
+    #[default]
+    Json,
+    /// A human-readable table.
+    Text,
+}
+
+/// Metadata problems of the tracks the user sees. Covers are decoded to check their size,
+/// so the audit runs on a blocking thread.
+pub async fn audit(
+    state: AppState,
+    Query(params): Query<AuditParams>,
+) -> Result<Response, ApiError> {
+    let report = task::spawn_blocking(move || {
+        let library = state.snapshot();
+        AuditService::new(AuditServiceOptions::default()).audit(library.audios())
+    })
+    .await
+    .map_err(io::Error::other)?;
+    Ok(match params.format {
+        AuditFormat::Json => Json(report).into_response(),
+        AuditFormat::Text => (
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            report.to_string(),
+        )
+            .into_response(),
+    })
+}
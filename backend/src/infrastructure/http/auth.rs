@@ -0,0 +1,73 @@
+use std::{io, sync::Arc};
+
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use tokio::task;
+
+use crate::{application::service::UserServiceError, domain::entity::user::User};
+
+use super::{ApiError, AppState, Users};
+
+/// Authenticates requests with HTTP basic authentication or with an API token sent as a
+/// bearer token. The user is then found in the [`AppState`] handlers are given.
+pub async fn authenticate(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let authorization = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .ok_or(ApiError::Unauthorized)?;
+    // Passwords are hashed with Argon2, which is slow by design
+    let users = Arc::clone(&state.users);
+    let user = task::spawn_blocking(move || user(&users, &authorization))
+        .await
+        .map_err(io::Error::other)??;
+    request.extensions_mut().insert(Arc::new(user));
+    Ok(next.run(request).await)
+}
+
+fn user(users: &Users, authorization: &str) -> Result<User, ApiError> {
+    let (scheme, credentials) = authorization
+        .split_once(' ')
+        .ok_or(ApiError::Unauthorized)?;
+    let credentials = credentials.trim();
+    let user = if scheme.eq_ignore_ascii_case("bearer") {
+        users.authenticate_token(credentials)
+    } else if scheme.eq_ignore_ascii_case("basic") {
+        let decoded = STANDARD
+            .decode(credentials)
+            .ok()
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+            .ok_or(ApiError::Unauthorized)?;
+        let (username, password) = decoded.split_once(':').ok_or(ApiError::Unauthorized)?;
+        users.authenticate(username, password)
+    } else {
+        return Err(ApiError::Unauthorized);
+    };
+    user.map_err(|err| match err {
+        UserServiceError::InvalidCredentials => ApiError::Unauthorized,
+        err => err.into(),
+    })
+}
+
+/// The state of a request, along with the user [`authenticate`] found.
+impl FromRequestParts<AppState> for AppState {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, ApiError> {
+        let user = parts
+            .extensions
+            .get::<Arc<User>>()
+            .ok_or(ApiError::Unauthorized)?;
+        Ok(state.clone().with_user(Arc::clone(user)))
+    }
+}
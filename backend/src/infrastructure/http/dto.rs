@@ -0,0 +1,583 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{
+    application::service::{
+        ArtistSummary, BatchEditReport, DuplicateGroup, DuplicateStrategy, FileEditReport,
+        FileEditResult, FileOperationKind, FileOperationReport, GenreNode, Library,
+        ResolvedPlaylist,
+    },
+    domain::entity::{
+        activity::Play,
+        album::Album,
+        audio::{
+            cover::Cover,
+            field::AudioField,
+            tag_change::{TagChange, TagValue},
+            Audio,
+        },
+        edit_journal::{EditJournal, EditJournalState},
+        library_root::LibraryRoot,
+        playlist::PlaylistEntry,
+        scrobble::{Listen, QueuedListen, ScrobblerAccount, ScrobblerKind},
+        smart_playlist::SmartPlaylist,
+        user::{ApiToken, Role, User},
+    },
+};
+
+#[derive(Debug, Serialize)]
+pub struct TrackDto {
+    pub id: String,
+    pub title: String,
+    pub artist: String,
+    pub artist_id: String,
+    pub album: String,
+    pub album_id: Option<String>,
+    pub album_artist: String,
+    pub album_artist_id: String,
+    pub year: Option<u16>,
+    pub genre: String,
+    pub track_number: Option<u16>,
+    pub disc_number: Option<u16>,
+    pub compilation: bool,
+    /// In seconds.
+    pub duration: Option<f64>,
+    /// In kbit/s.
+    pub bitrate: Option<u32>,
+    /// Lowercase file extension.
+    pub format: String,
+    pub cover_id: Option<String>,
+    /// Name of the library root the track was gathered from.
+    pub root: Option<String>,
+}
+
+impl TrackDto {
+    pub fn new(library: &Library, audio: &Audio) -> Self {
+        Self {
+            id: audio.id(),
+            title: audio.title().name().clone(),
+            artist: audio.artist().name().clone(),
+            artist_id: audio.artist().id(),
+            album: audio.album_title().name().clone(),
+            album_id: library.album_of(audio).map(Album::id),
+            album_artist: audio.album_artist().name().clone(),
+            album_artist_id: audio.album_artist().id(),
+            year: audio.year().map(|year| year.0),
+            genre: audio.genre().name().clone(),
+            track_number: *audio.track_number(),
+            disc_number: *audio.disc_number(),
+            compilation: *audio.compilation(),
+            duration: audio.duration().map(|duration| duration.as_secs_f64()),
+            bitrate: *audio.bitrate(),
+            format: audio
+                .path()
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+            cover_id: cover_id(audio.album_cover()),
+            root: audio.root().clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchHitDto {
+    pub score: f64,
+    #[serde(flatten)]
+    pub track: TrackDto,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlbumDto {
+    pub id: String,
+    pub title: String,
+    pub artist: String,
+    pub artist_id: String,
+    pub year: Option<u16>,
+    pub compilation: bool,
+    pub genres: Vec<String>,
+    pub track_count: usize,
+    /// Total of the known track durations, in seconds.
+    pub duration: f64,
+    /// Cover of the first track that has one.
+    pub cover_id: Option<String>,
+}
+
+impl From<&Album> for AlbumDto {
+    fn from(album: &Album) -> Self {
+        let mut genres = Vec::new();
+        for track in album.tracks() {
+            if !genres.contains(track.genre().name()) {
+                genres.push(track.genre().name().clone());
+            }
+        }
+        Self {
+            id: album.id(),
+            title: album.title().name().clone(),
+            artist: album.artist().name().clone(),
+            artist_id: album.artist().id(),
+            year: album.year().map(|year| year.0),
+            compilation: *album.compilation(),
+            genres,
+            track_count: album.tracks().len(),
+            duration: album
+                .tracks()
+                .iter()
+                .filter_map(|track| *track.duration())
+                .map(|duration| duration.as_secs_f64())
+                .fold(0.0, |total, duration| total + duration),
+            cover_id: album
+                .tracks()
+                .iter()
+                .find_map(|track| cover_id(track.album_cover())),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlbumDetailDto {
+    #[serde(flatten)]
+    pub album: AlbumDto,
+    pub tracks: Vec<TrackDto>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArtistDto {
+    pub id: String,
+    pub name: String,
+    pub album_count: usize,
+    pub track_count: usize,
+}
+
+impl From<&ArtistSummary> for ArtistDto {
+    fn from(summary: &ArtistSummary) -> Self {
+        Self {
+            id: summary.artist.id(),
+            name: summary.artist.name().clone(),
+            album_count: summary.album_count,
+            track_count: summary.track_count,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArtistDetailDto {
+    #[serde(flatten)]
+    pub artist: ArtistDto,
+    /// Albums of the artist, including the ones they only appear on.
+    pub albums: Vec<AlbumDto>,
+}
+
+/// A genre with the genres configured as its children.
+#[derive(Debug, Serialize)]
+pub struct GenreNodeDto {
+    pub name: String,
+    pub track_count: usize,
+    /// Tracks of the genre and of its descendants.
+    pub total_track_count: usize,
+    pub children: Vec<GenreNodeDto>,
+}
+
+impl From<GenreNode> for GenreNodeDto {
+    fn from(node: GenreNode) -> Self {
+        Self {
+            name: node.genre.name().clone(),
+            track_count: node.track_count,
+            total_track_count: node.total_track_count,
+            children: node.children.into_iter().map(Self::from).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlaylistDto {
+    pub id: String,
+    pub name: String,
+    /// Extension of the file.
+    pub format: String,
+    pub track_count: usize,
+    pub unresolved_count: usize,
+    /// Total of the known durations of the resolved tracks, in seconds.
+    pub duration: f64,
+}
+
+impl PlaylistDto {
+    pub fn new(library: &Library, playlist: &ResolvedPlaylist) -> Self {
+        Self {
+            id: playlist.id.clone(),
+            name: playlist.name.clone(),
+            format: playlist
+                .path
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+            track_count: playlist.tracks.len(),
+            unresolved_count: playlist.unresolved.len(),
+            duration: playlist
+                .tracks
+                .iter()
+                .filter_map(|id| library.audio(id).and_then(|audio| *audio.duration()))
+                .map(|duration| duration.as_secs_f64())
+                .fold(0.0, |total, duration| total + duration),
+        }
+    }
+}
+
+/// An entry that points to no track, as written in the playlist.
+#[derive(Debug, Serialize)]
+pub struct PlaylistEntryDto {
+    pub location: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// In seconds.
+    pub duration: Option<f64>,
+}
+
+impl From<&PlaylistEntry> for PlaylistEntryDto {
+    fn from(entry: &PlaylistEntry) -> Self {
+        Self {
+            location: entry.location.clone(),
+            title: entry.title.clone(),
+            artist: entry.artist.clone(),
+            album: entry.album.clone(),
+            duration: entry.duration.map(|duration| duration.as_secs_f64()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlaylistDetailDto {
+    #[serde(flatten)]
+    pub playlist: PlaylistDto,
+    pub tracks: Vec<TrackDto>,
+    pub unresolved: Vec<PlaylistEntryDto>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SmartPlaylistDto {
+    #[serde(flatten)]
+    pub playlist: SmartPlaylist,
+    pub track_count: usize,
+    /// Total of the known track durations, in seconds.
+    pub duration: f64,
+}
+
+impl SmartPlaylistDto {
+    pub fn new(playlist: SmartPlaylist, tracks: &[&Audio]) -> Self {
+        Self {
+            playlist,
+            track_count: tracks.len(),
+            duration: tracks
+                .iter()
+                .filter_map(|audio| *audio.duration())
+                .map(|duration| duration.as_secs_f64())
+                .fold(0.0, |total, duration| total + duration),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SmartPlaylistDetailDto {
+    #[serde(flatten)]
+    pub playlist: SmartPlaylistDto,
+    pub tracks: Vec<TrackDto>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlayDto {
+    pub track_id: String,
+    pub played_at: DateTime<Utc>,
+    /// In seconds.
+    pub listened: Option<f64>,
+    pub client: Option<String>,
+    /// `None` once the track left the library.
+    pub track: Option<TrackDto>,
+}
+
+impl PlayDto {
+    pub fn new(library: &Library, play: Play) -> Self {
+        Self {
+            track: library
+                .audio(&play.audio_id)
+                .map(|audio| TrackDto::new(library, audio)),
+            track_id: play.audio_id,
+            played_at: play.played_at,
+            listened: play.listened.map(|listened| listened.as_secs_f64()),
+            client: play.client,
+        }
+    }
+}
+
+/// A user, without their password hash.
+#[derive(Debug, Serialize)]
+pub struct UserDto {
+    pub id: String,
+    pub username: String,
+    pub role: Role,
+    pub library_roots: Vec<PathBuf>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<User> for UserDto {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            role: user.role,
+            library_roots: user.library_roots,
+            created_at: user.created_at,
+        }
+    }
+}
+
+/// A token, without the hash of its secret.
+#[derive(Debug, Serialize)]
+pub struct TokenDto {
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiToken> for TokenDto {
+    fn from(token: ApiToken) -> Self {
+        Self {
+            id: token.id,
+            name: token.name,
+            created_at: token.created_at,
+            last_used_at: token.last_used_at,
+        }
+    }
+}
+
+/// A token just created, with the secret that is not shown again.
+#[derive(Debug, Serialize)]
+pub struct CreatedTokenDto {
+    #[serde(flatten)]
+    pub token: TokenDto,
+    pub secret: String,
+}
+
+/// A Subsonic password just created. It can only be seen again by creating another one.
+#[derive(Debug, Serialize)]
+pub struct SubsonicPasswordDto {
+    pub password: String,
+}
+
+/// A library root with its scan rules.
+#[derive(Debug, Serialize)]
+pub struct LibraryRootDto {
+    #[serde(flatten)]
+    pub root: LibraryRoot,
+    pub track_count: usize,
+}
+
+impl LibraryRootDto {
+    pub fn new(library: &Library, root: &LibraryRoot) -> Self {
+        Self {
+            track_count: library
+                .audios()
+                .filter(|audio| audio.root().as_deref() == Some(root.name.as_str()))
+                .count(),
+            root: root.clone(),
+        }
+    }
+}
+
+/// A scrobbling account, without its credentials.
+#[derive(Debug, Serialize)]
+pub struct ScrobblerDto {
+    pub kind: ScrobblerKind,
+    pub url: String,
+    /// Listens waiting to be submitted to the service.
+    pub queued: usize,
+    /// Why submitting stopped, when the service refused the credentials. Listens are kept
+    /// until the account is connected again.
+    pub paused: Option<String>,
+}
+
+impl ScrobblerDto {
+    pub fn new(
+        account: &ScrobblerAccount,
+        queue: &[QueuedListen],
+        paused: &BTreeMap<ScrobblerKind, String>,
+    ) -> Self {
+        Self {
+            kind: account.kind(),
+            url: account.url().to_string(),
+            queued: queue
+                .iter()
+                .filter(|listen| listen.kind == account.kind())
+                .count(),
+            paused: paused.get(&account.kind()).cloned(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct QueuedListenDto {
+    pub id: i64,
+    pub kind: ScrobblerKind,
+    #[serde(flatten)]
+    pub listen: Listen,
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+}
+
+impl From<QueuedListen> for QueuedListenDto {
+    fn from(queued: QueuedListen) -> Self {
+        Self {
+            id: queued.id,
+            kind: queued.kind,
+            listen: queued.listen,
+            attempts: queued.attempts,
+            next_attempt_at: queued.next_attempt_at,
+            last_error: queued.last_error,
+        }
+    }
+}
+
+/// A change of a tag of a file, with pictures described rather than sent.
+#[derive(Debug, Serialize)]
+pub struct TagChangeDto {
+    pub field: AudioField,
+    /// `None` when the tag is absent.
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl From<&TagChange> for TagChangeDto {
+    fn from(change: &TagChange) -> Self {
+        Self {
+            field: change.field,
+            before: change.before.as_ref().map(TagValue::to_string),
+            after: change.after.as_ref().map(TagValue::to_string),
+        }
+    }
+}
+
+/// The outcome of a batch edit or of its undo, file by file.
+#[derive(Debug, Serialize)]
+pub struct BatchEditReportDto {
+    /// `None` for dry runs and batches that wrote nothing, which cannot be undone.
+    pub journal: Option<EditJournalDto>,
+    pub files: Vec<FileEditReportDto>,
+}
+
+impl From<&BatchEditReport> for BatchEditReportDto {
+    fn from(report: &BatchEditReport) -> Self {
+        Self {
+            journal: report.journal.as_ref().map(EditJournalDto::from),
+            files: report.files.iter().map(FileEditReportDto::from).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileEditReportDto {
+    pub path: PathBuf,
+    /// `edited`, `unchanged` or `failed`.
+    pub status: &'static str,
+    pub changes: Vec<TagChangeDto>,
+    pub error: Option<String>,
+}
+
+impl From<&FileEditReport> for FileEditReportDto {
+    fn from(report: &FileEditReport) -> Self {
+        let (status, changes, error) = match &report.result {
+            FileEditResult::Edited(changes) => ("edited", changes.as_slice(), None),
+            FileEditResult::Unchanged => ("unchanged", [].as_slice(), None),
+            FileEditResult::Failed(err) => ("failed", [].as_slice(), Some(err.clone())),
+        };
+        Self {
+            path: report.path.clone(),
+            status,
+            changes: changes.iter().map(TagChangeDto::from).collect(),
+            error,
+        }
+    }
+}
+
+/// A batch edit that can be undone, without its changes.
+#[derive(Debug, Serialize)]
+pub struct EditJournalDto {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub description: String,
+    /// `pending` when the batch was interrupted, in which case undoing it restores every
+    /// file it planned to write.
+    pub state: EditJournalState,
+    pub file_count: usize,
+}
+
+impl From<&EditJournal> for EditJournalDto {
+    fn from(journal: &EditJournal) -> Self {
+        Self {
+            id: journal.id().clone(),
+            created_at: *journal.created_at(),
+            description: journal.description().clone(),
+            state: *journal.state(),
+            file_count: journal.entries().len(),
+        }
+    }
+}
+
+/// A file moved or copied by the organizer, or that would be in a dry run.
+#[derive(Debug, Serialize)]
+pub struct FileOperationDto {
+    pub kind: FileOperationKind,
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub error: Option<String>,
+}
+
+impl From<&FileOperationReport> for FileOperationDto {
+    fn from(report: &FileOperationReport) -> Self {
+        Self {
+            kind: report.operation.kind,
+            source: report.operation.source.clone(),
+            target: report.operation.target.clone(),
+            error: report.error.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroupDto {
+    /// How the copies were found.
+    pub strategy: DuplicateStrategy,
+    /// The copy recommended to keep, by format then bitrate.
+    pub keep: TrackDto,
+    pub duplicates: Vec<TrackDto>,
+}
+
+impl DuplicateGroupDto {
+    pub fn new(library: &Library, group: &DuplicateGroup) -> Self {
+        Self {
+            strategy: group.strategy,
+            keep: TrackDto::new(library, &group.keep),
+            duplicates: group
+                .duplicates
+                .iter()
+                .map(|audio| TrackDto::new(library, audio))
+                .collect(),
+        }
+    }
+}
+
+/// A track found to be the same recording as another one.
+#[derive(Debug, Serialize)]
+pub struct MatchDto {
+    /// Similarity of the fingerprints, from 0 to 1.
+    pub similarity: f64,
+    pub track: TrackDto,
+}
+
+/// `None` for the default cover.
+fn cover_id(cover: &Cover) -> Option<String> {
+    (!cover.is_default()).then(|| cover.id())
+}
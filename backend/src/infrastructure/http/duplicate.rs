@@ -0,0 +1,42 @@
+use std::io;
+
+use axum::{extract::Query, Json};
+use serde::Deserialize;
+use tokio::task;
+
+use crate::{
+    application::service::{DuplicateService, DuplicateServiceOptions, DuplicateStrategy},
+    infrastructure::repository::library_file_repository::FilesystemLibraryFileRepository,
+};
+
+use super::{dto::DuplicateGroupDto, ApiError, AppState};
+
+#[derive(Debug, Deserialize)]
+pub struct DuplicateParams {
+    /// The only strategy to run, all of them when not given.
+    strategy: Option<DuplicateStrategy>,
+}
+
+/// Groups of copies of the same tracks among the ones the user sees, with the copy
+/// recommended to keep. Files are hashed, so groups are found on a blocking thread.
+pub async fn duplicates(
+    state: AppState,
+    Query(params): Query<DuplicateParams>,
+) -> Result<Json<Vec<DuplicateGroupDto>>, ApiError> {
+    let groups = task::spawn_blocking(move || {
+        let library = state.snapshot();
+        let mut options = DuplicateServiceOptions::default();
+        if let Some(strategy) = params.strategy {
+            options.strategies = vec![strategy];
+        }
+        let audios = library.audios().cloned().collect::<Vec<_>>();
+        DuplicateService::new(FilesystemLibraryFileRepository, options)
+            .find(&audios)
+            .iter()
+            .map(|group| DuplicateGroupDto::new(&library, group))
+            .collect()
+    })
+    .await
+    .map_err(io::Error::other)?;
+    Ok(Json(groups))
+}
@@ -0,0 +1,148 @@
+use std::io;
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use thiserror::Error;
+
+use crate::{
+    application::service::{
+        ActivityServiceError, AudioQueryError, FingerprintServiceError, LibraryServiceError,
+        PathTemplateError, PlaylistServiceError, ScrobbleServiceError, SearchServiceError,
+        SmartPlaylistServiceError, TagEditServiceError, TranscodingServiceError, UserServiceError,
+    },
+    domain::entity::scrobble::ScrobbleError,
+    infrastructure::repository::audio_tag_writer::{
+        FilesystemAudioTagWriterError, TagDocumentError,
+    },
+};
+
+#[derive(Error, Debug)]
+pub enum ApiError {
+    #[error("Invalid request: {0}")]
+    BadRequest(&'static str),
+    #[error("{0} not found")]
+    NotFound(&'static str),
+    #[error("Authentication required")]
+    Unauthorized,
+    #[error("Access denied")]
+    Forbidden,
+    #[error("Failed to read file: {0}")]
+    IO(#[from] io::Error),
+    #[error(transparent)]
+    Library(#[from] LibraryServiceError),
+    #[error(transparent)]
+    Transcoding(#[from] TranscodingServiceError),
+    #[error("Invalid query: {0}")]
+    Search(#[from] SearchServiceError),
+    #[error("Invalid query: {0}")]
+    Query(#[from] AudioQueryError),
+    #[error(transparent)]
+    Playlist(#[from] PlaylistServiceError),
+    #[error(transparent)]
+    SmartPlaylist(#[from] SmartPlaylistServiceError),
+    #[error(transparent)]
+    Activity(#[from] ActivityServiceError),
+    #[error(transparent)]
+    User(#[from] UserServiceError),
+    #[error(transparent)]
+    Scrobble(#[from] ScrobbleServiceError),
+    #[error("Invalid template: {0}")]
+    Template(#[from] PathTemplateError),
+    #[error(transparent)]
+    Fingerprint(#[from] FingerprintServiceError),
+    #[error(transparent)]
+    TagEdit(#[from] TagEditServiceError),
+    #[error(transparent)]
+    TagWriter(#[from] FilesystemAudioTagWriterError),
+}
+
+impl ApiError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::IO(err) if err.kind() == io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+            Self::IO(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Library(LibraryServiceError::ScanInProgress) => StatusCode::CONFLICT,
+            Self::Library(LibraryServiceError::UnknownRoot(_)) => StatusCode::NOT_FOUND,
+            Self::Library(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Transcoding(TranscodingServiceError::UnknownProfile(_)) => {
+                StatusCode::BAD_REQUEST
+            }
+            Self::Transcoding(TranscodingServiceError::Busy) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Transcoding(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Search(_) | Self::Query(_) => StatusCode::BAD_REQUEST,
+            Self::Playlist(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::SmartPlaylist(SmartPlaylistServiceError::NotFound(_)) => StatusCode::NOT_FOUND,
+            Self::SmartPlaylist(SmartPlaylistServiceError::Repository(_)) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            Self::SmartPlaylist(SmartPlaylistServiceError::Forbidden(_)) => StatusCode::FORBIDDEN,
+            Self::SmartPlaylist(_) => StatusCode::BAD_REQUEST,
+            Self::Activity(ActivityServiceError::NotFound(..)) => StatusCode::NOT_FOUND,
+            Self::Activity(ActivityServiceError::Repository(_)) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            Self::User(UserServiceError::NotFound(_) | UserServiceError::TokenNotFound(_)) => {
+                StatusCode::NOT_FOUND
+            }
+            Self::User(UserServiceError::UsernameTaken(_) | UserServiceError::LastAdmin) => {
+                StatusCode::CONFLICT
+            }
+            // Only a wrong current password when changing it, which must not prompt for
+            // credentials again
+            Self::User(
+                UserServiceError::InvalidCredentials | UserServiceError::NoSubsonicPassword,
+            ) => StatusCode::FORBIDDEN,
+            Self::User(UserServiceError::User(_)) => StatusCode::BAD_REQUEST,
+            Self::User(UserServiceError::Repository(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Scrobble(ScrobbleServiceError::NotConnected(_)) => StatusCode::NOT_FOUND,
+            Self::Scrobble(ScrobbleServiceError::Scrobble(ScrobbleError::Unavailable(_))) => {
+                StatusCode::BAD_GATEWAY
+            }
+            // Credentials refused by the service, which must not prompt for the ones of
+            // this server
+            Self::Scrobble(ScrobbleServiceError::Scrobble(_)) => StatusCode::BAD_REQUEST,
+            Self::Scrobble(ScrobbleServiceError::Repository(_)) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            Self::Template(_) => StatusCode::BAD_REQUEST,
+            // The audio of the track could not be read
+            Self::Fingerprint(
+                FingerprintServiceError::Decoder(_) | FingerprintServiceError::Fingerprint(_),
+            ) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Fingerprint(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::TagEdit(TagEditServiceError::JournalNotFound(_)) => StatusCode::NOT_FOUND,
+            Self::TagEdit(TagEditServiceError::Journal(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::TagWriter(FilesystemAudioTagWriterError::TagDocument(
+                TagDocumentError::UnsupportedFormat(_),
+            )) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::TagWriter(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Errors are sent as `{"error": "<message>"}`. Clients are asked for basic authentication
+/// when they are not authenticated.
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        if status.is_server_error() {
+            log::error!("Failed to handle request: {}", self);
+        }
+        let mut response = (status, Json(json!({ "error": self.to_string() }))).into_response();
+        if status == StatusCode::UNAUTHORIZED {
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                header::HeaderValue::from_static("Basic realm=\"earr\""),
+            );
+        }
+        response
+    }
+}
@@ -0,0 +1,58 @@
+use std::io;
+
+use axum::{extract::Path, Json};
+use tokio::task;
+
+use super::{
+    dto::{MatchDto, TrackDto},
+    ApiError, AppState,
+};
+
+/// Groups of tracks the user sees that are the same recording by their acoustic
+/// fingerprints, whatever their tags. Audios not fingerprinted yet are decoded, so groups
+/// are found on a blocking thread.
+pub async fn duplicates(state: AppState) -> Result<Json<Vec<Vec<TrackDto>>>, ApiError> {
+    let groups = task::spawn_blocking(move || {
+        let library = state.snapshot();
+        let audios = library.audios().cloned().collect::<Vec<_>>();
+        state
+            .fingerprints
+            .find_duplicates(&audios)
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .map(|audio| TrackDto::new(&library, audio))
+                    .collect()
+            })
+            .collect()
+    })
+    .await
+    .map_err(io::Error::other)?;
+    Ok(Json(groups))
+}
+
+/// Tracks the user sees that are the same recording as the track, the most similar first.
+pub async fn matches(
+    state: AppState,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<MatchDto>>, ApiError> {
+    let matches = task::spawn_blocking(move || {
+        let library = state.snapshot();
+        let audio = library.audio(&id).ok_or(ApiError::NotFound("Track"))?;
+        let candidates = library.audios().cloned().collect::<Vec<_>>();
+        let matches = state
+            .fingerprints
+            .identify(audio, &candidates)?
+            .into_iter()
+            .map(|(candidate, similarity)| MatchDto {
+                similarity,
+                track: TrackDto::new(&library, candidate),
+            })
+            .collect();
+        Ok::<_, ApiError>(matches)
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok(Json(matches))
+}
@@ -0,0 +1,287 @@
+use std::io;
+
+use axum::{
+    extract::{Path, Query},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use tokio::task;
+
+use crate::application::service::{
+    AlbumFilter, AlbumSort, ArtistFilter, ArtistSort, AudioQuery, GenreFilter, GenreSort,
+    GenreSummary, LibraryServiceError, Page, Paginated, SortOrder, TrackFilter, TrackSort,
+};
+
+use super::{
+    dto::{AlbumDetailDto, AlbumDto, ArtistDetailDto, ArtistDto, GenreNodeDto, TrackDto},
+    ApiError, AppState,
+};
+
+/// Largest page a client may request.
+const MAX_LIMIT: usize = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct TrackParams {
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+    #[serde(default)]
+    sort: TrackSort,
+    #[serde(default)]
+    order: SortOrder,
+    q: Option<String>,
+    artist_id: Option<String>,
+    album_id: Option<String>,
+    genre: Option<String>,
+    min_year: Option<u16>,
+    max_year: Option<u16>,
+    compilation: Option<bool>,
+    root: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QueryParams {
+    q: String,
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlbumParams {
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+    #[serde(default)]
+    sort: AlbumSort,
+    #[serde(default)]
+    order: SortOrder,
+    q: Option<String>,
+    artist_id: Option<String>,
+    genre: Option<String>,
+    min_year: Option<u16>,
+    max_year: Option<u16>,
+    compilation: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArtistParams {
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+    #[serde(default)]
+    sort: ArtistSort,
+    #[serde(default)]
+    order: SortOrder,
+    q: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GenreParams {
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+    #[serde(default)]
+    sort: GenreSort,
+    #[serde(default)]
+    order: SortOrder,
+    q: Option<String>,
+    /// Whether genres are nested under their configured parents rather than listed.
+    #[serde(default)]
+    tree: bool,
+}
+
+pub(super) fn page(offset: usize, limit: Option<usize>) -> Page {
+    Page {
+        offset,
+        limit: limit.unwrap_or(Page::default().limit).min(MAX_LIMIT),
+    }
+}
+
+pub async fn tracks(
+    state: AppState,
+    Query(params): Query<TrackParams>,
+) -> Json<Paginated<TrackDto>> {
+    let library = state.snapshot();
+    let filter = TrackFilter {
+        query: params.q,
+        artist_id: params.artist_id,
+        album_id: params.album_id,
+        genre: params.genre,
+        min_year: params.min_year,
+        max_year: params.max_year,
+        compilation: params.compilation,
+        root: params.root,
+    };
+    let tracks = library
+        .tracks(
+            &filter,
+            params.sort,
+            params.order,
+            page(params.offset, params.limit),
+        )
+        .map(|audio| TrackDto::new(&library, audio));
+    Json(tracks)
+}
+
+/// Tracks selected with the query language, see [`AudioQuery::parse`], which runs against
+/// the stored library. Pages apply to the result of the query, after its own limit.
+pub async fn query(
+    state: AppState,
+    Query(params): Query<QueryParams>,
+) -> Result<Json<Paginated<TrackDto>>, ApiError> {
+    let mut query = AudioQuery::parse(&params.q)?;
+    let page = page(params.offset, params.limit);
+    let tracks = task::spawn_blocking(move || {
+        let library = state.snapshot();
+        // The store holds every track, so the limit applies once those the user may not
+        // access are left out
+        let limit = query.limit.take().unwrap_or(usize::MAX);
+        let stored = state
+            .library
+            .repository()
+            .query(&query)
+            .map_err(|err| LibraryServiceError::Repository(err.to_string()))?;
+        let audios = stored
+            .iter()
+            .filter_map(|audio| library.audio(&audio.id()))
+            .take(limit)
+            .collect::<Vec<_>>();
+        Ok::<_, ApiError>(Paginated {
+            total: audios.len(),
+            offset: page.offset,
+            items: audios
+                .into_iter()
+                .skip(page.offset)
+                .take(page.limit)
+                .map(|audio| TrackDto::new(&library, audio))
+                .collect(),
+        })
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok(Json(tracks))
+}
+
+pub async fn track(state: AppState, Path(id): Path<String>) -> Result<Json<TrackDto>, ApiError> {
+    let library = state.snapshot();
+    let audio = library.audio(&id).ok_or(ApiError::NotFound("Track"))?;
+    Ok(Json(TrackDto::new(&library, audio)))
+}
+
+pub async fn albums(
+    state: AppState,
+    Query(params): Query<AlbumParams>,
+) -> Json<Paginated<AlbumDto>> {
+    let filter = AlbumFilter {
+        query: params.q,
+        artist_id: params.artist_id,
+        genre: params.genre,
+        min_year: params.min_year,
+        max_year: params.max_year,
+        compilation: params.compilation,
+    };
+    let albums = state
+        .snapshot()
+        .list_albums(
+            &filter,
+            params.sort,
+            params.order,
+            page(params.offset, params.limit),
+        )
+        .map(AlbumDto::from);
+    Json(albums)
+}
+
+pub async fn album(
+    state: AppState,
+    Path(id): Path<String>,
+) -> Result<Json<AlbumDetailDto>, ApiError> {
+    let library = state.snapshot();
+    let album = library.album(&id).ok_or(ApiError::NotFound("Album"))?;
+    Ok(Json(AlbumDetailDto {
+        album: AlbumDto::from(album),
+        tracks: album
+            .tracks()
+            .iter()
+            .map(|audio| TrackDto::new(&library, audio))
+            .collect(),
+    }))
+}
+
+pub async fn artists(
+    state: AppState,
+    Query(params): Query<ArtistParams>,
+) -> Json<Paginated<ArtistDto>> {
+    let filter = ArtistFilter { query: params.q };
+    let artists = state
+        .snapshot()
+        .list_artists(
+            &filter,
+            params.sort,
+            params.order,
+            page(params.offset, params.limit),
+        )
+        .map(ArtistDto::from);
+    Json(artists)
+}
+
+pub async fn artist(
+    state: AppState,
+    Path(id): Path<String>,
+) -> Result<Json<ArtistDetailDto>, ApiError> {
+    let library = state.snapshot();
+    let artist = library.artist(&id).ok_or(ApiError::NotFound("Artist"))?;
+    let filter = AlbumFilter {
+        artist_id: Some(id),
+        ..AlbumFilter::default()
+    };
+    let albums = library.list_albums(
+        &filter,
+        AlbumSort::Year,
+        SortOrder::Ascending,
+        Page {
+            offset: 0,
+            limit: usize::MAX,
+        },
+    );
+    Ok(Json(ArtistDetailDto {
+        artist: ArtistDto::from(artist),
+        albums: albums.items.into_iter().map(AlbumDto::from).collect(),
+    }))
+}
+
+/// Genres as a page of the list, or as a tree of every genre with `tree`, in which case
+/// the paging, sorting and query parameters are ignored.
+pub async fn genres(state: AppState, Query(params): Query<GenreParams>) -> Response {
+    let library = state.snapshot();
+    if params.tree {
+        let tree = state.genres.browse(library.audios());
+        return Json(tree.into_iter().map(GenreNodeDto::from).collect::<Vec<_>>()).into_response();
+    }
+    let filter = GenreFilter { query: params.q };
+    let genres = library
+        .list_genres(
+            &filter,
+            params.sort,
+            params.order,
+            page(params.offset, params.limit),
+        )
+        .map(GenreSummary::clone);
+    Json(genres).into_response()
+}
+
+pub async fn cover(state: AppState, Path(id): Path<String>) -> Result<impl IntoResponse, ApiError> {
+    let library = state.snapshot();
+    let cover = library.cover(&id).ok_or(ApiError::NotFound("Cover"))?;
+    // Identifiers are content hashes, so covers never change
+    Ok((
+        [
+            (header::CONTENT_TYPE, cover.mime_type()),
+            (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+        ],
+        cover.data().to_vec(),
+    ))
+}
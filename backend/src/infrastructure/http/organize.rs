@@ -0,0 +1,86 @@
+use std::io;
+
+use axum::Json;
+use serde::Deserialize;
+use tokio::task;
+
+use crate::{
+    application::service::{OrganizeMode, OrganizerOptions, OrganizerService, PathTemplate},
+    infrastructure::repository::library_file_repository::FilesystemLibraryFileRepository,
+};
+
+use super::{dto::FileOperationDto, ApiError, AppState};
+
+#[derive(Debug, Deserialize)]
+pub struct OrganizeInput {
+    /// Path of the audios relative to the root, e.g. `{album_artist}/{album}/{track:02} {title}`.
+    template: String,
+    /// Name of the library root to organize.
+    root: String,
+    /// Tracks to organize, every track of the root when not given.
+    tracks: Option<Vec<String>>,
+    #[serde(default)]
+    mode: OrganizeMode,
+    /// Reports the operations without touching any file.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Moves or copies the audios of a root, with their sidecar files, to the paths given by a
+/// template, for administrators. The root is rescanned once files were moved or copied.
+pub async fn organize(
+    state: AppState,
+    Json(input): Json<OrganizeInput>,
+) -> Result<Json<Vec<FileOperationDto>>, ApiError> {
+    state.check_admin()?;
+    let template = input.template.parse::<PathTemplate>()?;
+    let root = state
+        .library
+        .roots()
+        .iter()
+        .find(|root| root.name == input.root)
+        .cloned()
+        .ok_or(ApiError::NotFound("Root"))?;
+
+    let reports = task::spawn_blocking(move || {
+        let library = state.snapshot();
+        let audios = match &input.tracks {
+            Some(ids) => ids
+                .iter()
+                .map(|id| {
+                    library
+                        .audio(id)
+                        .filter(|audio| audio.root().as_deref() == Some(root.name.as_str()))
+                        .cloned()
+                        .ok_or(ApiError::NotFound("Track"))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => library
+                .audios()
+                .filter(|audio| audio.root().as_deref() == Some(root.name.as_str()))
+                .cloned()
+                .collect(),
+        };
+        let options = OrganizerOptions {
+            mode: input.mode,
+            ..OrganizerOptions::new(template)
+        };
+        let organizer = OrganizerService::new(FilesystemLibraryFileRepository, options);
+        let reports = organizer.organize(&root.path, &audios, input.dry_run);
+
+        let done = reports.iter().any(|report| report.error.is_none());
+        if !input.dry_run && done {
+            if let Err(err) = state.library.start_root_scan(&root.name) {
+                log::warn!(
+                    "Failed to rescan {} after organizing it: {}",
+                    root.name,
+                    err
+                );
+            }
+        }
+        Ok::<_, ApiError>(reports)
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok(Json(reports.iter().map(FileOperationDto::from).collect()))
+}
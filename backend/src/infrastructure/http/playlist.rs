@@ -0,0 +1,126 @@
+use std::io;
+
+use axum::{
+    extract::{Path, Query},
+    http::header,
+    response::IntoResponse,
+    Json,
+};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use tokio::task;
+
+use crate::{application::service::ResolvedPlaylist, domain::entity::playlist::PlaylistFormat};
+
+use super::{
+    dto::{PlaylistDetailDto, PlaylistDto, PlaylistEntryDto, TrackDto},
+    ApiError, AppState, Playlists,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    format: PlaylistFormat,
+}
+
+/// Playlist files of the library. They are read again after a scan, which takes a while
+/// for large playlists, so it runs on a blocking thread.
+pub async fn playlists(state: AppState) -> Result<Json<Vec<PlaylistDto>>, ApiError> {
+    let playlists = task::spawn_blocking(move || {
+        let library = state.snapshot();
+        let playlists = state
+            .playlist_files()?
+            .iter()
+            .map(|playlist| PlaylistDto::new(&library, playlist))
+            .collect();
+        Ok::<_, ApiError>(playlists)
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok(Json(playlists))
+}
+
+pub async fn playlist(
+    state: AppState,
+    Path(id): Path<String>,
+) -> Result<Json<PlaylistDetailDto>, ApiError> {
+    let playlist = task::spawn_blocking(move || {
+        let library = state.snapshot();
+        let playlist = find(&state, &id)?;
+        Ok::<_, ApiError>(PlaylistDetailDto {
+            playlist: PlaylistDto::new(&library, &playlist),
+            tracks: playlist
+                .tracks
+                .iter()
+                .filter_map(|id| library.audio(id))
+                .map(|audio| TrackDto::new(&library, audio))
+                .collect(),
+            unresolved: playlist
+                .unresolved
+                .iter()
+                .map(PlaylistEntryDto::from)
+                .collect(),
+        })
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok(Json(playlist))
+}
+
+/// Downloads a playlist converted to another format. Locations are relative to the
+/// directory of the original file, and unresolved entries are left out.
+pub async fn export(
+    state: AppState,
+    Path(id): Path<String>,
+    Query(params): Query<ExportParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let format = params.format;
+    let (playlist, content) = task::spawn_blocking(move || {
+        let library = state.snapshot();
+        let playlist = find(&state, &id)?;
+        let audios = playlist
+            .tracks
+            .iter()
+            .filter_map(|id| library.audio(id))
+            .collect::<Vec<_>>();
+        let content =
+            Playlists::to_playlist(&playlist.name, &audios, playlist.path.parent()).render(format);
+        Ok::<_, ApiError>((playlist, content))
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.mime_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                content_disposition(&playlist.name, format),
+            ),
+        ],
+        content,
+    ))
+}
+
+fn find(state: &AppState, id: &str) -> Result<ResolvedPlaylist, ApiError> {
+    state
+        .playlist_files()?
+        .into_iter()
+        .find(|playlist| playlist.id == id)
+        .ok_or(ApiError::NotFound("Playlist"))
+}
+
+/// Names the download after the playlist, with an ASCII fallback for older clients.
+pub(super) fn content_disposition(name: &str, format: PlaylistFormat) -> String {
+    let name = format!("{name}.{}", format.extension());
+    let fallback = name
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' | '/' => '_',
+            c if c.is_control() || !c.is_ascii() => '_',
+            c => c,
+        })
+        .collect::<String>();
+    format!(
+        "attachment; filename=\"{fallback}\"; filename*=UTF-8''{}",
+        utf8_percent_encode(&name, NON_ALPHANUMERIC)
+    )
+}
@@ -0,0 +1,43 @@
+use axum::{extract::Query, http::StatusCode, Json};
+use serde::Deserialize;
+
+use crate::application::service::ScanStatus;
+
+use super::{dto::LibraryRootDto, ApiError, AppState};
+
+#[derive(Debug, Deserialize)]
+pub struct ScanParams {
+    /// Name of the only root to scan, all of them when not given.
+    root: Option<String>,
+}
+
+pub async fn status(state: AppState) -> Json<ScanStatus> {
+    Json(state.library.status())
+}
+
+/// Starts a scan of every root, or of a single one, in the background, for users who may
+/// edit. Its progress is polled with [`status`].
+pub async fn start(
+    state: AppState,
+    Query(params): Query<ScanParams>,
+) -> Result<(StatusCode, Json<ScanStatus>), ApiError> {
+    state.check_can_edit()?;
+    match &params.root {
+        Some(root) => state.library.start_root_scan(root)?,
+        None => state.library.start_scan()?,
+    }
+    Ok((StatusCode::ACCEPTED, Json(state.library.status())))
+}
+
+/// The roots audios are gathered from, with the number of tracks of each the user sees.
+pub async fn roots(state: AppState) -> Json<Vec<LibraryRootDto>> {
+    let library = state.snapshot();
+    Json(
+        state
+            .library
+            .roots()
+            .iter()
+            .map(|root| LibraryRootDto::new(&library, root))
+            .collect(),
+    )
+}
@@ -0,0 +1,163 @@
+use std::io;
+
+use axum::{extract::Path, http::StatusCode, Json};
+use chrono::Utc;
+use serde::Deserialize;
+use tokio::task;
+
+use crate::domain::entity::{
+    activity::Play,
+    audio::Audio,
+    scrobble::{
+        Listen, ScrobblerAccount, ScrobblerKind, DEFAULT_LAST_FM_URL, DEFAULT_LISTENBRAINZ_URL,
+    },
+};
+
+use super::{
+    dto::{QueuedListenDto, ScrobblerDto},
+    ApiError, AppState,
+};
+
+/// Credentials of a scrobbling account. ListenBrainz takes a `token`, Last.fm an `api_key`
+/// and `api_secret` along with either a `session_key` or the `username` and `password` to
+/// obtain one with.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ScrobblerInput {
+    /// The public service when not given.
+    url: Option<String>,
+    token: Option<String>,
+    api_key: Option<String>,
+    api_secret: Option<String>,
+    session_key: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+/// Scrobbling accounts of the authenticated user.
+pub async fn scrobblers(state: AppState) -> Result<Json<Vec<ScrobblerDto>>, ApiError> {
+    let user_id = &state.user().id;
+    let queue = state.scrobbles.queue(user_id)?;
+    let paused = state.scrobbles.paused(user_id)?;
+    let accounts = state.scrobbles.accounts(user_id)?;
+    Ok(Json(
+        accounts
+            .iter()
+            .map(|account| ScrobblerDto::new(account, &queue, &paused))
+            .collect(),
+    ))
+}
+
+/// Connects the authenticated user to a scrobbling service, replacing the account they had
+/// there. Credentials are checked with the service, so it runs on a blocking thread.
+pub async fn connect(
+    state: AppState,
+    Path(kind): Path<ScrobblerKind>,
+    Json(input): Json<ScrobblerInput>,
+) -> Result<Json<ScrobblerDto>, ApiError> {
+    let account = task::spawn_blocking(move || {
+        let account = account(&state, kind, input)?;
+        let user_id = &state.user().id;
+        let account = state.scrobbles.connect(user_id, account)?;
+        Ok::<_, ApiError>(ScrobblerDto::new(
+            &account,
+            &state.scrobbles.queue(user_id)?,
+            &state.scrobbles.paused(user_id)?,
+        ))
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok(Json(account))
+}
+
+/// Disconnects the authenticated user from a service, dropping the listens not sent yet.
+pub async fn disconnect(
+    state: AppState,
+    Path(kind): Path<ScrobblerKind>,
+) -> Result<StatusCode, ApiError> {
+    state.scrobbles.disconnect(&state.user().id, kind)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Listens of the authenticated user waiting to be submitted, the oldest first.
+pub async fn queue(state: AppState) -> Result<Json<Vec<QueuedListenDto>>, ApiError> {
+    let queue = state.scrobbles.queue(&state.user().id)?;
+    Ok(Json(queue.into_iter().map(QueuedListenDto::from).collect()))
+}
+
+/// Tells the services of the authenticated user that the track started playing.
+pub async fn now_playing(state: AppState, Path(id): Path<String>) -> Result<StatusCode, ApiError> {
+    let library = state.snapshot();
+    let audio = library.audio(&id).ok_or(ApiError::NotFound("Track"))?;
+    send_now_playing(&state, audio);
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Queues the play of the audio for the services of the user, when it was listened to long
+/// enough. The play is recorded regardless, so failures are only logged.
+pub fn scrobble_play(state: &AppState, audio: &Audio, play: &Play) {
+    let listen = Listen::new(audio, play.played_at);
+    if !listen.counts(play.listened) {
+        return;
+    }
+    if let Err(err) = state.scrobbles.scrobble(&state.user().id, &listen) {
+        log::error!("Failed to queue listen: {}", err);
+    }
+}
+
+/// Sends what the user is playing in the background, so that clients do not wait for the
+/// services.
+pub fn send_now_playing(state: &AppState, audio: &Audio) {
+    let scrobbles = state.scrobbles.clone();
+    let user_id = state.user().id.clone();
+    let listen = Listen::new(audio, Utc::now());
+    task::spawn_blocking(move || {
+        if let Err(err) = scrobbles.now_playing(&user_id, &listen) {
+            log::warn!("Failed to send now playing: {}", err);
+        }
+    });
+}
+
+fn account(
+    state: &AppState,
+    kind: ScrobblerKind,
+    input: ScrobblerInput,
+) -> Result<ScrobblerAccount, ApiError> {
+    match kind {
+        ScrobblerKind::ListenBrainz => Ok(ScrobblerAccount::ListenBrainz {
+            url: input
+                .url
+                .unwrap_or_else(|| DEFAULT_LISTENBRAINZ_URL.to_string()),
+            token: input
+                .token
+                .ok_or(ApiError::BadRequest("token is required"))?,
+        }),
+        ScrobblerKind::LastFm => {
+            let url = input.url.unwrap_or_else(|| DEFAULT_LAST_FM_URL.to_string());
+            let (Some(api_key), Some(api_secret)) = (input.api_key, input.api_secret) else {
+                return Err(ApiError::BadRequest("api_key and api_secret are required"));
+            };
+            let session_key = match (input.session_key, input.username, input.password) {
+                (Some(session_key), _, _) => session_key,
+                (None, Some(username), Some(password)) => state.scrobbles.last_fm_session(
+                    &url,
+                    &api_key,
+                    &api_secret,
+                    &username,
+                    &password,
+                )?,
+                _ => {
+                    return Err(ApiError::BadRequest(
+                        "session_key or username and password are required",
+                    ))
+                }
+            };
+            Ok(ScrobblerAccount::LastFm {
+                url,
+                api_key,
+                api_secret,
+                session_key,
+            })
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::io;
+
+use axum::{extract::Query, Json};
+use serde::Deserialize;
+use tokio::task;
+
+use crate::application::service::Paginated;
+
+use super::{
+    dto::{SearchHitDto, TrackDto},
+    library::page,
+    ApiError, AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct SearchParams {
+    q: String,
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+/// Full-text search of tracks, best matches first. Searching may first update the index,
+/// which takes a while after a scan, so it runs on a blocking thread.
+pub async fn search(
+    state: AppState,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<Paginated<SearchHitDto>>, ApiError> {
+    let hits = task::spawn_blocking(move || {
+        let library = state.snapshot();
+        let hits = state
+            .search
+            .search(
+                &state.library.library(),
+                &library,
+                &params.q,
+                page(params.offset, params.limit),
+            )?
+            .map(|hit| SearchHitDto {
+                score: hit.score,
+                track: TrackDto::new(&library, hit.audio),
+            });
+        Ok::<_, ApiError>(hits)
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok(Json(hits))
+}
@@ -0,0 +1,184 @@
+use std::{io, sync::Arc};
+
+use axum::{
+    extract::{Path, Query},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use tokio::task;
+
+use crate::{
+    application::service::Library,
+    domain::entity::{
+        audio::Audio,
+        playlist::PlaylistFormat,
+        smart_playlist::{Rule, RuleSort, SmartPlaylist},
+    },
+};
+
+use super::{
+    dto::{SmartPlaylistDetailDto, SmartPlaylistDto, TrackDto},
+    playlist::content_disposition,
+    ApiError, AppState, Playlists,
+};
+
+/// A smart playlist definition, without its identifier.
+#[derive(Debug, Deserialize)]
+pub struct SmartPlaylistInput {
+    name: String,
+    #[serde(default)]
+    rule: Rule,
+    #[serde(default)]
+    sort: Vec<RuleSort>,
+    limit: Option<usize>,
+    /// Whether other users see the playlist.
+    #[serde(default)]
+    public: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    #[serde(default = "default_format")]
+    format: PlaylistFormat,
+}
+
+fn default_format() -> PlaylistFormat {
+    PlaylistFormat::M3u8
+}
+
+/// Rules are evaluated against the whole library, so handlers evaluating them run on a
+/// blocking thread.
+pub async fn playlists(state: AppState) -> Result<Json<Vec<SmartPlaylistDto>>, ApiError> {
+    let playlists = task::spawn_blocking(move || {
+        let library = state.snapshot();
+        state
+            .smart_playlists
+            .playlists(state.user())?
+            .into_iter()
+            .map(|playlist| {
+                let tracks = tracks(&state, &library, &playlist)?;
+                Ok(SmartPlaylistDto::new(playlist, &tracks))
+            })
+            .collect::<Result<_, ApiError>>()
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok(Json(playlists))
+}
+
+pub async fn playlist(
+    state: AppState,
+    Path(id): Path<String>,
+) -> Result<Json<SmartPlaylistDetailDto>, ApiError> {
+    let playlist = task::spawn_blocking(move || {
+        let playlist = state.smart_playlists.playlist(state.user(), &id)?;
+        detail(&state, playlist)
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok(Json(playlist))
+}
+
+pub async fn create(
+    state: AppState,
+    Json(input): Json<SmartPlaylistInput>,
+) -> Result<impl IntoResponse, ApiError> {
+    let playlist = SmartPlaylist {
+        sort: input.sort,
+        limit: input.limit,
+        public: input.public,
+        ..SmartPlaylist::new(&input.name, input.rule)
+    };
+    let playlist = task::spawn_blocking(move || {
+        let playlist = state.smart_playlists.create(state.user(), playlist)?;
+        detail(&state, playlist)
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok((StatusCode::CREATED, Json(playlist)))
+}
+
+pub async fn update(
+    state: AppState,
+    Path(id): Path<String>,
+    Json(input): Json<SmartPlaylistInput>,
+) -> Result<Json<SmartPlaylistDetailDto>, ApiError> {
+    let playlist = SmartPlaylist {
+        id,
+        name: input.name,
+        rule: input.rule,
+        sort: input.sort,
+        limit: input.limit,
+        owner: None,
+        public: input.public,
+    };
+    let playlist = task::spawn_blocking(move || {
+        let playlist = state.smart_playlists.update(state.user(), playlist)?;
+        detail(&state, playlist)
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok(Json(playlist))
+}
+
+pub async fn delete(state: AppState, Path(id): Path<String>) -> Result<StatusCode, ApiError> {
+    state.smart_playlists.delete(state.user(), &id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Downloads the current tracks as a static playlist, M3U8 by default, with absolute paths.
+pub async fn export(
+    state: AppState,
+    Path(id): Path<String>,
+    Query(params): Query<ExportParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (playlist, content) = task::spawn_blocking(move || {
+        let playlist = state.smart_playlists.playlist(state.user(), &id)?;
+        let library = state.snapshot();
+        let tracks = tracks(&state, &library, &playlist)?;
+        let content = Playlists::to_playlist(&playlist.name, &tracks, None).render(params.format);
+        Ok::<_, ApiError>((playlist, content))
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok((
+        [
+            (header::CONTENT_TYPE, params.format.mime_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                content_disposition(&playlist.name, params.format),
+            ),
+        ],
+        content,
+    ))
+}
+
+fn detail(state: &AppState, playlist: SmartPlaylist) -> Result<SmartPlaylistDetailDto, ApiError> {
+    let library = state.snapshot();
+    let tracks = tracks(state, &library, &playlist)?;
+    Ok(SmartPlaylistDetailDto {
+        tracks: tracks
+            .iter()
+            .map(|audio| TrackDto::new(&library, audio))
+            .collect(),
+        playlist: SmartPlaylistDto::new(playlist, &tracks),
+    })
+}
+
+/// Evaluates the playlist with the part of the library the user sees and their play
+/// statistics, ratings and favorites of the tracks.
+pub(super) fn tracks<'a>(
+    state: &AppState,
+    library: &'a Arc<Library>,
+    playlist: &SmartPlaylist,
+) -> Result<Vec<&'a Audio>, ApiError> {
+    let user_id = &state.user().id;
+    Ok(state
+        .smart_playlists
+        .tracks(user_id, library, playlist, &state.activity.stats(user_id)?)
+        .iter()
+        .filter_map(|id| library.audio(id))
+        .collect())
+}
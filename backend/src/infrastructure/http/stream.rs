@@ -0,0 +1,229 @@
+use std::{
+    io::SeekFrom,
+    path::{Path as FilePath, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    body::Body,
+    extract::Path,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+use tokio_util::io::ReaderStream;
+
+use super::{ApiError, AppState};
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Serves the original file of a track.
+pub async fn stream(
+    state: AppState,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let path = state
+        .snapshot()
+        .audio(&id)
+        .ok_or(ApiError::NotFound("Track"))?
+        .path()
+        .clone();
+    let path = authorize(&state, &path).await?;
+    serve_file(&path, content_type(&path), &headers).await
+}
+
+/// Resolves `path`, following symbolic links, and checks that it lies inside a library root.
+pub(super) async fn authorize(state: &AppState, path: &FilePath) -> Result<PathBuf, ApiError> {
+    let path = fs::canonicalize(path).await?;
+    if state
+        .library_roots
+        .iter()
+        .any(|root| path.starts_with(root))
+    {
+        Ok(path)
+    } else {
+        Err(ApiError::Forbidden)
+    }
+}
+
+/// MIME type of the container, guessed from the file extension.
+pub(super) fn content_type(path: &FilePath) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "mp3" => "audio/mpeg",
+        "flac" => "audio/flac",
+        "m4a" | "m4b" | "mp4" | "alac" => "audio/mp4",
+        "aac" => "audio/aac",
+        "ogg" | "oga" | "opus" => "audio/ogg",
+        "wav" => "audio/wav",
+        "aif" | "aiff" | "aifc" => "audio/aiff",
+        "wma" => "audio/x-ms-wma",
+        "webm" => "audio/webm",
+        "mka" => "audio/x-matroska",
+        "ape" => "audio/x-ape",
+        "wv" => "audio/x-wavpack",
+        "dsf" => "audio/x-dsf",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Serves a file with support for conditional and range requests.
+pub(super) async fn serve_file(
+    path: &FilePath,
+    content_type: &'static str,
+    headers: &HeaderMap,
+) -> Result<Response, ApiError> {
+    let mut file = File::open(path).await?;
+    let metadata = file.metadata().await?;
+    let length = metadata.len();
+    let modified = metadata.modified()?;
+    let modified_secs = modified
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let etag = entity_tag(length, modified);
+    let last_modified = DateTime::<Utc>::from(modified)
+        .format(HTTP_DATE_FORMAT)
+        .to_string();
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    response_headers.insert(header::ETAG, header_value(&etag));
+    response_headers.insert(header::LAST_MODIFIED, header_value(&last_modified));
+
+    if is_not_modified(headers, &etag, modified_secs) {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|range| range.to_str().ok())
+        .filter(|_| if_range_matches(headers, &etag, modified_secs))
+        .map(|range| parse_range(range, length))
+        .unwrap_or(ByteRange::Full);
+    let (status, start, end) = match range {
+        ByteRange::Full => (StatusCode::OK, 0, length),
+        ByteRange::Partial(start, end) => {
+            response_headers.insert(
+                header::CONTENT_RANGE,
+                header_value(&format!("bytes {start}-{end}/{length}")),
+            );
+            (StatusCode::PARTIAL_CONTENT, start, end + 1)
+        }
+        ByteRange::Unsatisfiable => {
+            response_headers.insert(
+                header::CONTENT_RANGE,
+                header_value(&format!("bytes */{length}")),
+            );
+            return Ok((StatusCode::RANGE_NOT_SATISFIABLE, response_headers).into_response());
+        }
+    };
+
+    file.seek(SeekFrom::Start(start)).await?;
+    response_headers.insert(
+        header::CONTENT_LENGTH,
+        header_value(&(end - start).to_string()),
+    );
+    let body = Body::from_stream(ReaderStream::new(file.take(end - start)));
+    Ok((status, response_headers, body).into_response())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteRange {
+    Full,
+    /// First and last byte, inclusive.
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+/// Parses a single `bytes` range. Malformed headers and multiple ranges are ignored, which
+/// results in the whole file being sent.
+fn parse_range(value: &str, length: u64) -> ByteRange {
+    let Some(range) = value.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if range.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = range.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    if start.is_empty() {
+        // Suffix range: the last `end` bytes
+        return match end.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if length == 0 => ByteRange::Unsatisfiable,
+            Ok(suffix) => ByteRange::Partial(length.saturating_sub(suffix), length - 1),
+            Err(_) => ByteRange::Full,
+        };
+    }
+    let Ok(start) = start.parse::<u64>() else {
+        return ByteRange::Full;
+    };
+    let end = if end.is_empty() {
+        u64::MAX
+    } else {
+        match end.parse::<u64>() {
+            Ok(end) if end >= start => end,
+            _ => return ByteRange::Full,
+        }
+    };
+    if start >= length {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial(start, end.min(length - 1))
+}
+
+/// Strong validator derived from the size and modification time of the file.
+fn entity_tag(length: u64, modified: SystemTime) -> String {
+    let modified = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("\"{:x}-{:x}\"", length, modified.as_nanos())
+}
+
+fn is_not_modified(headers: &HeaderMap, etag: &str, modified_secs: u64) -> bool {
+    if let Some(if_none_match) = header_str(headers, header::IF_NONE_MATCH) {
+        // Weak comparison
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag);
+    }
+    header_str(headers, header::IF_MODIFIED_SINCE)
+        .and_then(parse_http_date)
+        .is_some_and(|since| modified_secs <= since)
+}
+
+/// A range is only honored if the representation the client has is still current.
+fn if_range_matches(headers: &HeaderMap, etag: &str, modified_secs: u64) -> bool {
+    match header_str(headers, header::IF_RANGE) {
+        None => true,
+        // Strong comparison
+        Some(if_range) if if_range.starts_with('"') => if_range == etag,
+        Some(if_range) if if_range.starts_with("W/") => false,
+        Some(if_range) => parse_http_date(if_range) == Some(modified_secs),
+    }
+}
+
+fn parse_http_date(value: &str) -> Option<u64> {
+    NaiveDateTime::parse_from_str(value.trim(), HTTP_DATE_FORMAT)
+        .ok()
+        .and_then(|date| u64::try_from(date.and_utc().timestamp()).ok())
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn header_value(value: &str) -> HeaderValue {
+    HeaderValue::from_str(value).expect("generated header values are visible ASCII")
+}
@@ -0,0 +1,288 @@
+use std::{str::FromStr, sync::Arc};
+
+use axum::{
+    extract::{rejection::FormRejection, Path, Query, State},
+    http::{HeaderMap, Method},
+    response::Response,
+    routing::get,
+    Form, Router,
+};
+use thiserror::Error;
+
+use crate::{application::service::UserServiceError, domain::entity::user::User};
+
+use super::{ApiError, AppState};
+
+mod activity;
+mod browsing;
+mod media;
+mod playlists;
+mod response;
+
+use response::{Format, Node};
+
+/// Version of the Subsonic API that is implemented.
+const API_VERSION: &str = "1.16.1";
+
+/// An error sent in a `failed` response. Subsonic clients expect them with a 200 status.
+#[derive(Error, Debug)]
+#[error("{message}")]
+pub struct SubsonicError {
+    code: u32,
+    message: String,
+}
+
+impl SubsonicError {
+    fn generic(message: impl Into<String>) -> Self {
+        Self {
+            code: 0,
+            message: message.into(),
+        }
+    }
+
+    fn missing_parameter(name: &str) -> Self {
+        Self {
+            code: 10,
+            message: format!("Required parameter is missing: {name}"),
+        }
+    }
+
+    fn wrong_credentials() -> Self {
+        Self {
+            code: 40,
+            message: "Wrong username or password".to_string(),
+        }
+    }
+
+    fn token_authentication_unsupported() -> Self {
+        Self {
+            code: 41,
+            message: "Token authentication needs a Subsonic password, create one for the user \
+                      or use an API key"
+                .to_string(),
+        }
+    }
+
+    fn conflicting_authentication() -> Self {
+        Self {
+            code: 43,
+            message: "Multiple conflicting authentication mechanisms provided".to_string(),
+        }
+    }
+
+    fn invalid_api_key() -> Self {
+        Self {
+            code: 44,
+            message: "Invalid API key".to_string(),
+        }
+    }
+
+    fn not_found(what: &str) -> Self {
+        Self {
+            code: 70,
+            message: format!("{what} not found"),
+        }
+    }
+}
+
+impl From<ApiError> for SubsonicError {
+    fn from(err: ApiError) -> Self {
+        let code = match err {
+            ApiError::Forbidden => 50,
+            ApiError::NotFound(_) => 70,
+            _ => 0,
+        };
+        Self {
+            code,
+            message: err.to_string(),
+        }
+    }
+}
+
+/// What a method answers with: a payload of the `subsonic-response` element, or a
+/// response of its own for binary content.
+enum Reply {
+    Node(Option<Node>),
+    Raw(Response),
+}
+
+/// Query parameters, along with the ones of a form sent in a `POST`. They may be repeated.
+struct Params(Vec<(String, String)>);
+
+impl Params {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn all(&self, name: &str) -> Vec<&str> {
+        self.0
+            .iter()
+            .filter(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    fn required(&self, name: &str) -> Result<&str, SubsonicError> {
+        self.get(name)
+            .ok_or_else(|| SubsonicError::missing_parameter(name))
+    }
+
+    fn parse<T: FromStr>(&self, name: &str) -> Result<Option<T>, SubsonicError> {
+        self.get(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| SubsonicError::generic(format!("Invalid parameter: {name}")))
+            })
+            .transpose()
+    }
+}
+
+/// Routes of the Subsonic API, mounted under `/rest`. Methods may be called with or
+/// without the `.view` suffix.
+pub fn router() -> Router<AppState> {
+    Router::new().route("/{method}", get(handle).post(handle))
+}
+
+async fn handle(
+    State(state): State<AppState>,
+    Path(method): Path<String>,
+    http_method: Method,
+    Query(mut params): Query<Vec<(String, String)>>,
+    headers: HeaderMap,
+    form: Result<Form<Vec<(String, String)>>, FormRejection>,
+) -> Response {
+    // Clients that keep credentials out of URLs send them in an urlencoded body. The form
+    // extractor reads the query of other methods, which is already there
+    if http_method == Method::POST {
+        if let Ok(Form(form)) = form {
+            params.extend(form);
+        }
+    }
+    let params = Params(params);
+    let format = Format::new(params.get("f"));
+    let method = method.strip_suffix(".view").unwrap_or(&method);
+    let reply = match authenticate(&state, &params) {
+        Ok(user) => dispatch(&state.with_user(Arc::new(user)), method, &params, &headers).await,
+        Err(err) => Err(err),
+    };
+    match reply {
+        Ok(Reply::Node(payload)) => format.ok(payload),
+        Ok(Reply::Raw(response)) => response,
+        Err(err) => format.error(&err),
+    }
+}
+
+async fn dispatch(
+    state: &AppState,
+    method: &str,
+    params: &Params,
+    headers: &HeaderMap,
+) -> Result<Reply, SubsonicError> {
+    let node = match method {
+        "ping" => None,
+        "getLicense" => Some(Node::new("license").attribute("valid", true)),
+        "getOpenSubsonicExtensions" => {
+            let api_key = Node::new("openSubsonicExtension")
+                .attribute("name", "apiKeyAuthentication")
+                .list("versions", vec![Node::new("version").text("1")]);
+            Some(Node::new("openSubsonicExtensions").list("openSubsonicExtensions", vec![api_key]))
+        }
+        "getMusicFolders" => Some(browsing::music_folders(state)),
+        "getIndexes" => Some(browsing::indexes(state)),
+        "getArtists" => Some(browsing::artists(state)),
+        "getArtist" => Some(browsing::artist(state, params)?),
+        "getMusicDirectory" => Some(browsing::music_directory(state, params)?),
+        "getAlbum" => Some(browsing::album(state, params)?),
+        "getSong" => Some(browsing::song(state, params)?),
+        "getGenres" => Some(browsing::genres(state)),
+        "getAlbumList2" => Some(browsing::album_list(state, params)?),
+        "search3" => Some(browsing::search(state, params)?),
+        "stream" | "download" => {
+            return media::stream(state, params, headers).await.map(Reply::Raw)
+        }
+        "getCoverArt" => return media::cover_art(state, params).map(Reply::Raw),
+        "scrobble" => {
+            activity::scrobble(state, params)?;
+            None
+        }
+        "star" | "unstar" => {
+            activity::star(state, params, method == "star")?;
+            None
+        }
+        "setRating" => {
+            activity::set_rating(state, params)?;
+            None
+        }
+        "getStarred" => Some(activity::starred(state, "starred")?),
+        "getStarred2" => Some(activity::starred(state, "starred2")?),
+        "getPlaylists" => Some(playlists::playlists(state).await?),
+        "getPlaylist" => Some(playlists::playlist(state, params).await?),
+        "createPlaylist" | "updatePlaylist" | "deletePlaylist" => {
+            return Err(SubsonicError::generic("Playlists are read-only"))
+        }
+        _ => return Err(SubsonicError::generic(format!("Unknown method: {method}"))),
+    };
+    Ok(Reply::Node(node))
+}
+
+/// Authenticates the user `u` with the password `p`, in clear or hex encoded with an `enc:`
+/// prefix, or the token `t` made of the MD5 of the password followed by the salt `s`, or the
+/// user of the API token `apiKey`. Account passwords are only kept hashed, so tokens are
+/// checked against the Subsonic password of the user, see [`User::subsonic_password`].
+fn authenticate(state: &AppState, params: &Params) -> Result<User, SubsonicError> {
+    let error = |err: UserServiceError| -> SubsonicError { ApiError::from(err).into() };
+    if let Some(key) = params.get("apiKey") {
+        if params.get("u").is_some() {
+            return Err(SubsonicError::conflicting_authentication());
+        }
+        return state
+            .users
+            .authenticate_token(key)
+            .map_err(|err| match err {
+                UserServiceError::InvalidCredentials => SubsonicError::invalid_api_key(),
+                err => error(err),
+            });
+    }
+    let username = params.required("u")?;
+    if let Some(token) = params.get("t") {
+        let salt = params.required("s")?;
+        return state
+            .users
+            .authenticate_subsonic_token(username, token, salt)
+            .map_err(|err| match err {
+                UserServiceError::InvalidCredentials => SubsonicError::wrong_credentials(),
+                UserServiceError::NoSubsonicPassword => {
+                    SubsonicError::token_authentication_unsupported()
+                }
+                err => error(err),
+            });
+    }
+    let password = params.required("p")?;
+    let password = match password.strip_prefix("enc:") {
+        Some(hex) => decode_hex(hex)
+            .and_then(|password| String::from_utf8(password).ok())
+            .ok_or_else(SubsonicError::wrong_credentials)?,
+        None => password.to_string(),
+    };
+    state
+        .users
+        .authenticate_subsonic(username, &password)
+        .map_err(|err| match err {
+            UserServiceError::InvalidCredentials => SubsonicError::wrong_credentials(),
+            err => error(err),
+        })
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
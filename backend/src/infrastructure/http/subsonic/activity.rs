@@ -0,0 +1,254 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+
+use crate::{
+    application::service::{ActivityServiceError, Library},
+    domain::entity::{
+        activity::{Annotation, ItemKind, Play, Rating},
+        album::Album,
+    },
+};
+
+use super::{
+    super::{scrobble, ApiError, AppState},
+    browsing::{album_node, artist_node, song_node},
+    Node, Params, SubsonicError,
+};
+
+/// Records the plays of the `id` parameters, at the matching `time` parameters or now, and
+/// queues them for the scrobbling services of the user. Notifications of what is now
+/// playing, sent with `submission=false`, are forwarded to the services and not recorded.
+pub fn scrobble(state: &AppState, params: &Params) -> Result<(), SubsonicError> {
+    let library = state.snapshot();
+    let ids = params.all("id");
+    if ids.is_empty() {
+        return Err(SubsonicError::missing_parameter("id"));
+    }
+    let submission = params.parse::<bool>("submission")?.unwrap_or(true);
+    let times = params.all("time");
+    for (index, id) in ids.into_iter().enumerate() {
+        let audio = library
+            .audio(id)
+            .ok_or_else(|| SubsonicError::not_found("Song"))?;
+        if !submission {
+            scrobble::send_now_playing(state, audio);
+            continue;
+        }
+        let played_at = match times.get(index) {
+            Some(time) => time
+                .parse()
+                .ok()
+                .and_then(|millis| Utc.timestamp_millis_opt(millis).single())
+                .ok_or_else(|| SubsonicError::generic("Invalid parameter: time"))?,
+            None => Utc::now(),
+        };
+        let play = state
+            .activity
+            .record_play(
+                &state.user().id,
+                &library,
+                Play {
+                    audio_id: id.to_string(),
+                    played_at,
+                    listened: None,
+                    client: params.get("c").map(str::to_string),
+                },
+            )
+            .map_err(error)?;
+        scrobble::scrobble_play(state, audio, &play);
+    }
+    Ok(())
+}
+
+/// Marks or unmarks as favorites the songs, albums or artists of the `id` parameters and
+/// the albums and artists of the `albumId` and `artistId` parameters.
+pub fn star(state: &AppState, params: &Params, favorite: bool) -> Result<(), SubsonicError> {
+    let library = state.snapshot();
+    let items = params
+        .all("id")
+        .into_iter()
+        .map(|id| Ok((kind_of(&library, id)?, id)))
+        .chain(
+            params
+                .all("albumId")
+                .into_iter()
+                .map(|id| Ok((ItemKind::Album, id))),
+        )
+        .chain(
+            params
+                .all("artistId")
+                .into_iter()
+                .map(|id| Ok((ItemKind::Artist, id))),
+        )
+        .collect::<Result<Vec<_>, SubsonicError>>()?;
+    for (kind, id) in items {
+        state
+            .activity
+            .favorite(&state.user().id, &library, kind, id, favorite)
+            .map_err(error)?;
+    }
+    Ok(())
+}
+
+/// Rates a song, album or artist from 1 to 5 stars, 0 removing the rating.
+pub fn set_rating(state: &AppState, params: &Params) -> Result<(), SubsonicError> {
+    let library = state.snapshot();
+    let id = params.required("id")?;
+    let rating = match params
+        .parse::<u8>("rating")?
+        .ok_or_else(|| SubsonicError::missing_parameter("rating"))?
+    {
+        0 => None,
+        stars => Some(
+            Rating::try_from(stars)
+                .map_err(|_| SubsonicError::generic("Invalid parameter: rating"))?,
+        ),
+    };
+    state
+        .activity
+        .rate(
+            &state.user().id,
+            &library,
+            kind_of(&library, id)?,
+            id,
+            rating,
+        )
+        .map_err(error)?;
+    Ok(())
+}
+
+/// Favorite artists, albums and songs, the most recently marked first, in an element named
+/// `name` for `getStarred` and `getStarred2`.
+pub fn starred(state: &AppState, name: &'static str) -> Result<Node, SubsonicError> {
+    let library = state.snapshot();
+    let favorites = |kind| -> Result<Vec<Annotation>, SubsonicError> {
+        let mut annotations = state
+            .activity
+            .annotations(&state.user().id, kind)
+            .map_err(error)?
+            .into_iter()
+            .filter(|annotation| annotation.favorited_at.is_some())
+            .collect::<Vec<_>>();
+        annotations.sort_by_key(|annotation| Reverse(annotation.favorited_at));
+        Ok(annotations)
+    };
+    let artists = favorites(ItemKind::Artist)?
+        .iter()
+        .filter_map(|annotation| {
+            let summary = library.artist(&annotation.id)?;
+            Some(annotate(artist_node(summary), annotation))
+        })
+        .collect();
+    let albums = favorites(ItemKind::Album)?
+        .iter()
+        .filter_map(|annotation| {
+            let album = library.album(&annotation.id)?;
+            Some(annotate(album_node(album), annotation))
+        })
+        .collect();
+    let songs = favorites(ItemKind::Track)?
+        .iter()
+        .filter_map(|annotation| library.audio(&annotation.id))
+        .map(|audio| song_node(state, &library, audio))
+        .collect();
+    Ok(Node::new(name)
+        .list("artist", artists)
+        .list("album", albums)
+        .list("song", songs))
+}
+
+/// Albums for the `getAlbumList2` types based on activity: `frequent` and `recent` order
+/// the albums with played tracks by play count and last play, `highest` the rated albums
+/// by rating, and `starred` lists the favorite albums.
+pub fn album_list<'a>(
+    state: &AppState,
+    library: &'a Library,
+    kind: &str,
+) -> Result<Vec<&'a Album>, SubsonicError> {
+    let albums = match kind {
+        "frequent" | "recent" => {
+            let stats = state.activity.stats(&state.user().id).map_err(error)?;
+            let mut albums = library
+                .albums()
+                .iter()
+                .filter_map(|album| {
+                    let mut play_count = 0;
+                    let mut last_played = None;
+                    for stats in album
+                        .tracks()
+                        .iter()
+                        .filter_map(|track| stats.get(&track.id()))
+                    {
+                        play_count += stats.play_count;
+                        last_played = last_played.max(stats.last_played);
+                    }
+                    (play_count > 0).then_some((album, play_count, last_played))
+                })
+                .collect::<Vec<_>>();
+            if kind == "frequent" {
+                albums.sort_by_key(|(_, play_count, _)| Reverse(*play_count));
+            } else {
+                albums.sort_by_key(|(_, _, last_played)| Reverse(*last_played));
+            }
+            albums.into_iter().map(|(album, ..)| album).collect()
+        }
+        _ => {
+            let annotations = state
+                .activity
+                .annotations(&state.user().id, ItemKind::Album)
+                .map_err(error)?
+                .into_iter()
+                .map(|annotation| (annotation.id.clone(), annotation))
+                .collect::<HashMap<_, _>>();
+            let annotation = |album: &Album| annotations.get(&album.id());
+            let mut albums = library
+                .albums()
+                .iter()
+                .filter(|album| {
+                    annotation(album).is_some_and(|annotation| match kind {
+                        "highest" => annotation.rating.is_some(),
+                        _ => annotation.favorited_at.is_some(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            if kind == "highest" {
+                albums.sort_by_key(|album| {
+                    Reverse(annotation(album).and_then(|annotation| annotation.rating))
+                });
+            }
+            albums
+        }
+    };
+    Ok(albums)
+}
+
+/// Adds the `starred` and `userRating` attributes of an item.
+pub(super) fn annotate(node: Node, annotation: &Annotation) -> Node {
+    node.optional("starred", annotation.favorited_at.map(timestamp))
+        .optional("userRating", annotation.rating.map(|rating| rating.stars()))
+}
+
+pub(super) fn timestamp(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Folder based clients send the identifiers of songs, albums and artists alike.
+fn kind_of(library: &Library, id: &str) -> Result<ItemKind, SubsonicError> {
+    if library.audio(id).is_some() {
+        Ok(ItemKind::Track)
+    } else if library.album(id).is_some() {
+        Ok(ItemKind::Album)
+    } else if library.artist(id).is_some() {
+        Ok(ItemKind::Artist)
+    } else {
+        Err(SubsonicError::not_found("Item"))
+    }
+}
+
+fn error(err: ActivityServiceError) -> SubsonicError {
+    match err {
+        ActivityServiceError::NotFound(..) => SubsonicError::not_found("Item"),
+        err => ApiError::from(err).into(),
+    }
+}
@@ -0,0 +1,462 @@
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    application::service::{
+        AlbumFilter, AlbumSort, ArtistFilter, ArtistSort, ArtistSummary, Library, Page, SortOrder,
+        TrackFilter, TrackSort,
+    },
+    domain::entity::{
+        album::Album,
+        audio::{cover::Cover, Audio},
+    },
+};
+
+use super::{
+    super::{stream::content_type, AppState},
+    activity::{self, timestamp},
+    Node, Params, SubsonicError,
+};
+
+/// Largest list a client may request.
+const MAX_SIZE: usize = 500;
+
+/// The library roots by name, or the directories the user is restricted to.
+pub fn music_folders(state: &AppState) -> Node {
+    let user = state.user();
+    let names = if user.library_roots.is_empty() {
+        state
+            .library
+            .roots()
+            .iter()
+            .map(|root| root.name.clone())
+            .collect::<Vec<_>>()
+    } else {
+        user.library_roots
+            .iter()
+            .map(|root| {
+                root.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| root.display().to_string())
+            })
+            .collect()
+    };
+    let folders = names
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| {
+            Node::new("musicFolder")
+                .attribute("id", index + 1)
+                .attribute("name", name)
+        })
+        .collect();
+    Node::new("musicFolders").list("musicFolder", folders)
+}
+
+/// Album artists grouped by the first letter of their sort key, as folder based browsing.
+pub fn indexes(state: &AppState) -> Node {
+    let last_modified = state
+        .library
+        .status()
+        .finished_at
+        .map(|finished_at| finished_at.timestamp_millis())
+        .unwrap_or_default();
+    let library = state.snapshot();
+    let indexes = index_nodes(&library, |summary| {
+        Node::new("artist")
+            .attribute("id", summary.artist.id())
+            .attribute("name", summary.artist.name().clone())
+    });
+    Node::new("indexes")
+        .attribute("lastModified", last_modified)
+        .attribute("ignoredArticles", ignored_articles(&library))
+        .list("index", indexes)
+}
+
+/// Album artists grouped by the first letter of their sort key.
+pub fn artists(state: &AppState) -> Node {
+    let library = state.snapshot();
+    let indexes = index_nodes(&library, artist_node);
+    Node::new("artists")
+        .attribute("ignoredArticles", ignored_articles(&library))
+        .list("index", indexes)
+}
+
+pub fn artist(state: &AppState, params: &Params) -> Result<Node, SubsonicError> {
+    let library = state.snapshot();
+    let id = params.required("id")?;
+    let summary = library
+        .artist(id)
+        .ok_or_else(|| SubsonicError::not_found("Artist"))?;
+    let albums = artist_albums(&library, id)
+        .into_iter()
+        .map(album_node)
+        .collect();
+    Ok(artist_node(summary).list("album", albums))
+}
+
+/// Folder based browsing: an artist lists their albums and an album its songs.
+pub fn music_directory(state: &AppState, params: &Params) -> Result<Node, SubsonicError> {
+    let library = state.snapshot();
+    let id = params.required("id")?;
+    if let Some(album) = library.album(id) {
+        let songs = album
+            .tracks()
+            .iter()
+            .map(|audio| song_node(state, &library, audio))
+            .collect();
+        return Ok(Node::new("directory")
+            .attribute("id", album.id())
+            .attribute("parent", album.artist().id())
+            .attribute("name", album.title().name().clone())
+            .list("child", songs));
+    }
+    let summary = library
+        .artist(id)
+        .ok_or_else(|| SubsonicError::not_found("Directory"))?;
+    let albums = artist_albums(&library, id)
+        .into_iter()
+        .map(|album| {
+            Node::new("child")
+                .attribute("id", album.id())
+                .attribute("parent", album.artist().id())
+                .attribute("isDir", true)
+                .attribute("title", album.title().name().clone())
+                .attribute("album", album.title().name().clone())
+                .attribute("artist", album.artist().name().clone())
+                .optional("year", album.year().map(|year| year.0))
+                .optional("coverArt", album_cover(album).map(Cover::id))
+        })
+        .collect();
+    Ok(Node::new("directory")
+        .attribute("id", summary.artist.id())
+        .attribute("name", summary.artist.name().clone())
+        .list("child", albums))
+}
+
+pub fn album(state: &AppState, params: &Params) -> Result<Node, SubsonicError> {
+    let library = state.snapshot();
+    let album = library
+        .album(params.required("id")?)
+        .ok_or_else(|| SubsonicError::not_found("Album"))?;
+    let songs = album
+        .tracks()
+        .iter()
+        .map(|audio| song_node(state, &library, audio))
+        .collect();
+    Ok(album_node(album).list("song", songs))
+}
+
+pub fn song(state: &AppState, params: &Params) -> Result<Node, SubsonicError> {
+    let library = state.snapshot();
+    let audio = library
+        .audio(params.required("id")?)
+        .ok_or_else(|| SubsonicError::not_found("Song"))?;
+    Ok(song_node(state, &library, audio))
+}
+
+pub fn genres(state: &AppState) -> Node {
+    let genres = state
+        .snapshot()
+        .genres()
+        .iter()
+        .map(|genre| {
+            Node::new("genre")
+                .attribute("songCount", genre.track_count)
+                .attribute("albumCount", genre.album_count)
+                .text(genre.name.clone())
+        })
+        .collect();
+    Node::new("genres").list("genre", genres)
+}
+
+/// Lists albums in the order given by the `type` parameter.
+pub fn album_list(state: &AppState, params: &Params) -> Result<Node, SubsonicError> {
+    let library = state.snapshot();
+    let page = page(params, "size", "offset", 10)?;
+    let all = |filter: &AlbumFilter, sort, order| {
+        library
+            .list_albums(
+                filter,
+                sort,
+                order,
+                Page {
+                    offset: 0,
+                    limit: usize::MAX,
+                },
+            )
+            .items
+    };
+    let filter = AlbumFilter::default();
+    let albums = match params.required("type")? {
+        "alphabeticalByName" => all(&filter, AlbumSort::Title, SortOrder::Ascending),
+        "alphabeticalByArtist" => all(&filter, AlbumSort::Artist, SortOrder::Ascending),
+        // Without the date albums were added, the most recent releases come first
+        "newest" => all(&filter, AlbumSort::Year, SortOrder::Descending),
+        "byYear" => {
+            let from = params
+                .parse::<u16>("fromYear")?
+                .ok_or_else(|| SubsonicError::missing_parameter("fromYear"))?;
+            let to = params
+                .parse::<u16>("toYear")?
+                .ok_or_else(|| SubsonicError::missing_parameter("toYear"))?;
+            let filter = AlbumFilter {
+                min_year: Some(from.min(to)),
+                max_year: Some(from.max(to)),
+                ..AlbumFilter::default()
+            };
+            let order = if from > to {
+                SortOrder::Descending
+            } else {
+                SortOrder::Ascending
+            };
+            all(&filter, AlbumSort::Year, order)
+        }
+        "byGenre" => {
+            let filter = AlbumFilter {
+                genre: Some(params.required("genre")?.to_string()),
+                ..AlbumFilter::default()
+            };
+            all(&filter, AlbumSort::Artist, SortOrder::Ascending)
+        }
+        "random" => {
+            let mut albums = all(&filter, AlbumSort::Artist, SortOrder::Ascending);
+            shuffle(&mut albums);
+            albums
+        }
+        kind @ ("starred" | "frequent" | "recent" | "highest") => {
+            activity::album_list(state, &library, kind)?
+        }
+        kind => return Err(SubsonicError::generic(format!("Unknown list type: {kind}"))),
+    };
+    let albums = albums
+        .into_iter()
+        .skip(page.offset)
+        .take(page.limit)
+        .map(album_node)
+        .collect();
+    Ok(Node::new("albumList2").list("album", albums))
+}
+
+/// Matches artists, albums and songs against `query`. An empty query, which clients use to
+/// synchronize the whole library, matches everything.
+pub fn search(state: &AppState, params: &Params) -> Result<Node, SubsonicError> {
+    let library = state.snapshot();
+    let query = params.get("query").unwrap_or_default().trim();
+    let query = query.trim_matches('"').trim_end_matches('*');
+    let query = (!query.is_empty()).then(|| query.to_string());
+
+    let artists = library
+        .list_artists(
+            &ArtistFilter {
+                query: query.clone(),
+            },
+            ArtistSort::Name,
+            SortOrder::Ascending,
+            page(params, "artistCount", "artistOffset", 20)?,
+        )
+        .items
+        .into_iter()
+        .filter(|summary| summary.album_count > 0)
+        .map(artist_node)
+        .collect();
+    let albums = library
+        .list_albums(
+            &AlbumFilter {
+                query: query.clone(),
+                ..AlbumFilter::default()
+            },
+            AlbumSort::Artist,
+            SortOrder::Ascending,
+            page(params, "albumCount", "albumOffset", 20)?,
+        )
+        .items
+        .into_iter()
+        .map(album_node)
+        .collect();
+    let songs = library
+        .tracks(
+            &TrackFilter {
+                query,
+                ..TrackFilter::default()
+            },
+            TrackSort::Album,
+            SortOrder::Ascending,
+            page(params, "songCount", "songOffset", 20)?,
+        )
+        .items
+        .into_iter()
+        .map(|audio| song_node(state, &library, audio))
+        .collect();
+    Ok(Node::new("searchResult3")
+        .list("artist", artists)
+        .list("album", albums)
+        .list("song", songs))
+}
+
+fn page(params: &Params, size: &str, offset: &str, default: usize) -> Result<Page, SubsonicError> {
+    Ok(Page {
+        offset: params.parse(offset)?.unwrap_or_default(),
+        limit: params.parse(size)?.unwrap_or(default).min(MAX_SIZE),
+    })
+}
+
+fn index_nodes(library: &Library, artist: impl Fn(&ArtistSummary) -> Node) -> Vec<Node> {
+    let mut indexes: BTreeMap<String, Vec<Node>> = BTreeMap::new();
+    // Artists are already ordered by sort key
+    for summary in library
+        .artists()
+        .iter()
+        .filter(|summary| summary.album_count > 0)
+    {
+        let letter = summary
+            .artist
+            .sort_key(library.sort_key_options())
+            .chars()
+            .next()
+            .filter(|c| c.is_alphabetic())
+            .map(|c| c.to_uppercase().to_string())
+            .unwrap_or_else(|| "#".to_string());
+        indexes.entry(letter).or_default().push(artist(summary));
+    }
+    indexes
+        .into_iter()
+        .map(|(letter, artists)| {
+            Node::new("index")
+                .attribute("name", letter)
+                .list("artist", artists)
+        })
+        .collect()
+}
+
+fn ignored_articles(library: &Library) -> String {
+    library.sort_key_options().articles.join(" ")
+}
+
+fn artist_albums<'a>(library: &'a Library, artist_id: &str) -> Vec<&'a Album> {
+    let filter = AlbumFilter {
+        artist_id: Some(artist_id.to_string()),
+        ..AlbumFilter::default()
+    };
+    library
+        .list_albums(
+            &filter,
+            AlbumSort::Year,
+            SortOrder::Ascending,
+            Page {
+                offset: 0,
+                limit: usize::MAX,
+            },
+        )
+        .items
+}
+
+pub(super) fn artist_node(summary: &ArtistSummary) -> Node {
+    Node::new("artist")
+        .attribute("id", summary.artist.id())
+        .attribute("name", summary.artist.name().clone())
+        .attribute("albumCount", summary.album_count)
+}
+
+pub(super) fn album_node(album: &Album) -> Node {
+    let duration = album
+        .tracks()
+        .iter()
+        .filter_map(|track| *track.duration())
+        .map(|duration| duration.as_secs())
+        .sum::<u64>();
+    Node::new("album")
+        .attribute("id", album.id())
+        .attribute("name", album.title().name().clone())
+        .attribute("artist", album.artist().name().clone())
+        .attribute("artistId", album.artist().id())
+        .optional("coverArt", album_cover(album).map(Cover::id))
+        .attribute("songCount", album.tracks().len())
+        .attribute("duration", duration)
+        .optional("year", album.year().map(|year| year.0))
+        .optional(
+            "genre",
+            album
+                .tracks()
+                .first()
+                .map(|track| track.genre().name().clone()),
+        )
+}
+
+/// Cover of the first track that has one.
+fn album_cover(album: &Album) -> Option<&Cover> {
+    album
+        .tracks()
+        .iter()
+        .map(Audio::album_cover)
+        .find(|cover| !cover.is_default())
+}
+
+pub(super) fn song_node(state: &AppState, library: &Library, audio: &Audio) -> Node {
+    let album_id = library.album_of(audio).map(Album::id);
+    let suffix = audio
+        .path()
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    // Clients show the path relative to the music folder
+    let path = state
+        .library_roots
+        .iter()
+        .find_map(|root| audio.path().strip_prefix(root).ok())
+        .unwrap_or(audio.path());
+    let cover = audio.album_cover();
+    // Statistics that cannot be read are left out rather than failing the whole listing
+    let stats = state
+        .activity
+        .stats(&state.user().id)
+        .ok()
+        .and_then(|stats| stats.get(&audio.id()).copied())
+        .unwrap_or_default();
+    Node::new("song")
+        .attribute("id", audio.id())
+        .optional("parent", album_id.clone())
+        .attribute("isDir", false)
+        .attribute("title", audio.title().name().clone())
+        .attribute("album", audio.album_title().name().clone())
+        .attribute("artist", audio.artist().name().clone())
+        .optional("track", *audio.track_number())
+        .optional("discNumber", *audio.disc_number())
+        .optional("year", audio.year().map(|year| year.0))
+        .attribute("genre", audio.genre().name().clone())
+        .optional("coverArt", (!cover.is_default()).then(|| cover.id()))
+        .attribute("contentType", content_type(audio.path()))
+        .attribute("suffix", suffix)
+        .optional(
+            "duration",
+            audio.duration().map(|duration| duration.as_secs()),
+        )
+        .optional("bitRate", *audio.bitrate())
+        .attribute("path", path.to_string_lossy().into_owned())
+        .attribute("isVideo", false)
+        .optional("albumId", album_id)
+        .attribute("artistId", audio.artist().id())
+        .attribute("type", "music")
+        .attribute("mediaType", "song")
+        .attribute("playCount", stats.play_count)
+        .optional("played", stats.last_played.map(timestamp))
+        .optional("starred", stats.favorited_at.map(timestamp))
+        .optional("userRating", stats.rating.map(|rating| rating.stars()))
+}
+
+/// Fisher-Yates shuffle with a xorshift generator seeded from the clock.
+fn shuffle<T>(items: &mut [T]) {
+    let mut state = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or_default()
+        | 1;
+    for i in (1..items.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        items.swap(i, (state % (i as u64 + 1)) as usize);
+    }
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use axum::{
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+
+use crate::domain::entity::{audio::Audio, transcode_profile::TranscodeProfile};
+
+use super::{
+    super::{
+        stream::{authorize, content_type, serve_file},
+        transcode::transcoded_response,
+        AppState,
+    },
+    Params, SubsonicError,
+};
+
+/// Serves the original file, or a transcoded version when the client asks for another
+/// `format` or for a `maxBitRate`, in kbit/s, below the bitrate of the file.
+pub async fn stream(
+    state: &AppState,
+    params: &Params,
+    headers: &HeaderMap,
+) -> Result<Response, SubsonicError> {
+    let audio = state
+        .snapshot()
+        .audio(params.required("id")?)
+        .ok_or_else(|| SubsonicError::not_found("Song"))?
+        .clone();
+    let format = params.get("format");
+    let max_bitrate = params.parse::<u32>("maxBitRate")?.unwrap_or_default();
+    let offset = params.parse::<u64>("timeOffset")?.unwrap_or_default();
+
+    match profile(state.transcoding.profiles(), &audio, format, max_bitrate)? {
+        Some(profile) => {
+            let profile = profile.name.clone();
+            let offset = Duration::from_secs(offset);
+            Ok(transcoded_response(state, audio, profile, offset, headers).await?)
+        }
+        None => {
+            let path = authorize(state, audio.path()).await?;
+            Ok(serve_file(&path, content_type(&path), headers).await?)
+        }
+    }
+}
+
+/// Chooses the profile to transcode with, `None` to serve the original file. Among the
+/// profiles of the requested codec, the one with the highest bitrate not above
+/// `max_bitrate` is preferred.
+fn profile<'a>(
+    profiles: &'a [TranscodeProfile],
+    audio: &Audio,
+    format: Option<&str>,
+    max_bitrate: u32,
+) -> Result<Option<&'a TranscodeProfile>, SubsonicError> {
+    let codec = match format {
+        Some("raw") => return Ok(None),
+        Some(format) => Some(format),
+        None if max_bitrate > 0 && audio.bitrate().is_none_or(|bitrate| bitrate > max_bitrate) => {
+            None
+        }
+        None => return Ok(None),
+    };
+    let candidates = profiles
+        .iter()
+        .filter(|profile| codec.is_none_or(|codec| profile.codec.extension() == codec))
+        .collect::<Vec<_>>();
+    let within = candidates
+        .iter()
+        .filter(|profile| max_bitrate == 0 || profile.bitrate <= max_bitrate)
+        .max_by_key(|profile| profile.bitrate);
+    let profile = within
+        .or_else(|| candidates.iter().min_by_key(|profile| profile.bitrate))
+        .copied()
+        .ok_or_else(|| SubsonicError::generic("No transcoding profile for the format"))?;
+    Ok(Some(profile))
+}
+
+/// Serves a cover by its id, or the cover of an album or a song. Covers are not resized.
+pub fn cover_art(state: &AppState, params: &Params) -> Result<Response, SubsonicError> {
+    let library = state.snapshot();
+    let id = params.required("id")?;
+    if let Some(cover) = library.cover(id) {
+        // Cover ids are content hashes, so they never change
+        return Ok((
+            [
+                (header::CONTENT_TYPE, cover.mime_type()),
+                (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+            ],
+            cover.data().to_vec(),
+        )
+            .into_response());
+    }
+    let cover = library
+        .album(id)
+        .and_then(|album| album.tracks().first())
+        .or_else(|| library.audio(id))
+        .map(Audio::album_cover)
+        .filter(|cover| !cover.is_default())
+        .ok_or_else(|| SubsonicError::not_found("Cover art"))?;
+    Ok((
+        [(header::CONTENT_TYPE, cover.mime_type())],
+        cover.data().to_vec(),
+    )
+        .into_response())
+}
@@ -0,0 +1,112 @@
+use std::io;
+
+use tokio::task;
+
+use crate::{
+    application::service::{Library, SmartPlaylistServiceError},
+    domain::entity::{audio::Audio, smart_playlist::SmartPlaylist},
+};
+
+use super::{
+    super::{smart_playlist, ApiError, AppState},
+    browsing::song_node,
+    Node, Params, SubsonicError,
+};
+
+/// The playlist files of the library followed by the smart playlists the user sees. Files
+/// are read on a blocking thread as a scan makes them be read again.
+pub async fn playlists(state: &AppState) -> Result<Node, SubsonicError> {
+    let state = state.clone();
+    task::spawn_blocking(move || {
+        let library = state.snapshot();
+        let mut playlists = Vec::new();
+        for playlist in state.playlist_files()? {
+            let tracks = audios(&library, &playlist.tracks);
+            playlists.push(playlist_node(&playlist.id, &playlist.name, None, &tracks));
+        }
+        for playlist in state
+            .smart_playlists
+            .playlists(state.user())
+            .map_err(ApiError::from)?
+        {
+            let tracks = smart_playlist::tracks(&state, &library, &playlist)?;
+            let node = playlist_node(&playlist.id, &playlist.name, Some(&playlist), &tracks);
+            playlists.push(owned(&state, node, &playlist));
+        }
+        Ok(Node::new("playlists").list("playlist", playlists))
+    })
+    .await
+    .map_err(|err| ApiError::from(io::Error::other(err)))?
+}
+
+pub async fn playlist(state: &AppState, params: &Params) -> Result<Node, SubsonicError> {
+    let id = params.required("id")?.to_string();
+    let state = state.clone();
+    task::spawn_blocking(move || {
+        let library = state.snapshot();
+        let file = state
+            .playlist_files()?
+            .into_iter()
+            .find(|playlist| playlist.id == id);
+        let (node, tracks) =
+            match file {
+                Some(playlist) => {
+                    let tracks = audios(&library, &playlist.tracks);
+                    let node = playlist_node(&id, &playlist.name, None, &tracks);
+                    (node, tracks)
+                }
+                None => {
+                    let playlist = state.smart_playlists.playlist(state.user(), &id).map_err(
+                        |err| match err {
+                            SmartPlaylistServiceError::NotFound(_) => {
+                                SubsonicError::not_found("Playlist")
+                            }
+                            err => ApiError::from(err).into(),
+                        },
+                    )?;
+                    let tracks = smart_playlist::tracks(&state, &library, &playlist)?;
+                    let node = playlist_node(&id, &playlist.name, Some(&playlist), &tracks);
+                    (owned(&state, node, &playlist), tracks)
+                }
+            };
+        let entries = tracks
+            .iter()
+            .map(|audio| song_node(&state, &library, audio).rename("entry"))
+            .collect();
+        Ok(node.list("entry", entries))
+    })
+    .await
+    .map_err(|err| ApiError::from(io::Error::other(err)))?
+}
+
+fn audios<'a>(library: &'a Library, ids: &[String]) -> Vec<&'a Audio> {
+    ids.iter().filter_map(|id| library.audio(id)).collect()
+}
+
+/// Playlists cannot be edited through the API. Playlist files and smart playlists without
+/// an owner are shared by every user.
+fn playlist_node(id: &str, name: &str, smart: Option<&SmartPlaylist>, tracks: &[&Audio]) -> Node {
+    let public = smart.is_none_or(|playlist| playlist.public || playlist.owner.is_none());
+    let duration = tracks
+        .iter()
+        .filter_map(|audio| *audio.duration())
+        .map(|duration| duration.as_secs())
+        .sum::<u64>();
+    Node::new("playlist")
+        .attribute("id", id)
+        .attribute("name", name)
+        .attribute("public", public)
+        .attribute("readonly", true)
+        .attribute("songCount", tracks.len())
+        .attribute("duration", duration)
+}
+
+/// Adds the name of the user who owns a smart playlist, unless they no longer exist.
+fn owned(state: &AppState, node: Node, playlist: &SmartPlaylist) -> Node {
+    let owner = playlist
+        .owner
+        .as_ref()
+        .and_then(|owner| state.users.user(owner).ok())
+        .map(|user| user.username);
+    node.optional("owner", owner)
+}
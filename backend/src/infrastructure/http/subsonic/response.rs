@@ -0,0 +1,206 @@
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Map, Value};
+
+use super::{SubsonicError, API_VERSION};
+
+/// An element of a response, rendered as XML or JSON. Attributes become JSON fields and
+/// lists become JSON arrays, even when empty or with a single element.
+#[derive(Debug, Clone)]
+pub struct Node {
+    name: &'static str,
+    attributes: Vec<(&'static str, Value)>,
+    children: Vec<Children>,
+    text: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum Children {
+    One(Node),
+    Many(&'static str, Vec<Node>),
+}
+
+impl Node {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            attributes: Vec::new(),
+            children: Vec::new(),
+            text: None,
+        }
+    }
+
+    /// The same element under another name, such as a song listed as a playlist `entry`.
+    pub fn rename(mut self, name: &'static str) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub fn attribute(mut self, name: &'static str, value: impl Into<Value>) -> Self {
+        self.attributes.push((name, value.into()));
+        self
+    }
+
+    /// Adds the attribute only if there is a value.
+    pub fn optional(self, name: &'static str, value: Option<impl Into<Value>>) -> Self {
+        match value {
+            Some(value) => self.attribute(name, value),
+            None => self,
+        }
+    }
+
+    pub fn child(mut self, child: Node) -> Self {
+        self.children.push(Children::One(child));
+        self
+    }
+
+    /// Adds a list of elements named `name`.
+    pub fn list(mut self, name: &'static str, children: Vec<Node>) -> Self {
+        self.children.push(Children::Many(name, children));
+        self
+    }
+
+    /// Text content, which JSON holds in a `value` field.
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    fn to_json(&self) -> Value {
+        let mut object = Map::new();
+        for (name, value) in &self.attributes {
+            object.insert(name.to_string(), value.clone());
+        }
+        if let Some(text) = &self.text {
+            object.insert("value".to_string(), Value::String(text.clone()));
+        }
+        for children in &self.children {
+            match children {
+                Children::One(child) => {
+                    object.insert(child.name.to_string(), child.to_json());
+                }
+                Children::Many(name, children) => {
+                    let children = children.iter().map(Node::to_json).collect();
+                    object.insert(name.to_string(), Value::Array(children));
+                }
+            }
+        }
+        Value::Object(object)
+    }
+
+    fn write_xml(&self, xml: &mut String) {
+        xml.push('<');
+        xml.push_str(self.name);
+        for (name, value) in &self.attributes {
+            let value = match value {
+                Value::String(value) => escape(value),
+                value => value.to_string(),
+            };
+            xml.push_str(&format!(" {name}=\"{value}\""));
+        }
+        let empty = self.text.is_none()
+            && self.children.iter().all(|children| match children {
+                Children::One(_) => false,
+                Children::Many(_, children) => children.is_empty(),
+            });
+        if empty {
+            xml.push_str("/>");
+            return;
+        }
+        xml.push('>');
+        if let Some(text) = &self.text {
+            xml.push_str(&escape(text));
+        }
+        for children in &self.children {
+            match children {
+                Children::One(child) => child.write_xml(xml),
+                Children::Many(_, children) => {
+                    for child in children {
+                        child.write_xml(xml);
+                    }
+                }
+            }
+        }
+        xml.push_str(&format!("</{}>", self.name));
+    }
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Xml,
+    Json,
+}
+
+impl Format {
+    /// Parses the `f` parameter, XML being the default.
+    pub fn new(value: Option<&str>) -> Self {
+        match value {
+            Some("json") => Self::Json,
+            _ => Self::Xml,
+        }
+    }
+
+    pub fn ok(self, payload: Option<Node>) -> Response {
+        let root = Self::root("ok");
+        self.render(match payload {
+            Some(payload) => root.child(payload),
+            None => root,
+        })
+    }
+
+    pub fn error(self, error: &SubsonicError) -> Response {
+        let error = Node::new("error")
+            .attribute("code", error.code)
+            .attribute("message", error.message.clone());
+        self.render(Self::root("failed").child(error))
+    }
+
+    fn root(status: &str) -> Node {
+        Node::new("subsonic-response")
+            .attribute("status", status)
+            .attribute("version", API_VERSION)
+            .attribute("type", "earr")
+            .attribute("serverVersion", env!("CARGO_PKG_VERSION"))
+            .attribute("openSubsonic", true)
+    }
+
+    fn render(self, root: Node) -> Response {
+        match self {
+            Self::Json => {
+                let mut object = Map::new();
+                object.insert(root.name.to_string(), root.to_json());
+                let body = Value::Object(object).to_string();
+                ([(header::CONTENT_TYPE, "application/json")], body).into_response()
+            }
+            Self::Xml => {
+                let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+                let root = Node {
+                    attributes: [("xmlns", Value::from("http://subsonic.org/restapi"))]
+                        .into_iter()
+                        .chain(root.attributes)
+                        .collect(),
+                    ..root
+                };
+                root.write_xml(&mut xml);
+                ([(header::CONTENT_TYPE, "text/xml; charset=utf-8")], xml).into_response()
+            }
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::io;
+
+use axum::{extract::Path, Json};
+use serde::Deserialize;
+use tokio::task;
+
+use crate::{
+    application::service::{FileEditResult, Library, LibraryServiceError, TagEdit},
+    domain::{entity::audio::year::Year, repository::AudioTagWriter},
+};
+
+use super::{
+    dto::{BatchEditReportDto, EditJournalDto, TagChangeDto},
+    ApiError, AppState,
+};
+
+/// An edit of a batch. Text values are parsed like tags read from files.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagEditInput {
+    SetTitle(String),
+    SetArtist(String),
+    SetAlbumTitle(String),
+    SetAlbumArtist(String),
+    SetYear(Option<u16>),
+    SetGenre(String),
+    ReplaceGenre {
+        from: String,
+        to: String,
+    },
+    SetDiscNumber(Option<u16>),
+    SetCompilation(bool),
+    /// Identifier of a cover of the library.
+    SetCover(String),
+    RenumberTracks,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchEditInput {
+    tracks: Vec<String>,
+    edits: Vec<TagEditInput>,
+    /// Shown along with the journal, describing the edits when not given.
+    description: Option<String>,
+    /// Reports the changes without writing them.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Changes writing the metadata of the track into its file would make, without writing
+/// them. Files are read, so the diff is computed on a blocking thread.
+pub async fn diff(
+    state: AppState,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<TagChangeDto>>, ApiError> {
+    state.check_can_edit()?;
+    let changes = task::spawn_blocking(move || {
+        let library = state.snapshot();
+        let audio = library.audio(&id).ok_or(ApiError::NotFound("Track"))?;
+        Ok::<_, ApiError>(state.tag_writer.diff(audio)?)
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok(Json(changes.iter().map(TagChangeDto::from).collect()))
+}
+
+/// Writes the metadata of the track into its file, returning the changes made.
+pub async fn write(
+    state: AppState,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<TagChangeDto>>, ApiError> {
+    state.check_can_edit()?;
+    let changes = task::spawn_blocking(move || {
+        let library = state.snapshot();
+        let audio = library.audio(&id).ok_or(ApiError::NotFound("Track"))?;
+        Ok::<_, ApiError>(state.tag_writer.write(audio)?)
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok(Json(changes.iter().map(TagChangeDto::from).collect()))
+}
+
+/// Applies edits to many tracks as one batch that can be undone, for users who may edit.
+/// The library is rescanned once files were written.
+pub async fn edit(
+    state: AppState,
+    Json(input): Json<BatchEditInput>,
+) -> Result<Json<BatchEditReportDto>, ApiError> {
+    state.check_can_edit()?;
+    let report = task::spawn_blocking(move || {
+        let library = state.snapshot();
+        let audios = input
+            .tracks
+            .iter()
+            .map(|id| {
+                library
+                    .audio(id)
+                    .cloned()
+                    .ok_or(ApiError::NotFound("Track"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let edits = input
+            .edits
+            .into_iter()
+            .map(|edit| tag_edit(&library, edit))
+            .collect::<Result<Vec<_>, _>>()?;
+        let description = input
+            .description
+            .unwrap_or_else(|| format!("Edit of {} tracks", audios.len()));
+        let report = state
+            .tag_edits
+            .apply(&description, &audios, &edits, input.dry_run)?;
+        // Journals are only kept for batches that wrote files
+        if report.journal.is_some() {
+            rescan(&state);
+        }
+        Ok::<_, ApiError>(report)
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok(Json(BatchEditReportDto::from(&report)))
+}
+
+/// Batches that can be undone, among the ones that only edited files the user may access.
+pub async fn journals(state: AppState) -> Result<Json<Vec<EditJournalDto>>, ApiError> {
+    state.check_can_edit()?;
+    let user = state.user();
+    Ok(Json(
+        state
+            .tag_edits
+            .journals()?
+            .iter()
+            .filter(|journal| {
+                journal
+                    .entries()
+                    .iter()
+                    .all(|entry| user.can_access(&entry.path))
+            })
+            .map(EditJournalDto::from)
+            .collect(),
+    ))
+}
+
+/// Restores the tags a batch replaced. The journal is kept when a file could not be
+/// restored, so that the undo can be retried.
+pub async fn undo(
+    state: AppState,
+    Path(id): Path<String>,
+) -> Result<Json<BatchEditReportDto>, ApiError> {
+    let Json(journals) = journals(state.clone()).await?;
+    if !journals.iter().any(|journal| journal.id == id) {
+        return Err(ApiError::NotFound("Journal"));
+    }
+    let report = task::spawn_blocking(move || {
+        let report = state.tag_edits.undo(&id)?;
+        let restored = report
+            .files
+            .iter()
+            .any(|file| matches!(file.result, FileEditResult::Edited(_)));
+        if restored {
+            rescan(&state);
+        }
+        Ok::<_, ApiError>(report)
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok(Json(BatchEditReportDto::from(&report)))
+}
+
+fn tag_edit(library: &Library, input: TagEditInput) -> Result<TagEdit, ApiError> {
+    Ok(match input {
+        TagEditInput::SetTitle(title) => {
+            TagEdit::SetTitle(title.parse().map_err(invalid("empty title"))?)
+        }
+        TagEditInput::SetArtist(artist) => {
+            TagEdit::SetArtist(artist.parse().map_err(invalid("empty artist"))?)
+        }
+        TagEditInput::SetAlbumTitle(title) => {
+            TagEdit::SetAlbumTitle(title.parse().map_err(invalid("empty album title"))?)
+        }
+        TagEditInput::SetAlbumArtist(artist) => {
+            TagEdit::SetAlbumArtist(artist.parse().map_err(invalid("empty album artist"))?)
+        }
+        TagEditInput::SetYear(year) => TagEdit::SetYear(
+            year.map(|year| Year::try_from(i32::from(year)))
+                .transpose()
+                .map_err(invalid("invalid year"))?,
+        ),
+        TagEditInput::SetGenre(genre) => {
+            TagEdit::SetGenre(genre.parse().map_err(invalid("invalid genre"))?)
+        }
+        TagEditInput::ReplaceGenre { from, to } => TagEdit::ReplaceGenre {
+            from: from.parse().map_err(invalid("invalid genre"))?,
+            to: to.parse().map_err(invalid("invalid genre"))?,
+        },
+        TagEditInput::SetDiscNumber(disc_number) => TagEdit::SetDiscNumber(disc_number),
+        TagEditInput::SetCompilation(compilation) => TagEdit::SetCompilation(compilation),
+        TagEditInput::SetCover(id) => TagEdit::SetCover(
+            library
+                .cover(&id)
+                .cloned()
+                .ok_or(ApiError::NotFound("Cover"))?,
+        ),
+        TagEditInput::RenumberTracks => TagEdit::RenumberTracks,
+    })
+}
+
+fn invalid<E>(message: &'static str) -> impl FnOnce(E) -> ApiError {
+    move |_| ApiError::BadRequest(message)
+}
+
+/// Starts a scan once files were written, for the library to show the new tags.
+fn rescan(state: &AppState) {
+    match state.library.start_scan() {
+        Ok(()) => {}
+        // The running scan may have read the files before they were written
+        Err(LibraryServiceError::ScanInProgress) => {
+            log::warn!("Tags were written during a scan, rescan to see all of them")
+        }
+        Err(err) => log::error!("Failed to rescan after writing tags: {}", err),
+    }
+}
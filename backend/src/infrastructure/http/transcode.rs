@@ -0,0 +1,90 @@
+use std::{io, time::Duration};
+
+use axum::{
+    body::Body,
+    extract::{Path, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use tokio::task;
+use tokio_util::io::{ReaderStream, SyncIoBridge};
+
+use crate::{
+    application::service::TranscodedOutput,
+    domain::entity::{audio::Audio, transcode_profile::TranscodeProfile},
+};
+
+use super::{
+    stream::{authorize, serve_file},
+    ApiError, AppState,
+};
+
+/// Size of the buffer between the transcoder and the response.
+const PIPE_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct TranscodeParams {
+    profile: String,
+    /// Seconds to skip.
+    #[serde(default)]
+    offset: f64,
+}
+
+pub async fn profiles(state: AppState) -> Json<Vec<TranscodeProfile>> {
+    Json(state.transcoding.profiles().to_vec())
+}
+
+/// Serves a track transcoded with the requested profile. Cached outputs support range
+/// requests, outputs being transcoded are sent as they are produced.
+pub async fn transcode(
+    state: AppState,
+    Path(id): Path<String>,
+    Query(params): Query<TranscodeParams>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let offset = Duration::try_from_secs_f64(params.offset)
+        .map_err(|_| ApiError::BadRequest("offset must be a positive number of seconds"))?;
+    let audio = state
+        .snapshot()
+        .audio(&id)
+        .ok_or(ApiError::NotFound("Track"))?
+        .clone();
+    transcoded_response(&state, audio, params.profile, offset, &headers).await
+}
+
+/// Transcodes `audio` and sends the output, see [`transcode`].
+pub(super) async fn transcoded_response(
+    state: &AppState,
+    audio: Audio,
+    profile: String,
+    offset: Duration,
+    headers: &HeaderMap,
+) -> Result<Response, ApiError> {
+    authorize(state, audio.path()).await?;
+    let transcoding = state.transcoding.clone();
+    let transcoded = task::spawn_blocking(move || transcoding.transcode(&audio, &profile, offset))
+        .await
+        .map_err(io::Error::other)??;
+    let content_type = transcoded.profile.codec.mime_type();
+    match transcoded.output {
+        TranscodedOutput::Cached(path) => serve_file(&path, content_type, headers).await,
+        TranscodedOutput::Live(mut output) => {
+            let (writer, reader) = tokio::io::duplex(PIPE_SIZE);
+            task::spawn_blocking(move || {
+                // Fails when the client disconnects, which stops the transcoder
+                io::copy(&mut output, &mut SyncIoBridge::new(writer)).ok();
+            });
+            Ok((
+                StatusCode::OK,
+                [
+                    (header::CONTENT_TYPE, content_type),
+                    (header::ACCEPT_RANGES, "none"),
+                ],
+                Body::from_stream(ReaderStream::new(reader)),
+            )
+                .into_response())
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::{io, path::PathBuf};
+
+use axum::{extract::Path, http::StatusCode, Json};
+use serde::Deserialize;
+use tokio::task;
+
+use crate::{application::service::UserUpdate, domain::entity::user::Role};
+
+use super::{
+    dto::{CreatedTokenDto, SubsonicPasswordDto, TokenDto, UserDto},
+    ApiError, AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordInput {
+    current: String,
+    password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenInput {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserInput {
+    username: String,
+    password: String,
+    #[serde(default = "default_role")]
+    role: Role,
+    #[serde(default)]
+    library_roots: Vec<PathBuf>,
+}
+
+fn default_role() -> Role {
+    Role::User
+}
+
+/// Changes to a user. Fields that are not given are left as they are.
+#[derive(Debug, Deserialize)]
+pub struct UserChanges {
+    password: Option<String>,
+    role: Option<Role>,
+    library_roots: Option<Vec<PathBuf>>,
+}
+
+/// The authenticated user.
+pub async fn me(state: AppState) -> Json<UserDto> {
+    Json(UserDto::from(state.user().clone()))
+}
+
+/// Changes the password of the authenticated user, who must give the current one. Hashing
+/// takes a while, so it runs on a blocking thread.
+pub async fn change_password(
+    state: AppState,
+    Json(input): Json<PasswordInput>,
+) -> Result<StatusCode, ApiError> {
+    task::spawn_blocking(move || {
+        state
+            .users
+            .change_password(&state.user().id, &input.current, &input.password)
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Tokens of the authenticated user, the newest first.
+pub async fn tokens(state: AppState) -> Result<Json<Vec<TokenDto>>, ApiError> {
+    let tokens = state.users.tokens(&state.user().id)?;
+    Ok(Json(tokens.into_iter().map(TokenDto::from).collect()))
+}
+
+pub async fn create_token(
+    state: AppState,
+    Json(input): Json<TokenInput>,
+) -> Result<(StatusCode, Json<CreatedTokenDto>), ApiError> {
+    let (token, secret) = state.users.create_token(&state.user().id, &input.name)?;
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedTokenDto {
+            token: TokenDto::from(token),
+            secret,
+        }),
+    ))
+}
+
+pub async fn revoke_token(state: AppState, Path(id): Path<String>) -> Result<StatusCode, ApiError> {
+    state.users.revoke_token(&state.user().id, &id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Creates or replaces the password Subsonic clients authenticate with, shown once.
+pub async fn reset_subsonic_password(
+    state: AppState,
+) -> Result<(StatusCode, Json<SubsonicPasswordDto>), ApiError> {
+    let password = state.users.reset_subsonic_password(&state.user().id)?;
+    Ok((StatusCode::CREATED, Json(SubsonicPasswordDto { password })))
+}
+
+pub async fn delete_subsonic_password(state: AppState) -> Result<StatusCode, ApiError> {
+    state.users.delete_subsonic_password(&state.user().id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn users(state: AppState) -> Result<Json<Vec<UserDto>>, ApiError> {
+    state.check_admin()?;
+    let users = state.users.users()?;
+    Ok(Json(users.into_iter().map(UserDto::from).collect()))
+}
+
+pub async fn user(state: AppState, Path(id): Path<String>) -> Result<Json<UserDto>, ApiError> {
+    state.check_admin()?;
+    Ok(Json(UserDto::from(state.users.user(&id)?)))
+}
+
+pub async fn create(
+    state: AppState,
+    Json(input): Json<UserInput>,
+) -> Result<(StatusCode, Json<UserDto>), ApiError> {
+    state.check_admin()?;
+    let user = task::spawn_blocking(move || {
+        state.users.create(
+            &input.username,
+            &input.password,
+            input.role,
+            input.library_roots,
+        )
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok((StatusCode::CREATED, Json(UserDto::from(user))))
+}
+
+pub async fn update(
+    state: AppState,
+    Path(id): Path<String>,
+    Json(input): Json<UserChanges>,
+) -> Result<Json<UserDto>, ApiError> {
+    state.check_admin()?;
+    let user = task::spawn_blocking(move || {
+        state.users.update(
+            &id,
+            UserUpdate {
+                password: input.password,
+                role: input.role,
+                library_roots: input.library_roots,
+            },
+        )
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok(Json(UserDto::from(user)))
+}
+
+pub async fn delete(state: AppState, Path(id): Path<String>) -> Result<StatusCode, ApiError> {
+    state.check_admin()?;
+    state.users.delete(&id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
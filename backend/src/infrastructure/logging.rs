@@ -0,0 +1,88 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use chrono::Local;
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LoggerError {
+    #[error("Failed to open log file {0}: {1}")]
+    Open(PathBuf, io::Error),
+    #[error(transparent)]
+    Set(#[from] SetLoggerError),
+}
+
+/// Writes records as timestamped lines, to a file or the standard error.
+pub struct Logger {
+    level: LevelFilter,
+    file: Option<Mutex<File>>,
+}
+
+impl Logger {
+    /// Installs the logger for the whole process, appending to the file when given.
+    pub fn init(level: LevelFilter, file: Option<&Path>) -> Result<(), LoggerError> {
+        let file = file
+            .map(|path| {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|err| LoggerError::Open(path.to_path_buf(), err))
+            })
+            .transpose()?;
+        log::set_boxed_logger(Box::new(Self {
+            level,
+            file: file.map(Mutex::new),
+        }))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{} {:<5} {}: {}\n",
+            Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z"),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        // Failing to log has nowhere to be reported
+        match &self.file {
+            Some(file) => {
+                if let Ok(mut file) = file.lock() {
+                    let _ = file.write_all(line.as_bytes());
+                }
+            }
+            None => {
+                let _ = io::stderr().write_all(line.as_bytes());
+            }
+        }
+    }
+
+    fn flush(&self) {
+        match &self.file {
+            Some(file) => {
+                if let Ok(mut file) = file.lock() {
+                    let _ = file.flush();
+                }
+            }
+            None => {
+                let _ = io::stderr().flush();
+            }
+        }
+    }
+}
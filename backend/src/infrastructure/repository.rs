@@ -1 +1,16 @@
+pub mod activity_repository;
+pub mod audio_decoder;
 pub mod audio_gatherer_repository;
+pub mod audio_tag_writer;
+pub mod audio_transcoder;
+pub mod edit_journal_repository;
+pub mod fingerprint_repository;
+pub mod library_file_repository;
+pub mod library_repository;
+pub mod playlist_repository;
+pub mod scrobble_repository;
+pub mod scrobbler;
+pub mod smart_playlist_repository;
+pub mod sqlite;
+pub mod transcode_cache_repository;
+pub mod user_repository;
@@ -0,0 +1,4 @@
+mod sqlite_activity_repository;
+
+pub use sqlite_activity_repository::SqliteActivityRepository;
+pub use sqlite_activity_repository::SqliteActivityRepositoryError;
@@ -0,0 +1,342 @@
+use std::{collections::HashMap, path::Path, sync::Mutex, time::Duration};
+
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::{params, Connection, Row};
+use thiserror::Error;
+
+use crate::{
+    domain::{
+        entity::{
+            activity::{Annotation, ItemKind, Play, Rating, RatingError},
+            play_stats::PlayStats,
+        },
+        repository::ActivityRepository,
+    },
+    infrastructure::repository::sqlite,
+};
+
+/// Dates are stored as milliseconds since the Unix epoch.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS plays (
+        id INTEGER PRIMARY KEY,
+        user_id TEXT NOT NULL,
+        audio_id TEXT NOT NULL,
+        played_at INTEGER NOT NULL,
+        listened_ms INTEGER,
+        client TEXT
+    );
+    CREATE INDEX IF NOT EXISTS plays_played_at ON plays (user_id, played_at);
+    CREATE INDEX IF NOT EXISTS plays_audio_id ON plays (user_id, audio_id);
+    CREATE TABLE IF NOT EXISTS annotations (
+        user_id TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        item_id TEXT NOT NULL,
+        rating INTEGER,
+        favorited_at INTEGER,
+        PRIMARY KEY (user_id, kind, item_id)
+    );
+";
+
+/// Owner of the plays and annotations recorded before there were users.
+const UNOWNED: &str = "";
+
+/// Stores plays and annotations in a SQLite database, which may be the one of the
+/// library. They are keyed by user and item identifiers only, so they outlive the audios removed by a
+/// scan and apply again if the audios come back.
+pub struct SqliteActivityRepository {
+    connection: Mutex<Connection>,
+}
+
+#[derive(Error, Debug)]
+pub enum SqliteActivityRepositoryError {
+    #[error("Failed to access activity database: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Invalid stored rating: {0}")]
+    Rating(#[from] RatingError),
+    #[error("Invalid stored date: {0}")]
+    Date(i64),
+}
+
+impl SqliteActivityRepository {
+    /// Opens the database at `path`, creating it if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, SqliteActivityRepositoryError> {
+        let mut connection = sqlite::open(path)?;
+        migrate(&mut connection)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+impl ActivityRepository for SqliteActivityRepository {
+    type Error = SqliteActivityRepositoryError;
+
+    fn record_play(&self, user_id: &str, play: &Play) -> Result<(), Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        connection.execute(
+            "INSERT INTO plays (user_id, audio_id, played_at, listened_ms, client)
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                user_id,
+                play.audio_id,
+                play.played_at.timestamp_millis(),
+                play.listened
+                    .map(|listened| i64::try_from(listened.as_millis()).unwrap_or(i64::MAX)),
+                play.client,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn plays(&self, user_id: &str, offset: usize, limit: usize) -> Result<Vec<Play>, Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        let mut select = connection.prepare(
+            "SELECT audio_id, played_at, listened_ms, client FROM plays WHERE user_id = ?1
+            ORDER BY played_at DESC, id DESC LIMIT ?2 OFFSET ?3",
+        )?;
+        let mut rows = select.query(params![
+            user_id,
+            i64::try_from(limit).unwrap_or(i64::MAX),
+            i64::try_from(offset).unwrap_or(i64::MAX),
+        ])?;
+        let mut plays = Vec::new();
+        while let Some(row) = rows.next()? {
+            plays.push(Play {
+                audio_id: row.get(0)?,
+                played_at: date(row.get(1)?)?,
+                listened: row
+                    .get::<_, Option<i64>>(2)?
+                    .map(|listened| Duration::from_millis(listened.max(0) as u64)),
+                client: row.get(3)?,
+            });
+        }
+        Ok(plays)
+    }
+
+    fn play_total(&self, user_id: &str) -> Result<usize, Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        let total: i64 = connection.query_row(
+            "SELECT COUNT(*) FROM plays WHERE user_id = ?1",
+            [user_id],
+            |row| row.get(0),
+        )?;
+        Ok(total as usize)
+    }
+
+    fn track_stats(&self, user_id: &str) -> Result<HashMap<String, PlayStats>, Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        let mut stats: HashMap<String, PlayStats> = HashMap::new();
+        let mut select_plays = connection.prepare(
+            "SELECT audio_id, COUNT(*), MAX(played_at) FROM plays WHERE user_id = ?1
+            GROUP BY audio_id",
+        )?;
+        let mut rows = select_plays.query([user_id])?;
+        while let Some(row) = rows.next()? {
+            let entry = stats.entry(row.get(0)?).or_default();
+            entry.play_count = row.get(1)?;
+            entry.last_played = Some(date(row.get(2)?)?);
+        }
+        let mut select_annotations = connection.prepare(
+            "SELECT kind, item_id, rating, favorited_at FROM annotations
+            WHERE user_id = ?1 AND kind = ?2",
+        )?;
+        let mut rows = select_annotations.query([user_id, ItemKind::Track.as_str()])?;
+        while let Some(row) = rows.next()? {
+            let annotation = annotation(ItemKind::Track, row)?;
+            let entry = stats.entry(annotation.id).or_default();
+            entry.rating = annotation.rating;
+            entry.favorited_at = annotation.favorited_at;
+        }
+        Ok(stats)
+    }
+
+    fn annotation(
+        &self,
+        user_id: &str,
+        kind: ItemKind,
+        id: &str,
+    ) -> Result<Option<Annotation>, Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        let mut select = connection.prepare(
+            "SELECT kind, item_id, rating, favorited_at FROM annotations
+            WHERE user_id = ?1 AND kind = ?2 AND item_id = ?3",
+        )?;
+        let mut rows = select.query(params![user_id, kind.as_str(), id])?;
+        rows.next()?.map(|row| annotation(kind, row)).transpose()
+    }
+
+    fn annotations(&self, user_id: &str, kind: ItemKind) -> Result<Vec<Annotation>, Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        let mut select = connection.prepare(
+            "SELECT kind, item_id, rating, favorited_at FROM annotations
+            WHERE user_id = ?1 AND kind = ?2",
+        )?;
+        let mut rows = select.query([user_id, kind.as_str()])?;
+        let mut annotations = Vec::new();
+        while let Some(row) = rows.next()? {
+            annotations.push(annotation(kind, row)?);
+        }
+        Ok(annotations)
+    }
+
+    fn save_annotation(&self, user_id: &str, annotation: &Annotation) -> Result<(), Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        if annotation.is_empty() {
+            connection.execute(
+                "DELETE FROM annotations WHERE user_id = ?1 AND kind = ?2 AND item_id = ?3",
+                params![user_id, annotation.kind.as_str(), annotation.id],
+            )?;
+        } else {
+            connection.execute(
+                "INSERT OR REPLACE INTO annotations (user_id, kind, item_id, rating, favorited_at)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    user_id,
+                    annotation.kind.as_str(),
+                    annotation.id,
+                    annotation.rating.map(u8::from),
+                    annotation
+                        .favorited_at
+                        .map(|favorited_at| favorited_at.timestamp_millis()),
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn adopt_unowned(&self, user_id: &str) -> Result<usize, Self::Error> {
+        let mut connection = self.connection.lock().expect("connection lock poisoned");
+        let transaction = connection.transaction()?;
+        let plays = transaction.execute(
+            "UPDATE plays SET user_id = ?1 WHERE user_id = ?2",
+            params![user_id, UNOWNED],
+        )?;
+        // Annotations the user already made win over the unowned ones
+        let annotations = transaction.execute(
+            "UPDATE OR IGNORE annotations SET user_id = ?1 WHERE user_id = ?2",
+            params![user_id, UNOWNED],
+        )?;
+        transaction.execute("DELETE FROM annotations WHERE user_id = ?1", [UNOWNED])?;
+        transaction.commit()?;
+        Ok(plays + annotations)
+    }
+}
+
+/// Creates the schema, first upgrading the tables of databases from before users, whose
+/// rows become [`UNOWNED`].
+fn migrate(connection: &mut Connection) -> rusqlite::Result<()> {
+    let transaction = connection.transaction()?;
+    let legacy_plays = is_legacy(&transaction, "plays")?;
+    let legacy_annotations = is_legacy(&transaction, "annotations")?;
+    if legacy_plays {
+        // Indexes are created again on the user
+        transaction.execute_batch(
+            "DROP INDEX IF EXISTS plays_played_at;
+            DROP INDEX IF EXISTS plays_audio_id;",
+        )?;
+        transaction.execute(
+            &format!("ALTER TABLE plays ADD COLUMN user_id TEXT NOT NULL DEFAULT '{UNOWNED}'"),
+            [],
+        )?;
+    }
+    if legacy_annotations {
+        // The primary key gains the user, which needs a new table
+        transaction.execute_batch("ALTER TABLE annotations RENAME TO legacy_annotations")?;
+    }
+    transaction.execute_batch(SCHEMA)?;
+    if legacy_annotations {
+        transaction.execute(
+            "INSERT INTO annotations (user_id, kind, item_id, rating, favorited_at)
+            SELECT ?1, kind, item_id, rating, favorited_at FROM legacy_annotations",
+            [UNOWNED],
+        )?;
+        transaction.execute_batch("DROP TABLE legacy_annotations")?;
+    }
+    transaction.commit()
+}
+
+/// Whether the table exists without a user column.
+fn is_legacy(connection: &Connection, table: &str) -> rusqlite::Result<bool> {
+    let columns: Vec<String> = connection
+        .prepare("SELECT name FROM pragma_table_info(?1)")?
+        .query_map([table], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(!columns.is_empty() && !columns.iter().any(|column| column == "user_id"))
+}
+
+/// Reads an annotation selected as `kind, item_id, rating, favorited_at`.
+fn annotation(kind: ItemKind, row: &Row) -> Result<Annotation, SqliteActivityRepositoryError> {
+    Ok(Annotation {
+        kind,
+        id: row.get(1)?,
+        rating: row
+            .get::<_, Option<u8>>(2)?
+            .map(Rating::try_from)
+            .transpose()?,
+        favorited_at: row.get::<_, Option<i64>>(3)?.map(date).transpose()?,
+    })
+}
+
+fn date(millis: i64) -> Result<DateTime<Utc>, SqliteActivityRepositoryError> {
+    Utc.timestamp_millis_opt(millis)
+        .single()
+        .ok_or(SqliteActivityRepositoryError::Date(millis))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    #[test]
+    fn migrates_activity_from_before_users() {
+        let dir = env::temp_dir().join(format!("earr-activity-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("earr.sqlite3");
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE plays (
+                    id INTEGER PRIMARY KEY,
+                    audio_id TEXT NOT NULL,
+                    played_at INTEGER NOT NULL,
+                    listened_ms INTEGER,
+                    client TEXT
+                );
+                CREATE INDEX plays_played_at ON plays (played_at);
+                CREATE INDEX plays_audio_id ON plays (audio_id);
+                CREATE TABLE annotations (
+                    kind TEXT NOT NULL,
+                    item_id TEXT NOT NULL,
+                    rating INTEGER,
+                    favorited_at INTEGER,
+                    PRIMARY KEY (kind, item_id)
+                );
+                INSERT INTO plays (audio_id, played_at) VALUES ('a', 1000), ('b', 2000);
+                INSERT INTO annotations VALUES ('track', 'a', 4, NULL);",
+            )
+            .unwrap();
+
+        let repository = SqliteActivityRepository::open(&path).unwrap();
+        assert_eq!(repository.play_total("admin").unwrap(), 0);
+        assert_eq!(repository.adopt_unowned("admin").unwrap(), 3);
+        assert_eq!(repository.play_total("admin").unwrap(), 2);
+        let annotation = repository
+            .annotation("admin", ItemKind::Track, "a")
+            .unwrap()
+            .unwrap();
+        assert_eq!(annotation.rating, Some(Rating::try_from(4).unwrap()));
+
+        // Users now annotate the same item apart
+        repository.save_annotation("other", &annotation).unwrap();
+        assert_eq!(repository.adopt_unowned("admin").unwrap(), 0);
+        drop(repository);
+
+        // Opening again keeps the migrated tables
+        let repository = SqliteActivityRepository::open(&path).unwrap();
+        assert_eq!(repository.play_total("admin").unwrap(), 2);
+        drop(repository);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
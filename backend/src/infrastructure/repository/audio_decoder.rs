@@ -0,0 +1,4 @@
+mod ffmpeg_audio_decoder;
+
+pub use ffmpeg_audio_decoder::FfmpegAudioDecoder;
+pub use ffmpeg_audio_decoder::FfmpegAudioDecoderError;
@@ -0,0 +1,74 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use thiserror::Error;
+
+use crate::domain::repository::AudioDecoder;
+
+pub struct FfmpegAudioDecoder {
+    ffmpeg: PathBuf,
+}
+
+impl Default for FfmpegAudioDecoder {
+    fn default() -> Self {
+        Self::new("ffmpeg")
+    }
+}
+
+impl FfmpegAudioDecoder {
+    pub fn new<P: AsRef<Path>>(ffmpeg: P) -> Self {
+        Self {
+            ffmpeg: ffmpeg.as_ref().to_path_buf(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FfmpegAudioDecoderError {
+    #[error("Failed to execute ffmpeg: {0}")]
+    Ffmpeg(#[from] std::io::Error),
+    #[error("Failed to decode audio: {0}")]
+    Decode(String),
+}
+
+impl AudioDecoder for FfmpegAudioDecoder {
+    type Error = FfmpegAudioDecoderError;
+
+    fn decode(
+        &self,
+        path: &Path,
+        sample_rate: u32,
+        max_duration: Duration,
+    ) -> Result<Vec<i16>, Self::Error> {
+        let output = Command::new(&self.ffmpeg)
+            .arg("-v")
+            .arg("error")
+            .arg("-i")
+            .arg(path)
+            .arg("-t")
+            .arg(max_duration.as_secs_f64().to_string())
+            .arg("-vn")
+            .arg("-ac")
+            .arg("1")
+            .arg("-ar")
+            .arg(sample_rate.to_string())
+            .arg("-f")
+            .arg("s16le")
+            .arg("-")
+            .output()?;
+        if !output.status.success() {
+            return Err(FfmpegAudioDecoderError::Decode(
+                String::from_utf8_lossy(&output.stderr).into_owned(),
+            ));
+        }
+        let samples = output
+            .stdout
+            .chunks_exact(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect();
+        Ok(samples)
+    }
+}
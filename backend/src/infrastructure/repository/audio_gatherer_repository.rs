@@ -1,5 +1,7 @@
 pub mod audio_parser;
 
 mod filesystem_audio_gatherer_repository;
+mod scan_filter;
 pub use filesystem_audio_gatherer_repository::FilesystemAudioGathererRepository;
 pub use filesystem_audio_gatherer_repository::FilesystemAudioGathererRepositoryError;
+pub use scan_filter::{ScanFilter, ScanFilterError};
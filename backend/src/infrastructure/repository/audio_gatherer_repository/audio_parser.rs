@@ -1,16 +1,25 @@
-use crate::domain::entity::audio::{
-    artist::{Artist, ArtistError},
-    cover::{Cover, CoverError},
-    genre::{Genre, GenreError},
-    title::{Title, TitleError},
-    year::{Year, YearError},
-    Audio, AudioBuilder, AudioBuilderError,
+use std::{collections::BTreeSet, time::Duration};
+
+use chrono::{DateTime, Utc};
+
+use crate::domain::entity::{
+    activity::Rating,
+    audio::{
+        artist::{Artist, ArtistError},
+        cover::{Cover, CoverError},
+        field::AudioField,
+        genre::{Genre, GenreError},
+        title::{Title, TitleError},
+        year::{Year, YearError},
+        Audio, AudioBuilder, AudioBuilderError,
+    },
 };
 use thiserror::Error;
 use walkdir;
 
 pub mod audiotags;
 pub mod ffmpeg;
+pub mod path;
 pub mod resilient_audio_parser;
 
 pub trait AudioParser {
@@ -24,13 +33,46 @@ where
     fn parse(&self, entry: &walkdir::DirEntry) -> Result<Audio, AudioParserError> {
         let parsed_audio_try = self.try_parse(entry)?;
         let parsed_audio = AudioBuilder::default()
-            .title(parsed_audio_try.title.unwrap_or_default())
-            .artist(parsed_audio_try.artist.unwrap_or_default())
+            .title(with_sort_name(
+                parsed_audio_try.title.unwrap_or_default(),
+                parsed_audio_try.title_sort,
+                Title::with_sort_name,
+            ))
+            .artist(with_sort_name(
+                parsed_audio_try.artist.unwrap_or_default(),
+                parsed_audio_try.artist_sort,
+                Artist::with_sort_name,
+            ))
             .year(parsed_audio_try.year.ok())
-            .album_title(parsed_audio_try.album_title.unwrap_or_default())
-            .album_artist(parsed_audio_try.album_artist.unwrap_or_default())
+            .album_title(with_sort_name(
+                parsed_audio_try.album_title.unwrap_or_default(),
+                parsed_audio_try.album_title_sort,
+                Title::with_sort_name,
+            ))
+            .album_artist(with_sort_name(
+                parsed_audio_try.album_artist.unwrap_or_default(),
+                parsed_audio_try.album_artist_sort,
+                Artist::with_sort_name,
+            ))
             .album_cover(parsed_audio_try.album_cover.unwrap_or_default())
             .genre(parsed_audio_try.genre.unwrap_or_default())
+            .track_number(parsed_audio_try.track_number.ok())
+            .disc_number(parsed_audio_try.disc_number.ok())
+            .compilation(parsed_audio_try.compilation.unwrap_or_default())
+            .path(entry.path().to_path_buf())
+            .duration(parsed_audio_try.duration.ok())
+            .bitrate(parsed_audio_try.bitrate.ok())
+            .musicbrainz_recording_id(parsed_audio_try.musicbrainz_recording_id.ok().flatten())
+            .lyrics(parsed_audio_try.lyrics.ok().flatten())
+            .rating(parsed_audio_try.rating.ok().flatten())
+            .modified_at(
+                entry
+                    .metadata()
+                    .ok()
+                    .and_then(|metadata| metadata.modified().ok())
+                    .map(DateTime::<Utc>::from),
+            )
+            .inferred_fields(parsed_audio_try.inferred)
             .build()
             .map_err(AudioParserError::AudioBuilder)?;
         Ok(parsed_audio)
@@ -55,6 +97,10 @@ pub enum AudioParserError {
     Genre(#[from] GenreError),
     #[error("Failed to parse cover: {0}")]
     Cover(#[from] CoverError),
+    #[error("Failed to parse number: {0}")]
+    Number(String),
+    #[error("Failed to parse compilation flag: {0}")]
+    Compilation(String),
     #[error("Missing field: {0}")]
     MissingField(String),
     #[error("Inner parser error: {0}")]
@@ -62,12 +108,15 @@ pub enum AudioParserError {
 }
 type AudioParserResult<T> = Result<T, AudioParserError>;
 
-trait TryableAudioParser {
+/// A parser that may only be able to read some of the fields of an audio.
+/// Parsers can be chained with [`ResilientAudioParser`] so missing fields are filled by others.
+/// Parsers are shared with the scanning thread.
+pub trait TryableAudioParser: Send + Sync {
     fn try_parse(&self, entry: &walkdir::DirEntry) -> AudioParserResult<ParsedAudioTry>;
 }
 
 #[derive(Debug)]
-struct ParsedAudioTry {
+pub struct ParsedAudioTry {
     title: AudioParserResult<Title>,
     artist: AudioParserResult<Artist>,
     year: AudioParserResult<Year>,
@@ -75,8 +124,59 @@ struct ParsedAudioTry {
     album_artist: AudioParserResult<Artist>,
     album_cover: AudioParserResult<Cover>,
     genre: AudioParserResult<Genre>,
+    track_number: AudioParserResult<u16>,
+    disc_number: AudioParserResult<u16>,
+    compilation: AudioParserResult<bool>,
+    /// Optional tags are `Ok(None)` when the parser read the tags and they are absent, so
+    /// that the next parsers are not run for them, and `Err` when it could not tell.
+    title_sort: AudioParserResult<Option<String>>,
+    artist_sort: AudioParserResult<Option<String>>,
+    album_title_sort: AudioParserResult<Option<String>>,
+    album_artist_sort: AudioParserResult<Option<String>>,
+    duration: AudioParserResult<Duration>,
+    /// Average bitrate in kbit/s.
+    bitrate: AudioParserResult<u32>,
+    musicbrainz_recording_id: AudioParserResult<Option<String>>,
+    lyrics: AudioParserResult<Option<String>>,
+    rating: AudioParserResult<Option<Rating>>,
+    /// Fields whose values were inferred rather than read from tags.
+    inferred: BTreeSet<AudioField>,
+}
+
+/// Parses `n` or `n/total` style track and disc numbers.
+fn parse_number(value: &str) -> AudioParserResult<u16> {
+    let number = value.split('/').next().unwrap_or_default().trim();
+    number
+        .parse()
+        .map_err(|_| AudioParserError::Number(value.to_owned()))
+}
+
+fn with_sort_name<T>(
+    value: T,
+    sort_name: AudioParserResult<Option<String>>,
+    attach: fn(T, &str) -> T,
+) -> T {
+    match sort_name {
+        Ok(Some(sort_name)) => attach(value, &sort_name),
+        _ => value,
+    }
+}
+
+/// Parses the boolean flags used by `TCMP`, `COMPILATION` and `cpil` style tags.
+fn parse_flag(value: &str) -> AudioParserResult<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" => Ok(true),
+        "0" | "false" | "no" => Ok(false),
+        other => Err(AudioParserError::Compilation(other.to_owned())),
+    }
+}
+
+/// Parses a FMPS rating, a fraction from 0.0 to 1.0.
+fn parse_rating(value: &str) -> Option<Rating> {
+    value.trim().parse().ok().and_then(Rating::from_fraction)
 }
 
 pub use audiotags::AudiotagsAudioParser;
 pub use ffmpeg::FfmpegAudioParser;
+pub use path::{PathAudioParser, PathPattern, PathPatternError};
 pub use resilient_audio_parser::ResilientAudioParser;
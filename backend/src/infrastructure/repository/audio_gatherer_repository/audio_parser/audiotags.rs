@@ -1,9 +1,20 @@
-use audiotags::Tag;
+use std::{collections::BTreeSet, fs, path::Path, time::Duration};
+
+use audiotags::{AudioTag, Tag};
+use id3::TagLike;
 use thiserror::Error;
 
-use crate::domain::entity::audio::{cover::Cover, year::Year};
+use crate::domain::entity::{
+    activity::Rating,
+    audio::{cover::Cover, year::Year},
+};
+
+use super::{
+    parse_flag, parse_rating, AudioParserError, AudioParserResult, ParsedAudioTry,
+    TryableAudioParser,
+};
 
-use super::{AudioParserError, AudioParserResult, ParsedAudioTry, TryableAudioParser};
+mod mpeg;
 
 #[derive(Default)]
 pub struct AudiotagsAudioParser;
@@ -19,11 +30,12 @@ pub enum AudiotagsAudioParserError {
 impl TryableAudioParser for AudiotagsAudioParser {
     fn try_parse(&self, entry: &walkdir::DirEntry) -> AudioParserResult<ParsedAudioTry> {
         let entry_path = entry.path();
-        if entry_path.extension().is_none() {
+        let Some(extension) = entry_path.extension() else {
             return Err(AudioParserError::Inner(Box::new(
                 AudiotagsAudioParserError::NoExtension,
             )));
-        }
+        };
+        let extension = extension.to_string_lossy().to_lowercase();
         let audio_tags = Tag::new().read_from_path(entry_path).map_err(|err| {
             AudioParserError::Inner(Box::new(AudiotagsAudioParserError::from(err)))
         })?;
@@ -64,6 +76,41 @@ impl TryableAudioParser for AudiotagsAudioParser {
             .map(|cover| cover.data.to_vec())
             .and_then(|cover| Cover::try_from(cover).map_err(AudioParserError::Cover));
 
+        let track_number = audio_tags
+            .track_number()
+            .ok_or(AudioParserError::MissingField("track_number".to_owned()));
+
+        let disc_number = audio_tags
+            .disc_number()
+            .ok_or(AudioParserError::MissingField("disc_number".to_owned()));
+
+        // Fields not exposed by audiotags are read from the underlying tag
+        let raw_tag = RawTag::new(audio_tags, &extension);
+
+        let compilation = raw_tag
+            .optional(raw_tag.compilation(), "compilation")
+            .and_then(|compilation| compilation.unwrap_or(Ok(false)));
+
+        let title_sort = raw_tag.sort_tag(SortTag::Title);
+        let artist_sort = raw_tag.sort_tag(SortTag::Artist);
+        let album_title_sort = raw_tag.sort_tag(SortTag::AlbumTitle);
+        let album_artist_sort = raw_tag.sort_tag(SortTag::AlbumArtist);
+
+        let (duration, bitrate) = match raw_tag.stream_properties(entry_path) {
+            Some((duration, bitrate)) => (Ok(duration), Ok(bitrate)),
+            None => (
+                Err(AudioParserError::MissingField("duration".to_owned())),
+                Err(AudioParserError::MissingField("bitrate".to_owned())),
+            ),
+        };
+
+        let musicbrainz_recording_id = raw_tag.optional(
+            raw_tag.musicbrainz_recording_id(),
+            "musicbrainz_recording_id",
+        );
+        let lyrics = raw_tag.optional(raw_tag.lyrics(), "lyrics");
+        let rating = raw_tag.optional(raw_tag.rating(), "rating");
+
         let parsed_audio_try = ParsedAudioTry {
             title,
             artist,
@@ -72,7 +119,247 @@ impl TryableAudioParser for AudiotagsAudioParser {
             album_artist,
             album_cover,
             genre,
+            track_number,
+            disc_number,
+            compilation,
+            title_sort,
+            artist_sort,
+            album_title_sort,
+            album_artist_sort,
+            duration,
+            bitrate,
+            musicbrainz_recording_id,
+            lyrics,
+            rating,
+            inferred: BTreeSet::new(),
         };
         Ok(parsed_audio_try)
     }
 }
+
+#[derive(Clone, Copy)]
+enum SortTag {
+    Title,
+    Artist,
+    AlbumTitle,
+    AlbumArtist,
+}
+
+impl SortTag {
+    fn id3_frame(self) -> &'static str {
+        match self {
+            Self::Title => "TSOT",
+            Self::Artist => "TSOP",
+            Self::AlbumTitle => "TSOA",
+            Self::AlbumArtist => "TSO2",
+        }
+    }
+
+    fn vorbis_key(self) -> &'static str {
+        match self {
+            Self::Title => "TITLESORT",
+            Self::Artist => "ARTISTSORT",
+            Self::AlbumTitle => "ALBUMSORT",
+            Self::AlbumArtist => "ALBUMARTISTSORT",
+        }
+    }
+
+    fn mp4_fourcc(self) -> mp4ameta::Fourcc {
+        match self {
+            Self::Title => mp4ameta::Fourcc(*b"sonm"),
+            Self::Artist => mp4ameta::Fourcc(*b"soar"),
+            Self::AlbumTitle => mp4ameta::Fourcc(*b"soal"),
+            Self::AlbumArtist => mp4ameta::Fourcc(*b"soaa"),
+        }
+    }
+
+    fn field(self) -> &'static str {
+        match self {
+            Self::Title => "title_sort",
+            Self::Artist => "artist_sort",
+            Self::AlbumTitle => "album_title_sort",
+            Self::AlbumArtist => "album_artist_sort",
+        }
+    }
+}
+
+const MUSICBRAINZ_UFID_OWNER: &[u8] = b"http://musicbrainz.org\0";
+/// Name of the tag holding ratings in the Free Music Player Specifications.
+const FMPS_RATING: &str = "FMPS_Rating";
+
+enum RawTag {
+    Id3(id3::Tag),
+    Flac(metaflac::Tag),
+    Mp4(mp4ameta::Tag),
+    Unsupported,
+}
+
+impl RawTag {
+    fn new(audio_tag: Box<dyn AudioTag>, extension: &str) -> Self {
+        match extension {
+            "mp3" => Self::Id3(audio_tag.into()),
+            "flac" => Self::Flac(audio_tag.into()),
+            "m4a" | "m4b" | "m4p" | "m4v" | "isom" | "mp4" => Self::Mp4(audio_tag.into()),
+            _ => Self::Unsupported,
+        }
+    }
+
+    /// An optional tag read from the raw tag. It is only known to be absent when the format
+    /// is supported, otherwise the next parsers are left to read it.
+    fn optional<T>(&self, value: Option<T>, field: &str) -> AudioParserResult<Option<T>> {
+        match self {
+            Self::Unsupported => Err(AudioParserError::MissingField(field.to_owned())),
+            _ => Ok(value),
+        }
+    }
+
+    fn compilation(&self) -> Option<AudioParserResult<bool>> {
+        match self {
+            Self::Id3(tag) => tag
+                .get("TCMP")
+                .and_then(|frame| frame.content().text())
+                .map(parse_flag),
+            Self::Flac(tag) => tag
+                .get_vorbis("COMPILATION")
+                .and_then(|mut values| values.next())
+                .map(parse_flag),
+            Self::Mp4(tag) => tag
+                .data_of(&mp4ameta::ident::COMPILATION)
+                .next()
+                .map(|_| Ok(tag.compilation())),
+            Self::Unsupported => None,
+        }
+    }
+
+    /// Duration and average bitrate in kbit/s. When the container does not declare the
+    /// bitrate, it is estimated from the file size.
+    fn stream_properties(&self, path: &Path) -> Option<(Duration, u32)> {
+        let duration = match self {
+            Self::Id3(tag) => {
+                return mpeg::properties(path).or_else(|| {
+                    // TLEN holds the length in milliseconds
+                    let length = tag.get("TLEN")?.content().text()?.trim().parse().ok()?;
+                    Self::estimated(path, Duration::from_millis(length))
+                });
+            }
+            Self::Flac(tag) => tag.get_streaminfo().and_then(|stream_info| {
+                (stream_info.sample_rate > 0).then(|| {
+                    Duration::from_secs_f64(
+                        stream_info.total_samples as f64 / stream_info.sample_rate as f64,
+                    )
+                })
+            })?,
+            Self::Mp4(tag) => {
+                let duration = tag.duration()?;
+                if let Some(bitrate) = tag.avg_bitrate().filter(|bitrate| *bitrate > 0) {
+                    return Some((duration, bitrate / 1000));
+                }
+                duration
+            }
+            Self::Unsupported => return None,
+        };
+        Self::estimated(path, duration)
+    }
+
+    fn estimated(path: &Path, duration: Duration) -> Option<(Duration, u32)> {
+        let seconds = duration.as_secs_f64();
+        if seconds <= 0.0 {
+            return None;
+        }
+        let length = fs::metadata(path).ok()?.len();
+        Some((
+            duration,
+            (length as f64 * 8.0 / seconds / 1000.0).round() as u32,
+        ))
+    }
+
+    fn musicbrainz_recording_id(&self) -> Option<String> {
+        match self {
+            // UFID frames hold a null terminated owner followed by the identifier
+            Self::Id3(tag) => tag
+                .frames()
+                .filter(|frame| frame.id() == "UFID")
+                .filter_map(|frame| frame.content().to_unknown().ok())
+                .find_map(|unknown| {
+                    unknown
+                        .data
+                        .strip_prefix(MUSICBRAINZ_UFID_OWNER)
+                        .and_then(|identifier| String::from_utf8(identifier.to_vec()).ok())
+                }),
+            Self::Flac(tag) => tag
+                .get_vorbis("MUSICBRAINZ_TRACKID")
+                .and_then(|mut values| values.next())
+                .map(str::to_owned),
+            Self::Mp4(tag) => tag
+                .strings_of(&mp4ameta::FreeformIdent::new(
+                    "com.apple.iTunes",
+                    "MusicBrainz Track Id",
+                ))
+                .next()
+                .map(str::to_owned),
+            Self::Unsupported => None,
+        }
+        .map(|id| id.trim().to_owned())
+        .filter(|id| !id.is_empty())
+    }
+
+    fn lyrics(&self) -> Option<String> {
+        match self {
+            Self::Id3(tag) => tag.lyrics().next().map(|lyrics| lyrics.text.clone()),
+            Self::Flac(tag) => ["LYRICS", "UNSYNCEDLYRICS"].into_iter().find_map(|key| {
+                tag.get_vorbis(key)
+                    .and_then(|mut values| values.next())
+                    .map(str::to_owned)
+            }),
+            Self::Mp4(tag) => tag.lyrics().map(str::to_owned),
+            Self::Unsupported => None,
+        }
+        .filter(|lyrics| !lyrics.trim().is_empty())
+    }
+
+    /// The first POPM frame, the one most taggers write, or else a FMPS rating.
+    fn rating(&self) -> Option<Rating> {
+        match self {
+            Self::Id3(tag) => tag
+                .frames()
+                .find_map(|frame| frame.content().popularimeter())
+                .and_then(|popularimeter| Rating::from_popularimeter(popularimeter.rating))
+                .or_else(|| {
+                    tag.extended_texts()
+                        .find(|text| text.description.eq_ignore_ascii_case(FMPS_RATING))
+                        .and_then(|text| parse_rating(&text.value))
+                }),
+            Self::Flac(tag) => tag
+                .get_vorbis(FMPS_RATING)
+                .and_then(|mut values| values.next())
+                .and_then(parse_rating),
+            Self::Mp4(tag) => tag
+                .strings_of(&mp4ameta::FreeformIdent::new(
+                    "com.apple.iTunes",
+                    FMPS_RATING,
+                ))
+                .next()
+                .and_then(parse_rating),
+            Self::Unsupported => None,
+        }
+    }
+
+    fn sort_tag(&self, sort_tag: SortTag) -> AudioParserResult<Option<String>> {
+        let sort_name = match self {
+            Self::Id3(tag) => tag
+                .get(sort_tag.id3_frame())
+                .and_then(|frame| frame.content().text())
+                .map(str::to_owned),
+            Self::Flac(tag) => tag
+                .get_vorbis(sort_tag.vorbis_key())
+                .and_then(|mut values| values.next())
+                .map(str::to_owned),
+            Self::Mp4(tag) => tag
+                .strings_of(&sort_tag.mp4_fourcc())
+                .next()
+                .map(str::to_owned),
+            Self::Unsupported => None,
+        };
+        self.optional(sort_name, sort_tag.field())
+    }
+}
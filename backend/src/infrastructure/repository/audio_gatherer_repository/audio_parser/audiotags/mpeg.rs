@@ -0,0 +1,182 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    time::Duration,
+};
+
+/// How far after the ID3v2 tag the first frame is looked for.
+const SEARCH_BYTES: usize = 64 * 1024;
+
+/// Bitrates in kbit/s by version (1, 2/2.5), layer (I, II, III) and bitrate index.
+const BITRATES: [[[u32; 15]; 3]; 2] = [
+    [
+        [
+            0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+        ],
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+        ],
+        [
+            0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+        ],
+    ],
+    [
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+        ],
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+    ],
+];
+
+const SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+#[derive(Debug, Clone, Copy)]
+struct FrameHeader {
+    /// 1 for MPEG 1, 2 for MPEG 2 and 2.5
+    version: usize,
+    /// 1, 2 or 3
+    layer: usize,
+    bitrate: u32,
+    sample_rate: u32,
+    mono: bool,
+    length: usize,
+}
+
+impl FrameHeader {
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 4 || bytes[0] != 0xFF || bytes[1] & 0xE0 != 0xE0 {
+            return None;
+        }
+        let (version, sample_rate_divisor) = match (bytes[1] >> 3) & 0b11 {
+            0b11 => (1, 1),
+            0b10 => (2, 2),
+            0b00 => (2, 4),
+            _ => return None,
+        };
+        let layer = match (bytes[1] >> 1) & 0b11 {
+            0b11 => 1,
+            0b10 => 2,
+            0b01 => 3,
+            _ => return None,
+        };
+        let bitrate_index = (bytes[2] >> 4) as usize;
+        let sample_rate_index = ((bytes[2] >> 2) & 0b11) as usize;
+        if bitrate_index == 0 || bitrate_index == 15 || sample_rate_index == 3 {
+            return None;
+        }
+        let bitrate = BITRATES[version - 1][layer - 1][bitrate_index];
+        let sample_rate = SAMPLE_RATES[sample_rate_index] / sample_rate_divisor;
+        let padding = ((bytes[2] >> 1) & 1) as usize;
+        let length = match layer {
+            1 => (12 * bitrate as usize * 1000 / sample_rate as usize + padding) * 4,
+            3 if version == 2 => 72 * bitrate as usize * 1000 / sample_rate as usize + padding,
+            _ => 144 * bitrate as usize * 1000 / sample_rate as usize + padding,
+        };
+        Some(Self {
+            version,
+            layer,
+            bitrate,
+            sample_rate,
+            mono: bytes[3] >> 6 == 0b11,
+            length,
+        })
+    }
+
+    fn samples_per_frame(&self) -> u32 {
+        match (self.layer, self.version) {
+            (1, _) => 384,
+            (3, 2) => 576,
+            _ => 1152,
+        }
+    }
+
+    /// Offset of the Xing/Info header, right after the side information.
+    fn xing_offset(&self) -> usize {
+        match (self.version, self.mono) {
+            (1, false) => 4 + 32,
+            (1, true) | (2, false) => 4 + 17,
+            _ => 4 + 9,
+        }
+    }
+
+    /// Frame count and byte count declared by a Xing/Info or VBRI header.
+    fn vbr_counts(&self, frame: &[u8]) -> Option<(u32, Option<u32>)> {
+        let read_u32 = |offset: usize| {
+            frame
+                .get(offset..offset + 4)
+                .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        };
+
+        let xing = self.xing_offset();
+        if matches!(frame.get(xing..xing + 4), Some(b"Xing") | Some(b"Info")) {
+            let flags = read_u32(xing + 4)?;
+            if flags & 1 == 0 {
+                return None;
+            }
+            let frames = read_u32(xing + 8)?;
+            let bytes = (flags & 2 != 0).then(|| read_u32(xing + 12)).flatten();
+            return Some((frames, bytes));
+        }
+        if frame.get(36..40) == Some(b"VBRI") {
+            return Some((read_u32(36 + 14)?, read_u32(36 + 10)));
+        }
+        None
+    }
+}
+
+/// Returns the duration and the average bitrate in kbit/s of an MPEG audio (MP3) file, read
+/// from its first frame header and the Xing/Info or VBRI header of variable bitrate files.
+pub fn properties(path: &Path) -> Option<(Duration, u32)> {
+    let mut file = File::open(path).ok()?;
+    let file_length = file.metadata().ok()?.len();
+
+    let mut id3_header = [0u8; 10];
+    file.read_exact(&mut id3_header).ok()?;
+    let mut audio_start = 0u64;
+    if &id3_header[..3] == b"ID3" {
+        let size = id3_header[6..10]
+            .iter()
+            .fold(0u64, |size, byte| (size << 7) | (*byte & 0x7F) as u64);
+        let footer = if id3_header[5] & 0x10 != 0 { 10 } else { 0 };
+        audio_start = 10 + size + footer;
+    }
+
+    file.seek(SeekFrom::Start(audio_start)).ok()?;
+    let mut buffer = Vec::with_capacity(SEARCH_BYTES);
+    file.by_ref()
+        .take(SEARCH_BYTES as u64)
+        .read_to_end(&mut buffer)
+        .ok()?;
+
+    // A frame header is trusted when the next frame starts where it says it ends
+    let (offset, header) = (0..buffer.len()).find_map(|offset| {
+        let header = FrameHeader::parse(&buffer[offset..])?;
+        let next = buffer.get(offset + header.length..)?;
+        FrameHeader::parse(next).map(|_| (offset, header))
+    })?;
+    audio_start += offset as u64;
+
+    let mut audio_length = file_length.saturating_sub(audio_start);
+    file.seek(SeekFrom::End(-128)).ok()?;
+    let mut id3v1 = [0u8; 3];
+    if file.read_exact(&mut id3v1).is_ok() && &id3v1 == b"TAG" {
+        audio_length = audio_length.saturating_sub(128);
+    }
+
+    let frame = &buffer[offset..(offset + header.length).min(buffer.len())];
+    match header.vbr_counts(frame) {
+        Some((frames, bytes)) if frames > 0 => {
+            let seconds =
+                frames as f64 * header.samples_per_frame() as f64 / header.sample_rate as f64;
+            let bytes = bytes.map_or(audio_length, u64::from);
+            let bitrate = (bytes as f64 * 8.0 / seconds / 1000.0).round() as u32;
+            Some((Duration::from_secs_f64(seconds), bitrate))
+        }
+        _ => {
+            let seconds = audio_length as f64 * 8.0 / (header.bitrate as f64 * 1000.0);
+            Some((Duration::from_secs_f64(seconds), header.bitrate))
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use std::{
+    collections::{BTreeSet, HashMap},
     num::ParseIntError,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
+    time::Duration,
 };
 
 use chrono::{Datelike, NaiveDate};
@@ -10,10 +13,32 @@ use thiserror::Error;
 
 use crate::domain::entity::audio::{cover::Cover, year::Year};
 
-use super::{AudioParserError, AudioParserResult, ParsedAudioTry, TryableAudioParser};
+use super::{
+    parse_flag, parse_number, parse_rating, AudioParserError, AudioParserResult, ParsedAudioTry,
+    TryableAudioParser,
+};
+
+/// Reads tags and stream properties with ffprobe, and covers with ffmpeg.
+#[derive(Debug, Clone)]
+pub struct FfmpegAudioParser {
+    ffprobe: PathBuf,
+    ffmpeg: PathBuf,
+}
 
-#[derive(Default)]
-pub struct FfmpegAudioParser;
+impl Default for FfmpegAudioParser {
+    fn default() -> Self {
+        Self::new("ffprobe", "ffmpeg")
+    }
+}
+
+impl FfmpegAudioParser {
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(ffprobe: P, ffmpeg: Q) -> Self {
+        Self {
+            ffprobe: ffprobe.as_ref().to_path_buf(),
+            ffmpeg: ffmpeg.as_ref().to_path_buf(),
+        }
+    }
+}
 
 #[derive(Error, Debug)]
 pub enum FfmpegAudioParserError {
@@ -29,13 +54,26 @@ pub enum FfmpegAudioParserError {
     InvalidDate(#[from] chrono::ParseError),
     #[error("Invalid year: {0}")]
     InvalidYear(#[from] ParseIntError),
+    #[error("No audio stream")]
+    NoAudioStream,
 }
 
 impl TryableAudioParser for FfmpegAudioParser {
     fn try_parse(&self, entry: &walkdir::DirEntry) -> AudioParserResult<ParsedAudioTry> {
         let entry_path = entry.path();
-        let ffprobe_output = Self::get_ffprobe_output(entry_path)
+        let ffprobe_output = self
+            .get_ffprobe_output(entry_path)
             .map_err(|err| AudioParserError::Inner(Box::new(err)))?;
+        // ffprobe also reads images and videos, which are not audios
+        if !ffprobe_output
+            .streams()
+            .iter()
+            .any(|stream| stream.codec_type().as_deref() == Some("audio"))
+        {
+            return Err(AudioParserError::Inner(Box::new(
+                FfmpegAudioParserError::NoAudioStream,
+            )));
+        }
 
         let tags = ffprobe_output.format().tags();
 
@@ -43,19 +81,16 @@ impl TryableAudioParser for FfmpegAudioParser {
 
         let title = tags
             .title()
-            .as_deref()
             .ok_or(AudioParserError::MissingField("title".to_owned()))
             .and_then(|title| title.parse().map_err(AudioParserError::Title));
 
         let artist = tags
             .artist()
-            .as_deref()
             .ok_or(AudioParserError::MissingField("artist".to_owned()))
             .and_then(|artist| artist.parse().map_err(AudioParserError::Artist));
 
         let year = tags
             .year()
-            .as_deref()
             .ok_or(AudioParserError::MissingField("year".to_owned()))
             .and_then(|year| {
                 year.parse::<i32>()
@@ -63,7 +98,6 @@ impl TryableAudioParser for FfmpegAudioParser {
             })
             .or_else(|_| {
                 tags.date()
-                    .as_deref()
                     .ok_or(AudioParserError::MissingField("date".to_owned()))
                     .and_then(|date| {
                         NaiveDate::parse_from_str(date, "%Y-%m-%d")
@@ -78,23 +112,56 @@ impl TryableAudioParser for FfmpegAudioParser {
 
         let album_title = tags
             .album()
-            .as_deref()
             .ok_or(AudioParserError::MissingField("album_title".to_owned()))
             .and_then(|album_title| album_title.parse().map_err(AudioParserError::AlbumTitle));
 
         let album_artist = tags
             .album_artist()
-            .as_deref()
             .ok_or(AudioParserError::MissingField("album_artist".to_owned()))
             .and_then(|album_artist| album_artist.parse().map_err(AudioParserError::AlbumArtist));
 
         let genre = tags
             .genre()
-            .as_deref()
             .ok_or(AudioParserError::MissingField("genre".to_owned()))
             .and_then(|genre| genre.parse().map_err(AudioParserError::Genre));
 
-        let album_cover = Self::get_cover_bytes(entry_path)
+        let track_number = tags
+            .track()
+            .ok_or(AudioParserError::MissingField("track_number".to_owned()))
+            .and_then(parse_number);
+
+        let disc_number = tags
+            .disc()
+            .ok_or(AudioParserError::MissingField("disc_number".to_owned()))
+            .and_then(parse_number);
+
+        // ffprobe reports every tag, so absent optional tags are not looked for further
+        let compilation = tags.compilation().map_or(Ok(false), parse_flag);
+
+        let title_sort = Ok(tags.title_sort().map(str::to_owned));
+        let artist_sort = Ok(tags.artist_sort().map(str::to_owned));
+        let album_title_sort = Ok(tags.album_sort().map(str::to_owned));
+        let album_artist_sort = Ok(tags.album_artist_sort().map(str::to_owned));
+
+        let format = ffprobe_output.format();
+        let duration = format
+            .duration()
+            .as_deref()
+            .and_then(|duration| duration.parse::<f64>().ok())
+            .and_then(|duration| Duration::try_from_secs_f64(duration).ok())
+            .ok_or(AudioParserError::MissingField("duration".to_owned()));
+        let bitrate = format
+            .bit_rate()
+            .as_deref()
+            .and_then(|bitrate| bitrate.parse::<u32>().ok())
+            .map(|bitrate| bitrate / 1000)
+            .ok_or(AudioParserError::MissingField("bitrate".to_owned()));
+        let musicbrainz_recording_id = Ok(tags.musicbrainz_recording_id().map(str::to_owned));
+        let lyrics = Ok(tags.lyrics().map(str::to_owned));
+        let rating = Ok(tags.rating().and_then(parse_rating));
+
+        let album_cover = self
+            .get_cover_bytes(entry_path)
             .map_err(|err| AudioParserError::Inner(Box::new(err)))
             .and_then(|cover| Cover::try_from(cover).map_err(AudioParserError::Cover));
 
@@ -107,6 +174,19 @@ impl TryableAudioParser for FfmpegAudioParser {
             // TODO: use Lazy<_> for the album cover in the ParsedAudioTry class...
             album_cover,
             genre,
+            track_number,
+            disc_number,
+            compilation,
+            title_sort,
+            artist_sort,
+            album_title_sort,
+            album_artist_sort,
+            duration,
+            bitrate,
+            musicbrainz_recording_id,
+            lyrics,
+            rating,
+            inferred: BTreeSet::new(),
         };
         Ok(parsed_audio_try)
     }
@@ -114,15 +194,16 @@ impl TryableAudioParser for FfmpegAudioParser {
 
 impl FfmpegAudioParser {
     fn get_ffprobe_output(
+        &self,
         entry_path: &std::path::Path,
     ) -> Result<FfprobeOutput, FfmpegAudioParserError> {
-        let ffprobe_output = Command::new("ffprobe")
+        let ffprobe_output = Command::new(&self.ffprobe)
             .arg("-v")
             .arg("quiet")
             .arg("-of")
             .arg("json")
             .arg("-show_entries")
-            .arg("format_tags")
+            .arg("format=duration,bit_rate:format_tags:stream=codec_type")
             .arg(entry_path)
             .stdout(Stdio::piped())
             .output()
@@ -132,8 +213,11 @@ impl FfmpegAudioParser {
         Ok(ffprobe_output)
     }
 
-    fn get_cover_bytes(entry_path: &std::path::Path) -> Result<Vec<u8>, FfmpegAudioParserError> {
-        let ffprobe_output = Command::new("ffmpeg")
+    fn get_cover_bytes(
+        &self,
+        entry_path: &std::path::Path,
+    ) -> Result<Vec<u8>, FfmpegAudioParserError> {
+        let ffprobe_output = Command::new(&self.ffmpeg)
             .arg("-i")
             .arg(entry_path)
             .arg("-an")
@@ -150,23 +234,151 @@ impl FfmpegAudioParser {
 
 #[derive(Debug, Deserialize, Getters)]
 struct FfprobeOutput {
+    #[serde(default)]
+    streams: Vec<FfprobeStream>,
     format: FfprobeFormat,
 }
 
+#[derive(Debug, Deserialize, Getters)]
+struct FfprobeStream {
+    codec_type: Option<String>,
+}
+
 #[derive(Debug, Deserialize, Getters)]
 struct FfprobeFormat {
+    duration: Option<String>,
+    bit_rate: Option<String>,
+    #[serde(default)]
     tags: FfprobeTags,
 }
 
-#[derive(Debug, Deserialize, Getters)]
-#[serde(rename_all = "UPPERCASE")]
-struct FfprobeTags {
-    title: Option<String>,
-    album: Option<String>,
-    #[serde(rename = "album_artist")]
-    album_artist: Option<String>,
-    artist: Option<String>,
-    date: Option<String>,
-    year: Option<String>,
-    genre: Option<String>,
+/// Tags as reported by ffprobe. Their keys depend on the container, `TITLE` in Vorbis
+/// comments and `title` in ID3 for instance, and a file may carry several keys for the same
+/// field, so they are looked up case-insensitively from a list of candidates.
+#[derive(Debug, Default, Deserialize)]
+#[serde(transparent)]
+struct FfprobeTags(HashMap<String, String>);
+
+impl FfprobeTags {
+    /// The value of the first of the keys present, compared case-insensitively. A key
+    /// present in several cases resolves to its exact spelling, else to the first in order.
+    fn get(&self, keys: &[&str]) -> Option<&str> {
+        keys.iter().find_map(|key| {
+            self.0
+                .get(*key)
+                .or_else(|| {
+                    self.0
+                        .iter()
+                        .filter(|(tag, _)| tag.eq_ignore_ascii_case(key))
+                        .min_by_key(|(tag, _)| tag.as_str())
+                        .map(|(_, value)| value)
+                })
+                .map(String::as_str)
+        })
+    }
+
+    fn title(&self) -> Option<&str> {
+        self.get(&["title"])
+    }
+
+    fn album(&self) -> Option<&str> {
+        self.get(&["album"])
+    }
+
+    fn album_artist(&self) -> Option<&str> {
+        self.get(&["album_artist", "albumartist", "album artist"])
+    }
+
+    fn artist(&self) -> Option<&str> {
+        self.get(&["artist"])
+    }
+
+    fn date(&self) -> Option<&str> {
+        self.get(&["date"])
+    }
+
+    fn year(&self) -> Option<&str> {
+        self.get(&["year"])
+    }
+
+    fn genre(&self) -> Option<&str> {
+        self.get(&["genre"])
+    }
+
+    fn track(&self) -> Option<&str> {
+        self.get(&["track", "tracknumber"])
+    }
+
+    fn disc(&self) -> Option<&str> {
+        self.get(&["disc", "discnumber"])
+    }
+
+    fn compilation(&self) -> Option<&str> {
+        self.get(&["compilation"])
+    }
+
+    fn title_sort(&self) -> Option<&str> {
+        self.get(&["titlesort", "title-sort", "sort_name"])
+    }
+
+    fn artist_sort(&self) -> Option<&str> {
+        self.get(&["artistsort", "artist-sort", "sort_artist"])
+    }
+
+    fn album_sort(&self) -> Option<&str> {
+        self.get(&["albumsort", "album-sort", "sort_album"])
+    }
+
+    fn album_artist_sort(&self) -> Option<&str> {
+        self.get(&["albumartistsort", "album_artist-sort", "sort_album_artist"])
+    }
+
+    fn musicbrainz_recording_id(&self) -> Option<&str> {
+        self.get(&["musicbrainz_trackid", "MusicBrainz Track Id"])
+    }
+
+    fn lyrics(&self) -> Option<&str> {
+        self.get(&["lyrics", "unsyncedlyrics", "lyrics-eng", "lyrics-XXX"])
+    }
+
+    fn rating(&self) -> Option<&str> {
+        self.get(&["fmps_rating"])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_tags_whatever_their_case_and_duplicates() {
+        let output: FfprobeOutput = serde_json::from_str(
+            r#"{"streams": [{"codec_type": "audio"}],
+                "format": {"duration": "183.5", "bit_rate": "320000", "tags": {
+                "TITLE": "Song", "album_artist": "Band", "TRACKNUMBER": "3/12",
+                "LYRICS": "First", "UNSYNCEDLYRICS": "Second",
+                "FMPS_RATING": "0.8", "fmps_rating": "0.2",
+                "MusicBrainz Track Id": "1234", "ARTISTSORT": "Band, The"
+            }}}"#,
+        )
+        .unwrap();
+        let format = output.format();
+        let tags = format.tags();
+        assert_eq!(format.duration().as_deref(), Some("183.5"));
+        assert_eq!(tags.title(), Some("Song"));
+        assert_eq!(tags.album_artist(), Some("Band"));
+        assert_eq!(tags.track(), Some("3/12"));
+        assert_eq!(tags.lyrics(), Some("First"));
+        assert_eq!(tags.musicbrainz_recording_id(), Some("1234"));
+        assert_eq!(tags.artist_sort(), Some("Band, The"));
+        assert_eq!(tags.rating(), Some("0.2"));
+        assert_eq!(tags.genre(), None);
+    }
+
+    #[test]
+    fn reads_formats_without_tags() {
+        let output: FfprobeOutput =
+            serde_json::from_str(r#"{"format": {"duration": "1.0"}}"#).unwrap();
+        assert_eq!(output.format().tags().title(), None);
+    }
 }
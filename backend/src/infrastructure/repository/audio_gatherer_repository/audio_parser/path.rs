@@ -0,0 +1,402 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    str::FromStr,
+};
+
+use regex::Regex;
+use thiserror::Error;
+
+use crate::domain::entity::{
+    audio::{field::AudioField, year::Year},
+    library_root::DEFAULT_EXTENSIONS,
+};
+
+use super::{
+    parse_number, AudioParserError, AudioParserResult, ParsedAudioTry, TryableAudioParser,
+};
+
+/// Infers metadata from the file path of untagged audios using [`PathPattern`]s.
+///
+/// The first pattern matching the path is used. Every field read from it is marked as
+/// inferred, so this parser is meant to be the last one of a [`ResilientAudioParser`] chain.
+/// As any file name matches some pattern, only files with an audio extension are parsed.
+///
+/// [`ResilientAudioParser`]: super::ResilientAudioParser
+pub struct PathAudioParser {
+    patterns: Vec<PathPattern>,
+    /// Lowercase extensions of the files parsed.
+    extensions: HashSet<String>,
+}
+
+/// Patterns used when a root configures none.
+pub const DEFAULT_PATH_PATTERNS: [&str; 6] = [
+    "{album_artist}/{year} - {album}/{disc}-{track} - {title}",
+    "{album_artist}/{year} - {album}/{track} - {title}",
+    "{album_artist}/{album}/{track} - {title}",
+    "{album_artist}/{album}/{track}. {title}",
+    "{artist} - {title}",
+    "{title}",
+];
+
+impl Default for PathAudioParser {
+    fn default() -> Self {
+        Self::from_patterns(&DEFAULT_PATH_PATTERNS).expect("default path patterns are valid")
+    }
+}
+
+impl PathAudioParser {
+    /// Parses the patterns of a root, falling back to [`DEFAULT_PATH_PATTERNS`] when there
+    /// are none.
+    pub fn from_patterns<S: AsRef<str>>(patterns: &[S]) -> Result<Self, PathPatternError> {
+        if patterns.is_empty() {
+            return Ok(Self::default());
+        }
+        let patterns = patterns
+            .iter()
+            .map(|pattern| pattern.as_ref().parse())
+            .collect::<Result<_, _>>()?;
+        Ok(Self::new(patterns))
+    }
+
+    /// A parser of the files with one of [`DEFAULT_EXTENSIONS`].
+    pub fn new(patterns: Vec<PathPattern>) -> Self {
+        Self {
+            patterns,
+            extensions: DEFAULT_EXTENSIONS
+                .iter()
+                .map(|extension| extension.to_string())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PathAudioParserError {
+    #[error("No path pattern matches {0}")]
+    NoMatch(String),
+    #[error("Not an audio file: {0}")]
+    NotAudio(String),
+}
+
+impl TryableAudioParser for PathAudioParser {
+    fn try_parse(&self, entry: &walkdir::DirEntry) -> AudioParserResult<ParsedAudioTry> {
+        let entry_path = entry.path();
+        let is_audio = entry_path.extension().is_some_and(|extension| {
+            self.extensions
+                .contains(&extension.to_string_lossy().to_lowercase())
+        });
+        if !is_audio {
+            return Err(AudioParserError::Inner(Box::new(
+                PathAudioParserError::NotAudio(entry_path.display().to_string()),
+            )));
+        }
+        let fields = self
+            .patterns
+            .iter()
+            .find_map(|pattern| pattern.captures(entry_path))
+            .ok_or_else(|| {
+                AudioParserError::Inner(Box::new(PathAudioParserError::NoMatch(
+                    entry_path.display().to_string(),
+                )))
+            })?;
+
+        let field = |field: PathField| {
+            fields
+                .get(&field)
+                .map(String::as_str)
+                .ok_or(AudioParserError::MissingField(field.name().to_owned()))
+        };
+
+        let title = field(PathField::Title)
+            .and_then(|title| title.parse().map_err(AudioParserError::Title));
+
+        let artist = field(PathField::Artist)
+            .and_then(|artist| artist.parse().map_err(AudioParserError::Artist));
+
+        let year = field(PathField::Year)
+            .and_then(|year| {
+                year.parse::<i32>()
+                    .map_err(|_| AudioParserError::Number(year.to_owned()))
+            })
+            .and_then(|year| Year::try_from(year).map_err(AudioParserError::Year));
+
+        let album_title = field(PathField::AlbumTitle)
+            .and_then(|album_title| album_title.parse().map_err(AudioParserError::AlbumTitle));
+
+        let album_artist = field(PathField::AlbumArtist)
+            .and_then(|album_artist| album_artist.parse().map_err(AudioParserError::AlbumArtist));
+
+        let genre = field(PathField::Genre)
+            .and_then(|genre| genre.parse().map_err(AudioParserError::Genre));
+
+        let track_number = field(PathField::TrackNumber).and_then(parse_number);
+
+        let disc_number = field(PathField::DiscNumber).and_then(parse_number);
+
+        let inferred = fields.keys().map(|field| field.audio_field()).collect();
+
+        let parsed_audio_try = ParsedAudioTry {
+            title,
+            artist,
+            year,
+            album_title,
+            album_artist,
+            album_cover: Err(AudioParserError::MissingField("album_cover".to_owned())),
+            genre,
+            track_number,
+            disc_number,
+            compilation: Err(AudioParserError::MissingField("compilation".to_owned())),
+            title_sort: Err(AudioParserError::MissingField("title_sort".to_owned())),
+            artist_sort: Err(AudioParserError::MissingField("artist_sort".to_owned())),
+            album_title_sort: Err(AudioParserError::MissingField(
+                "album_title_sort".to_owned(),
+            )),
+            album_artist_sort: Err(AudioParserError::MissingField(
+                "album_artist_sort".to_owned(),
+            )),
+            duration: Err(AudioParserError::MissingField("duration".to_owned())),
+            bitrate: Err(AudioParserError::MissingField("bitrate".to_owned())),
+            musicbrainz_recording_id: Err(AudioParserError::MissingField(
+                "musicbrainz_recording_id".to_owned(),
+            )),
+            lyrics: Err(AudioParserError::MissingField("lyrics".to_owned())),
+            rating: Err(AudioParserError::MissingField("rating".to_owned())),
+            inferred,
+        };
+        Ok(parsed_audio_try)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum PathField {
+    Title,
+    Artist,
+    Year,
+    AlbumTitle,
+    AlbumArtist,
+    Genre,
+    TrackNumber,
+    DiscNumber,
+}
+
+impl PathField {
+    fn from_placeholder(placeholder: &str) -> Option<Self> {
+        match placeholder {
+            "title" => Some(Self::Title),
+            "artist" => Some(Self::Artist),
+            "year" => Some(Self::Year),
+            "album" | "album_title" => Some(Self::AlbumTitle),
+            "album_artist" => Some(Self::AlbumArtist),
+            "genre" => Some(Self::Genre),
+            "track" => Some(Self::TrackNumber),
+            "disc" => Some(Self::DiscNumber),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::Artist => "artist",
+            Self::Year => "year",
+            Self::AlbumTitle => "album_title",
+            Self::AlbumArtist => "album_artist",
+            Self::Genre => "genre",
+            Self::TrackNumber => "track_number",
+            Self::DiscNumber => "disc_number",
+        }
+    }
+
+    fn audio_field(self) -> AudioField {
+        match self {
+            Self::Title => AudioField::Title,
+            Self::Artist => AudioField::Artist,
+            Self::Year => AudioField::Year,
+            Self::AlbumTitle => AudioField::AlbumTitle,
+            Self::AlbumArtist => AudioField::AlbumArtist,
+            Self::Genre => AudioField::Genre,
+            Self::TrackNumber => AudioField::TrackNumber,
+            Self::DiscNumber => AudioField::DiscNumber,
+        }
+    }
+
+    fn regex(self) -> &'static str {
+        match self {
+            Self::Year => r"(\d{4})",
+            Self::TrackNumber | Self::DiscNumber => r"(\d{1,3})",
+            _ => r"(.+?)",
+        }
+    }
+}
+
+/// A pattern over the trailing components of an audio path, such as
+/// `{album_artist}/{year} - {album}/{track} - {title}`.
+///
+/// The last segment is matched against the file name without extension. Supported
+/// placeholders are `title`, `artist`, `album` (or `album_title`), `album_artist`, `year`,
+/// `genre`, `track` and `disc`.
+#[derive(Debug, Clone)]
+pub struct PathPattern {
+    segments: Vec<(Regex, Vec<PathField>)>,
+}
+
+#[derive(Error, Debug)]
+pub enum PathPatternError {
+    #[error("Path pattern cannot be empty")]
+    Empty,
+    #[error("Unknown placeholder: {{{0}}}")]
+    UnknownPlaceholder(String),
+    #[error("Unclosed placeholder in segment: {0}")]
+    UnclosedPlaceholder(String),
+    #[error("Invalid path pattern: {0}")]
+    Regex(#[from] regex::Error),
+}
+
+impl FromStr for PathPattern {
+    type Err = PathPatternError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let segments = s
+            .trim_matches('/')
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(Self::parse_segment)
+            .collect::<Result<Vec<_>, _>>()?;
+        if segments.is_empty() {
+            return Err(PathPatternError::Empty);
+        }
+        Ok(Self { segments })
+    }
+}
+
+impl PathPattern {
+    fn parse_segment(segment: &str) -> Result<(Regex, Vec<PathField>), PathPatternError> {
+        let mut regex = String::from("^");
+        let mut fields = Vec::new();
+        let mut rest = segment;
+        while let Some(start) = rest.find('{') {
+            regex.push_str(&regex::escape(&rest[..start]));
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| PathPatternError::UnclosedPlaceholder(segment.to_owned()))?;
+            let placeholder = &rest[start + 1..start + end];
+            let field = PathField::from_placeholder(placeholder)
+                .ok_or_else(|| PathPatternError::UnknownPlaceholder(placeholder.to_owned()))?;
+            regex.push_str(field.regex());
+            fields.push(field);
+            rest = &rest[start + end + 1..];
+        }
+        regex.push_str(&regex::escape(rest));
+        regex.push('$');
+        Ok((Regex::new(&regex)?, fields))
+    }
+
+    fn captures(&self, path: &Path) -> Option<HashMap<PathField, String>> {
+        let file_stem = path.file_stem()?.to_string_lossy();
+        let mut components = path
+            .parent()?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>();
+        components.push(file_stem);
+        let first = components.len().checked_sub(self.segments.len())?;
+
+        let mut fields = HashMap::new();
+        for ((regex, segment_fields), component) in self.segments.iter().zip(&components[first..]) {
+            let captures = regex.captures(component)?;
+            for (field, capture) in segment_fields.iter().zip(captures.iter().skip(1)) {
+                if let Some(capture) = capture {
+                    fields
+                        .entry(*field)
+                        .or_insert_with(|| capture.as_str().trim().to_owned());
+                }
+            }
+        }
+        Some(fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// Parses an empty file created at `path` under a directory named after `name`, which is
+    /// removed afterwards.
+    fn parse_with(
+        parser: &PathAudioParser,
+        name: &str,
+        path: &str,
+    ) -> AudioParserResult<ParsedAudioTry> {
+        let root =
+            std::env::temp_dir().join(format!("earr-path-parser-{}-{}", name, std::process::id()));
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, b"").unwrap();
+        let entry = walkdir::WalkDir::new(&path)
+            .into_iter()
+            .next()
+            .unwrap()
+            .unwrap();
+        let parsed = parser.try_parse(&entry);
+        fs::remove_dir_all(&root).unwrap();
+        parsed
+    }
+
+    fn parse(name: &str, file_name: &str) -> AudioParserResult<ParsedAudioTry> {
+        let path = format!("Band/2001 - Album/{file_name}");
+        parse_with(&PathAudioParser::default(), name, &path)
+    }
+
+    #[test]
+    fn infers_fields_from_the_first_matching_pattern() {
+        let parsed = parse("first-match", "1-04 - Song.flac").unwrap();
+        assert_eq!(parsed.title.unwrap().name(), "Song");
+        assert_eq!(parsed.album_artist.unwrap().name(), "Band");
+        assert_eq!(parsed.track_number.unwrap(), 4);
+        assert_eq!(parsed.disc_number.unwrap(), 1);
+        assert!(parsed.inferred.contains(&AudioField::Title));
+    }
+
+    #[test]
+    fn leaves_out_files_that_are_not_audios() {
+        assert!(parse("cover", "cover.jpg").is_err());
+        assert!(parse("nfo", "album.nfo").is_err());
+        assert!(parse("readme", "README").is_err());
+    }
+
+    #[test]
+    fn uses_the_configured_patterns() {
+        let parser =
+            PathAudioParser::from_patterns(&["{genre}/{artist} - {album}/{title}"]).unwrap();
+        let parsed = parse_with(&parser, "configured", "Jazz/Band - Album/Song.mp3").unwrap();
+        assert_eq!(parsed.genre.unwrap().name(), "Jazz");
+        assert_eq!(parsed.artist.unwrap().name(), "Band");
+        assert_eq!(parsed.album_title.unwrap().name(), "Album");
+        assert_eq!(parsed.title.unwrap().name(), "Song");
+        // Paths with fewer directories than the pattern do not match
+        assert!(parse_with(&parser, "unmatched", "Song.mp3").is_err());
+
+        let no_patterns: [&str; 0] = [];
+        let parser = PathAudioParser::from_patterns(&no_patterns).unwrap();
+        assert_eq!(parser.patterns.len(), DEFAULT_PATH_PATTERNS.len());
+        assert!(PathAudioParser::from_patterns(&["{title}", "{nope}"]).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        assert!(matches!(
+            "{album}/{nope}".parse::<PathPattern>(),
+            Err(PathPatternError::UnknownPlaceholder(_))
+        ));
+        assert!(matches!(
+            "{album".parse::<PathPattern>(),
+            Err(PathPatternError::UnclosedPlaceholder(_))
+        ));
+        assert!(matches!(
+            "/".parse::<PathPattern>(),
+            Err(PathPatternError::Empty)
+        ));
+    }
+}
@@ -1,19 +1,35 @@
-use std::ops::Deref;
+use std::{collections::BTreeSet, ops::Deref};
 
 use once_cell::sync::Lazy;
 use thiserror::Error;
 
+use crate::domain::entity::audio::field::AudioField;
+
 use super::{
-    audiotags::AudiotagsAudioParser, ffmpeg::FfmpegAudioParser, AudioParserError, ParsedAudioTry,
-    TryableAudioParser,
+    audiotags::AudiotagsAudioParser, ffmpeg::FfmpegAudioParser, path::PathAudioParser,
+    AudioParserError, ParsedAudioTry, TryableAudioParser,
 };
 
-#[derive(Default)]
-pub struct ResilientAudioParser;
+pub struct ResilientAudioParser {
+    parsers: Vec<Box<dyn TryableAudioParser>>,
+}
+
+impl Default for ResilientAudioParser {
+    /// Tag parsers first, with path inference last so it only fills fields missing from tags.
+    fn default() -> Self {
+        Self::new(vec![
+            Box::new(AudiotagsAudioParser),
+            Box::new(FfmpegAudioParser::default()),
+            Box::new(PathAudioParser::default()),
+        ])
+    }
+}
 
 impl ResilientAudioParser {
-    fn parsers() -> Vec<Box<dyn TryableAudioParser>> {
-        vec![Box::new(AudiotagsAudioParser), Box::new(FfmpegAudioParser)]
+    /// Creates a parser that tries `parsers` in order, falling back to the next one for
+    /// every field the previous parsers could not read.
+    pub fn new(parsers: Vec<Box<dyn TryableAudioParser>>) -> Self {
+        Self { parsers }
     }
 }
 
@@ -26,11 +42,13 @@ pub enum ResilientAudioParserError {
 }
 impl TryableAudioParser for ResilientAudioParser {
     fn try_parse(&self, entry: &walkdir::DirEntry) -> Result<ParsedAudioTry, AudioParserError> {
-        let parsers = Self::parsers();
-        let first_parser = parsers.first().ok_or(AudioParserError::Inner(Box::new(
-            ResilientAudioParserError::NoParserLeft,
-        )))?;
-        Self::parse_inner(first_parser.as_ref(), &parsers[1..], entry)
+        let first_parser = self
+            .parsers
+            .first()
+            .ok_or(AudioParserError::Inner(Box::new(
+                ResilientAudioParserError::NoParserLeft,
+            )))?;
+        Self::parse_inner(first_parser.as_ref(), &self.parsers[1..], entry)
     }
 }
 
@@ -38,7 +56,7 @@ impl TryableAudioParser for ResilientAudioParser {
 macro_rules! resilient_getter {
     ($field:ident, $current_parsed_audio_try:ident, $next_parsed_audio_try_lazy:ident) => {
         $current_parsed_audio_try.$field.or_else(|_e| {
-            println!("Failed to parse field: {}, next parser", stringify!($field));
+            log::debug!("Failed to parse field: {}, next parser", stringify!($field));
             $next_parsed_audio_try_lazy
                 .deref()
                 .as_ref()
@@ -57,6 +75,28 @@ macro_rules! resilient_getter {
                 .cloned()
         })
     };
+    // Same as above, but also records in `$inferred` whether the value used was inferred
+    ($field:ident, $audio_field:expr, $current_parsed_audio_try:ident, $next_parsed_audio_try_lazy:ident, $inferred:ident) => {{
+        let inferred = if $current_parsed_audio_try.$field.is_ok() {
+            $current_parsed_audio_try.inferred.contains(&$audio_field)
+        } else {
+            $next_parsed_audio_try_lazy
+                .deref()
+                .as_ref()
+                .is_ok_and(|next_parsed_audio_try| {
+                    next_parsed_audio_try.inferred.contains(&$audio_field)
+                })
+        };
+        let value = resilient_getter!(
+            $field,
+            $current_parsed_audio_try,
+            $next_parsed_audio_try_lazy
+        );
+        if value.is_ok() && inferred {
+            $inferred.insert($audio_field);
+        }
+        value
+    }};
 }
 
 impl ResilientAudioParser {
@@ -80,14 +120,93 @@ impl ResilientAudioParser {
         };
 
         let next_parsed_audio_try = Lazy::new(next_parse_inner_closure);
+        let mut inferred = BTreeSet::new();
 
-        let title = resilient_getter!(title, parsed_audio_try, next_parsed_audio_try);
-        let artist = resilient_getter!(artist, parsed_audio_try, next_parsed_audio_try);
-        let year = resilient_getter!(year, parsed_audio_try, next_parsed_audio_try);
-        let album_title = resilient_getter!(album_title, parsed_audio_try, next_parsed_audio_try);
-        let album_artist = resilient_getter!(album_artist, parsed_audio_try, next_parsed_audio_try);
-        let album_cover = resilient_getter!(album_cover, parsed_audio_try, next_parsed_audio_try);
-        let genre = resilient_getter!(genre, parsed_audio_try, next_parsed_audio_try);
+        let title = resilient_getter!(
+            title,
+            AudioField::Title,
+            parsed_audio_try,
+            next_parsed_audio_try,
+            inferred
+        );
+        let artist = resilient_getter!(
+            artist,
+            AudioField::Artist,
+            parsed_audio_try,
+            next_parsed_audio_try,
+            inferred
+        );
+        let year = resilient_getter!(
+            year,
+            AudioField::Year,
+            parsed_audio_try,
+            next_parsed_audio_try,
+            inferred
+        );
+        let album_title = resilient_getter!(
+            album_title,
+            AudioField::AlbumTitle,
+            parsed_audio_try,
+            next_parsed_audio_try,
+            inferred
+        );
+        let album_artist = resilient_getter!(
+            album_artist,
+            AudioField::AlbumArtist,
+            parsed_audio_try,
+            next_parsed_audio_try,
+            inferred
+        );
+        let album_cover = resilient_getter!(
+            album_cover,
+            AudioField::AlbumCover,
+            parsed_audio_try,
+            next_parsed_audio_try,
+            inferred
+        );
+        let genre = resilient_getter!(
+            genre,
+            AudioField::Genre,
+            parsed_audio_try,
+            next_parsed_audio_try,
+            inferred
+        );
+        let track_number = resilient_getter!(
+            track_number,
+            AudioField::TrackNumber,
+            parsed_audio_try,
+            next_parsed_audio_try,
+            inferred
+        );
+        let disc_number = resilient_getter!(
+            disc_number,
+            AudioField::DiscNumber,
+            parsed_audio_try,
+            next_parsed_audio_try,
+            inferred
+        );
+        let compilation = resilient_getter!(
+            compilation,
+            AudioField::Compilation,
+            parsed_audio_try,
+            next_parsed_audio_try,
+            inferred
+        );
+        let title_sort = resilient_getter!(title_sort, parsed_audio_try, next_parsed_audio_try);
+        let artist_sort = resilient_getter!(artist_sort, parsed_audio_try, next_parsed_audio_try);
+        let album_title_sort =
+            resilient_getter!(album_title_sort, parsed_audio_try, next_parsed_audio_try);
+        let album_artist_sort =
+            resilient_getter!(album_artist_sort, parsed_audio_try, next_parsed_audio_try);
+        let duration = resilient_getter!(duration, parsed_audio_try, next_parsed_audio_try);
+        let bitrate = resilient_getter!(bitrate, parsed_audio_try, next_parsed_audio_try);
+        let musicbrainz_recording_id = resilient_getter!(
+            musicbrainz_recording_id,
+            parsed_audio_try,
+            next_parsed_audio_try
+        );
+        let lyrics = resilient_getter!(lyrics, parsed_audio_try, next_parsed_audio_try);
+        let rating = resilient_getter!(rating, parsed_audio_try, next_parsed_audio_try);
 
         let parsed_audio_try = ParsedAudioTry {
             title,
@@ -97,7 +216,101 @@ impl ResilientAudioParser {
             album_artist,
             album_cover,
             genre,
+            track_number,
+            disc_number,
+            compilation,
+            title_sort,
+            artist_sort,
+            album_title_sort,
+            album_artist_sort,
+            duration,
+            bitrate,
+            musicbrainz_recording_id,
+            lyrics,
+            rating,
+            inferred,
         };
         Ok(parsed_audio_try)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+    use crate::infrastructure::repository::audio_gatherer_repository::audio_parser::AudioParserResult;
+
+    /// Reads every field but lyrics, which it may tell absent, and counts its runs.
+    struct FakeParser {
+        lyrics: fn() -> AudioParserResult<Option<String>>,
+        runs: Arc<AtomicUsize>,
+    }
+
+    impl TryableAudioParser for FakeParser {
+        fn try_parse(&self, _entry: &walkdir::DirEntry) -> AudioParserResult<ParsedAudioTry> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            Ok(ParsedAudioTry {
+                title: Ok("Title".parse().unwrap()),
+                artist: Ok("Artist".parse().unwrap()),
+                year: Ok(2000.try_into().unwrap()),
+                album_title: Ok("Album".parse().unwrap()),
+                album_artist: Ok("Artist".parse().unwrap()),
+                album_cover: Ok(Default::default()),
+                genre: Ok("Rock".parse().unwrap()),
+                track_number: Ok(1),
+                disc_number: Ok(1),
+                compilation: Ok(false),
+                title_sort: Ok(None),
+                artist_sort: Ok(None),
+                album_title_sort: Ok(None),
+                album_artist_sort: Ok(None),
+                duration: Ok(Default::default()),
+                bitrate: Ok(320),
+                musicbrainz_recording_id: Ok(None),
+                lyrics: (self.lyrics)(),
+                rating: Ok(None),
+                inferred: BTreeSet::new(),
+            })
+        }
+    }
+
+    fn parse(first_lyrics: fn() -> AudioParserResult<Option<String>>) -> (ParsedAudioTry, usize) {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let parser = ResilientAudioParser::new(vec![
+            Box::new(FakeParser {
+                lyrics: first_lyrics,
+                runs: Arc::new(AtomicUsize::new(0)),
+            }),
+            Box::new(FakeParser {
+                lyrics: || Ok(Some("Next".to_owned())),
+                runs: Arc::clone(&runs),
+            }),
+        ]);
+        let entry = walkdir::WalkDir::new(env!("CARGO_MANIFEST_DIR"))
+            .max_depth(0)
+            .into_iter()
+            .next()
+            .unwrap()
+            .unwrap();
+        let parsed = parser.try_parse(&entry).unwrap();
+        (parsed, runs.load(Ordering::SeqCst))
+    }
+
+    #[test]
+    fn absent_optional_tags_do_not_run_next_parsers() {
+        let (parsed, runs) = parse(|| Ok(None));
+        assert!(matches!(parsed.lyrics, Ok(None)));
+        assert_eq!(runs, 0);
+    }
+
+    #[test]
+    fn unread_tags_are_read_by_next_parsers() {
+        let (parsed, runs) = parse(|| Err(AudioParserError::MissingField("lyrics".to_owned())));
+        assert_eq!(parsed.lyrics.unwrap().as_deref(), Some("Next"));
+        assert_eq!(runs, 1);
+    }
+}
@@ -1,24 +1,103 @@
-use crate::domain::entity::audio::Audio;
+use crate::domain::entity::audio::{genre::normalizer::GenreNormalizer, Audio};
+use crate::domain::entity::library_root::{LibraryRoot, ParserKind};
+use crate::domain::entity::playlist::PlaylistFormat;
 use crate::domain::repository::AudioGathererRepository;
 use std::io;
-use std::path::{Path, PathBuf};
+use std::path::Path;
+use std::sync::Arc;
 use thiserror::Error;
-use walkdir::WalkDir;
 
-use super::audio_parser::{AudioParser, AudioParserError};
+use super::audio_parser::{
+    AudioParser, AudioParserError, AudiotagsAudioParser, FfmpegAudioParser, PathAudioParser,
+    ResilientAudioParser, TryableAudioParser,
+};
+use super::scan_filter::{ScanFilter, ScanFilterError};
 
+/// Gathers audios from one or several roots, each read with its own parser.
 pub struct FilesystemAudioGathererRepository<AP: AudioParser> {
-    path: PathBuf,
-    _audio_parsermarker: std::marker::PhantomData<AP>,
+    roots: Vec<LibraryRoot>,
+    /// Parsers of the roots, in the same order.
+    audio_parsers: Vec<Arc<AP>>,
+    repair_encoding: bool,
+    /// Normalizer of the genres, the default one of [`Genre`] when not set.
+    ///
+    /// [`Genre`]: crate::domain::entity::audio::genre::Genre
+    genre_normalizer: Option<Arc<GenreNormalizer>>,
 }
 
-impl<AP: AudioParser> FilesystemAudioGathererRepository<AP> {
+impl<AP: AudioParser + Default> FilesystemAudioGathererRepository<AP> {
     pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self::with_parser(path, AP::default())
+    }
+}
+
+impl FilesystemAudioGathererRepository<ResilientAudioParser> {
+    /// Creates a repository that reads each root with its chain of parsers, running the
+    /// ffmpeg tools of `ffmpeg` when chained.
+    pub fn from_roots(
+        roots: impl IntoIterator<Item = LibraryRoot>,
+        ffmpeg: &FfmpegAudioParser,
+    ) -> Self {
+        Self::with_roots(roots.into_iter().map(|root| {
+            let parsers = root
+                .parsers
+                .iter()
+                .map(|kind| -> Box<dyn TryableAudioParser> {
+                    match kind {
+                        ParserKind::Tags => Box::new(AudiotagsAudioParser),
+                        ParserKind::Ffmpeg => Box::new(ffmpeg.clone()),
+                        ParserKind::Path => Box::new(
+                            PathAudioParser::from_patterns(&root.path_patterns).unwrap_or_else(
+                                |err| {
+                                    log::warn!(
+                                        "Invalid path patterns of root {}, using the default \
+                                         ones: {}",
+                                        root.name,
+                                        err
+                                    );
+                                    PathAudioParser::default()
+                                },
+                            ),
+                        ),
+                    }
+                })
+                .collect();
+            (root, ResilientAudioParser::new(parsers))
+        }))
+    }
+}
+
+impl<AP: AudioParser> FilesystemAudioGathererRepository<AP> {
+    /// Creates a repository that uses an already configured parser.
+    pub fn with_parser<P: AsRef<Path>>(path: P, audio_parser: AP) -> Self {
+        Self::with_roots([(LibraryRoot::from_path(path.as_ref()), audio_parser)])
+    }
+
+    /// Creates a repository that gathers each root with its parser.
+    pub fn with_roots(roots: impl IntoIterator<Item = (LibraryRoot, AP)>) -> Self {
+        let (roots, audio_parsers) = roots
+            .into_iter()
+            .map(|(root, audio_parser)| (root, Arc::new(audio_parser)))
+            .unzip();
         Self {
-            path: path.as_ref().to_path_buf(),
-            _audio_parsermarker: std::marker::PhantomData,
+            roots,
+            audio_parsers,
+            repair_encoding: false,
+            genre_normalizer: None,
         }
     }
+
+    /// Repairs double-encoded text tags of every gathered audio.
+    pub fn with_encoding_repair(mut self, repair_encoding: bool) -> Self {
+        self.repair_encoding = repair_encoding;
+        self
+    }
+
+    /// Normalizes the genres of every gathered audio with the given aliases and hierarchy.
+    pub fn with_genre_normalizer(mut self, genre_normalizer: GenreNormalizer) -> Self {
+        self.genre_normalizer = Some(Arc::new(genre_normalizer));
+        self
+    }
 }
 
 #[derive(Error, Debug)]
@@ -27,24 +106,75 @@ pub enum FilesystemAudioGathererRepositoryError {
     IO(#[from] io::Error),
     #[error("Failed to parse audio: {0}")]
     AudioParser(#[from] AudioParserError),
+    #[error("Unknown library root: {0}")]
+    UnknownRoot(String),
+    #[error("Invalid scan rules of library root {0}: {1}")]
+    ScanFilter(String, ScanFilterError),
 }
 
-impl<AP: AudioParser + Default> AudioGathererRepository for FilesystemAudioGathererRepository<AP> {
-    type Error = FilesystemAudioGathererRepositoryError;
-    fn gather(&self) -> Result<Box<dyn Iterator<Item = Audio>>, Self::Error> {
-        let walker = WalkDir::new(&self.path).into_iter();
-        let audio_iter = walker
-            .flatten()
-            .filter(|entry| entry.file_type().is_file())
-            .filter_map(|entry| {
-                AP::default()
+impl<AP: AudioParser + 'static> FilesystemAudioGathererRepository<AP> {
+    fn gather_from(
+        &self,
+        root: &LibraryRoot,
+        audio_parser: &Arc<AP>,
+    ) -> Result<Box<dyn Iterator<Item = Audio>>, FilesystemAudioGathererRepositoryError> {
+        let filter = ScanFilter::new(root).map_err(|err| {
+            FilesystemAudioGathererRepositoryError::ScanFilter(root.name.clone(), err)
+        })?;
+        let name = root.name.clone();
+        let repair_encoding = self.repair_encoding;
+        let genre_normalizer = self.genre_normalizer.clone();
+        let audio_parser = Arc::clone(audio_parser);
+        let audio_iter = filter
+            .walk()
+            // Playlists are read by the playlist repository
+            .filter(|entry| PlaylistFormat::from_path(entry.path()).is_none())
+            .filter_map(move |entry| {
+                audio_parser
                     .parse(&entry)
                     .map_err(|e| {
-                        eprintln!("Failed to read audio: {}", e);
+                        log::error!("Failed to read audio: {}", e);
                     })
                     .ok()
+            })
+            .map(move |audio| {
+                let mut audio = audio.with_root(&name);
+                if let Some(genre_normalizer) = &genre_normalizer {
+                    audio = audio.normalize_genre(genre_normalizer);
+                }
+                if repair_encoding {
+                    audio.repair_encoding()
+                } else {
+                    audio
+                }
             });
-
         Ok(Box::new(audio_iter))
     }
 }
+
+impl<AP: AudioParser + 'static> AudioGathererRepository for FilesystemAudioGathererRepository<AP> {
+    type Error = FilesystemAudioGathererRepositoryError;
+    fn gather(&self) -> Result<Box<dyn Iterator<Item = Audio>>, Self::Error> {
+        let iters = self
+            .roots
+            .iter()
+            .zip(&self.audio_parsers)
+            .map(|(root, audio_parser)| self.gather_from(root, audio_parser))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Box::new(iters.into_iter().flatten()))
+    }
+
+    fn gather_root(&self, name: &str) -> Result<Box<dyn Iterator<Item = Audio>>, Self::Error> {
+        let (root, audio_parser) = self
+            .roots
+            .iter()
+            .zip(&self.audio_parsers)
+            .find(|(root, _)| root.name == name)
+            .ok_or_else(|| FilesystemAudioGathererRepositoryError::UnknownRoot(name.to_string()))?;
+        self.gather_from(root, audio_parser)
+    }
+
+    fn roots(&self) -> &[LibraryRoot] {
+        &self.roots
+    }
+}
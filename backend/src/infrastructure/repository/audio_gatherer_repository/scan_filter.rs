@@ -0,0 +1,364 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use globset::{Glob, GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
+use thiserror::Error;
+use walkdir::{DirEntry, WalkDir};
+
+use crate::domain::entity::library_root::{LibraryRoot, SymlinkPolicy, IGNORE_FILE_NAME};
+
+#[derive(Error, Debug)]
+pub enum ScanFilterError {
+    #[error("Invalid glob {0}: {1}")]
+    Glob(String, globset::Error),
+}
+
+/// Decides which directories are entered and which files are gathered in a root, following
+/// its scan rules.
+pub struct ScanFilter {
+    root: PathBuf,
+    /// Resolved root, which links must lead into when only followed within it.
+    canonical_root: PathBuf,
+    extensions: HashSet<String>,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    ignore_files: bool,
+    skip_hidden: bool,
+    max_depth: Option<usize>,
+    symlinks: SymlinkPolicy,
+    /// Ignore files of the directories met so far, `None` for those without.
+    ignores: HashMap<PathBuf, Option<IgnoreFile>>,
+    /// Resolved directories entered so far, so that a directory reached through several
+    /// links is only scanned once.
+    visited: HashSet<PathBuf>,
+}
+
+impl ScanFilter {
+    pub fn new(root: &LibraryRoot) -> Result<Self, ScanFilterError> {
+        let include = if root.include.is_empty() {
+            None
+        } else {
+            Some(glob_set(&root.include)?)
+        };
+        Ok(Self {
+            root: root.path.clone(),
+            canonical_root: fs::canonicalize(&root.path).unwrap_or_else(|_| root.path.clone()),
+            extensions: root
+                .extensions
+                .iter()
+                .map(|extension| extension.trim_start_matches('.').to_lowercase())
+                .collect(),
+            include,
+            exclude: glob_set(&root.exclude)?,
+            ignore_files: root.ignore_files,
+            skip_hidden: root.skip_hidden,
+            max_depth: root.max_depth,
+            symlinks: root.symlinks,
+            ignores: HashMap::new(),
+            visited: HashSet::new(),
+        })
+    }
+
+    /// The files of the root that pass the filter. Symbolic link loops and unreadable
+    /// entries are reported and skipped.
+    pub fn walk(mut self) -> impl Iterator<Item = DirEntry> {
+        let mut walker =
+            WalkDir::new(&self.root).follow_links(self.symlinks != SymlinkPolicy::Skip);
+        if let Some(max_depth) = self.max_depth {
+            walker = walker.max_depth(max_depth);
+        }
+        walker
+            .into_iter()
+            .filter_entry(move |entry| self.admits(entry))
+            .filter_map(|entry| match entry {
+                Ok(entry) => Some(entry),
+                Err(err) => {
+                    match err.loop_ancestor() {
+                        Some(ancestor) => log::warn!(
+                            "Skipped symbolic link loop from {} to {}",
+                            err.path().unwrap_or(ancestor).display(),
+                            ancestor.display()
+                        ),
+                        None => log::error!("Failed to read directory entry: {}", err),
+                    }
+                    None
+                }
+            })
+            .filter(|entry| entry.file_type().is_file())
+    }
+
+    /// Whether a directory is entered or a file gathered.
+    fn admits(&mut self, entry: &DirEntry) -> bool {
+        if entry.depth() == 0 {
+            return true;
+        }
+        let path = entry.path();
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        let is_dir = entry.file_type().is_dir();
+        if self.skip_hidden
+            && entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.starts_with('.'))
+        {
+            return false;
+        }
+        if self.exclude.is_match(relative) {
+            return false;
+        }
+        if !is_dir && !self.admits_file(relative) {
+            return false;
+        }
+        if self.ignore_files && self.ignored(path, is_dir) {
+            return false;
+        }
+        if entry.path_is_symlink() && !self.follows(path) {
+            return false;
+        }
+        if is_dir && self.symlinks != SymlinkPolicy::Skip {
+            // Directories are resolved only when links may lead to them twice
+            let resolved = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+            if !self.visited.insert(resolved) {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn admits_file(&self, relative: &Path) -> bool {
+        let extension = relative
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let allowed = self.extensions.is_empty()
+            || extension.is_some_and(|extension| self.extensions.contains(&extension));
+        allowed
+            && self
+                .include
+                .as_ref()
+                .is_none_or(|include| include.is_match(relative))
+    }
+
+    fn follows(&self, path: &Path) -> bool {
+        match self.symlinks {
+            SymlinkPolicy::Skip => false,
+            SymlinkPolicy::Follow => true,
+            SymlinkPolicy::WithinRoot => {
+                fs::canonicalize(path).is_ok_and(|target| target.starts_with(&self.canonical_root))
+            }
+        }
+    }
+
+    /// Whether an ignore file of the directories containing the path leaves it out. The
+    /// file of the nearest directory with a matching rule decides.
+    fn ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        for directory in path.ancestors().skip(1) {
+            if !directory.starts_with(&self.root) {
+                break;
+            }
+            let ignore = self
+                .ignores
+                .entry(directory.to_path_buf())
+                .or_insert_with(|| IgnoreFile::read(directory));
+            let decision = ignore.as_ref().and_then(|ignore| {
+                let relative = path.strip_prefix(directory).ok()?;
+                ignore.matched(relative, is_dir)
+            });
+            if let Some(ignored) = decision {
+                return ignored;
+            }
+        }
+        false
+    }
+}
+
+/// Rules of an ignore file, with the syntax of `.gitignore` files: patterns without a slash
+/// match names at any depth, a trailing slash only matches directories and a leading `!`
+/// brings back what a previous rule left out.
+struct IgnoreFile {
+    rules: Vec<IgnoreRule>,
+}
+
+struct IgnoreRule {
+    matcher: GlobMatcher,
+    negated: bool,
+    directory_only: bool,
+}
+
+impl IgnoreFile {
+    /// Reads the ignore file of the directory, if any. Invalid rules are reported and
+    /// skipped rather than failing the scan.
+    fn read(directory: &Path) -> Option<Self> {
+        let path = directory.join(IGNORE_FILE_NAME);
+        let content = fs::read_to_string(&path).ok()?;
+        let rules = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                IgnoreRule::parse(line)
+                    .map_err(|err| log::warn!("Skipped rule of {}: {}", path.display(), err))
+                    .ok()
+            })
+            .collect();
+        Some(Self { rules })
+    }
+
+    /// Whether the last rule matching the path leaves it out, `None` when none matches.
+    fn matched(&self, relative: &Path, is_dir: bool) -> Option<bool> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.directory_only) && rule.matcher.is_match(relative))
+            .map(|rule| !rule.negated)
+    }
+}
+
+impl IgnoreRule {
+    fn parse(line: &str) -> Result<Self, ScanFilterError> {
+        let (negated, pattern) = match line.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, line),
+        };
+        let (directory_only, pattern) = match pattern.strip_suffix('/') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        Ok(Self {
+            matcher: glob(pattern)?.compile_matcher(),
+            negated,
+            directory_only,
+        })
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet, ScanFilterError> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(glob(pattern)?);
+    }
+    builder
+        .build()
+        .map_err(|err| ScanFilterError::Glob(patterns.join(", "), err))
+}
+
+/// Compiles a pattern relative to a directory. Patterns without a slash match names at any
+/// depth, and a leading slash anchors them without being part of the path.
+fn glob(pattern: &str) -> Result<Glob, ScanFilterError> {
+    let anchored = match pattern.strip_prefix('/') {
+        Some(pattern) => pattern.to_string(),
+        None if pattern.contains('/') => pattern.to_string(),
+        None => format!("**/{pattern}"),
+    };
+    GlobBuilder::new(&anchored)
+        .literal_separator(true)
+        .build()
+        .map_err(|err| ScanFilterError::Glob(pattern.to_string(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    /// Creates the files under a fresh directory named after the test.
+    fn tree(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = env::temp_dir().join(format!("earr-scan-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&root).ok();
+        for (path, content) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        root
+    }
+
+    fn walked(root: &LibraryRoot) -> Vec<String> {
+        let mut files: Vec<_> = ScanFilter::new(root)
+            .unwrap()
+            .walk()
+            .map(|entry| {
+                let relative = entry.path().strip_prefix(&root.path).unwrap();
+                relative.to_string_lossy().replace('\\', "/")
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn ignore_files_leave_out_matches() {
+        let path = tree(
+            "ignore",
+            &[
+                (
+                    ".earrignore",
+                    "# Rules\n*.wav\nDemos/\n!keep.wav\n/Top.mp3\n",
+                ),
+                ("a.mp3", ""),
+                ("b.wav", ""),
+                ("keep.wav", ""),
+                ("Top.mp3", ""),
+                ("Artist/Top.mp3", ""),
+                ("Artist/c.wav", ""),
+                ("Artist/Demos/d.mp3", ""),
+                // A file named like an ignored directory is kept
+                ("Demos", ""),
+            ],
+        );
+        let mut root = LibraryRoot::new("music", &path);
+        root.extensions = Vec::new();
+        assert_eq!(
+            walked(&root),
+            ["Artist/Top.mp3", "Demos", "a.mp3", "keep.wav"]
+        );
+        fs::remove_dir_all(path).ok();
+    }
+
+    #[test]
+    fn nearest_ignore_file_decides() {
+        let path = tree(
+            "nested",
+            &[
+                (".earrignore", "*.mp3\n"),
+                ("a.mp3", ""),
+                ("Live/.earrignore", "!*.mp3\nbad.mp3\n"),
+                ("Live/b.mp3", ""),
+                ("Live/bad.mp3", ""),
+                ("Live/Set/c.mp3", ""),
+                ("Other/d.mp3", ""),
+            ],
+        );
+        let root = LibraryRoot::new("music", &path);
+        assert_eq!(walked(&root), ["Live/Set/c.mp3", "Live/b.mp3"]);
+
+        let mut root = root;
+        root.ignore_files = false;
+        assert_eq!(
+            walked(&root),
+            [
+                "Live/Set/c.mp3",
+                "Live/b.mp3",
+                "Live/bad.mp3",
+                "Other/d.mp3",
+                "a.mp3"
+            ]
+        );
+        fs::remove_dir_all(path).ok();
+    }
+
+    #[test]
+    fn invalid_rules_are_skipped() {
+        let path = tree(
+            "invalid",
+            &[(".earrignore", "[\nb.mp3\n"), ("a.mp3", ""), ("b.mp3", "")],
+        );
+        assert_eq!(walked(&LibraryRoot::new("music", &path)), ["a.mp3"]);
+        fs::remove_dir_all(path).ok();
+    }
+}
@@ -0,0 +1,6 @@
+mod filesystem_audio_tag_writer;
+mod tag_document;
+
+pub use filesystem_audio_tag_writer::FilesystemAudioTagWriter;
+pub use filesystem_audio_tag_writer::FilesystemAudioTagWriterError;
+pub use tag_document::TagDocumentError;
@@ -0,0 +1,495 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::domain::{
+    entity::audio::{
+        cover::Cover,
+        field::AudioField,
+        genre::Genre,
+        tag_change::{TagChange, TagValue},
+        text, Audio,
+    },
+    repository::AudioTagWriter,
+};
+
+use super::tag_document::{self, TagDocument, TagDocumentError};
+
+/// Writes tags into MP3, FLAC, MP4/M4A, OGG and Opus files.
+///
+/// Files are never modified in place: the edited file is written to a temporary file in the
+/// same directory, synced to disk and then renamed over the original.
+pub struct FilesystemAudioTagWriter {
+    ffprobe: PathBuf,
+    ffmpeg: PathBuf,
+    multi_value_separator: Option<String>,
+}
+
+impl Default for FilesystemAudioTagWriter {
+    fn default() -> Self {
+        Self::new("ffprobe", "ffmpeg")
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FilesystemAudioTagWriterError {
+    #[error("Failed to edit tags: {0}")]
+    TagDocument(#[from] TagDocumentError),
+    #[error("Failed to replace file: {0}")]
+    IO(#[from] io::Error),
+}
+
+impl FilesystemAudioTagWriter {
+    /// ffprobe and ffmpeg are used for the formats the other libraries cannot edit.
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(ffprobe: P, ffmpeg: Q) -> Self {
+        Self {
+            ffprobe: ffprobe.as_ref().to_path_buf(),
+            ffmpeg: ffmpeg.as_ref().to_path_buf(),
+            multi_value_separator: None,
+        }
+    }
+
+    /// Artists, album artists and genres containing `separator` (e.g. `"; "`) are written as
+    /// multiple values in formats that support it.
+    pub fn with_multi_value_separator(mut self, separator: &str) -> Self {
+        self.multi_value_separator = Some(separator.to_owned());
+        self
+    }
+
+    fn values(&self, value: &str, multi_value: bool) -> TagValue {
+        let values = match &self.multi_value_separator {
+            Some(separator) if multi_value => value
+                .split(separator.as_str())
+                .map(|value| value.trim().to_owned())
+                .filter(|value| !value.is_empty())
+                .collect(),
+            _ => vec![value.to_owned()],
+        };
+        TagValue::Text(values)
+    }
+
+    /// The value a field should have in the file. `None` means the field must be left as
+    /// is, which is the case for inferred fields and fields holding default values.
+    fn desired_value(&self, audio: &Audio, field: AudioField) -> Option<Option<TagValue>> {
+        if audio.is_inferred(field) {
+            return None;
+        }
+
+        let text = |value: &str, is_unknown: bool, multi_value: bool| {
+            (!is_unknown).then(|| Some(self.values(value, multi_value)))
+        };
+        let number =
+            |value: Option<u16>| value.map(|value| Some(TagValue::Text(vec![value.to_string()])));
+        match field {
+            AudioField::Title => text(
+                audio.title().name(),
+                *audio.title() == Default::default(),
+                false,
+            ),
+            AudioField::Artist => text(audio.artist().name(), audio.artist().is_unknown(), true),
+            AudioField::AlbumTitle => text(
+                audio.album_title().name(),
+                *audio.album_title() == Default::default(),
+                false,
+            ),
+            AudioField::AlbumArtist => text(
+                audio.album_artist().name(),
+                audio.album_artist().is_unknown(),
+                true,
+            ),
+            AudioField::Genre => text(
+                audio.genre().name(),
+                *audio.genre() == Genre::default(),
+                true,
+            ),
+            AudioField::Year => number(audio.year().map(|year| year.0)),
+            AudioField::TrackNumber => number(*audio.track_number()),
+            AudioField::DiscNumber => number(*audio.disc_number()),
+            AudioField::Compilation => Some(
+                audio
+                    .compilation()
+                    .then(|| TagValue::Text(vec!["1".to_owned()])),
+            ),
+            AudioField::AlbumCover => (*audio.album_cover() != Cover::default())
+                .then(|| Some(TagValue::Picture(audio.album_cover().data().to_vec()))),
+        }
+    }
+
+    /// Brings a value to the form earr parses it into, so that values that only differ in
+    /// representation (e.g. `3/12` and `3`, or `(17)` and `Rock`) are not rewritten.
+    fn canonical(field: AudioField, value: &TagValue) -> TagValue {
+        let TagValue::Text(values) = value else {
+            return value.clone();
+        };
+        let canonical = |value: &String| match field {
+            AudioField::Genre => value
+                .parse::<Genre>()
+                .map(|genre| genre.name().clone())
+                .unwrap_or_default(),
+            AudioField::Year => value.trim().chars().take(4).collect(),
+            AudioField::TrackNumber | AudioField::DiscNumber => value
+                .split('/')
+                .next()
+                .and_then(|number| number.trim().parse::<u16>().ok())
+                .map(|number| number.to_string())
+                .unwrap_or_else(|| value.clone()),
+            AudioField::Compilation => match value.trim().to_lowercase().as_str() {
+                "1" | "true" | "yes" => "1".to_owned(),
+                _ => "0".to_owned(),
+            },
+            _ => text::normalize(value),
+        };
+        TagValue::Text(values.iter().map(canonical).collect())
+    }
+
+    fn is_same(field: AudioField, before: Option<&TagValue>, after: Option<&TagValue>) -> bool {
+        match (before, after) {
+            (Some(before), Some(after)) => {
+                Self::canonical(field, before) == Self::canonical(field, after)
+            }
+            // An explicit "not a compilation" flag is the same as no flag
+            (Some(before), None) if field == AudioField::Compilation => {
+                Self::canonical(field, before) == TagValue::Text(vec!["0".to_owned()])
+            }
+            (before, after) => before == after,
+        }
+    }
+
+    /// `.<file stem>.earr-tmp.<extension>`, keeping the extension so the format is known.
+    fn temporary_path(path: &Path) -> PathBuf {
+        let file_stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let file_name = match path.extension() {
+            Some(extension) => format!(".{file_stem}.earr-tmp.{}", extension.to_string_lossy()),
+            None => format!(".{file_stem}.earr-tmp"),
+        };
+        path.with_file_name(file_name)
+    }
+
+    fn save_atomically(
+        document: &mut dyn TagDocument,
+        path: &Path,
+    ) -> Result<(), FilesystemAudioTagWriterError> {
+        let temporary_path = Self::temporary_path(path);
+        let result = document
+            .save(path, &temporary_path)
+            .map_err(FilesystemAudioTagWriterError::from)
+            .and_then(|_| {
+                fs::set_permissions(&temporary_path, fs::metadata(path)?.permissions())?;
+                File::open(&temporary_path)?.sync_all()?;
+                fs::rename(&temporary_path, path)?;
+                #[cfg(unix)]
+                if let Some(parent) = path.parent() {
+                    File::open(parent)?.sync_all()?;
+                }
+                Ok(())
+            });
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary_path);
+        }
+        result
+    }
+}
+
+impl AudioTagWriter for FilesystemAudioTagWriter {
+    type Error = FilesystemAudioTagWriterError;
+
+    fn diff(&self, audio: &Audio) -> Result<Vec<TagChange>, Self::Error> {
+        let document = tag_document::open(
+            audio.path(),
+            &self.ffprobe,
+            &self.ffmpeg,
+            self.multi_value_separator.as_deref(),
+        )?;
+        let changes = AudioField::WRITABLE
+            .into_iter()
+            .filter_map(|field| {
+                let after = self.desired_value(audio, field)?;
+                let before = document.get(field);
+                (!Self::is_same(field, before.as_ref(), after.as_ref())).then_some(TagChange {
+                    field,
+                    before,
+                    after,
+                })
+            })
+            .collect();
+        Ok(changes)
+    }
+
+    fn apply(&self, path: &Path, changes: &[TagChange]) -> Result<(), Self::Error> {
+        let mut document = tag_document::open(
+            path,
+            &self.ffprobe,
+            &self.ffmpeg,
+            self.multi_value_separator.as_deref(),
+        )?;
+        for change in changes {
+            document.set(change.field, change.after.as_ref())?;
+        }
+        Self::save_atomically(document.as_mut(), path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process::Command};
+
+    use ::id3::{frame::ExtendedText, TagLike, Version};
+    use mp4ameta::{Data, FreeformIdent};
+
+    use crate::domain::entity::audio::{artist::Artist, title::Title, AudioBuilder};
+
+    use super::*;
+
+    const MOOD: FreeformIdent<'static> = FreeformIdent::new("com.apple.iTunes", "MOOD");
+
+    /// A fresh directory named after the test, removed by [`cleanup`].
+    fn directory(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("earr-tags-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn cleanup(path: &Path) {
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    fn audio(path: &Path, title: &str, artist: &str, genre: Genre) -> Audio {
+        AudioBuilder::default()
+            .title(title.parse().unwrap())
+            .artist(artist.parse().unwrap())
+            .year(None)
+            .album_title(Title::default())
+            .album_artist(Artist::default())
+            .album_cover(Cover::default())
+            .genre(genre)
+            .track_number(None)
+            .disc_number(None)
+            .compilation(false)
+            .path(path.to_path_buf())
+            .build()
+            .unwrap()
+    }
+
+    fn atom(ident: &[u8; 4], content: &[u8]) -> Vec<u8> {
+        let mut atom = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+        atom.extend_from_slice(ident);
+        atom.extend_from_slice(content);
+        atom
+    }
+
+    /// An MP3 with ID3v2.3 tags, including a user defined frame earr does not know about.
+    fn mp3(name: &str) -> PathBuf {
+        let path = directory(name).join("track.mp3");
+        fs::write(&path, [0xFF, 0xFB, 0x90, 0x00].repeat(64)).unwrap();
+        let mut tag = ::id3::Tag::new();
+        tag.set_title("Old Title");
+        tag.set_artist("AC/DC");
+        tag.set_genre("Rock; Live");
+        tag.add_frame(ExtendedText {
+            description: "MOOD".to_owned(),
+            value: "Calm".to_owned(),
+        });
+        tag.write_to_path(&path, Version::Id3v23).unwrap();
+        path
+    }
+
+    /// A FLAC with a bare stream info block and a comment earr does not know about.
+    fn flac(name: &str) -> PathBuf {
+        let path = directory(name).join("track.flac");
+        let mut bytes = b"fLaC".to_vec();
+        // Last metadata block, stream info, 34 bytes long
+        bytes.extend_from_slice(&[0x80, 0, 0, 34]);
+        bytes.extend_from_slice(&[0; 34]);
+        bytes.extend_from_slice(&[0xFF, 0xF8, 0x69, 0x08].repeat(64));
+        fs::write(&path, bytes).unwrap();
+        let mut tag = metaflac::Tag::read_from_path(&path).unwrap();
+        tag.set_vorbis("TITLE", vec!["Old Title"]);
+        tag.set_vorbis("ARTIST", vec!["AC/DC"]);
+        tag.set_vorbis("GENRE", vec!["Rock; Live"]);
+        tag.set_vorbis("MOOD", vec!["Calm"]);
+        tag.write_to_path(&path).unwrap();
+        path
+    }
+
+    /// An M4A with a movie header, some media data and a freeform atom earr does not know
+    /// about.
+    fn m4a(name: &str) -> PathBuf {
+        let path = directory(name).join("track.m4a");
+        let mut header = vec![0; 12];
+        // Time scale and duration
+        header.extend_from_slice(&1000u32.to_be_bytes());
+        header.extend_from_slice(&1000u32.to_be_bytes());
+        header.extend_from_slice(&[0; 80]);
+        let mut bytes = atom(b"ftyp", b"M4A \0\0\0\0M4A isom");
+        bytes.extend(atom(b"moov", &atom(b"mvhd", &header)));
+        bytes.extend(atom(b"mdat", &[0x21; 256]));
+        fs::write(&path, bytes).unwrap();
+        let mut tag = mp4ameta::Tag::read_from_path(&path).unwrap();
+        tag.set_title("Old Title");
+        tag.set_artist("AC/DC");
+        tag.set_genre("Rock; Live");
+        tag.set_data(MOOD, Data::Utf8("Calm".to_owned()));
+        tag.write_to_path(&path).unwrap();
+        path
+    }
+
+    /// An Opus file made with ffmpeg, or `None` when ffmpeg is not available.
+    fn opus(name: &str) -> Option<PathBuf> {
+        let ffprobe = Command::new("ffprobe").arg("-version").output();
+        if !ffprobe.is_ok_and(|output| output.status.success()) {
+            return None;
+        }
+        let path = directory(name).join("track.opus");
+        let output = Command::new("ffmpeg")
+            .args(["-v", "error", "-f", "lavfi", "-i", "anullsrc", "-t", "0.1"])
+            .args(["-c:a", "libopus"])
+            .args(["-metadata:s:a:0", "TITLE=Old Title"])
+            .args(["-metadata:s:a:0", "ARTIST=AC/DC"])
+            .args(["-metadata:s:a:0", "GENRE=Rock; Live"])
+            .args(["-metadata:s:a:0", "MOOD=Calm"])
+            .arg(&path)
+            .output()
+            .ok()?;
+        output.status.success().then_some(path)
+    }
+
+    fn read(writer: &FilesystemAudioTagWriter, path: &Path, field: AudioField) -> Option<TagValue> {
+        tag_document::open(
+            path,
+            &writer.ffprobe,
+            &writer.ffmpeg,
+            writer.multi_value_separator.as_deref(),
+        )
+        .unwrap()
+        .get(field)
+    }
+
+    fn text(values: &[&str]) -> Option<TagValue> {
+        Some(TagValue::Text(
+            values.iter().map(|value| value.to_string()).collect(),
+        ))
+    }
+
+    /// Retitles the file, checking that the diff leaves the file alone, only reports the
+    /// title, and is empty once applied.
+    fn assert_round_trip(path: &Path) {
+        let writer = FilesystemAudioTagWriter::default();
+        let audio = audio(path, "New Title", "AC/DC", "Rock; Live".parse().unwrap());
+        let original = fs::read(path).unwrap();
+
+        let changes = writer.diff(&audio).unwrap();
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| change.field)
+                .collect::<Vec<_>>(),
+            vec![AudioField::Title]
+        );
+        assert_eq!(fs::read(path).unwrap(), original);
+
+        writer.apply(path, &changes).unwrap();
+        assert!(writer.diff(&audio).unwrap().is_empty());
+        assert_eq!(read(&writer, path, AudioField::Title), text(&["New Title"]));
+        assert_eq!(read(&writer, path, AudioField::Artist), text(&["AC/DC"]));
+        assert_eq!(
+            read(&writer, path, AudioField::Genre),
+            text(&["Rock; Live"])
+        );
+        assert!(!FilesystemAudioTagWriter::temporary_path(path).exists());
+    }
+
+    #[test]
+    fn round_trips_mp3() {
+        let path = mp3("mp3");
+        assert_round_trip(&path);
+
+        let tag = ::id3::Tag::read_from_path(&path).unwrap();
+        assert_eq!(tag.version(), Version::Id3v23);
+        assert!(tag
+            .extended_texts()
+            .any(|text| text.description == "MOOD" && text.value == "Calm"));
+        cleanup(&path);
+    }
+
+    #[test]
+    fn round_trips_flac() {
+        let path = flac("flac");
+        assert_round_trip(&path);
+
+        let tag = metaflac::Tag::read_from_path(&path).unwrap();
+        assert_eq!(
+            tag.get_vorbis("MOOD").unwrap().collect::<Vec<_>>(),
+            vec!["Calm"]
+        );
+        cleanup(&path);
+    }
+
+    #[test]
+    fn round_trips_m4a() {
+        let path = m4a("m4a");
+        let media_data = atom(b"mdat", &[0x21; 256]);
+        assert_round_trip(&path);
+
+        let tag = mp4ameta::Tag::read_from_path(&path).unwrap();
+        assert_eq!(tag.strings_of(&MOOD).collect::<Vec<_>>(), vec!["Calm"]);
+        let bytes = fs::read(&path).unwrap();
+        assert!(bytes
+            .windows(media_data.len())
+            .any(|window| window == media_data));
+        cleanup(&path);
+    }
+
+    #[test]
+    fn round_trips_opus() {
+        let Some(path) = opus("opus") else {
+            eprintln!("ffmpeg is not available, skipping");
+            return;
+        };
+        assert_round_trip(&path);
+
+        let output = Command::new("ffprobe")
+            .args(["-v", "quiet", "-show_entries", "stream_tags=MOOD"])
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(String::from_utf8_lossy(&output.stdout).contains("Calm"));
+        cleanup(&path);
+    }
+
+    #[test]
+    fn splits_values_with_the_configured_separator() {
+        let path = mp3("separator");
+        let writer = FilesystemAudioTagWriter::default().with_multi_value_separator("; ");
+        assert_eq!(
+            read(&writer, &path, AudioField::Genre),
+            text(&["Rock", "Live"])
+        );
+        assert_eq!(read(&writer, &path, AudioField::Artist), text(&["AC/DC"]));
+
+        let audio = audio(&path, "Old Title", "Simon; Garfunkel", Genre::default());
+        let changes = writer.diff(&audio).unwrap();
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| change.field)
+                .collect::<Vec<_>>(),
+            vec![AudioField::Artist]
+        );
+        writer.apply(&path, &changes).unwrap();
+        assert!(writer.diff(&audio).unwrap().is_empty());
+        assert_eq!(
+            read(&writer, &path, AudioField::Artist),
+            text(&["Simon", "Garfunkel"])
+        );
+        assert_eq!(
+            ::id3::Tag::read_from_path(&path).unwrap().artist(),
+            Some("Simon; Garfunkel")
+        );
+        cleanup(&path);
+    }
+}
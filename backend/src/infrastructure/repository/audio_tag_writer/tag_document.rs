@@ -0,0 +1,135 @@
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::domain::entity::audio::{field::AudioField, tag_change::TagValue};
+
+mod flac;
+mod id3;
+mod mp4;
+mod vorbis_comment;
+
+/// The tags of an audio file loaded in memory, editable field by field.
+pub trait TagDocument {
+    fn get(&self, field: AudioField) -> Option<TagValue>;
+    /// Sets a field, removing it when `value` is `None`.
+    fn set(&mut self, field: AudioField, value: Option<&TagValue>) -> Result<(), TagDocumentError>;
+    /// Writes `source` with the edited tags into `target`, leaving `source` untouched.
+    /// Tags and frames this document does not know about are preserved.
+    fn save(&mut self, source: &Path, target: &Path) -> Result<(), TagDocumentError>;
+}
+
+#[derive(Error, Debug)]
+pub enum TagDocumentError {
+    #[error("Unsupported format: {0}")]
+    UnsupportedFormat(String),
+    #[error("Invalid value for {0}: {1}")]
+    InvalidValue(AudioField, String),
+    #[error("Failed to copy file: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("ID3 error: {0}")]
+    Id3(#[from] ::id3::Error),
+    #[error("FLAC error: {0}")]
+    Flac(#[from] metaflac::Error),
+    #[error("MP4 error: {0}")]
+    Mp4(#[from] mp4ameta::Error),
+    #[error("ffmpeg error: {0}")]
+    Ffmpeg(String),
+    #[error("Failed to parse ffprobe output: {0}")]
+    FfprobeJson(#[from] serde_json::Error),
+}
+
+/// Opens the tags of the file at `path`, choosing the implementation by extension. Formats
+/// without a native library are read with `ffprobe` and rewritten with `ffmpeg`.
+///
+/// Formats that store a field as a single text keep the values of multi-value fields joined
+/// with `multi_value_separator`. Without one, such texts are a single value.
+pub fn open(
+    path: &Path,
+    ffprobe: &Path,
+    ffmpeg: &Path,
+    multi_value_separator: Option<&str>,
+) -> Result<Box<dyn TagDocument>, TagDocumentError> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "mp3" => Ok(Box::new(id3::Id3Document::open(
+            path,
+            multi_value_separator,
+        )?)),
+        "flac" => Ok(Box::new(flac::FlacDocument::open(path)?)),
+        "m4a" | "m4b" | "mp4" => Ok(Box::new(mp4::Mp4Document::open(path)?)),
+        "ogg" | "oga" | "opus" => Ok(Box::new(vorbis_comment::VorbisCommentDocument::open(
+            path,
+            ffprobe,
+            ffmpeg,
+            multi_value_separator,
+        )?)),
+        other => Err(TagDocumentError::UnsupportedFormat(other.to_owned())),
+    }
+}
+
+/// Fields that may hold several values, such as several artists.
+fn is_multi_value(field: AudioField) -> bool {
+    matches!(
+        field,
+        AudioField::Artist | AudioField::AlbumArtist | AudioField::Genre
+    )
+}
+
+/// The values of a field stored as a single text.
+fn split(field: AudioField, value: &str, separator: Option<&str>) -> Vec<String> {
+    match separator {
+        Some(separator) if is_multi_value(field) => value
+            .split(separator)
+            .map(|value| value.trim().to_owned())
+            .filter(|value| !value.is_empty())
+            .collect(),
+        _ => vec![value.to_owned()],
+    }
+}
+
+/// Stores values as a single text, the inverse of [`split`].
+fn join(values: &[String], separator: Option<&str>) -> String {
+    values.join(separator.unwrap_or("; "))
+}
+
+fn text(value: Option<&TagValue>) -> Option<&[String]> {
+    match value {
+        Some(TagValue::Text(values)) => Some(values),
+        _ => None,
+    }
+}
+
+fn picture(value: Option<&TagValue>) -> Option<&[u8]> {
+    match value {
+        Some(TagValue::Picture(data)) => Some(data),
+        _ => None,
+    }
+}
+
+/// Parses the first value of a numeric field, accepting `n/total` values.
+fn number(field: AudioField, value: Option<&TagValue>) -> Result<Option<u16>, TagDocumentError> {
+    let Some(values) = text(value) else {
+        return Ok(None);
+    };
+    let first = values.first().map(String::as_str).unwrap_or_default();
+    first
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .parse()
+        .map(Some)
+        .map_err(|_| TagDocumentError::InvalidValue(field, first.to_owned()))
+}
+
+fn picture_mime_type(data: &[u8]) -> &'static str {
+    if data.starts_with(b"\x89PNG") {
+        "image/png"
+    } else {
+        "image/jpeg"
+    }
+}
@@ -0,0 +1,83 @@
+use std::{fs, path::Path};
+
+use metaflac::{block::PictureType, Tag};
+
+use crate::domain::entity::audio::{field::AudioField, tag_change::TagValue};
+
+use super::{picture, picture_mime_type, text, TagDocument, TagDocumentError};
+
+pub struct FlacDocument {
+    tag: Tag,
+}
+
+impl FlacDocument {
+    pub fn open(path: &Path) -> Result<Self, TagDocumentError> {
+        Ok(Self {
+            tag: Tag::read_from_path(path)?,
+        })
+    }
+
+    fn vorbis_key(field: AudioField) -> Option<&'static str> {
+        match field {
+            AudioField::Title => Some("TITLE"),
+            AudioField::Artist => Some("ARTIST"),
+            AudioField::Year => Some("DATE"),
+            AudioField::AlbumTitle => Some("ALBUM"),
+            AudioField::AlbumArtist => Some("ALBUMARTIST"),
+            AudioField::Genre => Some("GENRE"),
+            AudioField::TrackNumber => Some("TRACKNUMBER"),
+            AudioField::DiscNumber => Some("DISCNUMBER"),
+            AudioField::Compilation => Some("COMPILATION"),
+            AudioField::AlbumCover => None,
+        }
+    }
+}
+
+impl TagDocument for FlacDocument {
+    fn get(&self, field: AudioField) -> Option<TagValue> {
+        if field == AudioField::AlbumCover {
+            return self
+                .tag
+                .pictures()
+                .find(|picture| picture.picture_type == PictureType::CoverFront)
+                .or_else(|| self.tag.pictures().next())
+                .map(|picture| TagValue::Picture(picture.data.clone()));
+        }
+
+        let values = self
+            .tag
+            .get_vorbis(Self::vorbis_key(field)?)?
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        (!values.is_empty()).then_some(TagValue::Text(values))
+    }
+
+    fn set(&mut self, field: AudioField, value: Option<&TagValue>) -> Result<(), TagDocumentError> {
+        if field == AudioField::AlbumCover {
+            self.tag.remove_picture_type(PictureType::CoverFront);
+            if let Some(data) = picture(value) {
+                self.tag.add_picture(
+                    picture_mime_type(data),
+                    PictureType::CoverFront,
+                    data.to_vec(),
+                );
+            }
+            return Ok(());
+        }
+
+        let Some(key) = Self::vorbis_key(field) else {
+            return Ok(());
+        };
+        match text(value) {
+            Some(values) => self.tag.set_vorbis(key, values.to_vec()),
+            None => self.tag.remove_vorbis(key),
+        }
+        Ok(())
+    }
+
+    fn save(&mut self, source: &Path, target: &Path) -> Result<(), TagDocumentError> {
+        fs::copy(source, target)?;
+        self.tag.write_to_path(target)?;
+        Ok(())
+    }
+}
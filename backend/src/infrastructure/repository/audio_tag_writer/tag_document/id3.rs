@@ -0,0 +1,158 @@
+use std::{fs, path::Path};
+
+use ::id3::{
+    frame::{Picture, PictureType, Timestamp},
+    ErrorKind, Tag, TagLike, Version,
+};
+
+use crate::domain::entity::audio::{field::AudioField, tag_change::TagValue};
+
+use super::{join, number, picture, picture_mime_type, split, text, TagDocument, TagDocumentError};
+
+/// ID3v2 tags, written back with the version they were read with so that players limited
+/// to ID3v2.3 still read them. Files without tags get ID3v2.4 ones.
+///
+/// Before ID3v2.4, values are separated with `/`, which single values such as `AC/DC` also
+/// contain, so these versions store the values of a field as a single text.
+pub struct Id3Document {
+    tag: Tag,
+    multi_value_separator: Option<String>,
+}
+
+impl Id3Document {
+    pub fn open(
+        path: &Path,
+        multi_value_separator: Option<&str>,
+    ) -> Result<Self, TagDocumentError> {
+        let tag = match Tag::read_from_path(path) {
+            Ok(tag) => tag,
+            Err(err) if matches!(err.kind, ErrorKind::NoTag) => Tag::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            tag,
+            multi_value_separator: multi_value_separator.map(str::to_owned),
+        })
+    }
+
+    fn separates_values(&self) -> bool {
+        self.tag.version() == Version::Id3v24
+    }
+
+    fn frame_id(field: AudioField) -> Option<&'static str> {
+        match field {
+            AudioField::Title => Some("TIT2"),
+            AudioField::Artist => Some("TPE1"),
+            AudioField::AlbumTitle => Some("TALB"),
+            AudioField::AlbumArtist => Some("TPE2"),
+            AudioField::Genre => Some("TCON"),
+            AudioField::Compilation => Some("TCMP"),
+            _ => None,
+        }
+    }
+}
+
+impl TagDocument for Id3Document {
+    fn get(&self, field: AudioField) -> Option<TagValue> {
+        match field {
+            AudioField::Year => self
+                .tag
+                .date_recorded()
+                .map(|timestamp| timestamp.year)
+                .or_else(|| self.tag.year())
+                .map(|year| TagValue::Text(vec![year.to_string()])),
+            AudioField::TrackNumber => self
+                .tag
+                .track()
+                .map(|track| TagValue::Text(vec![track.to_string()])),
+            AudioField::DiscNumber => self
+                .tag
+                .disc()
+                .map(|disc| TagValue::Text(vec![disc.to_string()])),
+            AudioField::AlbumCover => self
+                .tag
+                .pictures()
+                .find(|picture| picture.picture_type == PictureType::CoverFront)
+                .or_else(|| self.tag.pictures().next())
+                .map(|picture| TagValue::Picture(picture.data.clone())),
+            field => {
+                let frame = self.tag.get(Self::frame_id(field)?)?;
+                let values = frame.content().text_values()?;
+                if self.separates_values() {
+                    return Some(TagValue::Text(values.map(str::to_owned).collect()));
+                }
+                // The `/` separators were read as value separators
+                let value = values.collect::<Vec<_>>().join("/");
+                Some(TagValue::Text(split(
+                    field,
+                    &value,
+                    self.multi_value_separator.as_deref(),
+                )))
+            }
+        }
+    }
+
+    fn set(&mut self, field: AudioField, value: Option<&TagValue>) -> Result<(), TagDocumentError> {
+        match field {
+            AudioField::Year => {
+                self.tag.remove_year();
+                self.tag.remove_date_recorded();
+                match number(field, value)? {
+                    // The recording time frame only exists since ID3v2.4
+                    Some(year) if !self.separates_values() => self.tag.set_year(year.into()),
+                    Some(year) => self.tag.set_date_recorded(Timestamp {
+                        year: year.into(),
+                        month: None,
+                        day: None,
+                        hour: None,
+                        minute: None,
+                        second: None,
+                    }),
+                    None => {}
+                }
+            }
+            AudioField::TrackNumber => match number(field, value)? {
+                Some(track) => self.tag.set_track(track.into()),
+                None => self.tag.remove_track(),
+            },
+            AudioField::DiscNumber => match number(field, value)? {
+                Some(disc) => self.tag.set_disc(disc.into()),
+                None => self.tag.remove_disc(),
+            },
+            AudioField::AlbumCover => {
+                self.tag.remove_picture_by_type(PictureType::CoverFront);
+                if let Some(data) = picture(value) {
+                    self.tag.add_frame(Picture {
+                        mime_type: picture_mime_type(data).to_owned(),
+                        picture_type: PictureType::CoverFront,
+                        description: String::new(),
+                        data: data.to_vec(),
+                    });
+                }
+            }
+            field => {
+                let Some(frame_id) = Self::frame_id(field) else {
+                    return Ok(());
+                };
+                self.tag.remove(frame_id);
+                match text(value) {
+                    Some(values) if self.separates_values() => {
+                        self.tag.set_text_values(frame_id, values)
+                    }
+                    Some(values) => self.tag.set_text(
+                        frame_id,
+                        join(values, self.multi_value_separator.as_deref()),
+                    ),
+                    None => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn save(&mut self, source: &Path, target: &Path) -> Result<(), TagDocumentError> {
+        fs::copy(source, target)?;
+        self.tag.write_to_path(target, self.tag.version())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use std::{fs, path::Path};
+
+use mp4ameta::{Img, Tag};
+
+use crate::domain::entity::audio::{field::AudioField, tag_change::TagValue};
+
+use super::{number, picture, text, TagDocument, TagDocumentError};
+
+pub struct Mp4Document {
+    tag: Tag,
+}
+
+impl Mp4Document {
+    pub fn open(path: &Path) -> Result<Self, TagDocumentError> {
+        Ok(Self {
+            tag: Tag::read_from_path(path)?,
+        })
+    }
+}
+
+impl TagDocument for Mp4Document {
+    fn get(&self, field: AudioField) -> Option<TagValue> {
+        let single =
+            |value: Option<&str>| value.map(|value| TagValue::Text(vec![value.to_owned()]));
+        let multiple = |values: Vec<&str>| {
+            (!values.is_empty())
+                .then(|| TagValue::Text(values.into_iter().map(str::to_owned).collect()))
+        };
+        match field {
+            AudioField::Title => single(self.tag.title()),
+            AudioField::Artist => multiple(self.tag.artists().collect()),
+            AudioField::Year => single(self.tag.year()),
+            AudioField::AlbumTitle => single(self.tag.album()),
+            AudioField::AlbumArtist => multiple(self.tag.album_artists().collect()),
+            AudioField::Genre => multiple(self.tag.genres().collect()),
+            AudioField::TrackNumber => self
+                .tag
+                .track_number()
+                .map(|track| TagValue::Text(vec![track.to_string()])),
+            AudioField::DiscNumber => self
+                .tag
+                .disc_number()
+                .map(|disc| TagValue::Text(vec![disc.to_string()])),
+            AudioField::Compilation => self
+                .tag
+                .data_of(&mp4ameta::ident::COMPILATION)
+                .next()
+                .map(|_| TagValue::Text(vec![u8::from(self.tag.compilation()).to_string()])),
+            AudioField::AlbumCover => self
+                .tag
+                .artwork()
+                .map(|artwork| TagValue::Picture(artwork.data.to_vec())),
+        }
+    }
+
+    fn set(&mut self, field: AudioField, value: Option<&TagValue>) -> Result<(), TagDocumentError> {
+        let values = text(value).map(<[String]>::to_vec);
+        match field {
+            AudioField::Title => match values {
+                Some(values) => self.tag.set_title(values.join(" ")),
+                None => self.tag.remove_title(),
+            },
+            AudioField::Artist => match values {
+                Some(values) => self.tag.set_artists(values),
+                None => self.tag.remove_artists(),
+            },
+            AudioField::Year => match values {
+                Some(values) => self.tag.set_year(values.join(" ")),
+                None => self.tag.remove_year(),
+            },
+            AudioField::AlbumTitle => match values {
+                Some(values) => self.tag.set_album(values.join(" ")),
+                None => self.tag.remove_album(),
+            },
+            AudioField::AlbumArtist => match values {
+                Some(values) => self.tag.set_album_artists(values),
+                None => self.tag.remove_album_artists(),
+            },
+            AudioField::Genre => match values {
+                Some(values) => self.tag.set_genres(values),
+                None => self.tag.remove_genres(),
+            },
+            AudioField::TrackNumber => match number(field, value)? {
+                Some(track) => self.tag.set_track_number(track),
+                None => self.tag.remove_track_number(),
+            },
+            AudioField::DiscNumber => match number(field, value)? {
+                Some(disc) => self.tag.set_disc_number(disc),
+                None => self.tag.remove_disc_number(),
+            },
+            AudioField::Compilation => match number(field, value)? {
+                Some(1) => self.tag.set_compilation(),
+                _ => self.tag.remove_compilation(),
+            },
+            AudioField::AlbumCover => match picture(value) {
+                Some(data) if data.starts_with(b"\x89PNG") => {
+                    self.tag.set_artwork(Img::png(data.to_vec()))
+                }
+                Some(data) => self.tag.set_artwork(Img::jpeg(data.to_vec())),
+                None => self.tag.remove_artworks(),
+            },
+        }
+        Ok(())
+    }
+
+    fn save(&mut self, source: &Path, target: &Path) -> Result<(), TagDocumentError> {
+        fs::copy(source, target)?;
+        self.tag.write_to_path(target)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Deserialize;
+
+use crate::domain::entity::audio::{field::AudioField, tag_change::TagValue};
+
+use super::{join, picture, picture_mime_type, split, text, TagDocument, TagDocumentError};
+
+/// Vorbis comments of OGG and Opus files, read with ffprobe and rewritten with ffmpeg.
+///
+/// ffmpeg exposes covers of these containers as attached pictures, so the cover is kept
+/// apart and written back as a `METADATA_BLOCK_PICTURE` comment. It also writes a single
+/// comment by name, so the values of a field are joined in it.
+pub struct VorbisCommentDocument {
+    ffmpeg: PathBuf,
+    comments: Vec<(String, String)>,
+    cover: Option<Vec<u8>>,
+    multi_value_separator: Option<String>,
+}
+
+impl VorbisCommentDocument {
+    pub fn open(
+        path: &Path,
+        ffprobe: &Path,
+        ffmpeg: &Path,
+        multi_value_separator: Option<&str>,
+    ) -> Result<Self, TagDocumentError> {
+        let output = Command::new(ffprobe)
+            .arg("-v")
+            .arg("quiet")
+            .arg("-of")
+            .arg("json")
+            .arg("-select_streams")
+            .arg("a:0")
+            .arg("-show_entries")
+            .arg("format_tags:stream_tags")
+            .arg(path)
+            .stdout(Stdio::piped())
+            .output()
+            .map_err(|err| TagDocumentError::Ffmpeg(err.to_string()))?;
+        let output: FfprobeOutput = serde_json::from_slice(&output.stdout)?;
+
+        let mut document = Self {
+            ffmpeg: ffmpeg.to_path_buf(),
+            comments: Vec::new(),
+            cover: Self::read_cover(path, ffmpeg)?,
+            multi_value_separator: multi_value_separator.map(str::to_owned),
+        };
+        let stream_tags = output.streams.into_iter().flat_map(|stream| stream.tags);
+        for (key, value) in output.format.tags.into_iter().chain(stream_tags) {
+            if !key.eq_ignore_ascii_case("METADATA_BLOCK_PICTURE") {
+                document.remove(&key);
+                document.comments.push((key, value));
+            }
+        }
+        Ok(document)
+    }
+
+    fn read_cover(path: &Path, ffmpeg: &Path) -> Result<Option<Vec<u8>>, TagDocumentError> {
+        let output = Command::new(ffmpeg)
+            .arg("-v")
+            .arg("quiet")
+            .arg("-i")
+            .arg(path)
+            .arg("-map")
+            .arg("0:v?")
+            .arg("-frames:v")
+            .arg("1")
+            .arg("-c")
+            .arg("copy")
+            .arg("-f")
+            .arg("image2pipe")
+            .arg("-")
+            .output()
+            .map_err(|err| TagDocumentError::Ffmpeg(err.to_string()))?;
+        Ok((!output.stdout.is_empty()).then_some(output.stdout))
+    }
+
+    /// Comment names used for a field, the first one being the one written.
+    fn keys(field: AudioField) -> &'static [&'static str] {
+        match field {
+            AudioField::Title => &["TITLE"],
+            AudioField::Artist => &["ARTIST"],
+            AudioField::Year => &["DATE", "YEAR"],
+            AudioField::AlbumTitle => &["ALBUM"],
+            AudioField::AlbumArtist => &["ALBUMARTIST", "ALBUM_ARTIST", "ALBUM ARTIST"],
+            AudioField::Genre => &["GENRE"],
+            AudioField::TrackNumber => &["TRACKNUMBER", "TRACK"],
+            AudioField::DiscNumber => &["DISCNUMBER", "DISC"],
+            AudioField::Compilation => &["COMPILATION"],
+            AudioField::AlbumCover => &[],
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        self.comments
+            .retain(|(comment_key, _)| !comment_key.eq_ignore_ascii_case(key));
+    }
+
+    /// Builds a FLAC picture block, the payload of `METADATA_BLOCK_PICTURE`.
+    fn picture_block(data: &[u8]) -> Vec<u8> {
+        let mime_type = picture_mime_type(data).as_bytes();
+        let mut block = Vec::with_capacity(data.len() + 64);
+        // Front cover picture type
+        block.extend_from_slice(&3u32.to_be_bytes());
+        block.extend_from_slice(&(mime_type.len() as u32).to_be_bytes());
+        block.extend_from_slice(mime_type);
+        // Empty description, unknown width, height, depth and colors
+        block.extend_from_slice(&[0; 20]);
+        block.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        block.extend_from_slice(data);
+        block
+    }
+
+    fn escape(value: &str) -> String {
+        value
+            .chars()
+            .flat_map(|c| match c {
+                '=' | ';' | '#' | '\\' | '\n' => vec!['\\', c],
+                c => vec![c],
+            })
+            .collect()
+    }
+
+    fn ffmetadata(&self) -> String {
+        let mut ffmetadata = String::from(";FFMETADATA1\n[STREAM]\n");
+        for (key, value) in &self.comments {
+            ffmetadata.push_str(&format!("{}={}\n", Self::escape(key), Self::escape(value)));
+        }
+        if let Some(cover) = &self.cover {
+            let picture = STANDARD.encode(Self::picture_block(cover));
+            ffmetadata.push_str(&format!("METADATA_BLOCK_PICTURE={picture}\n"));
+        }
+        ffmetadata
+    }
+}
+
+impl TagDocument for VorbisCommentDocument {
+    fn get(&self, field: AudioField) -> Option<TagValue> {
+        if field == AudioField::AlbumCover {
+            return self.cover.clone().map(TagValue::Picture);
+        }
+
+        let keys = Self::keys(field);
+        let (_, value) = self.comments.iter().find(|(key, _)| {
+            keys.iter()
+                .any(|field_key| field_key.eq_ignore_ascii_case(key))
+        })?;
+        Some(TagValue::Text(split(
+            field,
+            value,
+            self.multi_value_separator.as_deref(),
+        )))
+    }
+
+    fn set(&mut self, field: AudioField, value: Option<&TagValue>) -> Result<(), TagDocumentError> {
+        if field == AudioField::AlbumCover {
+            self.cover = picture(value).map(<[u8]>::to_vec);
+            return Ok(());
+        }
+
+        let keys = Self::keys(field);
+        for key in keys {
+            self.remove(key);
+        }
+        if let (Some(key), Some(values)) = (keys.first(), text(value)) {
+            let value = join(values, self.multi_value_separator.as_deref());
+            self.comments.push((key.to_string(), value));
+        }
+        Ok(())
+    }
+
+    fn save(&mut self, source: &Path, target: &Path) -> Result<(), TagDocumentError> {
+        let mut ffmetadata_path = PathBuf::from(target);
+        ffmetadata_path.set_extension("ffmetadata");
+        fs::write(&ffmetadata_path, self.ffmetadata())?;
+
+        let output = Command::new(&self.ffmpeg)
+            .arg("-v")
+            .arg("error")
+            .arg("-y")
+            .arg("-i")
+            .arg(source)
+            .arg("-f")
+            .arg("ffmetadata")
+            .arg("-i")
+            .arg(&ffmetadata_path)
+            .arg("-map")
+            .arg("0:a")
+            .arg("-c")
+            .arg("copy")
+            .arg("-map_metadata")
+            .arg("-1")
+            .arg("-map_metadata:s:a:0")
+            .arg("1:s:0")
+            .arg(target)
+            .output();
+        let _ = fs::remove_file(&ffmetadata_path);
+
+        let output = output.map_err(|err| TagDocumentError::Ffmpeg(err.to_string()))?;
+        if !output.status.success() {
+            return Err(TagDocumentError::Ffmpeg(
+                String::from_utf8_lossy(&output.stderr).into_owned(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct FfprobeOutput {
+    #[serde(default)]
+    streams: Vec<FfprobeStream>,
+    #[serde(default)]
+    format: FfprobeFormat,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct FfprobeFormat {
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FfprobeStream {
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+}
@@ -0,0 +1,5 @@
+mod ffmpeg_audio_transcoder;
+
+pub use ffmpeg_audio_transcoder::FfmpegAudioTranscoder;
+pub use ffmpeg_audio_transcoder::FfmpegAudioTranscoderError;
+pub use ffmpeg_audio_transcoder::FfmpegOutput;
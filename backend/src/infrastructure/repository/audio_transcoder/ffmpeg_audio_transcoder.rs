@@ -0,0 +1,132 @@
+use std::{
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdout, Command, Stdio},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use thiserror::Error;
+
+use crate::domain::{
+    entity::transcode_profile::{AudioCodec, TranscodeProfile},
+    repository::AudioTranscoder,
+};
+
+pub struct FfmpegAudioTranscoder {
+    ffmpeg: PathBuf,
+}
+
+impl Default for FfmpegAudioTranscoder {
+    fn default() -> Self {
+        Self::new("ffmpeg")
+    }
+}
+
+impl FfmpegAudioTranscoder {
+    pub fn new<P: AsRef<Path>>(ffmpeg: P) -> Self {
+        Self {
+            ffmpeg: ffmpeg.as_ref().to_path_buf(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FfmpegAudioTranscoderError {
+    #[error("Failed to execute ffmpeg: {0}")]
+    Ffmpeg(#[from] io::Error),
+}
+
+impl AudioTranscoder for FfmpegAudioTranscoder {
+    type Error = FfmpegAudioTranscoderError;
+    type Output = FfmpegOutput;
+
+    fn transcode(
+        &self,
+        path: &Path,
+        profile: &TranscodeProfile,
+        offset: Duration,
+    ) -> Result<Self::Output, Self::Error> {
+        let mut command = Command::new(&self.ffmpeg);
+        command.args(["-v", "error", "-nostdin"]);
+        if !offset.is_zero() {
+            // Before the input so that ffmpeg seeks instead of decoding up to the offset
+            command.arg("-ss").arg(offset.as_secs_f64().to_string());
+        }
+        command.arg("-i").arg(path).args(["-map", "0:a:0", "-vn"]);
+        let (encoder, format) = match profile.codec {
+            AudioCodec::Opus => ("libopus", "ogg"),
+            AudioCodec::Mp3 => ("libmp3lame", "mp3"),
+            AudioCodec::Aac => ("aac", "adts"),
+        };
+        if profile.codec == AudioCodec::Opus {
+            // Opus does not support 44.1 kHz
+            command.args(["-ar", "48000"]);
+        }
+        command
+            .args(["-c:a", encoder])
+            .arg("-b:a")
+            .arg(format!("{}k", profile.bitrate))
+            .args(["-f", format, "pipe:1"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let mut child = command.spawn()?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let mut stderr = child.stderr.take().expect("stderr is piped");
+        // Drained concurrently so that ffmpeg never blocks on a full pipe
+        let stderr = thread::spawn(move || {
+            let mut message = String::new();
+            stderr.read_to_string(&mut message).ok();
+            message
+        });
+        Ok(FfmpegOutput {
+            child,
+            stdout,
+            stderr: Some(stderr),
+            finished: false,
+        })
+    }
+}
+
+/// Output of a running ffmpeg process. The process is killed if the output is dropped
+/// before the end.
+pub struct FfmpegOutput {
+    child: Child,
+    stdout: ChildStdout,
+    stderr: Option<JoinHandle<String>>,
+    finished: bool,
+}
+
+impl Read for FfmpegOutput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.stdout.read(buf)?;
+        if read == 0 && !buf.is_empty() && !self.finished {
+            self.finished = true;
+            let status = self.child.wait()?;
+            if !status.success() {
+                let message = self
+                    .stderr
+                    .take()
+                    .and_then(|stderr| stderr.join().ok())
+                    .unwrap_or_default();
+                return Err(io::Error::other(format!(
+                    "ffmpeg exited with {}: {}",
+                    status,
+                    message.trim()
+                )));
+            }
+        }
+        Ok(read)
+    }
+}
+
+impl Drop for FfmpegOutput {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.child.kill().ok();
+            self.child.wait().ok();
+        }
+    }
+}
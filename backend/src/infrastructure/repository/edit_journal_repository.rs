@@ -0,0 +1,4 @@
+mod filesystem_edit_journal_repository;
+
+pub use filesystem_edit_journal_repository::FilesystemEditJournalRepository;
+pub use filesystem_edit_journal_repository::FilesystemEditJournalRepositoryError;
@@ -0,0 +1,110 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::domain::{entity::edit_journal::EditJournal, repository::EditJournalRepository};
+
+/// Stores each journal as a JSON file named after its id.
+pub struct FilesystemEditJournalRepository {
+    path: PathBuf,
+}
+
+impl FilesystemEditJournalRepository {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// The file of a journal, `None` for ids that are not plain file names, which no journal
+    /// has.
+    fn journal_path(&self, id: &str) -> Option<PathBuf> {
+        let valid = !id.is_empty()
+            && !id.starts_with('.')
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        valid.then(|| self.path.join(format!("{id}.json")))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FilesystemEditJournalRepositoryError {
+    #[error("Failed to access journal: {0}")]
+    IO(#[from] io::Error),
+    #[error("Failed to serialize journal: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid journal id: {0}")]
+    InvalidId(String),
+}
+
+impl EditJournalRepository for FilesystemEditJournalRepository {
+    type Error = FilesystemEditJournalRepositoryError;
+
+    fn save(&self, journal: &EditJournal) -> Result<(), Self::Error> {
+        let path = self
+            .journal_path(journal.id())
+            .ok_or_else(|| FilesystemEditJournalRepositoryError::InvalidId(journal.id().clone()))?;
+        fs::create_dir_all(&self.path)?;
+        let json = serde_json::to_vec(journal)?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    fn find(&self, id: &str) -> Result<Option<EditJournal>, Self::Error> {
+        let Some(path) = self.journal_path(id) else {
+            return Ok(None);
+        };
+        match fs::read(path) {
+            Ok(json) => Ok(Some(serde_json::from_slice(&json)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<EditJournal>, Self::Error> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let mut journals = fs::read_dir(&self.path)?
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .map(|entry| {
+                let json = fs::read(entry.path())?;
+                Ok(serde_json::from_slice::<EditJournal>(&json)?)
+            })
+            .collect::<Result<Vec<_>, Self::Error>>()?;
+        journals.sort_by(|a, b| b.created_at().cmp(a.created_at()));
+        Ok(journals)
+    }
+
+    fn delete(&self, id: &str) -> Result<(), Self::Error> {
+        let Some(path) = self.journal_path(id) else {
+            return Ok(());
+        };
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_ids_that_are_not_file_names() {
+        let repository = FilesystemEditJournalRepository::new("journals");
+        assert!(repository
+            .journal_path("20260101T000000.000Z-0a1b2c3d")
+            .is_some());
+        assert!(repository.journal_path("../../etc/passwd").is_none());
+        assert!(repository.journal_path(".hidden").is_none());
+        assert!(repository.journal_path("").is_none());
+        assert!(repository.find("../secret").unwrap().is_none());
+    }
+}
@@ -0,0 +1,4 @@
+mod filesystem_fingerprint_repository;
+
+pub use filesystem_fingerprint_repository::FilesystemFingerprintRepository;
+pub use filesystem_fingerprint_repository::FilesystemFingerprintRepositoryError;
@@ -0,0 +1,58 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::domain::{entity::fingerprint::TrackFingerprint, repository::FingerprintRepository};
+
+/// Stores each fingerprint as a JSON file named after the hash of the audio path.
+pub struct FilesystemFingerprintRepository {
+    path: PathBuf,
+}
+
+impl FilesystemFingerprintRepository {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    fn fingerprint_path(&self, audio_path: &Path) -> PathBuf {
+        let hash = Sha256::digest(audio_path.as_os_str().as_encoded_bytes());
+        self.path.join(format!("{hash:x}.json"))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FilesystemFingerprintRepositoryError {
+    #[error("Failed to access fingerprint: {0}")]
+    IO(#[from] io::Error),
+    #[error("Failed to serialize fingerprint: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl FingerprintRepository for FilesystemFingerprintRepository {
+    type Error = FilesystemFingerprintRepositoryError;
+
+    fn save(&self, track_fingerprint: &TrackFingerprint) -> Result<(), Self::Error> {
+        fs::create_dir_all(&self.path)?;
+        let json = serde_json::to_vec(track_fingerprint)?;
+        fs::write(self.fingerprint_path(&track_fingerprint.path), json)?;
+        Ok(())
+    }
+
+    fn find(&self, path: &Path) -> Result<Option<TrackFingerprint>, Self::Error> {
+        match fs::read(self.fingerprint_path(path)) {
+            Ok(json) => {
+                let track_fingerprint: TrackFingerprint = serde_json::from_slice(&json)?;
+                // Guards against hash collisions
+                Ok((track_fingerprint.path == path).then_some(track_fingerprint))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
@@ -0,0 +1,4 @@
+mod filesystem_library_file_repository;
+
+pub use filesystem_library_file_repository::FilesystemLibraryFileRepository;
+pub use filesystem_library_file_repository::FilesystemLibraryFileRepositoryError;
@@ -0,0 +1,86 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::domain::repository::LibraryFileRepository;
+
+#[derive(Default)]
+pub struct FilesystemLibraryFileRepository;
+
+#[derive(Error, Debug)]
+pub enum FilesystemLibraryFileRepositoryError {
+    #[error("Target already exists: {0}")]
+    TargetExists(String),
+    #[error("Failed to access file: {0}")]
+    IO(#[from] io::Error),
+}
+
+impl FilesystemLibraryFileRepository {
+    fn prepare_target(target: &Path) -> Result<(), FilesystemLibraryFileRepositoryError> {
+        if target.exists() {
+            return Err(FilesystemLibraryFileRepositoryError::TargetExists(
+                target.display().to_string(),
+            ));
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(())
+    }
+}
+
+impl LibraryFileRepository for FilesystemLibraryFileRepository {
+    type Error = FilesystemLibraryFileRepositoryError;
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn size(&self, path: &Path) -> Result<u64, Self::Error> {
+        Ok(fs::metadata(path)?.len())
+    }
+
+    fn modified(&self, path: &Path) -> Result<SystemTime, Self::Error> {
+        Ok(fs::metadata(path)?.modified()?)
+    }
+
+    fn hash(&self, path: &Path) -> Result<String, Self::Error> {
+        let mut hasher = Sha256::new();
+        io::copy(&mut File::open(path)?, &mut hasher)?;
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    fn files(&self, directory: &Path) -> Result<Vec<PathBuf>, Self::Error> {
+        let mut files = fs::read_dir(directory)?
+            .flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+            .map(|entry| entry.path())
+            .collect::<Vec<_>>();
+        files.sort();
+        Ok(files)
+    }
+
+    fn copy(&self, source: &Path, target: &Path) -> Result<(), Self::Error> {
+        Self::prepare_target(target)?;
+        fs::copy(source, target)?;
+        Ok(())
+    }
+
+    fn rename(&self, source: &Path, target: &Path) -> Result<(), Self::Error> {
+        Self::prepare_target(target)?;
+        match fs::rename(source, target) {
+            Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+                fs::copy(source, target)?;
+                fs::remove_file(source)?;
+                Ok(())
+            }
+            result => Ok(result?),
+        }
+    }
+}
@@ -0,0 +1,4 @@
+mod sqlite_library_repository;
+
+pub use sqlite_library_repository::SqliteLibraryRepository;
+pub use sqlite_library_repository::SqliteLibraryRepositoryError;
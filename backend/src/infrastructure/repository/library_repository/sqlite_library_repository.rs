@@ -0,0 +1,417 @@
+use std::{collections::HashMap, path::Path, sync::Mutex};
+
+use rusqlite::{functions::FunctionFlags, params, params_from_iter, types::Value, Connection};
+use thiserror::Error;
+
+use crate::{
+    application::service::{fold, AudioQuery, SqlValue, FOLD_FUNCTION},
+    domain::{
+        entity::audio::{cover::Cover, Audio},
+        repository::LibraryRepository,
+    },
+    infrastructure::repository::sqlite,
+};
+
+/// Searchable fields have their own columns, the complete audio is kept as JSON next to
+/// them. Covers are stored once per distinct image.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS covers (
+        id TEXT PRIMARY KEY,
+        data BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS tracks (
+        id TEXT PRIMARY KEY,
+        path TEXT NOT NULL,
+        title TEXT NOT NULL,
+        artist TEXT NOT NULL,
+        album_title TEXT NOT NULL,
+        album_artist TEXT NOT NULL,
+        genre TEXT NOT NULL,
+        year INTEGER,
+        track_number INTEGER,
+        disc_number INTEGER,
+        compilation INTEGER NOT NULL,
+        duration REAL,
+        bitrate INTEGER,
+        extension TEXT NOT NULL,
+        cover_id TEXT REFERENCES covers (id),
+        audio TEXT NOT NULL
+    );
+";
+
+/// Stores the library in a SQLite database.
+pub struct SqliteLibraryRepository {
+    connection: Mutex<Connection>,
+}
+
+#[derive(Error, Debug)]
+pub enum SqliteLibraryRepositoryError {
+    #[error("Failed to access library database: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Failed to serialize audio: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl SqliteLibraryRepository {
+    /// Opens the database at `path`, creating it if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, SqliteLibraryRepositoryError> {
+        let connection = sqlite::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        connection.create_scalar_function(
+            FOLD_FUNCTION,
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |context| Ok(fold(&context.get::<String>(0)?)),
+        )?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Runs the query against the stored library, see [`AudioQuery::to_sql`].
+    pub fn query(&self, query: &AudioQuery) -> Result<Vec<Audio>, SqliteLibraryRepositoryError> {
+        let sql = query.to_sql();
+        let params = sql.params.iter().map(|param| match param {
+            SqlValue::Text(text) => Value::Text(text.clone()),
+            SqlValue::Integer(integer) => Value::Integer(*integer),
+            SqlValue::Real(real) => Value::Real(*real),
+        });
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        let mut select = connection.prepare(
+            &sql.select("audio, (SELECT data FROM covers WHERE covers.id = tracks.cover_id)"),
+        )?;
+        let mut rows = select.query(params_from_iter(params))?;
+        let mut audios = Vec::new();
+        while let Some(row) = rows.next()? {
+            let audio: Audio = serde_json::from_str(&row.get::<_, String>(0)?)?;
+            audios.push(match row.get::<_, Option<Vec<u8>>>(1)? {
+                Some(cover) => match Cover::try_from(cover) {
+                    Ok(cover) => audio.with_album_cover(cover),
+                    Err(_) => audio,
+                },
+                None => audio,
+            });
+        }
+        Ok(audios)
+    }
+}
+
+impl LibraryRepository for SqliteLibraryRepository {
+    type Error = SqliteLibraryRepositoryError;
+
+    fn replace(&self, audios: &[Audio]) -> Result<(), Self::Error> {
+        let mut connection = self.connection.lock().expect("connection lock poisoned");
+        let transaction = connection.transaction()?;
+        transaction.execute_batch("DELETE FROM tracks; DELETE FROM covers;")?;
+        {
+            let mut insert_cover =
+                transaction.prepare("INSERT OR IGNORE INTO covers (id, data) VALUES (?1, ?2)")?;
+            let mut insert_track = transaction.prepare(
+                "INSERT OR REPLACE INTO tracks (
+                    id, path, title, artist, album_title, album_artist, genre, year,
+                    track_number, disc_number, compilation, duration, bitrate, extension,
+                    cover_id, audio
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+            )?;
+            for audio in audios {
+                // Paths that are not valid UTF-8 cannot be serialized
+                let json = match serde_json::to_string(audio) {
+                    Ok(json) => json,
+                    Err(err) => {
+                        log::error!("Failed to store {}: {}", audio.path().display(), err);
+                        continue;
+                    }
+                };
+                let cover_id = if audio.album_cover().is_default() {
+                    None
+                } else {
+                    let cover = audio.album_cover();
+                    insert_cover.execute(params![cover.id(), cover.data()])?;
+                    Some(cover.id())
+                };
+                let extension = audio
+                    .path()
+                    .extension()
+                    .map(|extension| extension.to_string_lossy().to_lowercase())
+                    .unwrap_or_default();
+                insert_track.execute(params![
+                    audio.id(),
+                    audio.path().to_string_lossy(),
+                    audio.title().name(),
+                    audio.artist().name(),
+                    audio.album_title().name(),
+                    audio.album_artist().name(),
+                    audio.genre().name(),
+                    audio.year().map(|year| year.0),
+                    audio.track_number(),
+                    audio.disc_number(),
+                    audio.compilation(),
+                    audio.duration().map(|duration| duration.as_secs_f64()),
+                    audio.bitrate(),
+                    extension,
+                    cover_id,
+                    json,
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Vec<Audio>, Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        let mut covers = HashMap::new();
+        let mut select_covers = connection.prepare("SELECT id, data FROM covers")?;
+        let mut rows = select_covers.query([])?;
+        while let Some(row) = rows.next()? {
+            // Stored covers are never empty
+            if let Ok(cover) = Cover::try_from(row.get::<_, Vec<u8>>(1)?) {
+                covers.insert(row.get::<_, String>(0)?, cover);
+            }
+        }
+
+        let mut select_tracks =
+            connection.prepare("SELECT audio, cover_id FROM tracks ORDER BY path")?;
+        let mut rows = select_tracks.query([])?;
+        let mut audios = Vec::new();
+        while let Some(row) = rows.next()? {
+            let audio: Audio = serde_json::from_str(&row.get::<_, String>(0)?)?;
+            let cover = row
+                .get::<_, Option<String>>(1)?
+                .and_then(|cover_id| covers.get(&cover_id));
+            audios.push(match cover {
+                Some(cover) => audio.with_album_cover(cover.clone()),
+                None => audio,
+            });
+        }
+        Ok(audios)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use crate::domain::entity::audio::{
+        artist::Artist, genre::Genre, title::Title, year::Year, AudioBuilder,
+    };
+
+    use super::*;
+
+    fn audio(path: &str, cover: Cover) -> Audio {
+        AudioBuilder::default()
+            .title(Title::default())
+            .artist(Artist::default())
+            .year(None)
+            .album_title(Title::default())
+            .album_artist(Artist::default())
+            .album_cover(cover)
+            .genre(Genre::default())
+            .track_number(None)
+            .disc_number(None)
+            .compilation(false)
+            .path(PathBuf::from(path))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn load_shares_cover_between_tracks() {
+        let repository = SqliteLibraryRepository::open(":memory:").unwrap();
+        let cover = Cover::try_from(b"\x89PNG cover".to_vec()).unwrap();
+        repository
+            .replace(&[
+                audio("/music/1.flac", cover.clone()),
+                audio("/music/2.flac", cover.clone()),
+                audio("/music/3.flac", Cover::default()),
+            ])
+            .unwrap();
+
+        let audios = repository.load().unwrap();
+
+        assert_eq!(audios.len(), 3);
+        assert_eq!(audios[0].album_cover(), &cover);
+        assert_eq!(
+            audios[0].album_cover().data().as_ptr(),
+            audios[1].album_cover().data().as_ptr()
+        );
+        assert!(audios[2].album_cover().is_default());
+    }
+
+    /// A track with the given metadata, empty names standing for unknown ones.
+    fn track(
+        path: &str,
+        title: &str,
+        artist: &str,
+        genre: &str,
+        year: Option<u16>,
+        duration: Option<u64>,
+    ) -> AudioBuilder {
+        let mut builder = AudioBuilder::default();
+        builder
+            .title(title.parse::<Title>().unwrap_or_default())
+            .artist(artist.parse::<Artist>().unwrap_or_default())
+            .year(year.map(Year::new))
+            .album_title(Title::default())
+            .album_artist(Artist::default())
+            .album_cover(Cover::default())
+            .genre(genre.parse::<Genre>().unwrap_or_default())
+            .track_number(None)
+            .disc_number(None)
+            .compilation(false)
+            .path(PathBuf::from(path))
+            .duration(duration.map(Duration::from_secs));
+        builder
+    }
+
+    fn library() -> Vec<Audio> {
+        let cover = Cover::try_from(b"\x89PNG cover".to_vec()).unwrap();
+        vec![
+            track(
+                "/m/a.flac",
+                "So What",
+                "Miles Davis",
+                "Jazz",
+                Some(1959),
+                Some(562),
+            )
+            .bitrate(Some(900))
+            .album_cover(cover.clone())
+            .build()
+            .unwrap(),
+            track(
+                "/m/b.mp3",
+                "Blue in Green",
+                "Miles Davis",
+                "Jazz",
+                Some(1959),
+                Some(337),
+            )
+            .bitrate(Some(320))
+            .track_number(Some(3))
+            .build()
+            .unwrap(),
+            track(
+                "/m/c.flac",
+                "Café Society",
+                "Édith Piaf",
+                "Chanson",
+                Some(1995),
+                None,
+            )
+            .lyrics(Some("La vie en rose".to_owned()))
+            .build()
+            .unwrap(),
+            track(
+                "/m/d.ogg",
+                "Smells",
+                "Nirvana",
+                "Grunge",
+                Some(1991),
+                Some(301),
+            )
+            .compilation(true)
+            .album_cover(cover)
+            .build()
+            .unwrap(),
+            track("/m/e.mp3", "", "", "", None, Some(29))
+                .build()
+                .unwrap(),
+            track(
+                "/m/F.MP3",
+                "Untitled",
+                "Nirvana",
+                "Grunge",
+                Some(1999),
+                Some(200),
+            )
+            .bitrate(Some(128))
+            .build()
+            .unwrap(),
+        ]
+    }
+
+    #[test]
+    fn queries_match_in_memory_execution() {
+        let audios = library();
+        let repository = SqliteLibraryRepository::open(":memory:").unwrap();
+        repository.replace(&audios).unwrap();
+
+        let paths = |audios: Vec<&Audio>| {
+            audios
+                .into_iter()
+                .map(|audio| audio.path().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        let cases = [
+            (
+                "",
+                vec![
+                    "/m/F.MP3",
+                    "/m/a.flac",
+                    "/m/b.mp3",
+                    "/m/c.flac",
+                    "/m/d.ogg",
+                    "/m/e.mp3",
+                ],
+            ),
+            (
+                "format:flac year:1990..1999 missing:cover",
+                vec!["/m/c.flac"],
+            ),
+            ("artist:edith", vec!["/m/c.flac"]),
+            ("cafe", vec!["/m/c.flac"]),
+            (
+                "artist=\"miles davis\" sort:-duration",
+                vec!["/m/a.flac", "/m/b.mp3"],
+            ),
+            ("title:s* sort:title", vec!["/m/d.ogg", "/m/a.flac"]),
+            (
+                "year>1990 or bitrate:>=900 sort:-year",
+                vec!["/m/F.MP3", "/m/c.flac", "/m/d.ogg", "/m/a.flac"],
+            ),
+            ("not has:year", vec!["/m/e.mp3"]),
+            (
+                "-year:<1995 sort:year",
+                vec!["/m/c.flac", "/m/F.MP3", "/m/e.mp3"],
+            ),
+            (
+                "missing:title missing:genre missing:artist",
+                vec!["/m/e.mp3"],
+            ),
+            (
+                "has:track or compilation:true",
+                vec!["/m/b.mp3", "/m/d.ogg"],
+            ),
+            ("has:cover sort:-compilation", vec!["/m/d.ogg", "/m/a.flac"]),
+            (
+                "lyrics:rose or missing:lyrics sort:-lyrics limit:2",
+                vec!["/m/c.flac", "/m/F.MP3"],
+            ),
+            (
+                "duration:<5:00 sort:duration sort:path",
+                vec!["/m/e.mp3", "/m/F.MP3"],
+            ),
+            (
+                "sort:bitrate limit:4",
+                vec!["/m/F.MP3", "/m/b.mp3", "/m/a.flac", "/m/c.flac"],
+            ),
+            ("genre=grunge sort:-title", vec!["/m/F.MP3", "/m/d.ogg"]),
+            ("path:/m/f", vec!["/m/F.MP3"]),
+        ];
+        for (text, expected) in cases {
+            let query = AudioQuery::parse(text).unwrap();
+            let in_memory = paths(query.execute(&audios));
+            let stored = repository.query(&query).unwrap();
+            assert_eq!(in_memory, expected, "in memory: {text}");
+            assert_eq!(paths(stored.iter().collect()), expected, "stored: {text}");
+        }
+        // Covers are read back with the audios
+        let covered = repository
+            .query(&AudioQuery::parse("has:cover").unwrap())
+            .unwrap();
+        assert!(covered
+            .iter()
+            .all(|audio| !audio.album_cover().is_default()));
+    }
+}
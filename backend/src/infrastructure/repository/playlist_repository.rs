@@ -0,0 +1,4 @@
+mod filesystem_playlist_repository;
+
+pub use filesystem_playlist_repository::FilesystemPlaylistRepository;
+pub use filesystem_playlist_repository::FilesystemPlaylistRepositoryError;
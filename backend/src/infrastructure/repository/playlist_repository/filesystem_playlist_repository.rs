@@ -0,0 +1,86 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+use walkdir::WalkDir;
+
+use crate::domain::{
+    entity::playlist::{Playlist, PlaylistError, PlaylistFormat},
+    repository::PlaylistRepository,
+};
+
+/// Finds the playlists anywhere under the library roots.
+pub struct FilesystemPlaylistRepository {
+    paths: Vec<PathBuf>,
+}
+
+impl FilesystemPlaylistRepository {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self::with_paths([path])
+    }
+
+    /// Creates a repository that finds playlists in each of the directories.
+    pub fn with_paths<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Self {
+        Self {
+            paths: paths
+                .into_iter()
+                .map(|path| path.as_ref().to_path_buf())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FilesystemPlaylistRepositoryError {
+    #[error("Not a playlist file: {0}")]
+    UnknownFormat(String),
+    #[error("Failed to access playlist: {0}")]
+    IO(#[from] io::Error),
+    #[error("Failed to parse playlist: {0}")]
+    Playlist(#[from] PlaylistError),
+}
+
+fn format_of(path: &Path) -> Result<PlaylistFormat, FilesystemPlaylistRepositoryError> {
+    PlaylistFormat::from_path(path)
+        .ok_or_else(|| FilesystemPlaylistRepositoryError::UnknownFormat(path.display().to_string()))
+}
+
+impl PlaylistRepository for FilesystemPlaylistRepository {
+    type Error = FilesystemPlaylistRepositoryError;
+
+    fn find(&self) -> Result<Vec<PathBuf>, Self::Error> {
+        let mut paths = self
+            .paths
+            .iter()
+            .flat_map(|path| WalkDir::new(path).into_iter().flatten())
+            .filter(|entry| {
+                entry.file_type().is_file() && PlaylistFormat::from_path(entry.path()).is_some()
+            })
+            .map(|entry| entry.into_path())
+            .collect::<Vec<_>>();
+        paths.sort();
+        // Roots may be nested in one another
+        paths.dedup();
+        Ok(paths)
+    }
+
+    fn read(&self, path: &Path) -> Result<Playlist, Self::Error> {
+        let format = format_of(path)?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(Playlist::parse(&fs::read(path)?, format, &name)?)
+    }
+
+    fn write(&self, path: &Path, playlist: &Playlist) -> Result<(), Self::Error> {
+        let format = format_of(path)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, playlist.render(format))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,4 @@
+mod sqlite_scrobble_repository;
+
+pub use sqlite_scrobble_repository::SqliteScrobbleRepository;
+pub use sqlite_scrobble_repository::SqliteScrobbleRepositoryError;
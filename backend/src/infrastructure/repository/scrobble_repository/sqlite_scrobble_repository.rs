@@ -0,0 +1,253 @@
+use std::{collections::BTreeMap, path::Path, sync::Mutex};
+
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::{params, Connection, Row};
+use thiserror::Error;
+
+use crate::{
+    domain::{
+        entity::scrobble::{Listen, QueuedListen, ScrobblerAccount, ScrobblerKind},
+        repository::ScrobbleRepository,
+    },
+    infrastructure::repository::sqlite,
+};
+
+/// Dates are stored as milliseconds since the Unix epoch, and accounts and listens as JSON.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS scrobble_accounts (
+        user_id TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        account TEXT NOT NULL,
+        paused_reason TEXT,
+        PRIMARY KEY (user_id, kind)
+    );
+    CREATE TABLE IF NOT EXISTS scrobble_queue (
+        id INTEGER PRIMARY KEY,
+        user_id TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        listen TEXT NOT NULL,
+        listened_at INTEGER NOT NULL,
+        attempts INTEGER NOT NULL DEFAULT 0,
+        next_attempt_at INTEGER NOT NULL,
+        last_error TEXT
+    );
+    CREATE INDEX IF NOT EXISTS scrobble_queue_next_attempt_at
+        ON scrobble_queue (next_attempt_at);
+";
+
+const QUEUE_COLUMNS: &str = "id, user_id, kind, listen, attempts, next_attempt_at, last_error";
+
+/// Stores scrobbling accounts and the queue of listens in a SQLite database, which may be
+/// the one of the library, so that listens survive restarts and outages.
+pub struct SqliteScrobbleRepository {
+    connection: Mutex<Connection>,
+}
+
+#[derive(Error, Debug)]
+pub enum SqliteScrobbleRepositoryError {
+    #[error("Failed to access scrobble database: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Failed to serialize scrobble data: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid stored scrobbler kind: {0}")]
+    Kind(String),
+    #[error("Invalid stored date: {0}")]
+    Date(i64),
+}
+
+impl SqliteScrobbleRepository {
+    /// Opens the database at `path`, creating it if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, SqliteScrobbleRepositoryError> {
+        let connection = sqlite::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        migrate(&connection)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn select_queue(
+        &self,
+        condition: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<QueuedListen>, SqliteScrobbleRepositoryError> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        let mut select = connection.prepare(&format!(
+            "SELECT {QUEUE_COLUMNS} FROM scrobble_queue {condition}"
+        ))?;
+        let mut rows = select.query(params)?;
+        let mut listens = Vec::new();
+        while let Some(row) = rows.next()? {
+            listens.push(queued_listen(row)?);
+        }
+        Ok(listens)
+    }
+}
+
+impl ScrobbleRepository for SqliteScrobbleRepository {
+    type Error = SqliteScrobbleRepositoryError;
+
+    fn save_account(&self, user_id: &str, account: &ScrobblerAccount) -> Result<(), Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        connection.execute(
+            "INSERT OR REPLACE INTO scrobble_accounts (user_id, kind, account) VALUES (?1, ?2, ?3)",
+            params![
+                user_id,
+                account.kind().as_str(),
+                serde_json::to_string(account)?
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn accounts(&self, user_id: &str) -> Result<Vec<ScrobblerAccount>, Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        let mut select = connection
+            .prepare("SELECT account FROM scrobble_accounts WHERE user_id = ?1 ORDER BY kind")?;
+        let mut rows = select.query([user_id])?;
+        let mut accounts = Vec::new();
+        while let Some(row) = rows.next()? {
+            accounts.push(serde_json::from_str(&row.get::<_, String>(0)?)?);
+        }
+        Ok(accounts)
+    }
+
+    fn pause_account(
+        &self,
+        user_id: &str,
+        kind: ScrobblerKind,
+        reason: &str,
+    ) -> Result<(), Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        connection.execute(
+            "UPDATE scrobble_accounts SET paused_reason = ?3 WHERE user_id = ?1 AND kind = ?2",
+            params![user_id, kind.as_str(), reason],
+        )?;
+        Ok(())
+    }
+
+    fn paused_accounts(
+        &self,
+        user_id: &str,
+    ) -> Result<BTreeMap<ScrobblerKind, String>, Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        let mut select = connection.prepare(
+            "SELECT kind, paused_reason FROM scrobble_accounts
+            WHERE user_id = ?1 AND paused_reason IS NOT NULL",
+        )?;
+        let mut rows = select.query([user_id])?;
+        let mut paused = BTreeMap::new();
+        while let Some(row) = rows.next()? {
+            paused.insert(kind(row.get(0)?)?, row.get(1)?);
+        }
+        Ok(paused)
+    }
+
+    fn delete_account(&self, user_id: &str, kind: ScrobblerKind) -> Result<(), Self::Error> {
+        let mut connection = self.connection.lock().expect("connection lock poisoned");
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "DELETE FROM scrobble_accounts WHERE user_id = ?1 AND kind = ?2",
+            params![user_id, kind.as_str()],
+        )?;
+        transaction.execute(
+            "DELETE FROM scrobble_queue WHERE user_id = ?1 AND kind = ?2",
+            params![user_id, kind.as_str()],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn enqueue(
+        &self,
+        user_id: &str,
+        kind: ScrobblerKind,
+        listen: &Listen,
+    ) -> Result<(), Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        connection.execute(
+            "INSERT INTO scrobble_queue (user_id, kind, listen, listened_at, next_attempt_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                user_id,
+                kind.as_str(),
+                serde_json::to_string(listen)?,
+                listen.listened_at.timestamp_millis(),
+                Utc::now().timestamp_millis(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn due(&self, now: DateTime<Utc>, limit: usize) -> Result<Vec<QueuedListen>, Self::Error> {
+        self.select_queue(
+            "WHERE next_attempt_at <= ?1 ORDER BY listened_at, id LIMIT ?2",
+            params![
+                now.timestamp_millis(),
+                i64::try_from(limit).unwrap_or(i64::MAX)
+            ],
+        )
+    }
+
+    fn queued(&self, user_id: &str) -> Result<Vec<QueuedListen>, Self::Error> {
+        self.select_queue("WHERE user_id = ?1 ORDER BY listened_at, id", [user_id])
+    }
+
+    fn reschedule(&self, listen: &QueuedListen) -> Result<(), Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        connection.execute(
+            "UPDATE scrobble_queue SET attempts = ?2, next_attempt_at = ?3, last_error = ?4
+            WHERE id = ?1",
+            params![
+                listen.id,
+                listen.attempts,
+                listen.next_attempt_at.timestamp_millis(),
+                listen.last_error,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn dequeue(&self, id: i64) -> Result<(), Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        connection.execute("DELETE FROM scrobble_queue WHERE id = ?1", [id])?;
+        Ok(())
+    }
+}
+
+/// Reads a queued listen selected as [`QUEUE_COLUMNS`].
+fn queued_listen(row: &Row) -> Result<QueuedListen, SqliteScrobbleRepositoryError> {
+    let next_attempt_at = row.get(5)?;
+    Ok(QueuedListen {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        kind: kind(row.get(2)?)?,
+        listen: serde_json::from_str(&row.get::<_, String>(3)?)?,
+        attempts: row.get(4)?,
+        next_attempt_at: Utc
+            .timestamp_millis_opt(next_attempt_at)
+            .single()
+            .ok_or(SqliteScrobbleRepositoryError::Date(next_attempt_at))?,
+        last_error: row.get(6)?,
+    })
+}
+
+fn kind(kind: String) -> Result<ScrobblerKind, SqliteScrobbleRepositoryError> {
+    ScrobblerKind::ALL
+        .into_iter()
+        .find(|candidate| candidate.as_str() == kind)
+        .ok_or(SqliteScrobbleRepositoryError::Kind(kind))
+}
+
+/// Adds the columns missing from the tables of older databases.
+fn migrate(connection: &Connection) -> rusqlite::Result<()> {
+    let has_paused_reason = connection
+        .prepare(
+            "SELECT 1 FROM pragma_table_info('scrobble_accounts') WHERE name = 'paused_reason'",
+        )?
+        .exists([])?;
+    if !has_paused_reason {
+        connection.execute_batch("ALTER TABLE scrobble_accounts ADD COLUMN paused_reason TEXT")?;
+    }
+    Ok(())
+}
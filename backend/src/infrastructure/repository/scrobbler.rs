@@ -0,0 +1,3 @@
+mod http_scrobbler;
+
+pub use http_scrobbler::HttpScrobbler;
@@ -0,0 +1,574 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use md5::{Digest, Md5};
+use serde_json::{json, Map, Value};
+use ureq::{Agent, AgentBuilder, Response};
+
+use crate::domain::{
+    entity::scrobble::{Listen, ScrobbleError, ScrobblerAccount},
+    repository::Scrobbler,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(15);
+const USER_AGENT: &str = concat!("earr/", env!("CARGO_PKG_VERSION"));
+
+/// Sends listens over HTTP with the ListenBrainz or the Last.fm protocol, depending on the
+/// account. Services are reached at the URL of the account, which may be a local server.
+pub struct HttpScrobbler {
+    agent: Agent,
+}
+
+impl Default for HttpScrobbler {
+    fn default() -> Self {
+        Self {
+            agent: AgentBuilder::new()
+                .timeout(TIMEOUT)
+                .user_agent(USER_AGENT)
+                .build(),
+        }
+    }
+}
+
+impl HttpScrobbler {
+    fn listenbrainz(
+        &self,
+        url: &str,
+        token: &str,
+        listen_type: &str,
+        payload: Vec<Value>,
+    ) -> Result<(), ScrobbleError> {
+        let response = self
+            .agent
+            .post(&format!("{}/1/submit-listens", url.trim_end_matches('/')))
+            .set("Authorization", &format!("Token {token}"))
+            .send_json(json!({ "listen_type": listen_type, "payload": payload }));
+        listenbrainz_response(response).map(|_| ())
+    }
+
+    /// Calls a signed method of the Last.fm API and returns its JSON response.
+    fn last_fm(
+        &self,
+        url: &str,
+        api_secret: &str,
+        mut params: BTreeMap<String, String>,
+    ) -> Result<Value, ScrobbleError> {
+        // The signature covers every parameter but the format, ordered by name
+        let signature = params
+            .iter()
+            .map(|(name, value)| format!("{name}{value}"))
+            .collect::<String>();
+        let signature = Md5::digest(format!("{signature}{api_secret}"));
+        params.insert(
+            "api_sig".to_string(),
+            signature.iter().map(|byte| format!("{byte:02x}")).collect(),
+        );
+        params.insert("format".to_string(), "json".to_string());
+        let form = params
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+        let response = match self.agent.post(url).send_form(&form) {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(err) => return Err(ScrobbleError::Unavailable(err.to_string())),
+        };
+        let status = response.status();
+        let body = response
+            .into_json::<Value>()
+            .map_err(|err| ScrobbleError::Unavailable(err.to_string()))?;
+        if let Some(code) = body.get("error").and_then(Value::as_u64) {
+            let message = body
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            return Err(match code {
+                // Authentication failed, invalid API key, session or signature, suspended key
+                4 | 9 | 10 | 13 | 14 | 26 => ScrobbleError::Unauthorized(message),
+                // Operation failed, service offline or temporarily unavailable, rate limit
+                8 | 11 | 16 | 29 => ScrobbleError::Unavailable(message),
+                _ => ScrobbleError::Rejected(message),
+            });
+        }
+        if status >= 500 {
+            return Err(ScrobbleError::Unavailable(format!("HTTP status {status}")));
+        }
+        Ok(body)
+    }
+}
+
+impl Scrobbler for HttpScrobbler {
+    fn validate(&self, account: &ScrobblerAccount) -> Result<(), ScrobbleError> {
+        match account {
+            ScrobblerAccount::ListenBrainz { url, token } => {
+                let response = self
+                    .agent
+                    .get(&format!("{}/1/validate-token", url.trim_end_matches('/')))
+                    .set("Authorization", &format!("Token {token}"))
+                    .call();
+                let body = listenbrainz_response(response)?
+                    .into_json::<Value>()
+                    .map_err(|err| ScrobbleError::Unavailable(err.to_string()))?;
+                if body.get("valid").and_then(Value::as_bool) == Some(true) {
+                    Ok(())
+                } else {
+                    Err(ScrobbleError::Unauthorized("Invalid token".to_string()))
+                }
+            }
+            // A session key is only obtained with valid credentials
+            ScrobblerAccount::LastFm { .. } => Ok(()),
+        }
+    }
+
+    fn now_playing(
+        &self,
+        account: &ScrobblerAccount,
+        listen: &Listen,
+    ) -> Result<(), ScrobbleError> {
+        match account {
+            ScrobblerAccount::ListenBrainz { url, token } => self.listenbrainz(
+                url,
+                token,
+                "playing_now",
+                vec![json!({ "track_metadata": track_metadata(listen) })],
+            ),
+            ScrobblerAccount::LastFm {
+                url,
+                api_key,
+                api_secret,
+                session_key,
+            } => {
+                let mut params = BTreeMap::new();
+                params.insert("method".to_string(), "track.updateNowPlaying".to_string());
+                params.insert("api_key".to_string(), api_key.clone());
+                params.insert("sk".to_string(), session_key.clone());
+                params.extend(last_fm_track(listen, None));
+                self.last_fm(url, api_secret, params).map(|_| ())
+            }
+        }
+    }
+
+    fn submit(&self, account: &ScrobblerAccount, listens: &[Listen]) -> Result<(), ScrobbleError> {
+        if listens.is_empty() {
+            return Ok(());
+        }
+        match account {
+            ScrobblerAccount::ListenBrainz { url, token } => {
+                let listen_type = if listens.len() == 1 {
+                    "single"
+                } else {
+                    "import"
+                };
+                let payload = listens
+                    .iter()
+                    .map(|listen| {
+                        json!({
+                            "listened_at": listen.listened_at.timestamp(),
+                            "track_metadata": track_metadata(listen),
+                        })
+                    })
+                    .collect();
+                self.listenbrainz(url, token, listen_type, payload)
+            }
+            ScrobblerAccount::LastFm {
+                url,
+                api_key,
+                api_secret,
+                session_key,
+            } => {
+                let mut params = BTreeMap::new();
+                params.insert("method".to_string(), "track.scrobble".to_string());
+                params.insert("api_key".to_string(), api_key.clone());
+                params.insert("sk".to_string(), session_key.clone());
+                for (index, listen) in listens.iter().enumerate() {
+                    params.extend(last_fm_track(listen, Some(index)));
+                    params.insert(
+                        format!("timestamp[{index}]"),
+                        listen.listened_at.timestamp().to_string(),
+                    );
+                }
+                let body = self.last_fm(url, api_secret, params)?;
+                let ignored = body
+                    .pointer("/scrobbles/@attr/ignored")
+                    .and_then(|ignored| match ignored {
+                        Value::Number(ignored) => ignored.as_u64(),
+                        Value::String(ignored) => ignored.parse().ok(),
+                        _ => None,
+                    })
+                    .unwrap_or_default();
+                if ignored > 0 {
+                    log::warn!("Scrobbling service ignored {} listens", ignored);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn last_fm_session(
+        &self,
+        url: &str,
+        api_key: &str,
+        api_secret: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<String, ScrobbleError> {
+        let params = BTreeMap::from([
+            ("method".to_string(), "auth.getMobileSession".to_string()),
+            ("api_key".to_string(), api_key.to_string()),
+            ("username".to_string(), username.to_string()),
+            ("password".to_string(), password.to_string()),
+        ]);
+        self.last_fm(url, api_secret, params)?
+            .pointer("/session/key")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| ScrobbleError::Unavailable("No session key in response".to_string()))
+    }
+}
+
+/// Classifies the failures of the ListenBrainz API by status.
+fn listenbrainz_response(
+    response: Result<Response, ureq::Error>,
+) -> Result<Response, ScrobbleError> {
+    match response {
+        Ok(response) => Ok(response),
+        Err(ureq::Error::Status(status, response)) => {
+            let message = response
+                .into_json::<Value>()
+                .ok()
+                .and_then(|body| {
+                    body.get("error")
+                        .and_then(Value::as_str)
+                        .map(str::to_string)
+                })
+                .unwrap_or_else(|| format!("HTTP status {status}"));
+            Err(match status {
+                401 | 403 => ScrobbleError::Unauthorized(message),
+                429 | 500.. => ScrobbleError::Unavailable(message),
+                _ => ScrobbleError::Rejected(message),
+            })
+        }
+        Err(err) => Err(ScrobbleError::Unavailable(err.to_string())),
+    }
+}
+
+fn track_metadata(listen: &Listen) -> Value {
+    let mut additional_info = Map::new();
+    additional_info.insert("submission_client".to_string(), json!("earr"));
+    additional_info.insert(
+        "submission_client_version".to_string(),
+        json!(env!("CARGO_PKG_VERSION")),
+    );
+    if let Some(duration) = listen.duration {
+        additional_info.insert("duration_ms".to_string(), json!(duration.as_millis()));
+    }
+    if let Some(track_number) = listen.track_number {
+        additional_info.insert("tracknumber".to_string(), json!(track_number));
+    }
+    if let Some(mbid) = &listen.recording_mbid {
+        additional_info.insert("recording_mbid".to_string(), json!(mbid));
+    }
+    if let Some(album_artist) = &listen.album_artist {
+        additional_info.insert("albumartist".to_string(), json!(album_artist));
+    }
+    let mut metadata = Map::new();
+    metadata.insert("artist_name".to_string(), json!(listen.artist));
+    metadata.insert("track_name".to_string(), json!(listen.title));
+    if let Some(album) = &listen.album {
+        metadata.insert("release_name".to_string(), json!(album));
+    }
+    metadata.insert(
+        "additional_info".to_string(),
+        Value::Object(additional_info),
+    );
+    Value::Object(metadata)
+}
+
+/// Parameters of a track, suffixed with `[index]` when submitting several.
+fn last_fm_track(listen: &Listen, index: Option<usize>) -> BTreeMap<String, String> {
+    let name = |name: &str| match index {
+        Some(index) => format!("{name}[{index}]"),
+        None => name.to_string(),
+    };
+    let mut params = BTreeMap::new();
+    params.insert(name("artist"), listen.artist.clone());
+    params.insert(name("track"), listen.title.clone());
+    if let Some(album) = &listen.album {
+        params.insert(name("album"), album.clone());
+    }
+    if let Some(album_artist) = &listen.album_artist {
+        params.insert(name("albumArtist"), album_artist.clone());
+    }
+    if let Some(track_number) = listen.track_number {
+        params.insert(name("trackNumber"), track_number.to_string());
+    }
+    if let Some(duration) = listen.duration {
+        params.insert(name("duration"), duration.as_secs().to_string());
+    }
+    if let Some(mbid) = &listen.recording_mbid {
+        params.insert(name("mbid"), mbid.clone());
+    }
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread::{self, JoinHandle},
+    };
+
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    /// A request received by [`serve`].
+    struct Request {
+        line: String,
+        authorization: Option<String>,
+        body: String,
+    }
+
+    /// Answers the requests of a local listener with the given statuses and bodies, in
+    /// order, returning the requests received. Returns the URL it listens at.
+    fn serve(responses: Vec<(u16, &'static str)>) -> (String, JoinHandle<Vec<Request>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for (status, body) in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let mut length = 0;
+                let mut authorization = None;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    let header = header.trim_end();
+                    if header.is_empty() {
+                        break;
+                    }
+                    let (name, value) = header.split_once(':').unwrap();
+                    match name.to_ascii_lowercase().as_str() {
+                        "content-length" => length = value.trim().parse().unwrap(),
+                        "authorization" => authorization = Some(value.trim().to_string()),
+                        _ => {}
+                    }
+                }
+                let mut content = vec![0; length];
+                reader.read_exact(&mut content).unwrap();
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {status} Status\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+                requests.push(Request {
+                    line: line.trim_end().to_string(),
+                    authorization,
+                    body: String::from_utf8(content).unwrap(),
+                });
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    /// Decodes an `application/x-www-form-urlencoded` body.
+    fn form(body: &str) -> BTreeMap<String, String> {
+        let decode = |value: &str| {
+            let value = value.replace('+', " ");
+            let mut bytes = Vec::new();
+            let mut chars = value.bytes();
+            while let Some(byte) = chars.next() {
+                if byte == b'%' {
+                    let hex = [chars.next().unwrap(), chars.next().unwrap()];
+                    let hex = std::str::from_utf8(&hex).unwrap();
+                    bytes.push(u8::from_str_radix(hex, 16).unwrap());
+                } else {
+                    bytes.push(byte);
+                }
+            }
+            String::from_utf8(bytes).unwrap()
+        };
+        body.split('&')
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap();
+                (decode(name), decode(value))
+            })
+            .collect()
+    }
+
+    fn listen(title: &str, listened_at: i64) -> Listen {
+        Listen {
+            artist: "Daft Punk".to_string(),
+            title: title.to_string(),
+            album: Some("Discovery".to_string()),
+            album_artist: None,
+            track_number: Some(1),
+            duration: Some(Duration::from_secs(320)),
+            recording_mbid: None,
+            listened_at: Utc.timestamp_opt(listened_at, 0).unwrap(),
+        }
+    }
+
+    fn listenbrainz(url: &str) -> ScrobblerAccount {
+        ScrobblerAccount::ListenBrainz {
+            url: url.to_string(),
+            token: "secret-token".to_string(),
+        }
+    }
+
+    fn last_fm(url: &str) -> ScrobblerAccount {
+        ScrobblerAccount::LastFm {
+            url: url.to_string(),
+            api_key: "key".to_string(),
+            api_secret: "secret".to_string(),
+            session_key: "session".to_string(),
+        }
+    }
+
+    #[test]
+    fn submits_single_and_import_listens_to_listenbrainz() {
+        let (url, server) = serve(vec![(200, r#"{"status":"ok"}"#); 2]);
+        let scrobbler = HttpScrobbler::default();
+        let account = listenbrainz(&url);
+
+        scrobbler
+            .submit(&account, &[listen("One More Time", 1000)])
+            .unwrap();
+        scrobbler
+            .submit(
+                &account,
+                &[listen("Aerodynamic", 2000), listen("Digital Love", 3000)],
+            )
+            .unwrap();
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].line, "POST /1/submit-listens HTTP/1.1");
+        assert_eq!(
+            requests[0].authorization.as_deref(),
+            Some("Token secret-token")
+        );
+        let single: Value = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(single["listen_type"], "single");
+        assert_eq!(single["payload"][0]["listened_at"], 1000);
+        let metadata = &single["payload"][0]["track_metadata"];
+        assert_eq!(metadata["artist_name"], "Daft Punk");
+        assert_eq!(metadata["track_name"], "One More Time");
+        assert_eq!(metadata["release_name"], "Discovery");
+        assert_eq!(metadata["additional_info"]["duration_ms"], 320_000);
+        assert_eq!(metadata["additional_info"]["tracknumber"], 1);
+
+        let import: Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(import["listen_type"], "import");
+        assert_eq!(import["payload"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            import["payload"][1]["track_metadata"]["track_name"],
+            "Digital Love"
+        );
+    }
+
+    #[test]
+    fn signs_and_indexes_last_fm_scrobbles() {
+        let (url, server) = serve(vec![(
+            200,
+            r#"{"scrobbles":{"@attr":{"accepted":2,"ignored":0}}}"#,
+        )]);
+        HttpScrobbler::default()
+            .submit(
+                &last_fm(&url),
+                &[listen("Aerodynamic", 2000), listen("Digital Love", 3000)],
+            )
+            .unwrap();
+
+        let requests = server.join().unwrap();
+        let mut params = form(&requests[0].body);
+        assert_eq!(params["method"], "track.scrobble");
+        assert_eq!(params["sk"], "session");
+        assert_eq!(params["track[0]"], "Aerodynamic");
+        assert_eq!(params["track[1]"], "Digital Love");
+        assert_eq!(params["timestamp[1]"], "3000");
+        assert_eq!(params["duration[0]"], "320");
+        assert_eq!(params["format"], "json");
+
+        // Signed with every parameter but the format and the signature, sorted by name
+        let signature = params.remove("api_sig").unwrap();
+        params.remove("format");
+        let signed = params
+            .iter()
+            .map(|(name, value)| format!("{name}{value}"))
+            .collect::<String>();
+        let expected = Md5::digest(format!("{signed}secret"));
+        let expected = expected
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        assert_eq!(signature, expected);
+    }
+
+    #[test]
+    fn classifies_listenbrainz_failures() {
+        let (url, server) = serve(vec![
+            (
+                401,
+                r#"{"code":401,"error":"Invalid authorization token."}"#,
+            ),
+            (429, "{}"),
+            (503, "{}"),
+            (400, r#"{"code":400,"error":"Invalid listen"}"#),
+        ]);
+        let scrobbler = HttpScrobbler::default();
+        let account = listenbrainz(&url);
+        let submit = || scrobbler.submit(&account, &[listen("One More Time", 1000)]);
+
+        assert!(matches!(
+            submit(),
+            Err(ScrobbleError::Unauthorized(message)) if message == "Invalid authorization token."
+        ));
+        assert!(matches!(submit(), Err(ScrobbleError::Unavailable(_))));
+        assert!(matches!(submit(), Err(ScrobbleError::Unavailable(_))));
+        assert!(matches!(
+            submit(),
+            Err(ScrobbleError::Rejected(message)) if message == "Invalid listen"
+        ));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn classifies_last_fm_failures() {
+        let (url, server) = serve(vec![
+            (403, r#"{"error":9,"message":"Invalid session key"}"#),
+            (403, r#"{"error":13,"message":"Invalid method signature"}"#),
+            (200, r#"{"error":11,"message":"Service Offline"}"#),
+            (200, r#"{"error":29,"message":"Rate limit exceeded"}"#),
+            (400, r#"{"error":6,"message":"Invalid parameters"}"#),
+            (502, "{}"),
+        ]);
+        let scrobbler = HttpScrobbler::default();
+        let account = last_fm(&url);
+        let submit = || scrobbler.submit(&account, &[listen("One More Time", 1000)]);
+
+        assert!(matches!(submit(), Err(ScrobbleError::Unauthorized(_))));
+        assert!(matches!(submit(), Err(ScrobbleError::Unauthorized(_))));
+        assert!(matches!(submit(), Err(ScrobbleError::Unavailable(_))));
+        assert!(matches!(submit(), Err(ScrobbleError::Unavailable(_))));
+        assert!(matches!(submit(), Err(ScrobbleError::Rejected(_))));
+        assert!(matches!(submit(), Err(ScrobbleError::Unavailable(_))));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn unreachable_services_are_unavailable() {
+        // Binding then dropping leaves a port nothing listens on
+        let url = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}", listener.local_addr().unwrap())
+        };
+        let result = HttpScrobbler::default().submit(&listenbrainz(&url), &[listen("a", 1)]);
+        assert!(matches!(result, Err(ScrobbleError::Unavailable(_))));
+    }
+}
@@ -0,0 +1,4 @@
+mod filesystem_smart_playlist_repository;
+
+pub use filesystem_smart_playlist_repository::FilesystemSmartPlaylistRepository;
+pub use filesystem_smart_playlist_repository::FilesystemSmartPlaylistRepositoryError;
@@ -0,0 +1,90 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::domain::{entity::smart_playlist::SmartPlaylist, repository::SmartPlaylistRepository};
+
+/// Stores each smart playlist as a JSON file named after its id.
+pub struct FilesystemSmartPlaylistRepository {
+    path: PathBuf,
+}
+
+impl FilesystemSmartPlaylistRepository {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// `None` for ids that could point outside of the directory.
+    fn playlist_path(&self, id: &str) -> Option<PathBuf> {
+        let valid = !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        valid.then(|| self.path.join(format!("{id}.json")))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FilesystemSmartPlaylistRepositoryError {
+    #[error("Invalid smart playlist id: {0}")]
+    InvalidId(String),
+    #[error("Failed to access smart playlist: {0}")]
+    IO(#[from] io::Error),
+    #[error("Failed to serialize smart playlist: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl SmartPlaylistRepository for FilesystemSmartPlaylistRepository {
+    type Error = FilesystemSmartPlaylistRepositoryError;
+
+    fn save(&self, playlist: &SmartPlaylist) -> Result<(), Self::Error> {
+        let path = self.playlist_path(&playlist.id).ok_or_else(|| {
+            FilesystemSmartPlaylistRepositoryError::InvalidId(playlist.id.clone())
+        })?;
+        fs::create_dir_all(&self.path)?;
+        fs::write(path, serde_json::to_vec_pretty(playlist)?)?;
+        Ok(())
+    }
+
+    fn find(&self, id: &str) -> Result<Option<SmartPlaylist>, Self::Error> {
+        let Some(path) = self.playlist_path(id) else {
+            return Ok(None);
+        };
+        match fs::read(path) {
+            Ok(json) => Ok(Some(serde_json::from_slice(&json)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<SmartPlaylist>, Self::Error> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let mut playlists = fs::read_dir(&self.path)?
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .map(|entry| {
+                let json = fs::read(entry.path())?;
+                Ok(serde_json::from_slice::<SmartPlaylist>(&json)?)
+            })
+            .collect::<Result<Vec<_>, Self::Error>>()?;
+        playlists.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+        Ok(playlists)
+    }
+
+    fn delete(&self, id: &str) -> Result<(), Self::Error> {
+        let Some(path) = self.playlist_path(id) else {
+            return Ok(());
+        };
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
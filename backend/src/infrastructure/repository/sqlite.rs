@@ -0,0 +1,52 @@
+use std::{path::Path, time::Duration};
+
+use rusqlite::Connection;
+
+/// How long a connection waits for another one to release the database.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Opens the database shared by the SQLite repositories, creating it if needed.
+///
+/// Each repository has its own connection, so the database is put in WAL mode for reads
+/// not to be blocked by a write, and writes wait for each other instead of failing.
+pub fn open<P: AsRef<Path>>(path: P) -> rusqlite::Result<Connection> {
+    let connection = Connection::open(path)?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+    // Returns the resulting mode, which stays "memory" for in-memory databases
+    connection.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+    Ok(connection)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    #[test]
+    fn opens_databases_in_wal_mode() {
+        let dir = env::temp_dir().join(format!("earr-sqlite-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("earr.sqlite3");
+
+        let writer = open(&path).unwrap();
+        let reader = open(&path).unwrap();
+        let mode: String = reader
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+
+        // A pending write does not block readers of another connection
+        writer
+            .execute_batch("CREATE TABLE t (x INTEGER); BEGIN; INSERT INTO t VALUES (1);")
+            .unwrap();
+        let count: i64 = reader
+            .query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+        writer.execute_batch("COMMIT").unwrap();
+
+        drop((writer, reader));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,5 @@
+mod filesystem_transcode_cache_repository;
+
+pub use filesystem_transcode_cache_repository::FilesystemTranscodeCacheRepository;
+pub use filesystem_transcode_cache_repository::FilesystemTranscodeCacheRepositoryError;
+pub use filesystem_transcode_cache_repository::FilesystemTranscodeCacheWriter;
@@ -0,0 +1,140 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::SystemTime,
+};
+
+use thiserror::Error;
+
+use crate::domain::repository::TranscodeCacheRepository;
+
+/// Extension of outputs still being written.
+const PARTIAL_EXTENSION: &str = "part";
+
+static PARTIAL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Stores each output as a file named after its key. The modification time of a file is
+/// its last use.
+pub struct FilesystemTranscodeCacheRepository {
+    path: PathBuf,
+}
+
+impl FilesystemTranscodeCacheRepository {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FilesystemTranscodeCacheRepositoryError {
+    #[error("Failed to access transcode cache: {0}")]
+    IO(#[from] io::Error),
+}
+
+/// Writes to a temporary file that is removed unless committed.
+pub struct FilesystemTranscodeCacheWriter {
+    file: BufWriter<File>,
+    path: PathBuf,
+    committed: bool,
+}
+
+impl Write for FilesystemTranscodeCacheWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for FilesystemTranscodeCacheWriter {
+    fn drop(&mut self) {
+        if !self.committed {
+            fs::remove_file(&self.path).ok();
+        }
+    }
+}
+
+impl TranscodeCacheRepository for FilesystemTranscodeCacheRepository {
+    type Error = FilesystemTranscodeCacheRepositoryError;
+    type Writer = FilesystemTranscodeCacheWriter;
+
+    fn find(&self, key: &str) -> Result<Option<PathBuf>, Self::Error> {
+        let path = self.path.join(key);
+        match File::options().write(true).open(&path) {
+            Ok(file) => {
+                file.set_modified(SystemTime::now())?;
+                Ok(Some(path))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn create(&self, key: &str) -> Result<Self::Writer, Self::Error> {
+        fs::create_dir_all(&self.path)?;
+        let counter = PARTIAL_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = self.path.join(format!(
+            "{}.{}-{}.{}",
+            key,
+            process::id(),
+            counter,
+            PARTIAL_EXTENSION
+        ));
+        Ok(FilesystemTranscodeCacheWriter {
+            file: BufWriter::new(File::create(&path)?),
+            path,
+            committed: false,
+        })
+    }
+
+    fn commit(&self, key: &str, mut writer: Self::Writer) -> Result<(), Self::Error> {
+        writer.flush()?;
+        fs::rename(&writer.path, self.path.join(key))?;
+        writer.committed = true;
+        Ok(())
+    }
+
+    fn evict(&self, max_size: u64) -> Result<(), Self::Error> {
+        let mut outputs = Vec::new();
+        let entries = match fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            let metadata = entry.metadata()?;
+            if !metadata.is_file()
+                || path
+                    .extension()
+                    .is_some_and(|extension| extension == PARTIAL_EXTENSION)
+            {
+                continue;
+            }
+            outputs.push((metadata.modified()?, metadata.len(), path));
+        }
+
+        outputs.sort();
+        let mut size = outputs.iter().map(|(_, len, _)| len).sum::<u64>();
+        for (_, len, path) in outputs {
+            if size <= max_size {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => size -= len,
+                // Already evicted by another process
+                Err(err) if err.kind() == io::ErrorKind::NotFound => size -= len,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+}
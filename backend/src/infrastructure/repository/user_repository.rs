@@ -0,0 +1,4 @@
+mod sqlite_user_repository;
+
+pub use sqlite_user_repository::SqliteUserRepository;
+pub use sqlite_user_repository::SqliteUserRepositoryError;
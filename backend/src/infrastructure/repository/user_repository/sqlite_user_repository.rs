@@ -0,0 +1,229 @@
+use std::{path::Path, sync::Mutex};
+
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::{params, Connection, Row};
+use thiserror::Error;
+
+use crate::{
+    domain::{
+        entity::user::{ApiToken, Role, User},
+        repository::UserRepository,
+    },
+    infrastructure::repository::sqlite,
+};
+
+/// Dates are stored as milliseconds since the Unix epoch and library roots as a JSON array.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS users (
+        id TEXT PRIMARY KEY,
+        username TEXT NOT NULL UNIQUE COLLATE NOCASE,
+        password_hash TEXT NOT NULL,
+        role TEXT NOT NULL,
+        library_roots TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        subsonic_password TEXT
+    );
+    CREATE TABLE IF NOT EXISTS api_tokens (
+        id TEXT PRIMARY KEY,
+        user_id TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        secret_hash TEXT NOT NULL UNIQUE,
+        created_at INTEGER NOT NULL,
+        last_used_at INTEGER
+    );
+";
+
+const USER_COLUMNS: &str =
+    "id, username, password_hash, role, library_roots, created_at, subsonic_password";
+const TOKEN_COLUMNS: &str = "id, user_id, name, secret_hash, created_at, last_used_at";
+
+/// Stores users and tokens in a SQLite database, which may be the one of the library.
+pub struct SqliteUserRepository {
+    connection: Mutex<Connection>,
+}
+
+#[derive(Error, Debug)]
+pub enum SqliteUserRepositoryError {
+    #[error("Failed to access user database: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Failed to serialize library roots: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid stored role: {0}")]
+    Role(String),
+    #[error("Invalid stored date: {0}")]
+    Date(i64),
+}
+
+impl SqliteUserRepository {
+    /// Opens the database at `path`, creating it if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, SqliteUserRepositoryError> {
+        let connection = sqlite::open(path)?;
+        connection.execute_batch("PRAGMA foreign_keys = ON;")?;
+        connection.execute_batch(SCHEMA)?;
+        migrate(&connection)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn select_users(
+        &self,
+        condition: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<User>, SqliteUserRepositoryError> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        let mut select =
+            connection.prepare(&format!("SELECT {USER_COLUMNS} FROM users {condition}"))?;
+        let mut rows = select.query(params)?;
+        let mut users = Vec::new();
+        while let Some(row) = rows.next()? {
+            users.push(user(row)?);
+        }
+        Ok(users)
+    }
+
+    fn select_tokens(
+        &self,
+        condition: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<ApiToken>, SqliteUserRepositoryError> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        let mut select = connection.prepare(&format!(
+            "SELECT {TOKEN_COLUMNS} FROM api_tokens {condition}"
+        ))?;
+        let mut rows = select.query(params)?;
+        let mut tokens = Vec::new();
+        while let Some(row) = rows.next()? {
+            tokens.push(token(row)?);
+        }
+        Ok(tokens)
+    }
+}
+
+impl UserRepository for SqliteUserRepository {
+    type Error = SqliteUserRepositoryError;
+
+    fn save_user(&self, user: &User) -> Result<(), Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        connection.execute(
+            &format!(
+                "INSERT INTO users ({USER_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                ON CONFLICT (id) DO UPDATE SET username = ?2, password_hash = ?3, role = ?4,
+                library_roots = ?5, subsonic_password = ?7"
+            ),
+            params![
+                user.id,
+                user.username,
+                user.password_hash,
+                user.role.as_str(),
+                serde_json::to_string(&user.library_roots)?,
+                user.created_at.timestamp_millis(),
+                user.subsonic_password,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find_user(&self, id: &str) -> Result<Option<User>, Self::Error> {
+        Ok(self.select_users("WHERE id = ?1", [id])?.pop())
+    }
+
+    fn find_user_by_name(&self, username: &str) -> Result<Option<User>, Self::Error> {
+        Ok(self.select_users("WHERE username = ?1", [username])?.pop())
+    }
+
+    fn list_users(&self) -> Result<Vec<User>, Self::Error> {
+        self.select_users("ORDER BY username", [])
+    }
+
+    fn delete_user(&self, id: &str) -> Result<(), Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        connection.execute("DELETE FROM users WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    fn save_token(&self, token: &ApiToken) -> Result<(), Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        connection.execute(
+            &format!(
+                "INSERT OR REPLACE INTO api_tokens ({TOKEN_COLUMNS})
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)"
+            ),
+            params![
+                token.id,
+                token.user_id,
+                token.name,
+                token.secret_hash,
+                token.created_at.timestamp_millis(),
+                token
+                    .last_used_at
+                    .map(|last_used_at| last_used_at.timestamp_millis()),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find_token(&self, secret_hash: &str) -> Result<Option<ApiToken>, Self::Error> {
+        Ok(self
+            .select_tokens("WHERE secret_hash = ?1", [secret_hash])?
+            .pop())
+    }
+
+    fn list_tokens(&self, user_id: &str) -> Result<Vec<ApiToken>, Self::Error> {
+        self.select_tokens("WHERE user_id = ?1 ORDER BY created_at DESC", [user_id])
+    }
+
+    fn delete_token(&self, id: &str) -> Result<(), Self::Error> {
+        let connection = self.connection.lock().expect("connection lock poisoned");
+        connection.execute("DELETE FROM api_tokens WHERE id = ?1", [id])?;
+        Ok(())
+    }
+}
+
+/// Reads a user selected as [`USER_COLUMNS`].
+fn user(row: &Row) -> Result<User, SqliteUserRepositoryError> {
+    let role = row.get::<_, String>(3)?;
+    Ok(User {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        password_hash: row.get(2)?,
+        role: match role.as_str() {
+            "admin" => Role::Admin,
+            "user" => Role::User,
+            "read_only" => Role::ReadOnly,
+            _ => return Err(SqliteUserRepositoryError::Role(role)),
+        },
+        library_roots: serde_json::from_str(&row.get::<_, String>(4)?)?,
+        created_at: date(row.get(5)?)?,
+        subsonic_password: row.get(6)?,
+    })
+}
+
+/// Adds the columns missing from the tables of older databases.
+fn migrate(connection: &Connection) -> rusqlite::Result<()> {
+    let has_subsonic_password = connection
+        .prepare("SELECT 1 FROM pragma_table_info('users') WHERE name = 'subsonic_password'")?
+        .exists([])?;
+    if !has_subsonic_password {
+        connection.execute_batch("ALTER TABLE users ADD COLUMN subsonic_password TEXT")?;
+    }
+    Ok(())
+}
+
+/// Reads a token selected as [`TOKEN_COLUMNS`].
+fn token(row: &Row) -> Result<ApiToken, SqliteUserRepositoryError> {
+    Ok(ApiToken {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        name: row.get(2)?,
+        secret_hash: row.get(3)?,
+        created_at: date(row.get(4)?)?,
+        last_used_at: row.get::<_, Option<i64>>(5)?.map(date).transpose()?,
+    })
+}
+
+fn date(millis: i64) -> Result<DateTime<Utc>, SqliteUserRepositoryError> {
+    Utc.timestamp_millis_opt(millis)
+        .single()
+        .ok_or(SqliteUserRepositoryError::Date(millis))
+}